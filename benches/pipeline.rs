@@ -0,0 +1,67 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ulang::{
+    assembly::{generate_assembly, TargetPlatform},
+    lexer::Lexer,
+    parser::Parser,
+    synth::Generator,
+    tacky::Tacky,
+    typecheck::typecheck,
+};
+
+const SEED: u64 = 0x5eed;
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+fn lex(source: &str) -> Vec<ulang::lexer::FileToken> {
+    Lexer::from_content(source.to_string()).tokenize().unwrap()
+}
+
+fn lexer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex");
+    group.sample_size(10);
+    for lines in SIZES {
+        let source = Generator::new(SEED).program(lines);
+        group.throughput(Throughput::Elements(lex(&source).len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(lines), &source, |b, source| {
+            b.iter(|| lex(source))
+        });
+    }
+    group.finish();
+}
+
+fn parser(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    for lines in SIZES {
+        let source = Generator::new(SEED).program(lines);
+        let tokens = lex(&source);
+        let statements = source.matches(';').count();
+        group.throughput(Throughput::Elements(statements as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(lines), &tokens, |b, tokens| {
+            b.iter(|| Parser::new(tokens, &source).parse().unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// Lexing, parsing, lowering and codegen of a single function of `lines` lines.
+fn compile(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compile");
+    group.sample_size(10);
+    for lines in SIZES {
+        let source = Generator::new(SEED).function("main", lines - 2);
+        group.throughput(Throughput::Elements(lines as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(lines), &source, |b, source| {
+            b.iter(|| {
+                let tokens = lex(source);
+                let ast = Parser::new(&tokens, source).parse().unwrap();
+                let typed = typecheck(&ast).unwrap();
+                let tacky = Tacky::new(&typed).parse().unwrap();
+                generate_assembly(&tacky, TargetPlatform::X64Linux)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lexer, parser, compile);
+criterion_main!(benches);
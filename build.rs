@@ -0,0 +1,42 @@
+//! Sets `ULANG_BUILD_ID` to a hash of the sources of the compiler, so the
+//! compilation cache tells apart two builds with the same version.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+fn main() -> io::Result<()> {
+    let root = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let mut files = vec![root.join("Cargo.toml")];
+    sources(&root.join("src"), &mut files)?;
+    // Sorted, so the hash doesn't depend on the order of the directory.
+    files.sort();
+    // 64-bit FNV-1a of the path and the content of every file.
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for file in &files {
+        let path = file.strip_prefix(&root).unwrap_or(file);
+        let content = fs::read(file)?;
+        for byte in path.to_string_lossy().bytes().chain(content) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    println!("cargo:rustc-env=ULANG_BUILD_ID={:016x}", hash);
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=src");
+    Ok(())
+}
+
+/// Every file under `dir`.
+fn sources(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            sources(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
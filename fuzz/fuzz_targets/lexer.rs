@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ulang::lexer::Lexer;
+
+// Any input has to end in tokens or an error, never in a panic. Keeping
+// everything, the tokens give back the input.
+fuzz_target!(|data: &[u8]| {
+    let content = String::from_utf8_lossy(data).into_owned();
+    for trivia in [false, true] {
+        let _ = Lexer::from_content(content.clone())
+            .with_trivia(trivia)
+            .tokenize();
+    }
+    for trigraphs in [false, true] {
+        let mut lexer = Lexer::from_content(content.clone())
+            .with_whitespace(true)
+            .with_error_tokens(true)
+            .with_trigraphs(trigraphs);
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(lexer.detokenize(&tokens), content);
+    }
+});
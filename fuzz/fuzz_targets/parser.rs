@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ulang::{lexer::Lexer, parser::Parser};
+
+// Any token sequence the lexer accepts has to parse or fail with an error.
+fuzz_target!(|data: &[u8]| {
+    let mut lexer = Lexer::from_content(String::from_utf8_lossy(data).into_owned());
+    if let Ok(tokens) = lexer.tokenize() {
+        let _ = Parser::new(&tokens, &lexer.content).parse();
+    }
+});
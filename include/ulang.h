@@ -0,0 +1,51 @@
+/* C ABI of the ulang library, built with `cargo build --release --features capi`
+ * into target/release/libulang.so (libulang.dylib on macOS). */
+#ifndef ULANG_H
+#define ULANG_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define ULANG_OK 0
+/* The source did not compile, `diagnostics` says why. */
+#define ULANG_DIAGNOSTICS 1
+/* `src` or `result` is null, or an option is out of range. */
+#define ULANG_INVALID_ARGUMENT 2
+
+#define ULANG_TARGET_X64_LINUX 0
+#define ULANG_TARGET_MACOS_X64 1
+
+/* All zeros picks -O0 for Linux. */
+typedef struct ulang_options {
+    /* 0, 1 or 2, like -O. */
+    unsigned int opt_level;
+    /* ULANG_TARGET_X64_LINUX or ULANG_TARGET_MACOS_X64. */
+    unsigned int target;
+} ulang_options;
+
+/* Owned by the library until ulang_result_free. */
+typedef struct ulang_result {
+    /* AT&T assembly, NULL when the source did not compile. */
+    char *assembly;
+    /* JSON array of diagnostics, "[]" on success:
+     * [{"severity": "error", "message": "...",
+     *   "labels": [{"message": "...", "offset": 23, "length": 1, "line": 2, "column": 12}]}]
+     * Offsets and lengths count bytes, lines and columns start at 1. */
+    char *diagnostics;
+} ulang_result;
+
+/* Compiles the NUL-terminated UTF-8 source `src` to assembly. `options` may
+ * be NULL for the defaults. Unless a NULL `result` makes it return
+ * ULANG_INVALID_ARGUMENT, the fields of `result` are set and must be released
+ * with ulang_result_free. */
+int ulang_compile(const char *src, const ulang_options *options, ulang_result *result);
+
+/* Releases the strings of `result` and sets them to NULL. */
+void ulang_result_free(ulang_result *result);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
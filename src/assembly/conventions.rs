@@ -0,0 +1,131 @@
+//! What the assemblers and linkers of the targets expect from the emitted
+//! text, so the emitter asks [`TargetPlatform::conventions`] instead of
+//! testing the platform.
+//!
+//! Linux means GNU `as` and ELF objects, macOS the `as` of clang and Mach-O
+//! objects, which reject the ELF only directives like `.type` and the
+//! GNU-stack note.
+
+use super::TargetPlatform;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlatformConventions {
+    /// Put in front of the C name of a global symbol.
+    pub symbol_prefix: &'static str,
+    /// Keeps a label out of the symbol table of the object.
+    pub local_label_prefix: &'static str,
+    /// Put after the name of a C library function to call it.
+    pub libc_call_suffix: &'static str,
+    /// Starts a comment that runs to the end of the line.
+    pub comment: &'static str,
+    /// Aligns to a number of bytes, `.p2align` takes the power of two.
+    pub align_directive: &'static str,
+    /// Bytes every function starts at a multiple of.
+    pub function_alignment: u32,
+    /// Every function can have a section of its own, which the linker drops
+    /// when nothing refers to it, see [`FunctionSections`].
+    pub function_sections: bool,
+    /// Symbols get `.type` and `.size` directives, as ELF wants.
+    pub symbol_type_and_size: bool,
+    /// The compiler goes in an `.ident` directive rather than a comment.
+    pub ident_directive: bool,
+    /// Section marking the stack as not executable, without which the
+    /// linker warns and makes it executable.
+    pub stack_note: Option<&'static str>,
+    /// Section of the NUL terminated strings.
+    pub string_section: &'static str,
+    /// Section of the 8 byte constants.
+    pub literal8_section: &'static str,
+    /// Section of the pointers to the functions run before `main`.
+    pub init_section: &'static str,
+    /// Symbol the kernel starts a program without the C runtime at.
+    pub start_symbol: &'static str,
+    /// Number of the exit system call.
+    pub exit_syscall: u32,
+    /// `O_WRONLY | O_CREAT | O_TRUNC`.
+    pub open_truncate_flags: u32,
+}
+
+const LINUX: PlatformConventions = PlatformConventions {
+    symbol_prefix: "",
+    local_label_prefix: ".L",
+    libc_call_suffix: "@PLT",
+    comment: "#",
+    align_directive: ".balign",
+    function_alignment: 16,
+    function_sections: true,
+    symbol_type_and_size: true,
+    ident_directive: true,
+    stack_note: Some(".note.GNU-stack,\"\",@progbits"),
+    string_section: ".section\t.rodata",
+    literal8_section: ".section\t.rodata",
+    init_section: ".section\t.init_array,\"aw\"",
+    start_symbol: "_start",
+    exit_syscall: 60,
+    open_truncate_flags: 0o1101,
+};
+
+const MACOS: PlatformConventions = PlatformConventions {
+    symbol_prefix: "_",
+    local_label_prefix: "L",
+    libc_call_suffix: "",
+    comment: "##",
+    align_directive: ".p2align",
+    function_alignment: 16,
+    // Mach-O has no section per function, `ld -dead_strip` drops the
+    // unused ones of `.text` anyway.
+    function_sections: false,
+    symbol_type_and_size: false,
+    ident_directive: false,
+    stack_note: None,
+    string_section: ".cstring",
+    literal8_section: ".literal8",
+    init_section: ".section\t__DATA,__mod_init_func,mod_init_funcs",
+    start_symbol: "start",
+    exit_syscall: 0x2000001,
+    open_truncate_flags: 0x601,
+};
+
+impl PlatformConventions {
+    /// Directive aligning the next data to `bytes`, a power of two.
+    pub fn align(&self, bytes: u32) -> String {
+        debug_assert!(bytes.is_power_of_two());
+        if self.align_directive == ".p2align" {
+            format!("\t{} {}\n", self.align_directive, bytes.trailing_zeros())
+        } else {
+            format!("\t{} {}\n", self.align_directive, bytes)
+        }
+    }
+
+    /// Directives putting the code of the function `name`, a symbol as the
+    /// linker sees it, into its section and aligning its start.
+    pub fn function_section(&self, name: &str, sections: FunctionSections) -> String {
+        let section = match sections {
+            FunctionSections::Separate if self.function_sections => {
+                format!("\t.section\t.text.{},\"ax\",@progbits\n", name)
+            }
+            _ => "\t.text\n".to_string(),
+        };
+        section + &self.align(self.function_alignment)
+    }
+}
+
+/// Sections the code of the functions goes into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FunctionSections {
+    /// All of it in `.text`.
+    #[default]
+    Shared,
+    /// `.text.<name>` for every function, so `ld --gc-sections` can drop
+    /// the ones nothing calls. Targets without such sections use `.text`.
+    Separate,
+}
+
+impl TargetPlatform {
+    pub fn conventions(&self) -> &'static PlatformConventions {
+        match self {
+            TargetPlatform::X64Linux => &LINUX,
+            TargetPlatform::MacOsX64 => &MACOS,
+        }
+    }
+}
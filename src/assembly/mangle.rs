@@ -0,0 +1,60 @@
+//! Names of the symbols and labels in the generated assembly.
+//!
+//! Global symbols get the `_` prefix of macOS, labels private to the file the
+//! prefix that keeps them out of the object's symbol table, see
+//! [`PlatformConventions`]. The labels the compiler makes up, like
+//! `ulang.trap`, contain a dot, so they never clash with a C identifier.
+//!
+//! Labels can be made from anything, a switch case value or a name given in
+//! the source, so [`Mangler::local`] escapes every char the assemblers don't
+//! take in a symbol as `$<hex code>$`, `$` itself included. Different labels
+//! stay different once escaped, and [`NameGenerator`] keeps the labels of
+//! different functions apart.
+//!
+//! [`NameGenerator`]: crate::names::NameGenerator
+
+use std::fmt;
+
+use crate::ast::Identifier;
+
+use super::{PlatformConventions, TargetPlatform};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mangler {
+    conventions: &'static PlatformConventions,
+}
+
+impl Mangler {
+    pub fn new(platform: TargetPlatform) -> Self {
+        Self {
+            conventions: platform.conventions(),
+        }
+    }
+
+    /// Symbol the linker sees, of a function, a variable or a counter.
+    pub fn global(&self, name: &Identifier) -> String {
+        format!("{}{}", self.conventions.symbol_prefix, name)
+    }
+
+    /// Label only the file sees, of a jump target, a constant or a trap.
+    pub fn local(&self, name: impl fmt::Display) -> String {
+        let mut label = self.conventions.local_label_prefix.to_string();
+        for c in name.to_string().chars() {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+                label.push(c);
+            } else {
+                label += &format!("${:x}$", c as u32);
+            }
+        }
+        label
+    }
+
+    /// Function of the C library as the generated code calls it, through
+    /// the PLT on Linux.
+    pub fn libc(&self, name: &str) -> String {
+        format!(
+            "{}{}{}",
+            self.conventions.symbol_prefix, name, self.conventions.libc_call_suffix
+        )
+    }
+}
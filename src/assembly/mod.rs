@@ -1,4 +1,19 @@
-use std::{collections::HashMap, fmt};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+mod conventions;
+mod mangle;
+mod peephole;
+mod pipeline;
+mod source_map;
+mod stack_slots;
+
+pub use conventions::{FunctionSections, PlatformConventions};
+pub use mangle::Mangler;
+pub use pipeline::{AsmPass, AsmPipeline, AsmPipelineError};
+pub use source_map::{Mapping, SourceMap};
 
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum TargetPlatform {
@@ -6,8 +21,35 @@ pub enum TargetPlatform {
     X64Linux,
 }
 
+impl TargetPlatform {
+    /// The platform the compiler runs on, Linux or else macOS.
+    pub const fn host() -> Self {
+        if cfg!(target_os = "linux") {
+            TargetPlatform::X64Linux
+        } else {
+            TargetPlatform::MacOsX64
+        }
+    }
+}
+
+impl std::str::FromStr for TargetPlatform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x64-linux" => Ok(TargetPlatform::X64Linux),
+            "macos-x64" => Ok(TargetPlatform::MacOsX64),
+            _ => Err(format!(
+                "Unknown target \"{s}\", expected x64-linux or macos-x64"
+            )),
+        }
+    }
+}
+
 use crate::{
     ast::{Identifier, UnaryOperator},
+    names::NameGenerator,
+    optimize::PassManager,
     tacky::{Instruction, TackyBinaryOperator, TackyProgram, Value},
 };
 
@@ -15,37 +57,282 @@ use crate::{
 pub struct AsmProgram(pub AsmFunctionDef);
 
 #[derive(Debug, Clone)]
-pub struct AsmProgramWithReplacedPseudoRegisters(pub AsmProgram, i32);
+pub struct AsmGenerated(pub String);
 
 #[derive(Debug, Clone)]
-pub struct AsmProgramWithFixedInstructions(pub AsmProgram);
+pub struct AsmFunctionDef {
+    pub name: Identifier,
+    pub instructions: Vec<AsmInstruction>,
+    /// Targets of the failed runtime checks, emitted after the function.
+    pub traps: Vec<Trap>,
+    /// Counters incremented by the instrumentation, see [`Instrumentation`].
+    pub counters: Vec<Counter>,
+}
 
+/// Everything the assembly output can define at the top level.
 #[derive(Debug, Clone)]
-pub struct AsmWithFixedCmp(pub AsmProgram);
+pub enum AsmTopLevel {
+    Function(AsmFunctionDef),
+    StaticVariable(StaticVariable),
+}
 
+/// Variable with static storage, emitted into `.data`, or `.bss` when it is
+/// all zeroes.
 #[derive(Debug, Clone)]
-pub struct AsmGenerated(pub String);
+pub struct StaticVariable {
+    pub name: Identifier,
+    /// Visible to other object files, `false` for `static` variables.
+    pub global: bool,
+    /// In bytes.
+    pub alignment: u32,
+    pub init: Vec<StaticInit>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StaticInit {
+    Long(i32),
+    Quad(i64),
+    /// Run of zero bytes.
+    Zero(u32),
+}
 
+impl StaticInit {
+    fn is_zero(&self) -> bool {
+        matches!(
+            self,
+            StaticInit::Long(0) | StaticInit::Quad(0) | StaticInit::Zero(_)
+        )
+    }
+
+    fn size(&self) -> u32 {
+        match self {
+            StaticInit::Long(_) => 4,
+            StaticInit::Quad(_) => 8,
+            StaticInit::Zero(bytes) => *bytes,
+        }
+    }
+}
+
+/// Read-only constants of a translation unit, each stored once however many
+/// times it is used.
+///
+/// Labels are numbered per kind in the order the constants are first added,
+/// so the same program always gets the same names.
 #[derive(Debug, Clone)]
-pub struct AsmFunctionDef {
-    pub name: String,
-    pub instructions: Vec<AsmInstruction>,
+pub struct ConstantPool {
+    names: NameGenerator,
+    labels: HashMap<ConstantKey, Identifier>,
+    constants: Vec<(Identifier, Constant)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    /// Text without the terminating NUL, which is added when emitting it.
+    String(String),
+    Double(f64),
+}
+
+/// Content of a constant in a hashable form, doubles by their bits.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ConstantKey {
+    String(String),
+    Double(u64),
+}
+
+impl Default for ConstantPool {
+    fn default() -> Self {
+        Self {
+            names: NameGenerator::new(Identifier::from("ulang")),
+            labels: HashMap::new(),
+            constants: vec![],
+        }
+    }
+}
+
+impl ConstantPool {
+    /// Label of a NUL terminated copy of `text`.
+    pub fn string(&mut self, text: &str) -> Identifier {
+        self.add(
+            ConstantKey::String(text.to_string()),
+            Constant::String(text.to_string()),
+            "string",
+        )
+    }
+
+    /// Label of an 8 byte aligned `value`.
+    pub fn double(&mut self, value: f64) -> Identifier {
+        self.add(
+            ConstantKey::Double(value.to_bits()),
+            Constant::Double(value),
+            "double",
+        )
+    }
+
+    fn add(&mut self, key: ConstantKey, constant: Constant, kind: &'static str) -> Identifier {
+        if let Some(label) = self.labels.get(&key) {
+            return *label;
+        }
+        let label = self.names.fresh(kind);
+        self.labels.insert(key, label);
+        self.constants.push((label, constant));
+        label
+    }
+
+    /// Constants with their labels, in the order they were added.
+    pub fn constants(&self) -> &[(Identifier, Constant)] {
+        &self.constants
+    }
+}
+
+/// Exit status of a program stopped by a failed runtime check.
+pub const TRAP_EXIT_STATUS: i32 = 70;
+
+/// Checks compiled into the program that stop it with a message instead of
+/// letting the CPU fault.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeChecks {
+    /// Test the divisor of every division and remainder.
+    pub division_by_zero: bool,
+    /// Test the overflow flag after every addition, subtraction and multiplication.
+    pub overflow: bool,
+    /// Source file named in the messages, together with the line of the
+    /// operator when the TACKY has [`Instruction::Location`] markers.
+    pub file_name: String,
+}
+
+/// Code compiled into the program to measure how it runs.
+#[derive(Debug, Clone, Default)]
+pub struct Instrumentation {
+    /// Count the calls of every function and print the counts to stderr
+    /// when the program exits.
+    pub functions: bool,
+    /// Count the runs of every basic block and write them at exit to the
+    /// `.ulangcov` file at this path, read by [`crate::coverage`].
+    pub coverage: Option<String>,
+}
+
+/// 64-bit counter in `.bss`, written out at exit.
+#[derive(Debug, Clone)]
+pub struct Counter {
+    pub label: Identifier,
+    pub report: CounterReport,
+}
+
+#[derive(Debug, Clone)]
+pub enum CounterReport {
+    /// `<count>\t<function>` line on stderr.
+    Calls(String),
+    /// `<line>\t<count>` line in the coverage file for each source line of
+    /// the block.
+    Block { file: String, lines: Vec<usize> },
+}
+
+/// Code that prints `message` to stderr and exits with [`TRAP_EXIT_STATUS`].
+#[derive(Debug, Clone)]
+pub struct Trap {
+    pub label: Identifier,
+    pub message: String,
+}
+
+/// Size of the operands of an instruction, picks its suffix and the names of
+/// its registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsmType {
+    Byte,
+    Longword,
+    Quadword,
+}
+
+impl AsmType {
+    /// Suffix of the AT&T mnemonic, `movb`, `movl` or `movq`.
+    pub fn suffix(&self) -> char {
+        match self {
+            AsmType::Byte => 'b',
+            AsmType::Longword => 'l',
+            AsmType::Quadword => 'q',
+        }
+    }
+
+    /// Size in bytes.
+    pub fn size(&self) -> i32 {
+        match self {
+            AsmType::Byte => 1,
+            AsmType::Longword => 4,
+            AsmType::Quadword => 8,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum AsmInstruction {
-    Mov { src: Operand, dst: Operand },
-    Unary(AsmUnaryOperator, Operand),
-    Cmp(Operand, Operand),
+    Mov {
+        ty: AsmType,
+        src: Operand,
+        dst: Operand,
+    },
+    Unary(AsmUnaryOperator, AsmType, Operand),
+    Cmp(AsmType, Operand, Operand),
     AllocateStack(i32),
-    Binary(AsmBinaryOperator, Operand, Operand),
-    Idiv(Operand),
-    Cdq,
+    Push(AsmRegistry),
+    Pop(AsmRegistry),
+    /// Move between whole 64-bit registers, for the frame and stack pointers.
+    MovQ {
+        src: AsmRegistry,
+        dst: AsmRegistry,
+    },
+    Binary(AsmBinaryOperator, AsmType, Operand, Operand),
+    Idiv(AsmType, Operand),
+    /// Sign extends the dividend into DX, `cdq`, or `cqo` for a quadword.
+    Cdq(AsmType),
+    /// `offset(base)` computed into `dst` without touching the flags.
+    Lea {
+        base: AsmRegistry,
+        offset: i32,
+        dst: AsmRegistry,
+    },
     Jmp(Identifier),
     JmpCC(ConditionCode, Identifier),
+    /// Writes the low byte of the operand only.
     SetCC(ConditionCode, Operand),
     Label(Identifier),
+    /// Adds one to the [`Counter`] at the label.
+    IncrementCounter(Identifier),
+    /// Source position of the instructions that follow, emits no code.
+    Location {
+        line: usize,
+        column: usize,
+    },
+    /// Leaves the function, becomes a jump to the epilogue when the
+    /// instructions are fixed up.
     Return,
+    Ret,
+}
+
+impl AsmInstruction {
+    /// Mutable access to every operand of the instruction.
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            AsmInstruction::Mov { src, dst, .. }
+            | AsmInstruction::Cmp(_, src, dst)
+            | AsmInstruction::Binary(_, _, src, dst) => vec![src, dst],
+            AsmInstruction::Unary(_, _, operand)
+            | AsmInstruction::Idiv(_, operand)
+            | AsmInstruction::SetCC(_, operand) => vec![operand],
+            AsmInstruction::AllocateStack(_)
+            | AsmInstruction::Push(_)
+            | AsmInstruction::Pop(_)
+            | AsmInstruction::MovQ { .. }
+            | AsmInstruction::Cdq(_)
+            | AsmInstruction::Lea { .. }
+            | AsmInstruction::Jmp(_)
+            | AsmInstruction::JmpCC(_, _)
+            | AsmInstruction::Label(_)
+            | AsmInstruction::IncrementCounter(_)
+            | AsmInstruction::Location { .. }
+            | AsmInstruction::Return
+            | AsmInstruction::Ret => vec![],
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -56,6 +343,8 @@ pub enum ConditionCode {
     GE,
     L,
     LE,
+    /// Signed overflow
+    O,
 }
 
 impl TryFrom<&TackyBinaryOperator> for ConditionCode {
@@ -82,6 +371,7 @@ impl fmt::Display for ConditionCode {
             ConditionCode::GE => "ge",
             ConditionCode::L => "l",
             ConditionCode::LE => "le",
+            ConditionCode::O => "o",
         };
         write!(f, "{}", s)
     }
@@ -92,14 +382,18 @@ pub enum AsmUnaryOperator {
     Neg,
     Complement,
     Not,
+    Inc,
+    Dec,
 }
 
 impl fmt::Display for AsmUnaryOperator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            AsmUnaryOperator::Neg => write!(f, "negl"),
-            AsmUnaryOperator::Complement => write!(f, "notl"),
+            AsmUnaryOperator::Neg => write!(f, "neg"),
+            AsmUnaryOperator::Complement => write!(f, "not"),
             Self::Not => write!(f, "dddd"),
+            AsmUnaryOperator::Inc => write!(f, "inc"),
+            AsmUnaryOperator::Dec => write!(f, "dec"),
         }
     }
 }
@@ -109,6 +403,9 @@ pub enum AsmBinaryOperator {
     Add,
     Sub,
     Mult,
+    Xor,
+    /// Shift left by an immediate count.
+    Shl,
 }
 
 impl TryFrom<&TackyBinaryOperator> for AsmBinaryOperator {
@@ -118,6 +415,7 @@ impl TryFrom<&TackyBinaryOperator> for AsmBinaryOperator {
             TackyBinaryOperator::Add => Ok(AsmBinaryOperator::Add),
             TackyBinaryOperator::Substract => Ok(AsmBinaryOperator::Sub),
             TackyBinaryOperator::Multiply => Ok(AsmBinaryOperator::Mult),
+            TackyBinaryOperator::ShiftLeft => Ok(AsmBinaryOperator::Shl),
             _ => Err(()),
         }
     }
@@ -126,9 +424,11 @@ impl TryFrom<&TackyBinaryOperator> for AsmBinaryOperator {
 impl fmt::Display for AsmBinaryOperator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            AsmBinaryOperator::Add => write!(f, "addl"),
-            AsmBinaryOperator::Sub => write!(f, "subl"),
-            AsmBinaryOperator::Mult => write!(f, "imull"),
+            AsmBinaryOperator::Add => write!(f, "add"),
+            AsmBinaryOperator::Sub => write!(f, "sub"),
+            AsmBinaryOperator::Mult => write!(f, "imul"),
+            AsmBinaryOperator::Xor => write!(f, "xor"),
+            AsmBinaryOperator::Shl => write!(f, "sal"),
         }
     }
 }
@@ -143,21 +443,29 @@ impl From<&UnaryOperator> for AsmUnaryOperator {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Operand {
     Register(AsmRegistry),
-    Imm(i32),
+    Imm(i64),
     Stack(i32),
     Pseudo(Identifier),
 }
 
 impl fmt::Display for Operand {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.sized(AsmType::Longword))
+    }
+}
+
+impl Operand {
+    /// The operand as written in an instruction on `ty` sized values, only
+    /// registers have a name for each size.
+    pub fn sized(&self, ty: AsmType) -> String {
         match self {
-            Operand::Register(asm_registry) => write!(f, "{}", asm_registry),
-            Operand::Imm(i) => write!(f, "${}", i),
-            Operand::Stack(i) => write!(f, "{}(%rbp)", i),
-            Operand::Pseudo(identifier) => write!(f, "PSEUDO_{}", &identifier.0),
+            Operand::Register(asm_registry) => asm_registry.name(ty).to_string(),
+            Operand::Imm(i) => format!("${}", i),
+            Operand::Stack(i) => format!("{}(%rbp)", i),
+            Operand::Pseudo(identifier) => format!("PSEUDO_{}", &identifier.0),
         }
     }
 }
@@ -165,18 +473,36 @@ impl fmt::Display for Operand {
 impl From<&Value> for Operand {
     fn from(value: &Value) -> Self {
         match value {
-            Value::Constant(c) => Self::Imm(*c),
-            Value::Var(identifier) => Self::Pseudo(identifier.clone()),
+            Value::Constant(c) => Self::Imm(i64::from(*c)),
+            Value::Var(identifier) => Self::Pseudo(*identifier),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Registers the first arguments of a call are passed in, by the System V
+/// calling convention of both targets.
+const ARGUMENT_REGISTERS: [AsmRegistry; 6] = [
+    AsmRegistry::DI,
+    AsmRegistry::SI,
+    AsmRegistry::DX,
+    AsmRegistry::CX,
+    AsmRegistry::R8,
+    AsmRegistry::R9,
+];
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum AsmRegistry {
     AX,
+    CX,
     DX,
+    SI,
+    DI,
+    R8,
+    R9,
     R10,
     R11,
+    BP,
+    SP,
 }
 
 impl From<AsmRegistry> for Operand {
@@ -187,47 +513,240 @@ impl From<AsmRegistry> for Operand {
 
 impl fmt::Display for AsmRegistry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            AsmRegistry::DX => write!(f, "%edx"),
-            AsmRegistry::AX => write!(f, "%eax"),
-            AsmRegistry::R10 => write!(f, "%r10d"),
-            AsmRegistry::R11 => write!(f, "%r11d"),
+        write!(f, "{}", self.name(AsmType::Longword))
+    }
+}
+
+impl AsmRegistry {
+    /// Name of the part of the register holding a `ty` sized value.
+    pub fn name(&self, ty: AsmType) -> &'static str {
+        match (self, ty) {
+            (AsmRegistry::AX, AsmType::Byte) => "%al",
+            (AsmRegistry::AX, AsmType::Longword) => "%eax",
+            (AsmRegistry::AX, AsmType::Quadword) => "%rax",
+            (AsmRegistry::DX, AsmType::Byte) => "%dl",
+            (AsmRegistry::DX, AsmType::Longword) => "%edx",
+            (AsmRegistry::DX, AsmType::Quadword) => "%rdx",
+            (AsmRegistry::CX, AsmType::Byte) => "%cl",
+            (AsmRegistry::CX, AsmType::Longword) => "%ecx",
+            (AsmRegistry::CX, AsmType::Quadword) => "%rcx",
+            (AsmRegistry::SI, AsmType::Byte) => "%sil",
+            (AsmRegistry::SI, AsmType::Longword) => "%esi",
+            (AsmRegistry::SI, AsmType::Quadword) => "%rsi",
+            (AsmRegistry::DI, AsmType::Byte) => "%dil",
+            (AsmRegistry::DI, AsmType::Longword) => "%edi",
+            (AsmRegistry::DI, AsmType::Quadword) => "%rdi",
+            (AsmRegistry::R8, AsmType::Byte) => "%r8b",
+            (AsmRegistry::R8, AsmType::Longword) => "%r8d",
+            (AsmRegistry::R8, AsmType::Quadword) => "%r8",
+            (AsmRegistry::R9, AsmType::Byte) => "%r9b",
+            (AsmRegistry::R9, AsmType::Longword) => "%r9d",
+            (AsmRegistry::R9, AsmType::Quadword) => "%r9",
+            (AsmRegistry::R10, AsmType::Byte) => "%r10b",
+            (AsmRegistry::R10, AsmType::Longword) => "%r10d",
+            (AsmRegistry::R10, AsmType::Quadword) => "%r10",
+            (AsmRegistry::R11, AsmType::Byte) => "%r11b",
+            (AsmRegistry::R11, AsmType::Longword) => "%r11d",
+            (AsmRegistry::R11, AsmType::Quadword) => "%r11",
+            (AsmRegistry::BP, AsmType::Byte) => "%bpl",
+            (AsmRegistry::BP, AsmType::Longword) => "%ebp",
+            (AsmRegistry::BP, AsmType::Quadword) => "%rbp",
+            (AsmRegistry::SP, AsmType::Byte) => "%spl",
+            (AsmRegistry::SP, AsmType::Longword) => "%esp",
+            (AsmRegistry::SP, AsmType::Quadword) => "%rsp",
         }
     }
+
+    /// Name of the whole 64-bit register, as used in addresses.
+    pub fn quad_name(&self) -> &'static str {
+        self.name(AsmType::Quadword)
+    }
 }
 
 impl From<&TackyProgram> for AsmProgram {
     fn from(value: &TackyProgram) -> Self {
-        let tacky_function = &value.0;
+        AsmProgram::new(value, &RuntimeChecks::default())
+    }
+}
+
+impl AsmProgram {
+    pub fn new(tacky: &TackyProgram, checks: &RuntimeChecks) -> Self {
+        let tacky_function = &tacky.0;
         let mut function_def = AsmFunctionDef {
-            name: tacky_function.identifier.clone(),
+            name: tacky_function.identifier,
             instructions: vec![],
+            traps: vec![],
+            counters: vec![],
+        };
+        // Every value is an int for now, a parameter of another type is
+        // copied as one and can't be read, lowering to TACKY rejects it.
+        for (i, parameter) in tacky_function.parameters.iter().enumerate() {
+            let src = match ARGUMENT_REGISTERS.get(i) {
+                Some(register) => register.clone().into(),
+                // Above the saved frame pointer and the return address.
+                None => Operand::Stack(16 + 8 * (i - ARGUMENT_REGISTERS.len()) as i32),
+            };
+            function_def.instructions.push(AsmInstruction::Mov {
+                ty: AsmType::Longword,
+                src,
+                dst: Operand::Pseudo(*parameter),
+            });
+        }
+        let mut names = NameGenerator::new(tacky_function.identifier);
+        let mut line = None;
+        let location = |line: Option<usize>| match line {
+            Some(line) => format!("{}:{}", checks.file_name, line),
+            None => checks.file_name.clone(),
         };
         for instruction in &tacky_function.instruction {
+            let Instruction::Binary { operator, src2, .. } = instruction else {
+                if let Instruction::Location { line: l, .. } = instruction {
+                    line = Some(*l);
+                }
+                function_def.parse_instruction(instruction);
+                continue;
+            };
+            let divides = matches!(
+                operator,
+                TackyBinaryOperator::Divide | TackyBinaryOperator::Remainder
+            );
+            if divides && checks.division_by_zero {
+                let trap = function_def.trap(
+                    names.fresh("trap"),
+                    format!("division by zero at {}\n", location(line)),
+                );
+                function_def.instructions.push(AsmInstruction::Cmp(
+                    AsmType::Longword,
+                    Operand::Imm(0),
+                    src2.into(),
+                ));
+                function_def
+                    .instructions
+                    .push(AsmInstruction::JmpCC(ConditionCode::E, trap));
+            }
             function_def.parse_instruction(instruction);
+            let overflows = matches!(
+                operator,
+                TackyBinaryOperator::Add
+                    | TackyBinaryOperator::Substract
+                    | TackyBinaryOperator::Multiply
+            );
+            // The fixups only add movs around the operation, which keep the flags.
+            if overflows && checks.overflow {
+                let trap = function_def.trap(
+                    names.fresh("trap"),
+                    format!("signed integer overflow at {}\n", location(line)),
+                );
+                function_def
+                    .instructions
+                    .push(AsmInstruction::JmpCC(ConditionCode::O, trap));
+            }
         }
 
         AsmProgram(function_def)
     }
+
+    /// Adds the counters asked for by `instrumentation`, before the fixups so
+    /// they come right after the prologue.
+    pub fn instrument(&mut self, instrumentation: &Instrumentation) {
+        let function = &mut self.0;
+        let mut names = NameGenerator::new(function.name);
+        if let Some(file) = &instrumentation.coverage {
+            function.count_blocks(file, &mut names);
+        }
+        if instrumentation.functions {
+            let label = names.fresh("calls");
+            function
+                .instructions
+                .insert(0, AsmInstruction::IncrementCounter(label));
+            function.counters.push(Counter {
+                label,
+                report: CounterReport::Calls(function.name.to_string()),
+            });
+        }
+    }
 }
 impl AsmFunctionDef {
+    /// Registers a trap at `label` printing `message`.
+    fn trap(&mut self, label: Identifier, message: String) -> Identifier {
+        self.traps.push(Trap { label, message });
+        label
+    }
+
+    /// Increments a counter at the start of every basic block, split the way
+    /// [`crate::tacky::cfg::ControlFlowGraph`] splits the TACKY: at labels
+    /// and after jumps and returns. Jumps to traps leave the program, so they
+    /// don't end a block.
+    ///
+    /// A block is reported on the lines of its [`AsmInstruction::Location`]
+    /// markers. Blocks without any, like the ones picking the result of `&&`,
+    /// are counted but not reported, they can't tell which line they ran for.
+    fn count_blocks(&mut self, file: &str, names: &mut NameGenerator) {
+        let traps: HashSet<Identifier> = self.traps.iter().map(|trap| trap.label).collect();
+        let mut instructions = Vec::with_capacity(self.instructions.len() * 2);
+        let mut blocks: Vec<(Identifier, Vec<usize>)> = vec![];
+        let mut starts_block = true;
+        for instruction in std::mem::take(&mut self.instructions) {
+            let is_label = matches!(instruction, AsmInstruction::Label(_));
+            if is_label {
+                instructions.push(instruction.clone());
+            }
+            if is_label || starts_block {
+                let label = names.fresh("block");
+                instructions.push(AsmInstruction::IncrementCounter(label));
+                blocks.push((label, vec![]));
+                starts_block = false;
+            }
+            let lines = &mut blocks.last_mut().expect("a block was started").1;
+            match &instruction {
+                AsmInstruction::Label(_) => continue,
+                AsmInstruction::Location { line, .. } if !lines.contains(line) => lines.push(*line),
+                AsmInstruction::Jmp(_) | AsmInstruction::Return => starts_block = true,
+                AsmInstruction::JmpCC(_, target) if !traps.contains(target) => starts_block = true,
+                _ => {}
+            }
+            instructions.push(instruction);
+        }
+        self.instructions = instructions;
+        self.counters
+            .extend(blocks.into_iter().map(|(label, lines)| Counter {
+                label,
+                report: CounterReport::Block {
+                    file: file.to_string(),
+                    lines,
+                },
+            }));
+    }
+
     fn parse_instruction(&mut self, instruction: &Instruction) {
         match instruction {
-            Instruction::Jump(id) => self.instructions.push(AsmInstruction::Jmp(id.clone())),
-            Instruction::Label(id) => self.instructions.push(AsmInstruction::Label(id.clone())),
+            Instruction::Location { line, column } => {
+                self.instructions.push(AsmInstruction::Location {
+                    line: *line,
+                    column: *column,
+                });
+            }
+            Instruction::Jump(id) => self.instructions.push(AsmInstruction::Jmp(*id)),
+            Instruction::Label(id) => self.instructions.push(AsmInstruction::Label(*id)),
             crate::tacky::Instruction::JumpIfZero(val, id) => {
                 let value = val.into();
+                self.instructions.push(AsmInstruction::Cmp(
+                    AsmType::Longword,
+                    Operand::Imm(0),
+                    value,
+                ));
                 self.instructions
-                    .push(AsmInstruction::Cmp(Operand::Imm(0), value));
-                self.instructions
-                    .push(AsmInstruction::JmpCC(ConditionCode::E, id.clone()));
+                    .push(AsmInstruction::JmpCC(ConditionCode::E, *id));
             }
             crate::tacky::Instruction::JumpIfNotZero(val, id) => {
                 let value = val.into();
+                self.instructions.push(AsmInstruction::Cmp(
+                    AsmType::Longword,
+                    Operand::Imm(0),
+                    value,
+                ));
                 self.instructions
-                    .push(AsmInstruction::Cmp(Operand::Imm(0), value));
-                self.instructions
-                    .push(AsmInstruction::JmpCC(ConditionCode::NE, id.clone()));
+                    .push(AsmInstruction::JmpCC(ConditionCode::NE, *id));
             }
             crate::tacky::Instruction::Binary {
                 operator,
@@ -237,33 +756,45 @@ impl AsmFunctionDef {
             } => match operator {
                 TackyBinaryOperator::Divide => {
                     self.instructions.push(AsmInstruction::Mov {
+                        ty: AsmType::Longword,
                         src: src1.into(),
                         dst: Operand::Register(AsmRegistry::AX),
                     });
-                    self.instructions.push(AsmInstruction::Cdq);
-                    self.instructions.push(AsmInstruction::Idiv(src2.into()));
+                    self.instructions
+                        .push(AsmInstruction::Cdq(AsmType::Longword));
+                    self.instructions
+                        .push(AsmInstruction::Idiv(AsmType::Longword, src2.into()));
                     self.instructions.push(AsmInstruction::Mov {
+                        ty: AsmType::Longword,
                         src: AsmRegistry::AX.into(),
                         dst: dest.into(),
                     });
                 }
                 TackyBinaryOperator::Remainder => {
                     self.instructions.push(AsmInstruction::Mov {
+                        ty: AsmType::Longword,
                         src: src1.into(),
                         dst: Operand::Register(AsmRegistry::AX),
                     });
-                    self.instructions.push(AsmInstruction::Cdq);
-                    self.instructions.push(AsmInstruction::Idiv(src2.into()));
+                    self.instructions
+                        .push(AsmInstruction::Cdq(AsmType::Longword));
+                    self.instructions
+                        .push(AsmInstruction::Idiv(AsmType::Longword, src2.into()));
                     self.instructions.push(AsmInstruction::Mov {
-                        src: AsmRegistry::AX.into(),
+                        ty: AsmType::Longword,
+                        src: AsmRegistry::DX.into(),
                         dst: dest.into(),
                     });
                 }
                 o => {
                     if let Ok(condition_code) = o.try_into() {
-                        self.instructions
-                            .push(AsmInstruction::Cmp(src2.into(), src1.into()));
+                        self.instructions.push(AsmInstruction::Cmp(
+                            AsmType::Longword,
+                            src2.into(),
+                            src1.into(),
+                        ));
                         self.instructions.push(AsmInstruction::Mov {
+                            ty: AsmType::Longword,
                             src: Operand::Imm(0),
                             dst: dest.into(),
                         });
@@ -272,6 +803,7 @@ impl AsmFunctionDef {
                         return;
                     }
                     self.instructions.push(AsmInstruction::Mov {
+                        ty: AsmType::Longword,
                         src: src1.into(),
                         dst: dest.into(),
                     });
@@ -282,16 +814,21 @@ impl AsmFunctionDef {
 
                     self.instructions.push(AsmInstruction::Binary(
                         operator,
+                        AsmType::Longword,
                         src2.into(),
                         dest.into(),
                     ))
                 }
             },
             crate::tacky::Instruction::Return(value) => {
-                self.instructions.push(AsmInstruction::Mov {
-                    src: value.into(),
-                    dst: Operand::Register(AsmRegistry::AX),
-                });
+                // A function returning void only runs the epilogue.
+                if let Some(value) = value {
+                    self.instructions.push(AsmInstruction::Mov {
+                        ty: AsmType::Longword,
+                        src: value.into(),
+                        dst: Operand::Register(AsmRegistry::AX),
+                    });
+                }
                 self.instructions.push(AsmInstruction::Return);
             }
             crate::tacky::Instruction::Unary {
@@ -300,9 +837,13 @@ impl AsmFunctionDef {
                 dest,
             } => match operator {
                 UnaryOperator::Not => {
-                    self.instructions
-                        .push(AsmInstruction::Cmp(Operand::Imm(0), src.into()));
+                    self.instructions.push(AsmInstruction::Cmp(
+                        AsmType::Longword,
+                        Operand::Imm(0),
+                        src.into(),
+                    ));
                     self.instructions.push(AsmInstruction::Mov {
+                        ty: AsmType::Longword,
                         src: Operand::Imm(0),
                         dst: dest.into(),
                     });
@@ -311,15 +852,20 @@ impl AsmFunctionDef {
                 }
                 _ => {
                     self.instructions.push(AsmInstruction::Mov {
+                        ty: AsmType::Longword,
                         src: src.into(),
                         dst: dest.into(),
                     });
-                    self.instructions
-                        .push(AsmInstruction::Unary(operator.into(), dest.into()));
+                    self.instructions.push(AsmInstruction::Unary(
+                        operator.into(),
+                        AsmType::Longword,
+                        dest.into(),
+                    ));
                 }
             },
             Instruction::Copy { src, dest } => {
                 self.instructions.push(AsmInstruction::Mov {
+                    ty: AsmType::Longword,
                     src: src.into(),
                     dst: dest.into(),
                 });
@@ -351,253 +897,682 @@ impl PseudoRegistryHash {
             Some(c) => *c,
             None => {
                 self.counter -= 4;
-                self.hash.insert(id.clone(), self.counter);
+                self.hash.insert(*id, self.counter);
                 self.counter
             }
         }
     }
+    /// Replaces a pseudo register operand with its stack slot.
+    fn replace(&mut self, operand: &mut Operand) {
+        if let Operand::Pseudo(id) = operand {
+            *operand = Operand::Stack(self.get(id));
+        }
+    }
     pub fn stack_to_allocate(&self) -> i32 {
         self.counter.abs()
     }
 }
 
-impl From<AsmProgram> for AsmProgramWithReplacedPseudoRegisters {
-    fn from(value: AsmProgram) -> Self {
-        let mut hasher = PseudoRegistryHash::new();
-        let mut instructions = value.0.instructions.clone();
-        let mut new_instructions = vec![];
-        for (i, instruction) in instructions.iter().enumerate() {
-            match &instruction {
-                AsmInstruction::SetCC(cc, Operand::Pseudo(id)) => {
-                    let val = hasher.get(id);
-                    new_instructions
-                        .push((i, [AsmInstruction::SetCC(cc.clone(), Operand::Stack(val))]));
-                }
-                AsmInstruction::Cmp(op1, op2) => {
-                    let mut src_new = op1.clone();
-                    let mut dst_new = op2.clone();
-                    if let Operand::Pseudo(id) = op1 {
-                        let val = hasher.get(id);
-                        src_new = Operand::Stack(val);
-                    }
-                    if let Operand::Pseudo(id) = op2 {
-                        let val = hasher.get(id);
-                        dst_new = Operand::Stack(val);
-                    }
-                    new_instructions.push((i, [AsmInstruction::Cmp(src_new, dst_new)]));
-                }
-                AsmInstruction::Mov { src, dst } => {
-                    let mut src_new = src.clone();
-                    let mut dst_new = dst.clone();
-                    if let Operand::Pseudo(id) = src {
-                        let val = hasher.get(id);
-                        src_new = Operand::Stack(val);
-                    }
-                    if let Operand::Pseudo(id) = dst {
-                        let val = hasher.get(id);
-                        dst_new = Operand::Stack(val);
-                    }
-                    new_instructions.push((
-                        i,
-                        [AsmInstruction::Mov {
-                            src: src_new,
-                            dst: dst_new,
-                        }],
-                    ));
-                }
-                AsmInstruction::Unary(asm_unary_operator, Operand::Pseudo(id)) => {
-                    let val = hasher.get(id);
-                    new_instructions.push((
-                        i,
-                        [AsmInstruction::Unary(
-                            asm_unary_operator.clone(),
-                            Operand::Stack(val),
-                        )],
-                    ));
-                }
-                AsmInstruction::Binary(operator, o1, o2) => {
-                    let mut src_new = o1.clone();
-                    let mut dst_new = o2.clone();
-                    if let Operand::Pseudo(id) = o1 {
-                        let val = hasher.get(id);
-                        src_new = Operand::Stack(val);
-                    }
-                    if let Operand::Pseudo(id) = o2 {
-                        let val = hasher.get(id);
-                        dst_new = Operand::Stack(val);
-                    }
-                    new_instructions.push((
-                        i,
-                        [AsmInstruction::Binary(operator.clone(), src_new, dst_new)],
-                    ));
-                }
-                _ => {}
+/// Gives every pseudo register a stack slot, returns the program and the
+/// size of the stack frame.
+fn replace_pseudo_registers(program: AsmProgram) -> (AsmProgram, i32) {
+    let mut hasher = PseudoRegistryHash::new();
+    let instructions = program
+        .0
+        .instructions
+        .into_iter()
+        .map(|mut instruction| {
+            for operand in instruction.operands_mut() {
+                hasher.replace(operand);
             }
+            instruction
+        })
+        .collect();
+
+    (
+        AsmProgram(AsmFunctionDef {
+            name: program.0.name,
+            instructions,
+            traps: program.0.traps,
+            counters: program.0.counters,
+        }),
+        hasher.stack_to_allocate(),
+    )
+}
+
+/// Adds the prologue allocating `stack` bytes and the epilogue, and rewrites
+/// the instructions x86 doesn't accept.
+fn fix_instructions(program: AsmProgram, stack: i32) -> AsmProgram {
+    let AsmProgram(function) = program;
+    let mut instructions = Vec::with_capacity(function.instructions.len() + 8);
+    instructions.push(AsmInstruction::Push(AsmRegistry::BP));
+    instructions.push(AsmInstruction::MovQ {
+        src: AsmRegistry::SP,
+        dst: AsmRegistry::BP,
+    });
+    instructions.push(AsmInstruction::AllocateStack(stack));
+
+    // Every return jumps to the one epilogue, the last one falls into it.
+    let epilogue = NameGenerator::new(function.name).fresh("epilogue");
+    let mut jumps_to_epilogue = false;
+    let count = function.instructions.len();
+    for (i, instruction) in function.instructions.into_iter().enumerate() {
+        match instruction {
+            AsmInstruction::Return if i + 1 == count => {}
+            AsmInstruction::Return => {
+                instructions.push(AsmInstruction::Jmp(epilogue));
+                jumps_to_epilogue = true;
+            }
+            instruction => fix_instruction(instruction, &mut instructions),
         }
-        for (i, slice) in new_instructions.iter().rev() {
-            replace_with_multiple_elements(&mut instructions, *i, slice);
-        }
+    }
+    if jumps_to_epilogue {
+        instructions.push(AsmInstruction::Label(epilogue));
+    }
+    instructions.push(AsmInstruction::MovQ {
+        src: AsmRegistry::BP,
+        dst: AsmRegistry::SP,
+    });
+    instructions.push(AsmInstruction::Pop(AsmRegistry::BP));
+    instructions.push(AsmInstruction::Ret);
 
-        AsmProgramWithReplacedPseudoRegisters(
-            AsmProgram(AsmFunctionDef {
-                name: value.0.name.clone(),
-                instructions,
-            }),
-            hasher.stack_to_allocate(),
-        )
+    AsmProgram(AsmFunctionDef {
+        name: function.name,
+        instructions,
+        traps: function.traps,
+        counters: function.counters,
+    })
+}
+
+/// Whether the operand is an immediate only `movabsq` can take, one that
+/// doesn't fit the sign extended 32 bits of every other instruction.
+fn is_large_immediate(operand: &Operand) -> bool {
+    matches!(operand, Operand::Imm(value) if i32::try_from(*value).is_err())
+}
+
+/// Rewrites an instruction whose operands x86 does not accept into valid ones.
+fn fix_instruction(instruction: AsmInstruction, out: &mut Vec<AsmInstruction>) {
+    match instruction {
+        // Only a move into a register takes a 64-bit immediate, everything
+        // else reads it from R10.
+        AsmInstruction::Mov {
+            ty: AsmType::Quadword,
+            src,
+            dst: dst @ Operand::Stack(_),
+        } if is_large_immediate(&src) => {
+            out.push(AsmInstruction::Mov {
+                ty: AsmType::Quadword,
+                src,
+                dst: Operand::Register(AsmRegistry::R10),
+            });
+            out.push(AsmInstruction::Mov {
+                ty: AsmType::Quadword,
+                src: Operand::Register(AsmRegistry::R10),
+                dst,
+            });
+        }
+        AsmInstruction::Binary(operator, AsmType::Quadword, src, dst)
+            if is_large_immediate(&src) =>
+        {
+            out.push(AsmInstruction::Mov {
+                ty: AsmType::Quadword,
+                src,
+                dst: Operand::Register(AsmRegistry::R10),
+            });
+            fix_instruction(
+                AsmInstruction::Binary(
+                    operator,
+                    AsmType::Quadword,
+                    Operand::Register(AsmRegistry::R10),
+                    dst,
+                ),
+                out,
+            );
+        }
+        AsmInstruction::Cmp(AsmType::Quadword, src, dst) if is_large_immediate(&src) => {
+            out.push(AsmInstruction::Mov {
+                ty: AsmType::Quadword,
+                src,
+                dst: Operand::Register(AsmRegistry::R10),
+            });
+            fix_instruction(
+                AsmInstruction::Cmp(AsmType::Quadword, Operand::Register(AsmRegistry::R10), dst),
+                out,
+            );
+        }
+        // cmp takes neither two memory operands nor an immediate as the second one
+        AsmInstruction::Cmp(ty, src, Operand::Imm(value)) => {
+            out.push(AsmInstruction::Mov {
+                ty,
+                src: Operand::Imm(value),
+                dst: Operand::Register(AsmRegistry::R11),
+            });
+            out.push(AsmInstruction::Cmp(
+                ty,
+                src,
+                Operand::Register(AsmRegistry::R11),
+            ));
+        }
+        AsmInstruction::Cmp(ty, Operand::Stack(src), Operand::Stack(dst)) => {
+            out.push(AsmInstruction::Mov {
+                ty,
+                src: Operand::Stack(src),
+                dst: Operand::Register(AsmRegistry::R10),
+            });
+            out.push(AsmInstruction::Cmp(
+                ty,
+                Operand::Register(AsmRegistry::R10),
+                Operand::Stack(dst),
+            ));
+        }
+        AsmInstruction::Mov {
+            ty,
+            src: Operand::Stack(src),
+            dst: Operand::Stack(dst),
+        } => {
+            out.push(AsmInstruction::Mov {
+                ty,
+                src: Operand::Stack(src),
+                dst: Operand::Register(AsmRegistry::R10),
+            });
+            out.push(AsmInstruction::Mov {
+                ty,
+                src: Operand::Register(AsmRegistry::R10),
+                dst: Operand::Stack(dst),
+            });
+        }
+        AsmInstruction::Binary(AsmBinaryOperator::Mult, ty, src, dst) => {
+            out.push(AsmInstruction::Mov {
+                ty,
+                src: dst.clone(),
+                dst: Operand::Register(AsmRegistry::R11),
+            });
+            out.push(AsmInstruction::Binary(
+                AsmBinaryOperator::Mult,
+                ty,
+                src,
+                Operand::Register(AsmRegistry::R11),
+            ));
+            out.push(AsmInstruction::Mov {
+                ty,
+                src: Operand::Register(AsmRegistry::R11),
+                dst,
+            });
+        }
+        AsmInstruction::Binary(operator, ty, Operand::Stack(src), Operand::Stack(dst)) => {
+            out.push(AsmInstruction::Mov {
+                ty,
+                src: Operand::Stack(src),
+                dst: Operand::Register(AsmRegistry::R10),
+            });
+            out.push(AsmInstruction::Binary(
+                operator,
+                ty,
+                Operand::Register(AsmRegistry::R10),
+                Operand::Stack(dst),
+            ));
+        }
+        AsmInstruction::Idiv(ty, Operand::Imm(value)) => {
+            out.push(AsmInstruction::Mov {
+                ty,
+                src: Operand::Imm(value),
+                dst: Operand::Register(AsmRegistry::R10),
+            });
+            out.push(AsmInstruction::Idiv(
+                ty,
+                Operand::Register(AsmRegistry::R10),
+            ));
+        }
+        instruction => out.push(instruction),
     }
 }
 
-impl From<AsmProgramWithReplacedPseudoRegisters> for AsmProgramWithFixedInstructions {
-    fn from(value: AsmProgramWithReplacedPseudoRegisters) -> Self {
-        let mut instructions = vec![AsmInstruction::AllocateStack(value.1)];
-        instructions.extend(value.0 .0.instructions.clone());
+impl AsmProgram {
+    /// Assembly of a program whose instructions are fixed up, see
+    /// [`AsmPipeline`].
+    pub fn generate(&self, platform: TargetPlatform) -> AsmGenerated {
+        self.generate_with_source_map(platform, FunctionSections::default())
+            .0
+    }
 
-        let mut to_be_replaced = vec![];
-        for (i, instruction) in instructions.iter().enumerate() {
-            match instruction {
-                AsmInstruction::Cmp(src, dst) => {
-                    let first = AsmInstruction::Mov {
-                        src: src.clone(),
-                        dst: Operand::Register(AsmRegistry::R10),
-                    };
-                    let second =
-                        AsmInstruction::Cmp(Operand::Register(AsmRegistry::R10), dst.clone());
-                    to_be_replaced.push((i, [first, second].to_vec()));
-                }
-                AsmInstruction::Mov { src, dst } => {
-                    let Operand::Stack(src) = src else {
-                        continue;
-                    };
-                    let Operand::Stack(dst) = dst else {
-                        continue;
-                    };
-                    let first = AsmInstruction::Mov {
-                        src: Operand::Stack(*src),
-                        dst: Operand::Register(AsmRegistry::R10),
-                    };
-                    let second = AsmInstruction::Mov {
-                        src: Operand::Register(AsmRegistry::R10),
-                        dst: Operand::Stack(*dst),
-                    };
-                    to_be_replaced.push((i, [first, second].to_vec()));
-                }
-                AsmInstruction::Binary(AsmBinaryOperator::Mult, src, dst) => {
-                    let first = AsmInstruction::Mov {
-                        src: dst.clone(),
-                        dst: Operand::Register(AsmRegistry::R11),
-                    };
-                    let second = AsmInstruction::Binary(
-                        AsmBinaryOperator::Mult,
-                        src.clone(),
-                        Operand::Register(AsmRegistry::R11),
-                    );
-                    let third = AsmInstruction::Mov {
-                        src: Operand::Register(AsmRegistry::R11),
-                        dst: dst.clone(),
-                    };
-                    to_be_replaced.push((i, [first, second, third].to_vec()));
-                }
-                AsmInstruction::Binary(operator, src, dst) => {
-                    let Operand::Stack(src) = src else {
-                        continue;
-                    };
-                    let Operand::Stack(dst) = dst else {
-                        continue;
-                    };
-                    let first = AsmInstruction::Mov {
-                        src: Operand::Stack(*src),
-                        dst: Operand::Register(AsmRegistry::R10),
-                    };
-                    let second = AsmInstruction::Binary(
-                        operator.clone(),
-                        Operand::Register(AsmRegistry::R10),
-                        Operand::Stack(*dst),
-                    );
-                    to_be_replaced.push((i, [first, second].to_vec()));
-                }
-                AsmInstruction::Idiv(op) => {
-                    let Operand::Imm(value) = op else {
-                        continue;
-                    };
-                    let first = AsmInstruction::Mov {
-                        src: Operand::Imm(*value),
-                        dst: Operand::Register(AsmRegistry::R10),
-                    };
-                    let second = AsmInstruction::Idiv(Operand::Register(AsmRegistry::R10));
-                    to_be_replaced.push((i, [first, second].to_vec()));
-                }
-                _ => {}
-            }
+    /// Also maps the lines of the output to the [`AsmInstruction::Location`]
+    /// markers before them.
+    pub fn generate_with_source_map(
+        &self,
+        platform: TargetPlatform,
+        sections: FunctionSections,
+    ) -> (AsmGenerated, SourceMap) {
+        let mut map = SourceMap::default();
+        let mut result = generate_function(&self.0, platform, sections, 0, &mut map);
+        let mut constants = ConstantPool::default();
+        result += &generate_counters(&self.0.counters, &mut constants, platform);
+        result += &generate_constants(&constants, platform);
+        result += &generate_trailer(platform);
+        (AsmGenerated(result), map)
+    }
+}
+
+/// Assembly for a whole translation unit. Functions are written first, into
+/// the text sections of `sections`, then the variables, the counters and the
+/// constants.
+pub fn generate_top_levels(
+    items: &[AsmTopLevel],
+    constants: &ConstantPool,
+    platform: TargetPlatform,
+    sections: FunctionSections,
+) -> AsmGenerated {
+    let mut result = String::with_capacity(500);
+    let mut counters = vec![];
+    for item in items {
+        if let AsmTopLevel::Function(function) = item {
+            let lines = result.lines().count();
+            let map = &mut SourceMap::default();
+            result += &generate_function(function, platform, sections, lines, map);
+            counters.extend(function.counters.iter().cloned());
         }
-        for (i, ins) in to_be_replaced.iter().rev() {
-            replace_with_multiple_elements(&mut instructions, *i, ins);
+    }
+    for item in items {
+        if let AsmTopLevel::StaticVariable(variable) = item {
+            result += &generate_static_variable(variable, platform);
         }
-        AsmProgramWithFixedInstructions(AsmProgram(AsmFunctionDef {
-            name: value.0 .0.name.clone(),
-            instructions,
-        }))
     }
+    let mut constants = constants.clone();
+    result += &generate_counters(&counters, &mut constants, platform);
+    result += &generate_constants(&constants, platform);
+    result += &generate_trailer(platform);
+    AsmGenerated(result)
 }
 
-fn replace_with_multiple_elements<T: Clone>(vec: &mut Vec<T>, idx: usize, slice: &[T]) {
-    if idx < vec.len() {
-        // Remove the element at index idx
-        vec.remove(idx);
-        for el in slice.iter().rev() {
-            vec.insert(idx, el.clone());
+/// `lines` is the number of lines written before the function, the mappings
+/// of its code go to `map`.
+fn generate_function(
+    function_def: &AsmFunctionDef,
+    platform: TargetPlatform,
+    sections: FunctionSections,
+    lines: usize,
+    map: &mut SourceMap,
+) -> String {
+    let mut result = String::with_capacity(500);
+    let conventions = platform.conventions();
+    let mangler = Mangler::new(platform);
+    let name = mangler.global(&function_def.name);
+    result += &conventions.function_section(&name, sections);
+    // Every function is global until there are `static` ones.
+    result += &format!("\t.globl {}\n", name);
+    if conventions.symbol_type_and_size {
+        result += &format!("\t.type\t{}, @function\n", name);
+    }
+    result += &format!("{}:\n", name);
+    let mut asm_line = lines + result.lines().count();
+    let mut location = None;
+    for instruction in function_def.instructions.iter() {
+        let code = match instruction {
+            AsmInstruction::Location { line, column } => {
+                location = Some((*line, *column));
+                continue;
+            }
+            AsmInstruction::Mov {
+                ty: AsmType::Quadword,
+                src,
+                dst,
+            } if is_large_immediate(src) => {
+                format!("\tmovabsq\t{}, {}\n", src, dst.sized(AsmType::Quadword))
+            }
+            AsmInstruction::Mov { ty, src, dst } => format!(
+                "\tmov{}\t{}, {}\n",
+                ty.suffix(),
+                src.sized(*ty),
+                dst.sized(*ty)
+            ),
+            AsmInstruction::Unary(asm_unary_operator, ty, operand) => format!(
+                "\t{}{}\t{}\n",
+                asm_unary_operator,
+                ty.suffix(),
+                operand.sized(*ty)
+            ),
+            AsmInstruction::AllocateStack(i) => format!("\tsubq ${}, %rsp\n", i),
+            AsmInstruction::Push(register) => format!("\tpushq\t{}\n", register.quad_name()),
+            AsmInstruction::Pop(register) => format!("\tpopq\t{}\n", register.quad_name()),
+            AsmInstruction::MovQ { src, dst } => {
+                format!("\tmovq\t{}, {}\n", src.quad_name(), dst.quad_name())
+            }
+            AsmInstruction::Return => {
+                unreachable!("returns are replaced when fixing up the instructions")
+            }
+            AsmInstruction::Ret => "\tret\n".to_string(),
+            AsmInstruction::Cdq(AsmType::Quadword) => "\tcqo\n".to_string(),
+            AsmInstruction::Cdq(_) => "\tcdq\n".to_string(),
+            AsmInstruction::Binary(operator, ty, op1, op2) => format!(
+                "\t{}{}\t{}, {}\n",
+                operator,
+                ty.suffix(),
+                op1.sized(*ty),
+                op2.sized(*ty)
+            ),
+            AsmInstruction::Idiv(ty, op) => format!("\tidiv{}\t{}\n", ty.suffix(), op.sized(*ty)),
+            AsmInstruction::Lea { base, offset, dst } => {
+                format!("\tleal\t{}({}), {}\n", offset, base.quad_name(), dst)
+            }
+            AsmInstruction::Cmp(ty, o, o2) => format!(
+                "\tcmp{}\t{}, {}\n",
+                ty.suffix(),
+                o.sized(*ty),
+                o2.sized(*ty)
+            ),
+            AsmInstruction::Jmp(id) => format!("\tjmp\t{}\n", mangler.local(id)),
+            AsmInstruction::JmpCC(cc, o) => format!("\tj{}\t{}\n", cc, mangler.local(o)),
+            AsmInstruction::SetCC(cc, o) => {
+                format!("\tset{}\t{}\n", cc, o.sized(AsmType::Byte))
+            }
+            AsmInstruction::Label(id) => format!("{}:\n", mangler.local(id)),
+            AsmInstruction::IncrementCounter(label) => {
+                format!("\tincq\t{}(%rip)\n", mangler.global(label))
+            }
+        };
+        for _ in code.lines() {
+            asm_line += 1;
+            if let Some((line, column)) = location {
+                map.mappings.push(Mapping {
+                    asm_line,
+                    line,
+                    column,
+                });
+            }
         }
+        result += &code;
     }
+    result += &generate_traps(&function_def.traps, platform);
+
+    if conventions.symbol_type_and_size {
+        // The size spans the trap stubs too, so `nm -S` and `objdump`
+        // attribute them to the function.
+        result += &format!("\t.size\t{0}, .-{0}\n", name);
+    }
+    result
 }
 
-impl AsmProgramWithFixedInstructions {
-    pub fn generate(&self, platform: TargetPlatform) -> AsmGenerated {
-        let mut result = String::with_capacity(500);
+fn generate_static_variable(variable: &StaticVariable, platform: TargetPlatform) -> String {
+    let mut result = String::new();
+    let conventions = platform.conventions();
+    let name = Mangler::new(platform).global(&variable.name);
+    if variable.global {
+        result += &format!("\t.globl {}\n", name);
+    }
+    let zeroed = variable.init.iter().all(StaticInit::is_zero);
+    result += if zeroed { "\t.bss\n" } else { "\t.data\n" };
+    result += &conventions.align(variable.alignment);
+    if conventions.symbol_type_and_size {
+        result += &format!("\t.type\t{}, @object\n", name);
+        let size: u32 = variable.init.iter().map(StaticInit::size).sum();
+        result += &format!("\t.size\t{}, {}\n", name, size);
+    }
+    result += &format!("{}:\n", name);
+    for init in &variable.init {
+        result += &match init {
+            _ if zeroed => format!("\t.zero {}\n", init.size()),
+            StaticInit::Long(value) => format!("\t.long {}\n", value),
+            StaticInit::Quad(value) => format!("\t.quad {}\n", value),
+            StaticInit::Zero(bytes) => format!("\t.zero {}\n", bytes),
+        };
+    }
+    result
+}
 
-        let function_def = &self.0 .0;
-        if platform == TargetPlatform::MacOsX64 {
-            result += &format!("\t.globl _{}\n", function_def.name);
-            result += &format!("_{}:\n", function_def.name);
-        } else {
-            result += &format!("\t.globl {}\n", function_def.name);
-            result += &format!(".{}:\n", function_def.name);
-        }
-        result += "\tpush\t%rbp\n";
-        result += "\tmov\t%rsp, %rbp\n";
-        for instruction in function_def.instructions.iter() {
-            result += &match instruction {
-                AsmInstruction::Mov { src, dst } => format!("\tmovl\t{}, {}\n", src, dst),
-                AsmInstruction::Unary(asm_unary_operator, operand) => {
-                    format!("\t{}\t{}\n", asm_unary_operator, operand)
-                }
-                AsmInstruction::AllocateStack(i) => format!("\tsubq ${}, %rsp\n", i),
-                AsmInstruction::Return => "\tmovq\t%rbp, %rsp\n\tpopq\t%rbp\n\tret\n".to_string(),
-                AsmInstruction::Cdq => "\tcdq\n".to_string(),
-                AsmInstruction::Binary(operator, op1, op2) => {
-                    format!("\t{}\t{}, {}\n", operator, op1, op2)
-                }
-                AsmInstruction::Idiv(op) => format!("\tidivl\t{}\n", op),
-                AsmInstruction::Cmp(o, o2) => format!("\tcmpl\t{}, {}\n", o2, o),
-                AsmInstruction::Jmp(id) => format!("\tjmp\t.L{}\n", id),
-                AsmInstruction::JmpCC(cc, o) => format!("\tj{}\t.L{}\n", cc, o),
-                AsmInstruction::SetCC(cc, o) => format!("\tset{}\t{}\n", cc, o),
-                AsmInstruction::Label(id) => format!(".L{}:\n", id),
+/// The counters, zeroed in `.bss`, and a constructor registering with
+/// `atexit` a routine that writes them out: the calls to stderr and the
+/// blocks to their coverage file, replacing what an earlier run wrote.
+fn generate_counters(
+    counters: &[Counter],
+    constants: &mut ConstantPool,
+    platform: TargetPlatform,
+) -> String {
+    if counters.is_empty() {
+        return String::new();
+    }
+    let mut result = String::new();
+    for counter in counters {
+        result += &generate_static_variable(
+            &StaticVariable {
+                name: counter.label,
+                global: false,
+                alignment: 8,
+                init: vec![StaticInit::Quad(0)],
+            },
+            platform,
+        );
+    }
+    let conventions = platform.conventions();
+    let mangler = Mangler::new(platform);
+    let open_flags = conventions.open_truncate_flags;
+    let init = mangler.local("ulang.counters.init");
+    let dump = mangler.local("ulang.counters.dump");
+    result += &format!("\t{}\n", conventions.init_section);
+    result += &conventions.align(8);
+    result += &format!("\t.quad\t{}\n", init);
+    result += "\t.text\n";
+    result += &format!("{}:\n", init);
+    result += "\tpushq\t%rbp\n";
+    result += "\tmovq\t%rsp, %rbp\n";
+    result += &format!("\tleaq\t{}(%rip), %rdi\n", dump);
+    result += &format!("\tcall\t{}\n", mangler.libc("atexit"));
+    result += "\tpopq\t%rbp\n";
+    result += "\tret\n";
+
+    // dprintf(fd, format, counter) with the descriptor already in %edi, %al
+    // holds the number of vector registers the variadic call gets.
+    let print = |format: Identifier, counter: &Counter| {
+        let mut code = format!("\tleaq\t{}(%rip), %rsi\n", mangler.local(format));
+        code += &format!("\tmovq\t{}(%rip), %rdx\n", mangler.global(&counter.label));
+        code += "\txorl\t%eax, %eax\n";
+        code += &format!("\tcall\t{}\n", mangler.libc("dprintf"));
+        code
+    };
+    // %rbx keeps the descriptor of the coverage file across the calls, the
+    // padding after it keeps the stack aligned.
+    result += &format!("{}:\n", dump);
+    result += "\tpushq\t%rbp\n";
+    result += "\tmovq\t%rsp, %rbp\n";
+    result += "\tpushq\t%rbx\n";
+    result += "\tsubq\t$8, %rsp\n";
+    for counter in counters {
+        if let CounterReport::Calls(function) = &counter.report {
+            result += "\tmovl\t$2, %edi\n";
+            let format = constants.string(&format!("%lu\t{}\n", function));
+            result += &print(format, counter);
+        }
+    }
+    let mut files: Vec<&str> = vec![];
+    for counter in counters {
+        if let CounterReport::Block { file, .. } = &counter.report {
+            if !files.contains(&file.as_str()) {
+                files.push(file);
             }
         }
+    }
+    for (i, file) in files.into_iter().enumerate() {
+        let path = constants.string(file);
+        let skip = mangler.local(format_args!("ulang.counters.skip.{}", i));
+        result += &format!("\tleaq\t{}(%rip), %rdi\n", mangler.local(path));
+        result += &format!("\tmovl\t${}, %esi\n", open_flags);
+        result += &format!("\tmovl\t${}, %edx\n", 0o644);
+        result += "\txorl\t%eax, %eax\n";
+        result += &format!("\tcall\t{}\n", mangler.libc("open"));
+        result += "\ttestl\t%eax, %eax\n";
+        result += &format!("\tjs\t{}\n", skip);
+        result += "\tmovl\t%eax, %ebx\n";
+        for counter in counters {
+            let CounterReport::Block { file: f, lines } = &counter.report else {
+                continue;
+            };
+            if f != file {
+                continue;
+            }
+            for line in lines {
+                result += "\tmovl\t%ebx, %edi\n";
+                let format = constants.string(&format!("{}\t%lu\n", line));
+                result += &print(format, counter);
+            }
+        }
+        result += "\tmovl\t%ebx, %edi\n";
+        result += &format!("\tcall\t{}\n", mangler.libc("close"));
+        result += &format!("{}:\n", skip);
+    }
+    result += "\taddq\t$8, %rsp\n";
+    result += "\tpopq\t%rbx\n";
+    result += "\tpopq\t%rbp\n";
+    result += "\tret\n";
+    result
+}
 
-        if platform == TargetPlatform::X64Linux {
-            result += "\t.section\t.note.GNU-stack,\"\",@progbits\n";
+/// Strings go to the section the linker merges equal C strings in, doubles to
+/// the one for 8 byte literals on macOS and to `.rodata` on Linux.
+fn generate_constants(constants: &ConstantPool, platform: TargetPlatform) -> String {
+    let mut result = String::new();
+    let conventions = platform.conventions();
+    let mangler = Mangler::new(platform);
+    for (label, constant) in constants.constants() {
+        match constant {
+            Constant::String(text) => {
+                result += &format!("\t{}\n", conventions.string_section);
+                result += &format!("{}:\n", mangler.local(label));
+                result += &format!("\t.asciz\t\"{}\"\n", escape_ascii(text));
+            }
+            Constant::Double(value) => {
+                result += &format!("\t{}\n", conventions.literal8_section);
+                result += &conventions.align(8);
+                result += &format!("{}:\n", mangler.local(label));
+                result += &format!("\t.quad\t{}\n", value.to_bits());
+            }
         }
-        AsmGenerated(result)
     }
+    result
+}
+
+/// Directives closing the file.
+fn generate_trailer(platform: TargetPlatform) -> String {
+    let conventions = platform.conventions();
+    let compiler = format!("ulang {}", env!("CARGO_PKG_VERSION"));
+    let mut result = if conventions.ident_directive {
+        format!("\t.ident\t\"{}\"\n", compiler)
+    } else {
+        format!("{} {}\n", conventions.comment, compiler)
+    };
+    if let Some(note) = conventions.stack_note {
+        result += &format!("\t.section\t{}\n", note);
+    }
+    result
+}
+
+/// Stubs loading the message of each trap, the routine they jump to and the
+/// message texts.
+fn generate_traps(traps: &[Trap], platform: TargetPlatform) -> String {
+    if traps.is_empty() {
+        return String::new();
+    }
+    let mut result = String::new();
+    let mangler = Mangler::new(platform);
+    let routine = mangler.local("ulang.trap");
+    for trap in traps {
+        result += &format!("{}:\n", mangler.local(trap.label));
+        result += &format!("\tleaq\t{}.msg(%rip), %rsi\n", mangler.local(trap.label));
+        result += &format!("\tmovl\t${}, %edx\n", trap.message.len());
+        result += &format!("\tjmp\t{}\n", routine);
+    }
+    // write(2, message, length) followed by _exit, the stack alignment at the
+    // failed check is unknown so it is fixed up before calling into libc.
+    result += &format!("{}:\n", routine);
+    result += "\tandq\t$-16, %rsp\n";
+    result += "\tmovl\t$2, %edi\n";
+    result += &format!("\tcall\t{}\n", mangler.libc("write"));
+    result += &format!("\tmovl\t${}, %edi\n", TRAP_EXIT_STATUS);
+    result += &format!("\tcall\t{}\n", mangler.libc("_exit"));
+    for trap in traps {
+        result += &format!("{}.msg:\n", mangler.local(trap.label));
+        result += &format!("\t.ascii\t\"{}\"\n", escape_ascii(&trap.message));
+    }
+    result
+}
+
+/// Escapes `text` for an `.ascii` directive.
+fn escape_ascii(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'"' => "\\\"".to_string(),
+            b'\\' => "\\\\".to_string(),
+            b'\n' => "\\n".to_string(),
+            0x20..=0x7e => (byte as char).to_string(),
+            _ => format!("\\{:03o}", byte),
+        })
+        .collect()
+}
+
+/// Process entry point for programs linked without the C runtime, it calls
+/// `entry` and passes its result to the exit system call.
+pub fn generate_start(entry: &Identifier, platform: TargetPlatform) -> String {
+    let conventions = platform.conventions();
+    let (start, exit) = (conventions.start_symbol, conventions.exit_syscall);
+    let mut result = String::from("\t.text\n");
+    result += &format!("\t.globl {}\n", start);
+    if conventions.symbol_type_and_size {
+        result += &format!("\t.type\t{}, @function\n", start);
+    }
+    result += &format!("{}:\n", start);
+    // The stack is 16 byte aligned here, so after the call it is aligned
+    // like in any other function.
+    result += "\txorl\t%ebp, %ebp\n";
+    result += &format!("\tcall\t{}\n", Mangler::new(platform).global(entry));
+    result += "\tmovl\t%eax, %edi\n";
+    result += &format!("\tmovl\t${}, %eax\n", exit);
+    result += "\tsyscall\n";
+    result
 }
 
 pub fn generate_assembly(tacky: &TackyProgram, target: TargetPlatform) -> AsmGenerated {
-    let asm_ast: AsmProgram = tacky.into();
+    generate_optimized_assembly(
+        tacky,
+        target,
+        &PassManager::default(),
+        &RuntimeChecks::default(),
+    )
+}
+
+/// Same as [`generate_assembly`], compiling in `checks` and running the
+/// assembly passes of `passes` on the fixed up program.
+pub fn generate_optimized_assembly(
+    tacky: &TackyProgram,
+    target: TargetPlatform,
+    passes: &PassManager,
+    checks: &RuntimeChecks,
+) -> AsmGenerated {
+    generate_assembly_with_source_map(
+        tacky,
+        target,
+        passes,
+        checks,
+        &Instrumentation::default(),
+        FunctionSections::default(),
+    )
+    .0
+}
 
-    let asm_replaced: AsmProgramWithReplacedPseudoRegisters = asm_ast.into();
+/// Same as [`generate_optimized_assembly`], adding `instrumentation`, putting
+/// the function in the section `sections` gives it and also returning where
+/// the lines came from when `tacky` has [`Instruction::Location`] markers.
+pub fn generate_assembly_with_source_map(
+    tacky: &TackyProgram,
+    target: TargetPlatform,
+    passes: &PassManager,
+    checks: &RuntimeChecks,
+    instrumentation: &Instrumentation,
+    sections: FunctionSections,
+) -> (AsmGenerated, SourceMap) {
+    let mut asm_ast = AsmProgram::new(tacky, checks);
+    asm_ast.instrument(instrumentation);
 
-    let asm_fixed: AsmProgramWithFixedInstructions = asm_replaced.into();
+    let mut pipeline = AsmPipeline::new(asm_ast);
+    pipeline.run_all(passes).expect("the passes run in order");
 
-    asm_fixed.generate(target)
+    pipeline
+        .program()
+        .generate_with_source_map(target, sections)
 }
@@ -0,0 +1,154 @@
+use super::{AsmBinaryOperator, AsmInstruction, AsmProgram, AsmType, AsmUnaryOperator, Operand};
+
+/// Looks at the instructions starting at the current one and, when it
+/// matches, returns how many of them it replaces and with what.
+type Rule = fn(&[AsmInstruction]) -> Option<(usize, Vec<AsmInstruction>)>;
+
+const RULES: [Rule; 5] = [
+    remove_self_move,
+    remove_jump_to_next,
+    add_with_lea,
+    zero_with_xor,
+    increment_decrement,
+];
+
+impl AsmProgram {
+    /// Rewrites the instructions with [`RULES`], trying them in order at every
+    /// instruction and moving past whatever a rule produced.
+    pub fn peephole(&mut self) {
+        let instructions = std::mem::take(&mut self.0.instructions);
+        let mut result: Vec<AsmInstruction> = Vec::with_capacity(instructions.len());
+        let mut i = 0;
+        while i < instructions.len() {
+            let window = &instructions[i..];
+            match RULES.iter().find_map(|rule| rule(window)) {
+                Some((consumed, replacement)) => {
+                    result.extend(replacement);
+                    i += consumed;
+                }
+                None => {
+                    result.push(window[0].clone());
+                    i += 1;
+                }
+            }
+        }
+        self.0.instructions = result;
+    }
+}
+
+/// `movl %eax, %eax` has no effect.
+fn remove_self_move(window: &[AsmInstruction]) -> Option<(usize, Vec<AsmInstruction>)> {
+    match window {
+        [AsmInstruction::Mov { src, dst, .. }, ..] if src == dst => Some((1, vec![])),
+        _ => None,
+    }
+}
+
+/// A jump to the label right after it falls through anyway.
+fn remove_jump_to_next(window: &[AsmInstruction]) -> Option<(usize, Vec<AsmInstruction>)> {
+    match window {
+        [AsmInstruction::Jmp(target), AsmInstruction::Label(label), ..] if target == label => {
+            Some((1, vec![]))
+        }
+        _ => None,
+    }
+}
+
+/// `movl %eax, %edx; addl $c, %edx` becomes `leal c(%rax), %edx`, which does
+/// not write the flags, so it only applies when nothing reads them after.
+fn add_with_lea(window: &[AsmInstruction]) -> Option<(usize, Vec<AsmInstruction>)> {
+    match window {
+        [AsmInstruction::Mov {
+            ty: AsmType::Longword,
+            src: Operand::Register(base),
+            dst: Operand::Register(dst),
+        }, AsmInstruction::Binary(
+            AsmBinaryOperator::Add,
+            AsmType::Longword,
+            Operand::Imm(offset),
+            Operand::Register(add_dst),
+        ), rest @ ..]
+            if base != dst && dst == add_dst && flags_unused(rest) =>
+        {
+            Some((
+                2,
+                vec![AsmInstruction::Lea {
+                    base: base.clone(),
+                    offset: *offset as i32,
+                    dst: dst.clone(),
+                }],
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// `xorl %eax, %eax` is shorter than `movl $0, %eax` but clears the flags.
+fn zero_with_xor(window: &[AsmInstruction]) -> Option<(usize, Vec<AsmInstruction>)> {
+    match window {
+        [AsmInstruction::Mov {
+            ty,
+            src: Operand::Imm(0),
+            dst: dst @ Operand::Register(_),
+        }, rest @ ..]
+            if flags_unused(rest) =>
+        {
+            Some((
+                1,
+                vec![AsmInstruction::Binary(
+                    AsmBinaryOperator::Xor,
+                    *ty,
+                    dst.clone(),
+                    dst.clone(),
+                )],
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Adding or subtracting one becomes `incl`/`decl`. They leave the carry flag
+/// alone, which no condition code used here reads, and set overflow the same.
+fn increment_decrement(window: &[AsmInstruction]) -> Option<(usize, Vec<AsmInstruction>)> {
+    let [AsmInstruction::Binary(operator, ty, Operand::Imm(value), dst), ..] = window else {
+        return None;
+    };
+    let operator = match (operator, value) {
+        (AsmBinaryOperator::Add, 1) | (AsmBinaryOperator::Sub, -1) => AsmUnaryOperator::Inc,
+        (AsmBinaryOperator::Add, -1) | (AsmBinaryOperator::Sub, 1) => AsmUnaryOperator::Dec,
+        _ => return None,
+    };
+    Some((1, vec![AsmInstruction::Unary(operator, *ty, dst.clone())]))
+}
+
+/// Whether the flags are written again, or the function returns, before
+/// anything reads them along the fallthrough path. Jumps are not followed,
+/// the flags are assumed to be read at their target.
+fn flags_unused(rest: &[AsmInstruction]) -> bool {
+    for instruction in rest {
+        match instruction {
+            AsmInstruction::JmpCC(..) | AsmInstruction::SetCC(..) | AsmInstruction::Jmp(_) => {
+                return false
+            }
+            AsmInstruction::Cmp(..)
+            | AsmInstruction::Binary(..)
+            | AsmInstruction::Idiv(..)
+            | AsmInstruction::AllocateStack(_)
+            | AsmInstruction::IncrementCounter(_)
+            | AsmInstruction::Return
+            | AsmInstruction::Ret => return true,
+            // notl is the only unary instruction that keeps the flags
+            AsmInstruction::Unary(AsmUnaryOperator::Complement, ..) => {}
+            AsmInstruction::Unary(..) => return true,
+            AsmInstruction::Mov { .. }
+            | AsmInstruction::MovQ { .. }
+            | AsmInstruction::Push(_)
+            | AsmInstruction::Pop(_)
+            | AsmInstruction::Lea { .. }
+            | AsmInstruction::Label(_)
+            | AsmInstruction::Location { .. }
+            | AsmInstruction::Cdq(_) => {}
+        }
+    }
+    true
+}
@@ -0,0 +1,177 @@
+//! The passes between the assembly selected from TACKY and the one that gets
+//! emitted, run one at a time so a tool can look at the program after each.
+//!
+//! ```text
+//! coalesce_slots -> replace_pseudos -> fix_instructions -> peephole
+//! ```
+//!
+//! A pass run before the ones it needs, after the ones that have to follow
+//! it, or a second time when it can't be, is an [`AsmPipelineError`] and
+//! leaves the program as it was.
+
+use std::fmt;
+
+use thiserror::Error;
+
+use super::{fix_instructions, replace_pseudo_registers, AsmFunctionDef, AsmProgram};
+use crate::optimize::PassManager;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsmPass {
+    /// Lets pseudo registers that are never live together share a stack
+    /// slot, see [`AsmProgram::coalesce_stack_slots`].
+    CoalesceSlots,
+    /// Gives every pseudo register a stack slot.
+    ReplacePseudos,
+    /// Adds the prologue and the epilogue and rewrites the operands x86
+    /// doesn't accept.
+    FixInstructions,
+    /// Removes redundant instructions, see [`AsmProgram::peephole`].
+    Peephole,
+}
+
+impl AsmPass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AsmPass::CoalesceSlots => "coalesce-slots",
+            AsmPass::ReplacePseudos => "replace-pseudos",
+            AsmPass::FixInstructions => "fix-instructions",
+            AsmPass::Peephole => "peephole",
+        }
+    }
+
+    /// The pass that has to run before this one.
+    pub fn requires(&self) -> Option<AsmPass> {
+        match self {
+            AsmPass::CoalesceSlots | AsmPass::ReplacePseudos => None,
+            AsmPass::FixInstructions => Some(AsmPass::ReplacePseudos),
+            AsmPass::Peephole => Some(AsmPass::FixInstructions),
+        }
+    }
+
+    /// The pass that must not have run before this one.
+    pub fn precedes(&self) -> Option<AsmPass> {
+        match self {
+            AsmPass::CoalesceSlots => Some(AsmPass::ReplacePseudos),
+            _ => None,
+        }
+    }
+
+    /// Whether running the pass again is allowed, the peephole rules can
+    /// find more to do after their own changes.
+    pub fn repeatable(&self) -> bool {
+        *self == AsmPass::Peephole
+    }
+}
+
+impl fmt::Display for AsmPass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AsmPipelineError {
+    #[error("The {pass} pass needs {requires} to run first")]
+    OutOfOrder { pass: AsmPass, requires: AsmPass },
+    #[error("The {pass} pass has to run before {precedes}")]
+    TooLate { pass: AsmPass, precedes: AsmPass },
+    #[error("The {0} pass already ran")]
+    AlreadyRan(AsmPass),
+}
+
+#[derive(Debug, Clone)]
+pub struct AsmPipeline {
+    program: AsmProgram,
+    /// Size of the stack frame, known once the pseudo registers are replaced.
+    stack: i32,
+    completed: Vec<AsmPass>,
+}
+
+impl AsmPipeline {
+    /// `program` as it is selected from TACKY, before any pass.
+    pub fn new(program: AsmProgram) -> Self {
+        Self {
+            program,
+            stack: 0,
+            completed: vec![],
+        }
+    }
+
+    pub fn program(&self) -> &AsmProgram {
+        &self.program
+    }
+
+    pub fn into_program(self) -> AsmProgram {
+        self.program
+    }
+
+    /// Passes run so far, in order.
+    pub fn completed(&self) -> &[AsmPass] {
+        &self.completed
+    }
+
+    pub fn coalesce_slots(&mut self) -> Result<&AsmProgram, AsmPipelineError> {
+        self.run(AsmPass::CoalesceSlots)
+    }
+
+    pub fn replace_pseudos(&mut self) -> Result<&AsmProgram, AsmPipelineError> {
+        self.run(AsmPass::ReplacePseudos)
+    }
+
+    pub fn fix_instructions(&mut self) -> Result<&AsmProgram, AsmPipelineError> {
+        self.run(AsmPass::FixInstructions)
+    }
+
+    pub fn peephole(&mut self) -> Result<&AsmProgram, AsmPipelineError> {
+        self.run(AsmPass::Peephole)
+    }
+
+    /// Runs the passes every program needs with the assembly passes of
+    /// `passes`, the ones on pseudo registers before they are replaced.
+    pub fn run_all(&mut self, passes: &PassManager) -> Result<&AsmProgram, AsmPipelineError> {
+        passes.run_assembly_on_pseudos(self)?;
+        self.replace_pseudos()?;
+        self.fix_instructions()?;
+        passes.run_assembly(self)?;
+        Ok(&self.program)
+    }
+
+    /// Runs `pass` and returns the program it produced.
+    pub fn run(&mut self, pass: AsmPass) -> Result<&AsmProgram, AsmPipelineError> {
+        if let Some(requires) = pass.requires().filter(|p| !self.completed.contains(p)) {
+            return Err(AsmPipelineError::OutOfOrder { pass, requires });
+        }
+        if let Some(precedes) = pass.precedes().filter(|p| self.completed.contains(p)) {
+            return Err(AsmPipelineError::TooLate { pass, precedes });
+        }
+        if !pass.repeatable() && self.completed.contains(&pass) {
+            return Err(AsmPipelineError::AlreadyRan(pass));
+        }
+        match pass {
+            AsmPass::CoalesceSlots => self.program.coalesce_stack_slots(),
+            AsmPass::ReplacePseudos => {
+                let (program, stack) = replace_pseudo_registers(self.take());
+                self.program = program;
+                self.stack = stack;
+            }
+            AsmPass::FixInstructions => {
+                self.program = fix_instructions(self.take(), self.stack);
+            }
+            AsmPass::Peephole => self.program.peephole(),
+        }
+        self.completed.push(pass);
+        Ok(&self.program)
+    }
+
+    /// The program, leaving an empty function of the same name in its place.
+    fn take(&mut self) -> AsmProgram {
+        let empty = AsmProgram(AsmFunctionDef {
+            name: self.program.0.name,
+            instructions: vec![],
+            traps: vec![],
+            counters: vec![],
+        });
+        std::mem::replace(&mut self.program, empty)
+    }
+}
@@ -0,0 +1,67 @@
+//! Relates lines of the generated assembly to the C source they came from.
+//!
+//! Only code after an [`AsmInstruction::Location`](super::AsmInstruction::Location)
+//! marker is mapped, so the prologue and the trap stubs have no entry.
+
+use std::fmt::Write;
+
+use crate::json::string;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// Ordered by `asm_line`.
+    pub mappings: Vec<Mapping>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mapping {
+    /// 1-based line in the assembly.
+    pub asm_line: usize,
+    /// 1-based line in the C source.
+    pub line: usize,
+    /// 1-based column in the C source.
+    pub column: usize,
+}
+
+impl SourceMap {
+    /// Position in the C source of the code at `asm_line`.
+    pub fn get(&self, asm_line: usize) -> Option<&Mapping> {
+        self.mappings
+            .binary_search_by_key(&asm_line, |mapping| mapping.asm_line)
+            .ok()
+            .map(|i| &self.mappings[i])
+    }
+
+    /// The sidecar file written by `--source-map`, `file` being the assembly
+    /// and `source` the C file it was compiled from.
+    ///
+    /// ```json
+    /// {
+    ///   "version": 1,
+    ///   "file": "main.s",
+    ///   "source": "main.c",
+    ///   "mappings": [
+    ///     {"asm_line": 7, "line": 2, "column": 5}
+    ///   ]
+    /// }
+    /// ```
+    pub fn to_json(&self, file: &str, source: &str) -> String {
+        let mut json = String::new();
+        let _ = writeln!(json, "{{");
+        let _ = writeln!(json, "  \"version\": 1,");
+        let _ = writeln!(json, "  \"file\": {},", string(file));
+        let _ = writeln!(json, "  \"source\": {},", string(source));
+        let _ = writeln!(json, "  \"mappings\": [");
+        for (i, mapping) in self.mappings.iter().enumerate() {
+            let separator = if i + 1 < self.mappings.len() { "," } else { "" };
+            let _ = writeln!(
+                json,
+                "    {{\"asm_line\": {}, \"line\": {}, \"column\": {}}}{}",
+                mapping.asm_line, mapping.line, mapping.column, separator
+            );
+        }
+        let _ = writeln!(json, "  ]");
+        let _ = writeln!(json, "}}");
+        json
+    }
+}
@@ -0,0 +1,155 @@
+//! Sharing stack slots between pseudo registers that are never live at the
+//! same time.
+//!
+//! Every pseudo register gets its own 4 bytes of the frame, so a long
+//! expression needs as many slots as it has temporaries, even though each is
+//! read once right after it is written. Before the slots are handed out,
+//! pseudo registers that don't interfere are renamed to one of them, and
+//! [`replace_pseudos`](super::AsmPipeline::replace_pseudos) gives the
+//! group a single slot.
+//!
+//! Two pseudo registers interfere when one is written while the other is
+//! live, a write that is never read included, it would still clobber the
+//! slot.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::Identifier;
+
+use super::{AsmInstruction, AsmProgram, Operand};
+
+impl AsmProgram {
+    /// Renames the pseudo registers so the ones that don't interfere share
+    /// a name, the first of their group in the order they appear. Copies
+    /// between two of the same group are left out.
+    pub fn coalesce_stack_slots(&mut self) {
+        let instructions = &mut self.0.instructions;
+        let live_out = live_out(instructions);
+
+        let mut order: Vec<Identifier> = vec![];
+        let mut interference: HashMap<Identifier, HashSet<Identifier>> = HashMap::new();
+        for (instruction, live) in instructions.iter().zip(&live_out) {
+            let (uses, defs) = uses_and_defs(instruction);
+            for id in uses.iter().chain(&defs) {
+                if !interference.contains_key(id) {
+                    interference.insert(*id, HashSet::new());
+                    order.push(*id);
+                }
+            }
+            for def in &defs {
+                for other in live.iter().filter(|other| *other != def) {
+                    interference.entry(*def).or_default().insert(*other);
+                    interference.entry(*other).or_default().insert(*def);
+                }
+            }
+        }
+
+        // Greedy, every pseudo register joins the first group none of its
+        // neighbours is in.
+        let mut groups: Vec<Identifier> = vec![];
+        let mut group_of: HashMap<Identifier, usize> = HashMap::new();
+        for id in order {
+            let taken: HashSet<usize> = interference[&id]
+                .iter()
+                .filter_map(|other| group_of.get(other).copied())
+                .collect();
+            let group = (0..)
+                .find(|g| !taken.contains(g))
+                .expect("groups are unbounded");
+            if group == groups.len() {
+                groups.push(id);
+            }
+            group_of.insert(id, group);
+        }
+
+        for instruction in instructions.iter_mut() {
+            for operand in instruction.operands_mut() {
+                if let Operand::Pseudo(id) = operand {
+                    *id = groups[group_of[id]];
+                }
+            }
+        }
+        instructions.retain(
+            |instruction| !matches!(instruction, AsmInstruction::Mov { src, dst, .. } if src == dst),
+        );
+    }
+}
+
+/// Pseudo registers the instruction reads and writes. `setcc` only writes
+/// the low byte, so it reads the rest.
+fn uses_and_defs(instruction: &AsmInstruction) -> (Vec<Identifier>, Vec<Identifier>) {
+    let pseudo = |operand: &Operand| match operand {
+        Operand::Pseudo(id) => Some(*id),
+        _ => None,
+    };
+    match instruction {
+        AsmInstruction::Mov { src, dst, .. } => (
+            pseudo(src).into_iter().collect(),
+            pseudo(dst).into_iter().collect(),
+        ),
+        AsmInstruction::Binary(_, _, src, dst) => {
+            let dst = pseudo(dst);
+            (
+                pseudo(src).into_iter().chain(dst).collect(),
+                dst.into_iter().collect(),
+            )
+        }
+        AsmInstruction::Unary(_, _, operand) | AsmInstruction::SetCC(_, operand) => {
+            let operand: Vec<_> = pseudo(operand).into_iter().collect();
+            (operand.clone(), operand)
+        }
+        AsmInstruction::Cmp(_, a, b) => (pseudo(a).into_iter().chain(pseudo(b)).collect(), vec![]),
+        AsmInstruction::Idiv(_, operand) => (pseudo(operand).into_iter().collect(), vec![]),
+        _ => (vec![], vec![]),
+    }
+}
+
+/// Pseudo registers live after every instruction.
+fn live_out(instructions: &[AsmInstruction]) -> Vec<HashSet<Identifier>> {
+    let labels: HashMap<Identifier, usize> = instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, instruction)| match instruction {
+            AsmInstruction::Label(id) => Some((*id, i)),
+            _ => None,
+        })
+        .collect();
+    // Jumps to a trap leave the function.
+    let successors = |i: usize| -> Vec<usize> {
+        let next = (i + 1 < instructions.len()).then_some(i + 1);
+        match &instructions[i] {
+            AsmInstruction::Return | AsmInstruction::Ret => vec![],
+            AsmInstruction::Jmp(target) => labels.get(target).copied().into_iter().collect(),
+            AsmInstruction::JmpCC(_, target) => next
+                .into_iter()
+                .chain(labels.get(target).copied())
+                .collect(),
+            _ => next.into_iter().collect(),
+        }
+    };
+
+    let mut live_in: Vec<HashSet<Identifier>> = vec![HashSet::new(); instructions.len()];
+    let mut live_out: Vec<HashSet<Identifier>> = vec![HashSet::new(); instructions.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in (0..instructions.len()).rev() {
+            let out: HashSet<Identifier> = successors(i)
+                .into_iter()
+                .flat_map(|s| live_in[s].iter().copied())
+                .collect();
+            let (uses, defs) = uses_and_defs(&instructions[i]);
+            let mut live = out.clone();
+            for def in &defs {
+                live.remove(def);
+            }
+            live.extend(uses);
+            if live != live_in[i] {
+                live_in[i] = live;
+                changed = true;
+            }
+            live_out[i] = out;
+        }
+    }
+    live_out
+}
@@ -1,16 +1,33 @@
 use std::fmt;
 
-use crate::lexer::Token;
+use miette::SourceSpan;
 
-#[derive(Debug, Clone, Hash, PartialEq, PartialOrd, Ord, Eq)]
-pub struct Identifier(pub String);
+use crate::{lexer::Token, symbol::Symbol};
+
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, PartialOrd, Ord, Eq)]
+pub struct Identifier(pub Symbol);
+
+impl From<&str> for Identifier {
+    fn from(name: &str) -> Self {
+        Identifier(Symbol::intern(name))
+    }
+}
 
 impl fmt::Display for Identifier {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", &self.0)
+        write!(f, "{}", self.0)
     }
 }
 
+/// Identifies an expression, declaration or function of a parse, so analysis
+/// results can be kept in a [`SideTable`] instead of in the tree. Ids are
+/// unique within one [`Parser`] run, or one incremental session.
+///
+/// [`SideTable`]: crate::side_table::SideTable
+/// [`Parser`]: crate::parser::Parser
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
 #[derive(Debug, Clone)]
 pub enum AstNode {
     // Represents an expression, such as a variable, constant, or function call
@@ -26,7 +43,7 @@ pub enum AstNode {
     Program(Vec<AstNode>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UnaryOperator {
     Complement,
     Negate,
@@ -44,6 +61,56 @@ impl UnaryOperator {
     }
 }
 
+/// `++` or `--`, before or after the operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateOperator {
+    PreIncrement,
+    PreDecrement,
+    PostIncrement,
+    PostDecrement,
+}
+
+impl UpdateOperator {
+    /// The operator written before its operand, `None` for other tokens.
+    pub fn prefix(token: &Token) -> Option<Self> {
+        match token {
+            Token::Increment => Some(UpdateOperator::PreIncrement),
+            Token::Decrement => Some(UpdateOperator::PreDecrement),
+            _ => None,
+        }
+    }
+
+    /// The operator written after its operand, `None` for other tokens.
+    pub fn postfix(token: &Token) -> Option<Self> {
+        match token {
+            Token::Increment => Some(UpdateOperator::PostIncrement),
+            Token::Decrement => Some(UpdateOperator::PostDecrement),
+            _ => None,
+        }
+    }
+
+    /// Whether the expression evaluates to the updated value.
+    pub fn is_prefix(&self) -> bool {
+        matches!(
+            self,
+            UpdateOperator::PreIncrement | UpdateOperator::PreDecrement
+        )
+    }
+
+    pub fn is_increment(&self) -> bool {
+        matches!(
+            self,
+            UpdateOperator::PreIncrement | UpdateOperator::PostIncrement
+        )
+    }
+}
+
+impl fmt::Display for UpdateOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.is_increment() { "++" } else { "--" })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BinaryOperator {
     Add,
@@ -59,6 +126,20 @@ pub enum BinaryOperator {
     LessOrEqual,
     GreaterThan,
     GreaterOrEqual,
+    // Parsed so the type checker can say they aren't supported yet
+    BitwiseAnd,
+    BitwiseOr,
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            UnaryOperator::Complement => "~",
+            UnaryOperator::Negate => "-",
+            UnaryOperator::Not => "!",
+        };
+        f.write_str(s)
+    }
 }
 
 impl BinaryOperator {
@@ -77,8 +158,46 @@ impl BinaryOperator {
             BinaryOperator::LessOrEqual => 35,
             BinaryOperator::GreaterThan => 35,
             BinaryOperator::GreaterOrEqual => 35,
+            BinaryOperator::BitwiseAnd => 25,
+            BinaryOperator::BitwiseOr => 15,
         }
     }
+
+    /// Whether the operator compares its operands and yields 0 or 1.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOperator::Equal
+                | BinaryOperator::NotEqual
+                | BinaryOperator::LessThan
+                | BinaryOperator::LessOrEqual
+                | BinaryOperator::GreaterThan
+                | BinaryOperator::GreaterOrEqual
+        )
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Substract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Remainder => "%",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::LessThan => "<",
+            BinaryOperator::LessOrEqual => "<=",
+            BinaryOperator::GreaterThan => ">",
+            BinaryOperator::GreaterOrEqual => ">=",
+            BinaryOperator::BitwiseAnd => "&",
+            BinaryOperator::BitwiseOr => "|",
+        };
+        f.write_str(s)
+    }
 }
 
 impl TryFrom<Token> for BinaryOperator {
@@ -99,66 +218,226 @@ impl TryFrom<Token> for BinaryOperator {
             Token::LessThanEqualTo => Ok(Self::LessOrEqual),
             Token::GreaterThan => Ok(Self::GreaterThan),
             Token::GreaterThanEqualTo => Ok(Self::GreaterOrEqual),
+            Token::Ampersand => Ok(Self::BitwiseAnd),
+            Token::Pipe => Ok(Self::BitwiseOr),
             _ => Err(()),
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub enum Expression {
+pub struct Expression {
+    pub id: NodeId,
+    pub kind: ExpressionKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExpressionKind {
     Factor(Factor),
     // Constant(i32),
     // Unary(UnaryOperator, Box<Expression>),
-    Binary(Box<Expression>, BinaryOperator, Box<Expression>),
-    // An identifier (variable or function name)
-    // Identifier(String),
-
+    // Operands, the operator and the span of the operator
+    Binary(Box<Expression>, BinaryOperator, Box<Expression>, SourceSpan),
+    // Target, value and the span of the `=`, `a = b = c` nests to the right
+    Assignment(Box<Expression>, Box<Expression>, SourceSpan),
+    // `++` or `--`, its operand and the span of the operator
+    Update(UpdateOperator, Box<Expression>, SourceSpan),
+    // The operand of a unary `&` and the span of the `&`
+    AddressOf(Box<Expression>, SourceSpan),
     // // A function call with a name and arguments
     // FunctionCall {
-    //     name: String,
+    //     name: Identifier,
     //     arguments: Vec<Expression>,
     // },
 }
 
 #[derive(Debug, Clone)]
 pub enum Factor {
-    Constant(i32),
+    // A literal and where it appears in the source
+    Constant(i32, SourceSpan),
+    // A variable name and where it appears in the source
+    Variable(Identifier, SourceSpan),
     Unary(UnaryOperator, Box<Expression>),
     ParentedExpression(Box<Expression>),
 }
+
+impl Expression {
+    /// Span from the first to the last literal, name or operator of the
+    /// expression. Parentheses and leading unary operators are left out as
+    /// their tokens aren't kept.
+    pub fn span(&self) -> SourceSpan {
+        match &self.kind {
+            ExpressionKind::Factor(Factor::Constant(_, span))
+            | ExpressionKind::Factor(Factor::Variable(_, span)) => *span,
+            ExpressionKind::Factor(Factor::Unary(_, inner))
+            | ExpressionKind::Factor(Factor::ParentedExpression(inner)) => inner.span(),
+            ExpressionKind::Binary(left, _, right, _)
+            | ExpressionKind::Assignment(left, right, _) => join(left.span(), right.span()),
+            ExpressionKind::Update(operator, operand, span) if operator.is_prefix() => {
+                join(*span, operand.span())
+            }
+            ExpressionKind::Update(_, operand, span) => join(operand.span(), *span),
+            ExpressionKind::AddressOf(operand, span) => join(*span, operand.span()),
+        }
+    }
+}
+
+/// Span from the start of `first` to the end of `last`.
+fn join(first: SourceSpan, last: SourceSpan) -> SourceSpan {
+    let end = last.offset() + last.len();
+    SourceSpan::new(first.offset().into(), end - first.offset())
+}
+
+// A variable declaration with an identifier and an optional initializer expression
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    pub id: NodeId,
+    pub var_type: VarType,
+    pub name: Identifier,
+    // Where the name appears in the source
+    pub name_span: SourceSpan,
+    pub initializer: Option<Expression>,
+}
+
+// What a block is made of, C only allows declarations here and not wherever a
+// statement can be
+#[derive(Debug, Clone)]
+pub enum BlockItem {
+    Declaration(Declaration),
+    Statement(Statement),
+}
+
+impl BlockItem {
+    /// Span of the declared name, or [`Statement::span`].
+    pub fn span(&self) -> Option<SourceSpan> {
+        match self {
+            BlockItem::Declaration(declaration) => Some(declaration.name_span),
+            BlockItem::Statement(statement) => statement.span(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Statement {
-    // A variable declaration with an identifier and an optional initializer expression
-    VariableDeclaration {
-        var_type: VarType,
-        name: String,
-        initializer: Option<Expression>,
-    },
+    // A return statement with an optional return expression, the span covers
+    // everything from the keyword to the semicolon
+    ReturnStatement(Option<Expression>, SourceSpan),
+
+    // An expression evaluated for its side effects, like an assignment
+    Expression(Expression),
 
-    // A return statement with an optional return expression
-    ReturnStatement(Option<Expression>),
+    // Compound statement (block) containing declarations and statements
+    Compound(Vec<BlockItem>),
+}
 
-    // Compound statement (block) containing multiple statements
-    Compound(Vec<Statement>),
+impl Statement {
+    /// Span to point at when reporting the statement: the whole return
+    /// statement, the expression of an expression statement or the first item
+    /// of a block.
+    pub fn span(&self) -> Option<SourceSpan> {
+        match self {
+            Statement::ReturnStatement(_, span) => Some(*span),
+            Statement::Expression(expression) => Some(expression.span()),
+            Statement::Compound(items) => items.iter().find_map(BlockItem::span),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct FunctionDecl {
+    pub id: NodeId,
+
     // The return type of the function (e.g., int, void)
     pub return_type: VarType,
 
     // The name of the function
-    pub name: String,
+    pub name: Identifier,
+
+    // Where the name appears in the source
+    pub name_span: SourceSpan,
 
-    // The parameters of the function (name and type)
-    pub parameters: Vec<(VarType, String)>,
+    // The parameters of the function, in order
+    pub parameters: Vec<Parameter>,
 
     // The body of the function, which is a compound statement
     pub body: Statement,
 }
 
+// A parameter of a function definition, named since definitions need one
 #[derive(Debug, Clone)]
+pub struct Parameter {
+    pub var_type: VarType,
+    pub name: Identifier,
+    // Where the name appears in the source
+    pub name_span: SourceSpan,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum VarType {
+    Char,
     Int,
+    UInt,
+    Long,
+    ULong,
     Void,
+    Pointer(Box<VarType>),
+}
+
+impl VarType {
+    pub fn is_integer(&self) -> bool {
+        !matches!(self, VarType::Void | VarType::Pointer(_))
+    }
+
+    /// `char` is signed, like on every target of the compiler.
+    pub fn is_signed(&self) -> bool {
+        matches!(self, VarType::Char | VarType::Int | VarType::Long)
+    }
+
+    /// Size in bytes, `None` for void.
+    pub fn size(&self) -> Option<u32> {
+        match self {
+            VarType::Char => Some(1),
+            VarType::Int | VarType::UInt => Some(4),
+            VarType::Long | VarType::ULong | VarType::Pointer(_) => Some(8),
+            VarType::Void => None,
+        }
+    }
+
+    /// Integer conversion rank, an unsigned type has the rank of its signed
+    /// counterpart. Types that aren't integers rank lowest.
+    pub fn rank(&self) -> u8 {
+        match self {
+            VarType::Void | VarType::Pointer(_) => 0,
+            VarType::Char => 1,
+            VarType::Int | VarType::UInt => 2,
+            VarType::Long | VarType::ULong => 3,
+        }
+    }
+
+    /// The unsigned type of the same rank.
+    pub fn to_unsigned(&self) -> VarType {
+        match self {
+            VarType::Int => VarType::UInt,
+            VarType::Long => VarType::ULong,
+            ty => ty.clone(),
+        }
+    }
+}
+
+impl fmt::Display for VarType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VarType::Int => f.write_str("int"),
+            VarType::UInt => f.write_str("unsigned int"),
+            VarType::Long => f.write_str("long"),
+            VarType::ULong => f.write_str("unsigned long"),
+            VarType::Void => f.write_str("void"),
+            VarType::Char => f.write_str("char"),
+            // `char *`, then `char **` without a space between the stars.
+            VarType::Pointer(pointee) if matches!(**pointee, VarType::Pointer(_)) => {
+                write!(f, "{}*", pointee)
+            }
+            VarType::Pointer(pointee) => write!(f, "{} *", pointee),
+        }
+    }
 }
@@ -0,0 +1,318 @@
+//! Structural differences between the ASTs of two versions of a program, to
+//! check that a refactor of it keeps its structure.
+//!
+//! Only what the nodes are and how they nest counts: formatting, comments,
+//! spans, node ids and redundant parentheses don't. The items of a block are
+//! lined up on the longest run of identical ones, so an inserted statement is
+//! one difference instead of one for every statement after it.
+
+use std::fmt;
+
+use miette::SourceSpan;
+
+use crate::{
+    ast::{AstNode, BlockItem, Declaration, Expression, ExpressionKind, Factor, Statement},
+    lcs::{lcs, Edit},
+    printer,
+};
+
+/// Longest text of a node in a difference, longer ones are cut.
+const MAX_TEXT: usize = 60;
+
+/// A node of one of the trees, as C.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffNode {
+    /// The node on one line, a function is its signature.
+    pub text: String,
+    pub span: Option<SourceSpan>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AstDifference {
+    /// Where the nodes are, like `main > item 2 > value > right`, empty for
+    /// the program.
+    pub path: String,
+    /// The node of the old tree, `None` when it was added.
+    pub old: Option<DiffNode>,
+    /// The node of the new tree, `None` when it was removed.
+    pub new: Option<DiffNode>,
+}
+
+impl AstDifference {
+    /// The path, `program` for the program itself.
+    pub fn place(&self) -> &str {
+        if self.path.is_empty() {
+            "program"
+        } else {
+            &self.path
+        }
+    }
+}
+
+impl fmt::Display for AstDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.place();
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => write!(f, "{}: `{}` became `{}`", path, old.text, new.text),
+            (Some(old), None) => write!(f, "{}: `{}` was removed", path, old.text),
+            (None, Some(new)) => write!(f, "{}: `{}` was added", path, new.text),
+            (None, None) => write!(f, "{}", path),
+        }
+    }
+}
+
+/// The differences between `old` and `new` in the order of the trees, none
+/// when they have the same structure.
+pub fn diff(old: &AstNode, new: &AstNode) -> Vec<AstDifference> {
+    let mut differences = vec![];
+    compare("", &Shape::node(old), &Shape::node(new), &mut differences);
+    differences
+}
+
+/// A node reduced to what is compared.
+struct Shape {
+    /// What the node is without its children, like `+` or `return`.
+    label: String,
+    /// Name in the path of a node of a list, `item N` when it has none.
+    name: Option<String>,
+    text: String,
+    span: Option<SourceSpan>,
+    /// Children with a fixed role, like the operands of an operator.
+    parts: Vec<(&'static str, Shape)>,
+    /// Children lined up when compared, like the items of a block.
+    items: Vec<Shape>,
+}
+
+impl Shape {
+    fn leaf(label: String, text: String, span: Option<SourceSpan>) -> Self {
+        Self {
+            label,
+            name: None,
+            text: one_line(&text),
+            span,
+            parts: vec![],
+            items: vec![],
+        }
+    }
+
+    fn with_part(mut self, role: &'static str, part: Shape) -> Self {
+        self.parts.push((role, part));
+        self
+    }
+
+    fn node(node: &AstNode) -> Self {
+        match node {
+            AstNode::Program(nodes) => Self {
+                items: nodes.iter().map(Self::node).collect(),
+                ..Self::leaf("program".to_string(), String::new(), None)
+            },
+            AstNode::FunctionDeclaration(function) => {
+                let source = printer::to_c_source(node);
+                let signature = source.split(" {").next().unwrap_or_default();
+                Self {
+                    name: Some(function.name.to_string()),
+                    ..Self::leaf(
+                        signature.to_string(),
+                        signature.to_string(),
+                        Some(function.name_span),
+                    )
+                }
+                .with_part("body", Self::statement(&function.body))
+            }
+            AstNode::Statement(statement) => Self::statement(statement),
+            AstNode::Expression(expression) => Self::expression(expression),
+        }
+    }
+
+    fn statement(statement: &Statement) -> Self {
+        let text = printer::statement_to_c(statement);
+        let span = statement.span();
+        match statement {
+            Statement::ReturnStatement(value, _) => {
+                let shape = Self::leaf("return".to_string(), text, span);
+                match value {
+                    Some(value) => shape.with_part("value", Self::expression(value)),
+                    None => shape,
+                }
+            }
+            Statement::Expression(expression) => Self::leaf("expression".to_string(), text, span)
+                .with_part("expression", Self::expression(expression)),
+            Statement::Compound(items) => Self {
+                items: items.iter().map(Self::item).collect(),
+                ..Self::leaf("block".to_string(), text, span)
+            },
+        }
+    }
+
+    fn item(item: &BlockItem) -> Self {
+        match item {
+            BlockItem::Declaration(declaration) => Self::declaration(declaration),
+            BlockItem::Statement(statement) => Self::statement(statement),
+        }
+    }
+
+    fn declaration(declaration: &Declaration) -> Self {
+        let label = format!("declare {} {}", declaration.var_type, declaration.name);
+        let text = printer::declaration_to_c(declaration);
+        let shape = Self::leaf(label, text, Some(declaration.name_span));
+        match &declaration.initializer {
+            Some(initializer) => shape.with_part("initializer", Self::expression(initializer)),
+            None => shape,
+        }
+    }
+
+    fn expression(expression: &Expression) -> Self {
+        let text = printer::expression_to_c(expression);
+        let span = Some(expression.span());
+        let operator = |label: String| Self::leaf(label, text.clone(), span);
+        match &expression.kind {
+            ExpressionKind::Binary(left, op, right, _) => operator(op.to_string())
+                .with_part("left", Self::expression(left))
+                .with_part("right", Self::expression(right)),
+            ExpressionKind::Assignment(target, value, _) => operator("=".to_string())
+                .with_part("target", Self::expression(target))
+                .with_part("value", Self::expression(value)),
+            ExpressionKind::Update(op, operand, _) => {
+                let position = if op.is_prefix() { "prefix" } else { "postfix" };
+                operator(format!("{} {}", position, op))
+                    .with_part("operand", Self::expression(operand))
+            }
+            ExpressionKind::AddressOf(operand, _) => {
+                operator("&".to_string()).with_part("operand", Self::expression(operand))
+            }
+            ExpressionKind::Factor(Factor::Constant(c, _)) => operator(c.to_string()),
+            ExpressionKind::Factor(Factor::Variable(name, _)) => operator(name.to_string()),
+            ExpressionKind::Factor(Factor::Unary(op, inner)) => {
+                operator(op.to_string()).with_part("operand", Self::expression(inner))
+            }
+            // Grouping is already in the shape of the tree.
+            ExpressionKind::Factor(Factor::ParentedExpression(inner)) => Self::expression(inner),
+        }
+    }
+
+    fn diff_node(&self) -> DiffNode {
+        DiffNode {
+            text: self.text.clone(),
+            span: self.span,
+        }
+    }
+}
+
+/// The same structure: names, texts and spans don't count.
+impl PartialEq for Shape {
+    fn eq(&self, other: &Shape) -> bool {
+        self.label == other.label
+            && self.parts.len() == other.parts.len()
+            && self.items.len() == other.items.len()
+            && self
+                .parts
+                .iter()
+                .zip(&other.parts)
+                .all(|((role, a), (other_role, b))| role == other_role && a == b)
+            && self.items.iter().zip(&other.items).all(|(a, b)| a == b)
+    }
+}
+
+/// The text on one line with its whitespace collapsed, cut after
+/// [`MAX_TEXT`] chars.
+fn one_line(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(MAX_TEXT) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+fn join(path: &str, segment: &str) -> String {
+    if path.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{} > {}", path, segment)
+    }
+}
+
+fn compare(path: &str, old: &Shape, new: &Shape, differences: &mut Vec<AstDifference>) {
+    if old.label != new.label {
+        differences.push(AstDifference {
+            path: path.to_string(),
+            old: Some(old.diff_node()),
+            new: Some(new.diff_node()),
+        });
+        return;
+    }
+    for (role, old_part) in &old.parts {
+        let path = join(path, role);
+        match new.parts.iter().find(|(new_role, _)| new_role == role) {
+            Some((_, new_part)) => compare(&path, old_part, new_part, differences),
+            None => differences.push(AstDifference {
+                path,
+                old: Some(old_part.diff_node()),
+                new: None,
+            }),
+        }
+    }
+    for (role, new_part) in &new.parts {
+        if !old.parts.iter().any(|(old_role, _)| old_role == role) {
+            differences.push(AstDifference {
+                path: join(path, role),
+                old: None,
+                new: Some(new_part.diff_node()),
+            });
+        }
+    }
+    compare_items(path, &old.items, &new.items, differences);
+}
+
+/// Path of the `index`th item of a list.
+fn item_path(path: &str, item: &Shape, index: usize) -> String {
+    match &item.name {
+        Some(name) => join(path, name),
+        None => join(path, &format!("item {}", index + 1)),
+    }
+}
+
+/// Lines the items up on their longest common subsequence, then compares the
+/// ones between two matches pairwise and reports the rest as removed or
+/// added.
+fn compare_items(path: &str, old: &[Shape], new: &[Shape], differences: &mut Vec<AstDifference>) {
+    let (mut old_gap, mut new_gap) = (vec![], vec![]);
+    for edit in lcs(old, new) {
+        match edit {
+            Edit::Same(..) => {
+                compare_gap(path, old, new, &old_gap, &new_gap, differences);
+                old_gap.clear();
+                new_gap.clear();
+            }
+            Edit::Removed(i) => old_gap.push(i),
+            Edit::Added(j) => new_gap.push(j),
+        }
+    }
+    compare_gap(path, old, new, &old_gap, &new_gap, differences);
+}
+
+fn compare_gap(
+    path: &str,
+    old: &[Shape],
+    new: &[Shape],
+    old_gap: &[usize],
+    new_gap: &[usize],
+    differences: &mut Vec<AstDifference>,
+) {
+    for (&i, &j) in old_gap.iter().zip(new_gap) {
+        compare(&item_path(path, &new[j], j), &old[i], &new[j], differences);
+    }
+    for &i in old_gap.iter().skip(new_gap.len()) {
+        differences.push(AstDifference {
+            path: item_path(path, &old[i], i),
+            old: Some(old[i].diff_node()),
+            new: None,
+        });
+    }
+    for &j in new_gap.iter().skip(old_gap.len()) {
+        differences.push(AstDifference {
+            path: item_path(path, &new[j], j),
+            old: None,
+            new: Some(new[j].diff_node()),
+        });
+    }
+}
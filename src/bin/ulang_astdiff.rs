@@ -0,0 +1,72 @@
+use clap::Parser;
+use miette::{IntoDiagnostic, NamedSource, Report, Result};
+use std::{
+    path::{Path, PathBuf},
+    process::exit,
+};
+use ulang::{
+    ast::AstNode,
+    ast_diff::{self, DiffNode},
+    lexer::{line_column, Lexer},
+};
+
+/// Reports where the ASTs of two C files differ, ignoring formatting and
+/// comments. Exits with 0 when they have the same structure, 1 when they
+/// differ and 2 when a file can't be read or parsed.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct UlangAstDiff {
+    old: PathBuf,
+    new: PathBuf,
+}
+
+fn parse(path: &Path) -> Result<(String, AstNode)> {
+    let mut lexer = Lexer::from_path(path.to_path_buf()).into_diagnostic()?;
+    let source = NamedSource::new(path.to_string_lossy(), lexer.content.clone());
+    let tokens = lexer
+        .tokenize()
+        .map_err(|e| Report::new(e).with_source_code(source.clone()))?;
+    let ast = ulang::parser::Parser::new(&tokens, &lexer.content)
+        .parse()
+        .map_err(|e| Report::new(e).with_source_code(source))?;
+    Ok((lexer.content, ast))
+}
+
+/// `  - file:line:column  text`
+fn side(sign: char, path: &Path, source: &str, node: &DiffNode) -> String {
+    let location = match node.span {
+        Some(span) => {
+            let (line, column) = line_column(source, span.offset());
+            format!("{}:{}:{}", path.display(), line, column)
+        }
+        None => path.display().to_string(),
+    };
+    format!("  {} {}  {}", sign, location, node.text)
+}
+
+fn main() {
+    let opt = UlangAstDiff::parse();
+    let parsed = parse(&opt.old).and_then(|old| Ok((old, parse(&opt.new)?)));
+    let ((old_source, old), (new_source, new)) = parsed.unwrap_or_else(|e| {
+        eprintln!("{:?}", e);
+        exit(2);
+    });
+    let differences = ast_diff::diff(&old, &new);
+    for difference in &differences {
+        println!("{}", difference.place());
+        if let Some(node) = &difference.old {
+            println!("{}", side('-', &opt.old, &old_source, node));
+        }
+        if let Some(node) = &difference.new {
+            println!("{}", side('+', &opt.new, &new_source, node));
+        }
+    }
+    if !differences.is_empty() {
+        println!(
+            "{} difference{}",
+            differences.len(),
+            if differences.len() == 1 { "" } else { "s" }
+        );
+        exit(1);
+    }
+}
@@ -0,0 +1,26 @@
+use clap::Parser;
+use miette::{IntoDiagnostic, Result};
+use std::path::PathBuf;
+
+/// Shows how many times each line of a program compiled with `--coverage` ran
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct UlangCov {
+    /// coverage file written by the program, `<file>.ulangcov` by default
+    #[arg(long, value_name = "PATH")]
+    counts: Option<PathBuf>,
+    /// C source the program was compiled from
+    file: PathBuf,
+}
+
+fn main() -> Result<()> {
+    let opt = UlangCov::parse();
+    let source = std::fs::read_to_string(&opt.file).into_diagnostic()?;
+    let counts_path = opt
+        .counts
+        .unwrap_or_else(|| opt.file.with_extension("ulangcov"));
+    let coverage = std::fs::read_to_string(&counts_path).into_diagnostic()?;
+    let counts = ulang::coverage::parse_counts(&coverage).map_err(|e| miette::miette!(e))?;
+    print!("{}", ulang::coverage::annotate(&source, &counts));
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use clap::Parser;
+use miette::{miette, IntoDiagnostic, Result};
+use std::{
+    path::{Path, PathBuf},
+    process::{exit, Command},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use ulang::{
+    assembly::{self, TargetPlatform},
+    lexer::Lexer,
+    optimize::{OptimizationLevel, PassManager},
+    synth::Generator,
+    tacky::Tacky,
+    toolchain,
+    typecheck::typecheck,
+};
+
+const TARGET: TargetPlatform = TargetPlatform::host();
+
+/// Compiles programs with ulang and with the system C compiler and compares their exit codes
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct UlangDifftest {
+    /// number of random programs to generate and check
+    #[arg(long, value_name = "COUNT", default_value = "0")]
+    random: usize,
+    /// seed of the first random program, taken from the clock when missing
+    #[arg(long)]
+    seed: Option<u64>,
+    /// nesting limit of the generated expressions
+    #[arg(long, default_value = "4")]
+    max_depth: usize,
+    /// optimization level used by ulang
+    #[arg(short = 'O', value_name = "LEVEL", default_value = "0")]
+    opt_level: OptimizationLevel,
+    /// system compiler to compare with
+    #[arg(long, default_value = "cc")]
+    cc: String,
+    /// source files to check
+    files: Vec<PathBuf>,
+}
+
+struct Difftest {
+    opt: UlangDifftest,
+    work_dir: PathBuf,
+    divergences: usize,
+}
+
+impl Difftest {
+    fn ulang_status(&self, source: &str) -> Result<i32> {
+        let mut lexer = Lexer::from_content(source.to_string());
+        let tokens = lexer.tokenize()?;
+        let ast = ulang::parser::Parser::new(&tokens, &lexer.content).parse()?;
+        let typed = typecheck(&ast).map_err(|e| miette!(e))?;
+        let mut tacky = Tacky::new(&typed).parse().map_err(|e| miette!(e))?;
+        let passes = PassManager::new(self.opt.opt_level);
+        passes.run_tacky(&mut tacky).map_err(|e| miette!(e))?;
+        let asm = assembly::generate_optimized_assembly(
+            &tacky,
+            TARGET,
+            &passes,
+            &assembly::RuntimeChecks::default(),
+        );
+
+        let asm_path = self.work_dir.join("ulang.s");
+        std::fs::write(&asm_path, asm.0).into_diagnostic()?;
+        self.build_and_run(&asm_path, &[], "ulang")
+    }
+
+    fn cc_status(&self, source: &str) -> Result<i32> {
+        let c_path = self.work_dir.join("reference.c");
+        std::fs::write(&c_path, source).into_diagnostic()?;
+        // Overflow is undefined in C, make the reference wrap like ulang does.
+        self.build_and_run(&c_path, &["-O0", "-w", "-fwrapv"], "reference")
+    }
+
+    fn build_and_run(&self, input: &Path, flags: &[&str], name: &str) -> Result<i32> {
+        let binary = self.work_dir.join(name);
+        toolchain::run(&mut toolchain::link(&self.opt.cc, input, &binary, flags))?;
+        let status = Command::new(&binary).status().into_diagnostic()?;
+        status
+            .code()
+            .ok_or_else(|| miette!("{} terminated by a signal", name))
+    }
+
+    /// Compares both compilers on `source`, reporting under `name`.
+    fn check(&mut self, name: &str, source: &str) -> Result<()> {
+        let expected = self.cc_status(source)?;
+        let actual = self.ulang_status(source);
+        match actual {
+            Ok(actual) if actual == expected => return Ok(()),
+            Ok(actual) => println!(
+                "{}: {} exited with {}, ulang output with {}",
+                name, self.opt.cc, expected, actual
+            ),
+            Err(e) => println!("{}: ulang failed: {:?}", name, e),
+        }
+        println!("{}", source);
+        self.divergences += 1;
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    let opt = UlangDifftest::parse();
+    let work_dir = std::env::temp_dir().join(format!("ulang-difftest-{}", std::process::id()));
+    std::fs::create_dir_all(&work_dir).into_diagnostic()?;
+    let mut difftest = Difftest {
+        opt,
+        work_dir,
+        divergences: 0,
+    };
+
+    for file in difftest.opt.files.clone() {
+        let source = std::fs::read_to_string(&file).into_diagnostic()?;
+        difftest.check(&file.display().to_string(), &source)?;
+    }
+
+    let seed = difftest.opt.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |time| time.as_nanos() as u64)
+    });
+    for i in 0..difftest.opt.random as u64 {
+        let mut generator = Generator::new(seed + i);
+        generator.max_depth = difftest.opt.max_depth;
+        let source = generator.function("main", 1);
+        difftest.check(&format!("seed {}", seed + i), &source)?;
+    }
+
+    let checked = difftest.opt.files.len() + difftest.opt.random;
+    println!(
+        "{} programs checked, {} divergences",
+        checked, difftest.divergences
+    );
+    std::fs::remove_dir_all(&difftest.work_dir).into_diagnostic()?;
+    if difftest.divergences > 0 {
+        exit(1);
+    }
+    Ok(())
+}
@@ -1,12 +1,357 @@
-use clap::Parser;
-use miette::{IntoDiagnostic, Result};
-use std::{path::PathBuf, process::exit};
-use ulang::assembly;
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use miette::{
+    GraphicalTheme, IntoDiagnostic, MietteDiagnostic, MietteHandlerOpts, Result, Severity,
+};
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    process::{exit, Command, ExitStatus},
+    sync::OnceLock,
+};
+use ulang::{
+    assembly::{self, SourceMap, TargetPlatform},
+    ast::{AstNode, Identifier},
+    cache::{CacheKey, CompilationCache},
+    config::Config,
+    const_eval,
+    diagnostics::{
+        hidden_errors, BoxedDiagnostic, Deduplicate, DiagnosticSink, SeverityCounts, Stream,
+        WarningPolicy,
+    },
+    dialect::Standard,
+    lexer::FileToken,
+    optimize::{OptimizationLevel, Pass},
+    options::{CompileOptions, Stage},
+    reachability,
+    source_manager::{FileId, SourceManager},
+    tacky::{call_graph::CallGraph, TackyProgram},
+    toolchain::{self, ToolchainEnv},
+    typed_ast::TypedProgram,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Emit {
+    /// Tokens, one `line:column<TAB>token` per line
+    Tokens,
+    /// The parsed AST
+    Ast,
+    /// Graphviz graph of the parsed AST
+    AstDot,
+    /// C source printed back from the AST
+    C,
+    /// TACKY after the optimization passes
+    Tacky,
+    /// TACKY after the optimization passes, with the source position of every instruction
+    TackyText,
+    /// Graphviz graph of the TACKY control-flow graph
+    CfgDot,
+    /// Graphviz graph of which functions call which, with the length of their TACKY
+    CallgraphDot,
+    /// Assembly, as it is saved before linking
+    Asm,
+}
+
+impl Emit {
+    fn extension(&self) -> &'static str {
+        match self {
+            Emit::Tokens => "tokens",
+            Emit::Ast => "ast",
+            Emit::AstDot => "ast.dot",
+            Emit::C => "printed.c",
+            Emit::Tacky => "tacky",
+            Emit::TackyText => "tacky.txt",
+            Emit::CfgDot => "cfg.dot",
+            Emit::CallgraphDot => "callgraph.dot",
+            Emit::Asm => "s",
+        }
+    }
+
+    /// Whether the representation shows where the code came from.
+    fn debug_info(&self) -> bool {
+        *self == Emit::TackyText
+    }
+
+    /// The last stage the representation needs.
+    fn stage(&self) -> Stage {
+        match self {
+            Emit::Tokens => Stage::Lex,
+            Emit::Ast | Emit::AstDot | Emit::C => Stage::Parse,
+            Emit::CallgraphDot => Stage::Semantic,
+            Emit::Tacky | Emit::TackyText | Emit::CfgDot => Stage::Tacky,
+            Emit::Asm => Stage::Codegen,
+        }
+    }
+
+    fn render(&self, compiled: &Compiled) -> String {
+        let ast = || compiled.ast.as_ref().expect("the AST is parsed");
+        let tacky = || compiled.tacky.as_ref().expect("the TACKY is generated");
+        let typed = || compiled.typed.as_ref().expect("the types are checked");
+        match self {
+            Emit::Tokens => compiled
+                .tokens
+                .iter()
+                .map(|token| {
+                    let location = compiled.sources.resolve(compiled.file, token.offset);
+                    format!("{}:{}\t{:?}\n", location.line, location.column, token.token)
+                })
+                .collect(),
+            Emit::Ast => format!("{:#?}\n", ast()),
+            Emit::AstDot => ulang::dot::ast_to_dot(ast()),
+            Emit::C => ulang::printer::to_c_source(ast()),
+            Emit::Tacky => tacky().to_string(),
+            Emit::TackyText => tacky().to_annotated_string(),
+            Emit::CfgDot => ulang::dot::cfg_to_dot(tacky()),
+            Emit::CallgraphDot => ulang::dot::call_graph_to_dot(&CallGraph::new(typed())),
+            Emit::Asm => compiled
+                .assembly
+                .clone()
+                .expect("the assembly is generated"),
+        }
+    }
+}
+
+/// An optimization level written like `-O1`, `O1` or `1`.
+fn parse_level(level: &str) -> Result<OptimizationLevel, String> {
+    let level = level.strip_prefix('-').unwrap_or(level);
+    level.strip_prefix('O').unwrap_or(level).parse()
+}
+
+/// When the diagnostics are rendered with colors, unicode and terminal
+/// hyperlinks, see `--color`.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Default)]
+enum ColorChoice {
+    /// When both stdout and stderr are terminals, so piped diagnostics stay plain
+    #[default]
+    Auto,
+    Always,
+    /// Plain ASCII
+    Never,
+}
+
+/// Set once by [`ColorChoice::install`].
+static HYPERLINKS: OnceLock<bool> = OnceLock::new();
+
+impl ColorChoice {
+    /// Renders the diagnostics of miette this way. In a terminal, `auto`
+    /// leaves the theme to what miette detects, like `NO_COLOR`.
+    fn install(self) {
+        let fancy = match self {
+            ColorChoice::Auto => std::io::stdout().is_terminal() && std::io::stderr().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+        let _ = HYPERLINKS.set(fancy);
+        let _ = miette::set_hook(Box::new(move |_| {
+            let options = MietteHandlerOpts::new()
+                .force_graphical(true)
+                .terminal_links(fancy);
+            let options = match self {
+                _ if !fancy => options.graphical_theme(GraphicalTheme::none()),
+                ColorChoice::Always => options.color(true).unicode(true),
+                _ => options,
+            };
+            Box::new(options.build())
+        }));
+    }
+
+    /// Whether file names in diagnostics link to the file.
+    fn hyperlinks() -> bool {
+        HYPERLINKS.get().copied().unwrap_or_default()
+    }
+}
+
+/// Exit status of the driver for each stage that can fail, listed in `--help`.
+#[derive(Debug, Clone, Copy)]
+enum Failure {
+    /// Bad arguments, or a file that can't be read or written.
+    Usage = 1,
+    /// The preprocessor or the lexer.
+    Lex = 2,
+    Parse = 3,
+    Semantic = 4,
+    Codegen = 5,
+    /// Saving the assembly, or gcc failing to assemble and link it.
+    Link = 6,
+}
+
+impl Failure {
+    fn exit(self) -> ! {
+        exit(self as i32)
+    }
+}
+
+const EXIT_STATUS: &str = "\
+Exit status:
+  0  success, --interpret and `run` exit with the status of the program instead
+  1  usage error, or a file that can't be read or written
+  2  preprocessor or lexer error
+  3  parser error
+  4  semantic error
+  5  code generation error
+  6  assembling or linking failed";
+
+/// Options for compiling a file, shared by the subcommands that generate code.
+///
+/// The ones a `ulang.toml` can set are optional here, see [`Self::with_config`].
+#[derive(Args, Debug, Clone, Default)]
+struct CompileArgs {
+    /// optimization level: 0, 1 or 2, 0 by default
+    #[arg(short = 'O', value_name = "LEVEL")]
+    opt_level: Option<OptimizationLevel>,
+    /// generate code for TARGET: x64-linux or macos-x64, `$ULANG_TARGET` or the host by default
+    #[arg(long, value_name = "TARGET")]
+    target: Option<TargetPlatform>,
+    /// C compiler assembling and linking the program, `$CC` or gcc by default, the link command
+    /// ends with the flags in `$LDFLAGS`
+    #[arg(long, value_name = "PATH")]
+    cc: Option<String>,
+    /// check the program against the C standard STD: c89, c99 or c11
+    #[arg(long = "std", value_name = "STD")]
+    standard: Option<Standard>,
+    /// translate the trigraphs like `??<` and the digraphs `<%`, `%>` and `%:` before lexing
+    #[arg(long)]
+    trigraphs: bool,
+    /// don't report the warning CODE, like missing_return
+    #[arg(short = 'A', long, value_name = "CODE")]
+    allow: Vec<String>,
+    /// fail with exit status 4 when a warning is reported
+    #[arg(long)]
+    deny_warnings: bool,
+    /// stop showing errors after the first N, 0 shows all of them
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    error_limit: usize,
+    /// don't read the `ulang.toml` in the directory of FILE or one above it
+    #[arg(long)]
+    no_config: bool,
+    /// print the IR after the given optimization pass (ssa, fold, copy-prop, licm, strength-reduce, dce, jump-cleanup, coalesce-slots, peephole)
+    #[arg(long, value_name = "PASS")]
+    print_after: Vec<Pass>,
+    /// stop with a message like "division by zero at file:line" and exit status 70 on division by
+    /// zero and signed overflow of +, - and *
+    #[arg(long)]
+    checked_arithmetic: bool,
+    /// also write `<output>.map.json` relating every line of the assembly to the C source
+    #[arg(long)]
+    source_map: bool,
+    /// count the calls of every function and print `<count>\t<name>` lines to stderr when the
+    /// program exits
+    #[arg(long)]
+    instrument_functions: bool,
+    /// count the runs of every basic block and write them to `<file>.ulangcov` when the program
+    /// exits, `ulang-cov <file>` shows them next to the source
+    #[arg(long)]
+    coverage: bool,
+    /// compile the function NAME instead of the first one, like `main` it returns 0 when it runs
+    /// off its end; linked with the C runtime, the program still starts in `main`
+    #[arg(long, value_name = "NAME")]
+    entry: Option<String>,
+    /// don't link the C runtime (gcc -nostdlib), so nothing calls `main` and the runtime checks
+    /// and instrumentation that need the C library are not available
+    #[arg(long, conflicts_with_all = ["checked_arithmetic", "instrument_functions", "coverage"])]
+    freestanding: bool,
+    /// with --freestanding, also emit a `_start` that calls the entry point and exits with its
+    /// result through the exit system call
+    #[arg(long, requires = "freestanding")]
+    start: bool,
+    /// put every function in a `.text.<name>` section of its own and link with
+    /// `--gc-sections`, so the functions nothing calls are left out of the program
+    #[arg(long)]
+    function_sections: bool,
+    /// neither reuse nor store the assembly in the compilation cache, which is
+    /// `$ULANG_CACHE_DIR` or `ulang` in `$XDG_CACHE_HOME` or `~/.cache`
+    #[arg(long)]
+    no_cache: bool,
+    /// print the commands of the C compiler and the other tools with all their arguments instead
+    /// of running them, the assembly is still saved; `-###` does the same
+    #[arg(long)]
+    dry_run: bool,
+}
+
+impl CompileArgs {
+    /// The options with the ones the command line leaves unset taken from the
+    /// `ulang.toml` of `file`, exits when it is invalid.
+    fn with_config(mut self, file: &Path) -> Self {
+        if self.no_config {
+            return self;
+        }
+        let config = Config::for_file(file).unwrap_or_else(|e| {
+            eprintln!("{:?}", miette::Report::new(e));
+            Failure::Usage.exit();
+        });
+        self.opt_level = self.opt_level.or(config.opt_level);
+        self.target = self.target.or(config.target);
+        self.cc = self.cc.or(config.cc);
+        self.allow.extend(config.warnings.allow);
+        self.deny_warnings |= config.warnings.deny;
+        self
+    }
+
+    fn warnings(&self) -> WarningPolicy {
+        WarningPolicy {
+            allow: self.allow.clone(),
+            deny: self.deny_warnings,
+        }
+    }
+
+    /// Reused assembly has no source map and doesn't print the IR.
+    fn caches(&self) -> bool {
+        !(self.no_cache || self.source_map || !self.print_after.is_empty())
+    }
+
+    /// The ones the command line leaves unset taken from the environment.
+    fn with_environment(mut self, env: &ToolchainEnv) -> Self {
+        self.cc = self.cc.or(env.cc.clone());
+        self.target = self.target.or(env.target);
+        self
+    }
+
+    /// The options for compiling `file` up to `emit`, with the defaults of
+    /// the environment, then of its `ulang.toml`.
+    fn resolve(&self, file: &Path, emit: Stage) -> CompileOptions {
+        let env = toolchain_env();
+        let args = self.clone().with_environment(&env).with_config(file);
+        CompileOptions {
+            emit,
+            ldflags: env.ldflags,
+            ..CompileOptions::from(&args)
+        }
+    }
+}
+
+impl From<&CompileArgs> for CompileOptions {
+    fn from(args: &CompileArgs) -> Self {
+        let defaults = CompileOptions::default();
+        CompileOptions {
+            target: args.target.unwrap_or(defaults.target),
+            opt_level: args.opt_level.unwrap_or(defaults.opt_level),
+            warnings: args.warnings(),
+            standard: args.standard,
+            trigraphs: args.trigraphs,
+            debug_info: args.source_map,
+            checked_arithmetic: args.checked_arithmetic,
+            instrument_functions: args.instrument_functions,
+            coverage: args.coverage,
+            entry: args.entry.as_deref().map(Identifier::from),
+            freestanding: args.freestanding,
+            start: args.start,
+            function_sections: args.function_sections,
+            print_after: args.print_after.clone(),
+            cc: args.cc.clone().unwrap_or(defaults.cc),
+            cache: args.caches(),
+            dry_run: args.dry_run,
+            error_limit: Some(args.error_limit).filter(|&limit| limit > 0),
+            ..defaults
+        }
+    }
+}
 
 /// Simple C lang compiler driver
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = "Test compiler")]
-#[command(propagate_version = true)]
+#[command(name = "ulang_driver", version, about, long_about = "Test compiler")]
+#[command(after_help = EXIT_STATUS)]
+#[command(propagate_version = true, args_conflicts_with_subcommands = true)]
+#[command(group(ArgGroup::new("stage").args(["lex", "parse", "codegen", "tacky", "interpret", "emit"])))]
 struct UlangDriver {
     /// run the lexer, but stop before parsing
     #[arg(long)]
@@ -17,114 +362,720 @@ struct UlangDriver {
     /// perform lexing, parsing and assembly generation, but stop before code emission
     #[arg(long)]
     codegen: bool,
-    /// perform lexing, parsing and tacky generation, but stop before code assembly
+    /// perform lexing, parsing and tacky generation, print the TACKY with the source position of
+    /// every instruction, but stop before code assembly
     #[arg(long)]
     tacky: bool,
+    /// perform lexing, parsing and tacky generation, then run the tacky in the interpreter
+    #[arg(long)]
+    interpret: bool,
+    /// write the selected representation next to the source file and stop
+    #[arg(long, value_enum, value_name = "KIND")]
+    emit: Option<Emit>,
+    #[command(flatten)]
+    options: CompileArgs,
+    /// color the diagnostics and link their file names: auto, always or never
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t, global = true)]
+    color: ColorChoice,
+    /// print the value of the constant expression EXPR, like `1 + 2 * (3 - 1)`, instead of
+    /// compiling a file
+    #[arg(long, value_name = "EXPR", allow_hyphen_values = true, conflicts_with_all = ["stage", "file"])]
+    eval: Option<String>,
+    /// print the man page in roff to stdout
+    #[arg(long, hide = true, exclusive = true)]
+    generate_man: bool,
+    #[command(subcommand)]
+    command: Option<DriverCommand>,
     /// File to process
-    file: PathBuf,
+    #[arg(required_unless_present_any = ["generate_man", "eval"])]
+    file: Option<PathBuf>,
     /// Save to file
     save_path: Option<PathBuf>,
 }
 
+#[derive(Subcommand, Debug)]
+enum DriverCommand {
+    /// compile FILE to assembly and assemble and link it with gcc
+    Build {
+        #[command(flatten)]
+        options: CompileArgs,
+        file: PathBuf,
+        /// the program, FILE without its extension by default, the assembly is saved next to it
+        /// with the `.s` extension
+        output: Option<PathBuf>,
+    },
+    /// build FILE and run the program with ARGS, exiting with its status
+    Run {
+        #[command(flatten)]
+        options: CompileArgs,
+        /// run the program under CMD, an emulator like `qemu-x86_64` or `wine` followed by its
+        /// own arguments, for a program built for another --target
+        #[arg(long, value_name = "CMD")]
+        runner: Option<String>,
+        file: PathBuf,
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// compile every FILE to an object and bundle them into the static library OUTPUT with ar
+    Archive {
+        #[command(flatten)]
+        options: CompileArgs,
+        /// the library, like `libsupport.a`, the assembly and the object of every FILE are saved
+        /// next to FILE with the `.s` and `.o` extensions
+        #[arg(short, long)]
+        output: PathBuf,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// report the errors and warnings of FILE without generating code
+    Check {
+        #[command(flatten)]
+        options: CompileArgs,
+        file: PathBuf,
+    },
+    /// print the KIND representation of FILE to stdout, or save it to OUTPUT
+    Emit {
+        #[arg(value_enum)]
+        kind: Emit,
+        #[command(flatten)]
+        options: CompileArgs,
+        /// print a unified diff of KIND compiled at the optimization levels OLD and NEW instead,
+        /// like `--compare -O0 -O1`, to see what the passes of a level change
+        #[arg(
+            long,
+            num_args = 2,
+            value_names = ["OLD", "NEW"],
+            allow_hyphen_values = true,
+            value_parser = parse_level,
+            conflicts_with = "opt_level"
+        )]
+        compare: Vec<OptimizationLevel>,
+        file: PathBuf,
+        output: Option<PathBuf>,
+    },
+    /// print the tokens of FILE, one `line:column<TAB>token` per line
+    Lex { file: PathBuf },
+    /// run the C preprocessor (gcc -E -P) on FILE and save the result to OUTPUT, `<FILE>.i` by
+    /// default
+    Preprocess {
+        /// print the command of the preprocessor instead of running it
+        #[arg(long)]
+        dry_run: bool,
+        file: PathBuf,
+        output: Option<PathBuf>,
+    },
+    /// print the completion script for SHELL to stdout
+    ///
+    /// For example `ulang_driver completions bash > ~/.local/share/bash-completion/completions/ulang_driver`.
+    Completions { shell: Shell },
+    /// manage the compilation cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    /// remove every cached assembly
+    Clear,
+}
+
 impl UlangDriver {
-    fn is_valid(&self) -> bool {
-        let mut counter = 0;
+    /// The stage the flags stop after.
+    fn stage(&self) -> Stage {
         if self.lex {
-            counter += 1;
-        }
-        if self.parse {
-            counter += 1;
+            Stage::Lex
+        } else if self.parse {
+            Stage::Parse
+        } else if let Some(kind) = self.emit {
+            kind.stage()
+        } else if self.tacky || self.interpret {
+            Stage::Tacky
+        } else {
+            Stage::Codegen
         }
-        if self.codegen {
-            counter += 1;
+    }
+}
+
+impl From<&UlangDriver> for CompileOptions {
+    fn from(driver: &UlangDriver) -> Self {
+        let file = driver
+            .file
+            .as_deref()
+            .expect("the file is required without a subcommand");
+        let mut options = driver.options.resolve(file, driver.stage());
+        options.debug_info |= driver.tacky || driver.emit.is_some_and(|kind| kind.debug_info());
+        options
+    }
+}
+
+/// What the stages run by [`compile`] produced.
+struct Compiled {
+    options: CompileOptions,
+    sources: SourceManager,
+    file: FileId,
+    tokens: Vec<FileToken>,
+    ast: Option<AstNode>,
+    typed: Option<TypedProgram>,
+    tacky: Option<TackyProgram>,
+    assembly: Option<String>,
+    /// `None` for assembly reused from the cache.
+    source_map: Option<SourceMap>,
+}
+
+fn main() -> Result<()> {
+    // `-###` as in gcc, clap only takes single character short flags. The
+    // arguments after `--` belong to the program.
+    let mut separated = false;
+    let args = std::env::args_os().map(|arg| {
+        separated |= arg == "--";
+        match arg.to_str() {
+            Some("-###") if !separated => "--dry-run".into(),
+            _ => arg,
         }
-        if self.tacky {
-            counter += 1;
+    });
+    let opt = UlangDriver::try_parse_from(args).unwrap_or_else(|e| {
+        let _ = e.print();
+        if e.use_stderr() {
+            Failure::Usage.exit();
         }
+        exit(0);
+    });
+    opt.color.install();
+    if let Some(command) = &opt.command {
+        return run_command(command);
+    }
+    if opt.generate_man {
+        let man = clap_mangen::Man::new(UlangDriver::command());
+        return man.render(&mut std::io::stdout()).into_diagnostic();
+    }
+    if let Some(expression) = &opt.eval {
+        return eval(expression);
+    }
+    let file = opt
+        .file
+        .as_deref()
+        .expect("the file is required without a subcommand");
+    let options = CompileOptions::from(&opt);
+    let compiled = compile(file, &options, true)?;
 
-        counter <= 1 && self.file.exists()
+    if let Some(kind) = opt.emit {
+        let path = opt
+            .save_path
+            .clone()
+            .unwrap_or(file.with_extension(kind.extension()));
+        std::fs::write(&path, kind.render(&compiled)).into_diagnostic()?;
+        println!("Saved {}", path.display());
+        return Ok(());
+    }
+    if opt.interpret {
+        let tacky = compiled.tacky.as_ref().expect("the TACKY is generated");
+        let mut interpreter = ulang::tacky::interp::Interpreter::new(tacky);
+        interpreter.trap_overflow = compiled.options.checked_arithmetic;
+        // `argc` of a program started without arguments.
+        interpreter.arguments = vec![1];
+        let value = interpreter.run().map_err(|e| miette::miette!(e))?;
+        println!("Program returned {}", value);
+        exit(value);
     }
+    if options.emit < Stage::Codegen || opt.codegen {
+        return Ok(());
+    }
+    let path = opt.save_path.clone().unwrap_or(file.with_extension("s"));
+    link(&compiled, &path, &path.with_extension(""), true)
 }
 
-fn main() -> Result<()> {
-    let opt = UlangDriver::parse();
-    if !opt.is_valid() {
-        if opt.file.exists() {
-            eprintln!(
-                "Selected multiple options, only one option can be selected at the time: {:?}",
-                &opt
-            );
-        } else {
-            eprintln!("File \"{}\" does not exists!", opt.file.display());
+/// Prints the value of `expression`, exiting with the status of the stage
+/// that rejects it.
+fn eval(expression: &str) -> Result<()> {
+    let mut sources = SourceManager::default();
+    let id = sources.add(PathBuf::from("<eval>"), expression.to_string());
+    let report = |e: BoxedDiagnostic, failure: Failure| -> ! {
+        let report = miette::Report::new_boxed(e).with_source_code(sources.named_source(id));
+        eprintln!("{:?}", report);
+        failure.exit()
+    };
+    let mut lexer = sources.lexer(id);
+    let tokens = lexer
+        .tokenize()
+        .unwrap_or_else(|e| report(Box::new(e), Failure::Lex));
+    let parsed = ulang::parser::Parser::new(&tokens, &lexer.content).parse_whole_expression();
+    let expression = parsed.unwrap_or_else(|e| report(Box::new(e), Failure::Parse));
+    let value = const_eval::evaluate(&expression)
+        .unwrap_or_else(|e| report(Box::new(e), Failure::Semantic));
+    println!("{}", value);
+    Ok(())
+}
+
+fn run_command(command: &DriverCommand) -> Result<()> {
+    match command {
+        DriverCommand::Build {
+            options,
+            file,
+            output,
+        } => {
+            build(
+                &options.resolve(file, Stage::Codegen),
+                file,
+                output.as_deref(),
+            )?;
+        }
+        DriverCommand::Run {
+            options,
+            runner,
+            file,
+            args,
+        } => {
+            let options = options.resolve(file, Stage::Codegen);
+            let program = std::path::absolute(build(&options, file, None)?).into_diagnostic()?;
+            let mut command = ulang::runner::command(runner.as_deref(), &program);
+            command.args(args);
+            if options.dry_run {
+                println!("{}", toolchain::command_line(&command));
+                return Ok(());
+            }
+            let status = command.status().into_diagnostic()?;
+            exit(status_code(status));
+        }
+        DriverCommand::Archive {
+            options,
+            output,
+            files,
+        } => {
+            archive(options, files, output)?;
+        }
+        DriverCommand::Check { options, file } => {
+            compile(file, &options.resolve(file, Stage::Semantic), false)?;
+        }
+        DriverCommand::Emit {
+            kind,
+            options,
+            compare,
+            file,
+            output,
+        } => {
+            let mut options = options.resolve(file, kind.stage());
+            options.debug_info |= kind.debug_info();
+            let text = match compare.as_slice() {
+                [old, new] => {
+                    let render = |level| -> Result<String> {
+                        let options = CompileOptions {
+                            opt_level: level,
+                            ..options.clone()
+                        };
+                        Ok(kind.render(&compile(file, &options, false)?))
+                    };
+                    let name = |level| format!("{} -O{}", file.display(), level);
+                    ulang::text_diff::unified(
+                        &render(*old)?,
+                        &render(*new)?,
+                        &name(old),
+                        &name(new),
+                        3,
+                    )
+                }
+                _ => kind.render(&compile(file, &options, false)?),
+            };
+            match output {
+                Some(output) => std::fs::write(output, text).into_diagnostic()?,
+                None => print!("{}", text),
+            }
+        }
+        DriverCommand::Lex { file } => {
+            let options = CompileOptions {
+                emit: Stage::Lex,
+                ..CompileOptions::default()
+            };
+            let compiled = compile(file, &options, false)?;
+            print!("{}", Emit::Tokens.render(&compiled));
+        }
+        DriverCommand::Preprocess {
+            dry_run,
+            file,
+            output,
+        } => {
+            let output = output.clone().unwrap_or(file.with_extension("i"));
+            let defaults = CompileOptions::default();
+            let options = CompileOptions {
+                cc: toolchain_env().cc.unwrap_or(defaults.cc),
+                dry_run: *dry_run,
+                ..defaults
+            };
+            let mut command = toolchain::preprocess(&options.cc, file, &output);
+            if run_tool(&mut command, &options, false, Failure::Lex) {
+                println!("Saved {}", output.display());
+            }
+        }
+        DriverCommand::Completions { shell } => {
+            let mut command = UlangDriver::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(*shell, &mut command, name, &mut std::io::stdout());
+        }
+        DriverCommand::Cache {
+            action: CacheAction::Clear,
+        } => {
+            let Some(dir) = CompilationCache::default_dir() else {
+                miette::bail!("No cache directory, set ULANG_CACHE_DIR");
+            };
+            let removed = CompilationCache::new(dir).clear().into_diagnostic()?;
+            println!("Removed {} cached files", removed);
         }
-        exit(1);
     }
-    let mut lexer = ulang::lexer::Lexer::from_path(opt.file.clone()).into_diagnostic()?;
-    let tokens = lexer.tokenize()?;
+    Ok(())
+}
 
-    // if tokens.is_err() {
-    //     use miette::miette;
-    //     for error in tokens.unwrap_err() {
-    //         eprintln!("{}",miette!(error));
-    //     }
-    //     exit(1);
-    // }
+/// Prints how many errors and warnings were shown, and how many errors were
+/// held back by `--error-limit`.
+fn print_summary(sink: &dyn DiagnosticSink, limit: Option<usize>) {
+    let mut counts = SeverityCounts {
+        errors: sink.count(Severity::Error),
+        warnings: sink.count(Severity::Warning),
+        advice: 0,
+    };
+    let hidden = hidden_errors(counts, limit);
+    counts.errors -= hidden;
+    match (counts.summary(), hidden) {
+        (None, _) => {}
+        (Some(summary), 0) => eprintln!("{}", summary),
+        (Some(summary), _) => eprintln!(
+            "{}, {} more errors not shown, see --error-limit",
+            summary, hidden
+        ),
+    }
+}
 
-    // let tokens = tokens.expect("Failed");
-    println!("{:#?}", tokens);
+fn fail(sink: &dyn DiagnosticSink, limit: Option<usize>, failure: Failure) -> ! {
+    print_summary(sink, limit);
+    failure.exit()
+}
 
-    if opt.lex {
-        exit(0);
+/// Runs the stages for `file` up to the one `options` emits, exiting with the
+/// status of the first one that fails. `verbose` prints what every stage
+/// produced, as the driver does without a subcommand.
+fn compile(file: &Path, options: &CompileOptions, verbose: bool) -> Result<Compiled> {
+    let mut sources = SourceManager::default().with_hyperlinks(ColorChoice::hyperlinks());
+    let id = sources.load(file.to_path_buf()).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {}", file.display(), e);
+        Failure::Usage.exit();
+    });
+    let stop = options.emit;
+    let compiled = Compiled {
+        options: options.clone(),
+        sources,
+        file: id,
+        tokens: vec![],
+        ast: None,
+        typed: None,
+        tacky: None,
+        assembly: None,
+        source_map: None,
+    };
+
+    let target = options.target;
+    let checks = options.runtime_checks(&file.display().to_string());
+    let instrumentation = options.instrumentation(file).into_diagnostic()?;
+    let cache = CompilationCache::default_dir()
+        .filter(|_| stop == Stage::Codegen && options.cache)
+        .map(CompilationCache::new);
+    let key = format!(
+        "{:?}",
+        (
+            options.opt_level,
+            &checks,
+            &instrumentation,
+            &options.entry,
+            options.standard,
+            options.trigraphs,
+            options.freestanding,
+            options.start,
+            options.function_sections,
+        )
+    );
+    let key = CacheKey::new(compiled.sources.content(id), &key, target);
+    if let Some(assembly) = cache.as_ref().and_then(|cache| cache.get(key)) {
+        if verbose {
+            println!("Reusing the cached assembly {}", key);
+            println!("{}", assembly);
+        }
+        return Ok(Compiled {
+            assembly: Some(assembly),
+            ..compiled
+        });
     }
 
-    let mut parser = ulang::parser::Parser::new(tokens, lexer.path, lexer.content);
-    let ast = parser.parse()?;
-    println!("{:#?}", ast);
+    let mut lexer = compiled.sources.lexer(id).with_trigraphs(options.trigraphs);
+    let mut stream = Stream::new(|diagnostic| {
+        eprintln!("{:?}", miette::Report::new_boxed(diagnostic));
+    });
+    let warnings = &options.warnings;
+    let limit = options.error_limit;
+    let mut deduplicate = Deduplicate::new(&mut stream).with_limit(limit);
+    let mut filter = warnings.sink(&mut deduplicate);
+    let mut sink = compiled.sources.sink_for(id, &mut filter);
+    let Some(tokens) = lexer.tokenize_into(&mut sink) else {
+        fail(&sink, limit, Failure::Lex);
+    };
+    if verbose {
+        println!("{:#?}", tokens);
+    }
+    if stop == Stage::Lex {
+        print_summary(&sink, limit);
+        return Ok(Compiled { tokens, ..compiled });
+    }
 
-    if opt.parse {
-        exit(0);
+    let mut parser = ulang::parser::Parser::new(&tokens, &lexer.content);
+    if let Some(standard) = options.standard {
+        parser = parser.with_standard(standard);
+    }
+    let Some(ast) = parser.parse_into(&mut sink) else {
+        fail(&sink, limit, Failure::Parse);
+    };
+    if let Some(standard) = options.standard {
+        if !ulang::dialect::check_into(&lexer, &ast, standard, &mut sink) {
+            fail(&sink, limit, Failure::Parse);
+        }
+    }
+    if verbose {
+        println!("{:#?}", ast);
+    }
+    if stop == Stage::Parse {
+        print_summary(&sink, limit);
+        return Ok(Compiled {
+            tokens,
+            ast: Some(ast),
+            ..compiled
+        });
     }
 
-    let mut tacky = ulang::tacky::Tacky::from_program_node(&ast).unwrap();
-    let result = tacky.parse().unwrap();
-    println!("\nTacky\n{:#?}", result);
-    if opt.tacky {
-        exit(0);
+    let Some(typed) = ulang::typecheck::typecheck_into(&ast, &mut sink) else {
+        fail(&sink, limit, Failure::Semantic);
+    };
+    let entry = options.entry.unwrap_or_else(|| Identifier::from("main"));
+    if options.entry.is_some() && !typed.functions.iter().any(|f| f.name == entry) {
+        let message = format!("No entry function `{}`", entry);
+        sink.report(Box::new(MietteDiagnostic::new(message)));
+        fail(&sink, limit, Failure::Semantic);
+    }
+    for warning in reachability::missing_returns(&ast, entry) {
+        sink.report(Box::new(warning));
+    }
+    if warnings.deny && sink.count(Severity::Warning) > 0 {
+        print_summary(&sink, limit);
+        eprintln!("Failed because warnings are denied");
+        Failure::Semantic.exit();
+    }
+    if stop == Stage::Semantic {
+        print_summary(&sink, limit);
+        return Ok(Compiled {
+            tokens,
+            ast: Some(ast),
+            typed: Some(typed),
+            ..compiled
+        });
     }
 
-    #[cfg(target_os = "linux")]
-    let target = assembly::TargetPlatform::X64Linux;
-    #[cfg(not(target_os = "linux"))]
-    let target = assembly::TargetPlatform::MacOsX64;
-    let asm_final = ulang::assembly::generate_assembly(&result, target);
-    println!("{}", asm_final.0);
+    // Types the type checker accepts but lowering can't handle yet are
+    // reported here, as code generation errors.
+    let mut tacky = ulang::tacky::Tacky::new(&typed);
+    if let Some(entry) = options.entry {
+        tacky = tacky.with_entry(entry);
+    }
+    if options.source_locations() {
+        tacky = tacky.with_source_locations(&lexer.content);
+    }
+    let Some(mut result) = tacky.parse_into(&mut sink) else {
+        fail(&sink, limit, Failure::Codegen);
+    };
+    print_summary(&sink, limit);
 
-    if opt.codegen {
-        exit(0);
+    let passes = options.passes();
+    if let Err(e) = passes.run_tacky(&mut result) {
+        eprintln!("{:?}", miette::miette!(e));
+        Failure::Codegen.exit();
     }
-    let path = opt.save_path.unwrap_or(opt.file.with_extension("s"));
-    std::fs::write(&path, asm_final.0).expect("Failed to save file");
-    use std::process::Command;
-    let cmd = format!(
-        "gcc {} -o {}",
-        &path.display(),
-        path.with_extension("").display()
-    );
-    println!("Running: {}", &cmd);
-    let output = if cfg!(target_os = "windows") {
-        Command::new("cmd")
-            .args(["/C", &cmd])
-            .output()
-            .expect("failed to execute process")
-    } else {
-        Command::new("sh")
-            .arg("-c")
-            .arg(&cmd)
-            .output()
-            .expect("failed to execute process")
+    if verbose {
+        println!("\nTacky\n{}", result.to_annotated_string());
+    }
+    if stop == Stage::Tacky {
+        return Ok(Compiled {
+            tokens,
+            ast: Some(ast),
+            typed: Some(typed),
+            tacky: Some(result),
+            ..compiled
+        });
+    }
+
+    // The backend has no error type, whatever it can't handle panics.
+    let generated = std::panic::catch_unwind(|| {
+        assembly::generate_assembly_with_source_map(
+            &result,
+            target,
+            &passes,
+            &checks,
+            &instrumentation,
+            options.function_sections(),
+        )
+    });
+    let Ok((mut asm_final, source_map)) = generated else {
+        Failure::Codegen.exit();
     };
+    if options.start {
+        asm_final.0 += &assembly::generate_start(&result.0.identifier, target);
+    }
+    if verbose {
+        println!("{}", asm_final.0);
+    }
+    // Warnings would go unseen when the assembly is reused.
+    if let Some(cache) = cache.filter(|_| sink.count(Severity::Warning) == 0) {
+        if let Err(e) = cache.put(key, &asm_final.0) {
+            eprintln!("Failed to cache the assembly: {}", e);
+        }
+    }
+    Ok(Compiled {
+        tokens,
+        ast: Some(ast),
+        typed: Some(typed),
+        tacky: Some(result),
+        assembly: Some(asm_final.0),
+        source_map: Some(source_map),
+        ..compiled
+    })
+}
 
-    let hello = output.stdout;
-    println!("result: {:?}", hello);
+/// Compiles and links `file` into `output`, or `file` without its extension,
+/// and returns the path of the program.
+fn build(options: &CompileOptions, file: &Path, output: Option<&Path>) -> Result<PathBuf> {
+    let compiled = compile(file, options, false)?;
+    let program = output
+        .map(Path::to_path_buf)
+        .unwrap_or(file.with_extension(""));
+    link(&compiled, &program.with_extension("s"), &program, false)?;
+    Ok(program)
+}
+
+/// Saves the assembly to `path` and builds `program` from it with gcc.
+fn link(compiled: &Compiled, path: &Path, program: &Path, verbose: bool) -> Result<()> {
+    save_assembly(compiled, path)?;
+    let options = &compiled.options;
+    let mut flags: Vec<&str> = vec![];
+    if options.freestanding {
+        flags.push("-nostdlib");
+    }
+    if options.function_sections && options.target.conventions().function_sections {
+        flags.push("-Wl,--gc-sections");
+    }
+    flags.extend(options.ldflags.iter().map(String::as_str));
+    let mut command = toolchain::link(&options.cc, path, program, &flags);
+    run_tool(&mut command, options, verbose, Failure::Link);
+    Ok(())
+}
+
+/// Saves the assembly to `path` and assembles it into `object` with gcc.
+fn assemble(compiled: &Compiled, path: &Path, object: &Path) -> Result<()> {
+    save_assembly(compiled, path)?;
+    let mut command = toolchain::assemble(&compiled.options.cc, path, object);
+    run_tool(&mut command, &compiled.options, false, Failure::Link);
     Ok(())
 }
+
+/// Writes the assembly to `path`, and its source map next to it when asked
+/// for.
+fn save_assembly(compiled: &Compiled, path: &Path) -> Result<()> {
+    let options = &compiled.options;
+    let assembly = compiled
+        .assembly
+        .as_deref()
+        .expect("the assembly is generated");
+    if let Err(e) = std::fs::write(path, assembly) {
+        eprintln!("Failed to save {}: {}", path.display(), e);
+        Failure::Link.exit();
+    }
+    if let Some(source_map) = compiled.source_map.as_ref().filter(|_| options.debug_info) {
+        let map_path = path.with_extension("map.json");
+        let json = source_map.to_json(
+            &path.display().to_string(),
+            &compiled.sources.path(compiled.file).display().to_string(),
+        );
+        std::fs::write(&map_path, json).into_diagnostic()?;
+        println!("Saved {}", map_path.display());
+    }
+    Ok(())
+}
+
+/// Defaults of the toolchain from `CC`, `ULANG_TARGET` and `LDFLAGS`, exits
+/// when one is invalid.
+fn toolchain_env() -> ToolchainEnv {
+    ToolchainEnv::from_env().unwrap_or_else(|e| {
+        eprintln!("{:?}", miette::Report::new(e));
+        Failure::Usage.exit();
+    })
+}
+
+/// Runs a tool of the toolchain, exiting with `failure` when it fails. What
+/// it prints to stderr is passed on, its stdout only when `verbose`. Only
+/// prints the command with `--dry-run`, returns whether it ran.
+fn run_tool(
+    command: &mut Command,
+    options: &CompileOptions,
+    verbose: bool,
+    failure: Failure,
+) -> bool {
+    if options.dry_run {
+        println!("{}", toolchain::command_line(command));
+        return false;
+    }
+    if verbose {
+        println!("Running: {}", toolchain::command_line(command));
+    }
+    match toolchain::run(command) {
+        Ok(output) => {
+            if verbose {
+                print!("{}", output.stdout);
+            }
+            eprint!("{}", output.stderr);
+            true
+        }
+        Err(e) => {
+            eprintln!("{:?}", miette::Report::new(e));
+            failure.exit();
+        }
+    }
+}
+
+/// Compiles and assembles every file of `files` into an object next to it
+/// and bundles the objects into the static library `output` with `ar`.
+fn archive(options: &CompileArgs, files: &[PathBuf], output: &Path) -> Result<()> {
+    let mut objects = vec![];
+    for file in files {
+        let compiled = compile(file, &options.resolve(file, Stage::Codegen), false)?;
+        let object = file.with_extension("o");
+        assemble(&compiled, &file.with_extension("s"), &object)?;
+        objects.push(object);
+    }
+    let tools = CompileOptions {
+        dry_run: options.dry_run,
+        ..CompileOptions::default()
+    };
+    // `ar r` keeps the members already in the library, objects of files no
+    // longer listed would stay in it.
+    if !tools.dry_run {
+        let _ = std::fs::remove_file(output);
+    }
+    let mut command = toolchain::archive(output, &objects);
+    if run_tool(&mut command, &tools, false, Failure::Link) {
+        println!("Saved {}", output.display());
+    }
+    Ok(())
+}
+
+/// Status of a finished program as a shell reports it, 128 plus the signal
+/// for a program killed by one.
+fn status_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(1)
+}
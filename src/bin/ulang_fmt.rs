@@ -0,0 +1,56 @@
+use clap::Parser;
+use miette::{IntoDiagnostic, NamedSource, Report, Result};
+use std::{
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+/// Formatter for the C subset supported by ulang
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct UlangFmt {
+    /// don't write the files, exit with status 1 if any of them would be reformatted
+    #[arg(long)]
+    check: bool,
+    /// files to format
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+fn format_file(path: &Path) -> Result<(String, String)> {
+    let mut lexer = ulang::lexer::Lexer::from_path(path.to_path_buf())
+        .into_diagnostic()?
+        .with_trivia(true);
+    let source = NamedSource::new(path.to_string_lossy(), lexer.content.clone());
+    let tokens = lexer
+        .tokenize()
+        .map_err(|e| Report::new(e).with_source_code(source.clone()))?;
+    let formatted = ulang::format::format_tokens(&tokens, &lexer.end_trivia);
+    // Only programs the compiler accepts get formatted.
+    let mut parser = ulang::parser::Parser::new(&tokens, &lexer.content);
+    parser
+        .parse()
+        .map_err(|e| Report::new(e).with_source_code(source))?;
+    Ok((lexer.content, formatted))
+}
+
+fn main() -> Result<()> {
+    let opt = UlangFmt::parse();
+    let mut unformatted = 0;
+    for path in &opt.files {
+        let (original, formatted) = format_file(path)?;
+        if original == formatted {
+            continue;
+        }
+        if opt.check {
+            println!("{} is not formatted", path.display());
+            unformatted += 1;
+        } else {
+            std::fs::write(path, formatted).into_diagnostic()?;
+        }
+    }
+    if unformatted > 0 {
+        exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use clap::Parser;
+use miette::{miette, IntoDiagnostic, Result};
+use std::io::{BufRead, Write};
+use ulang::{
+    assembly::{self, TargetPlatform},
+    ast::AstNode,
+    lexer::Lexer,
+    optimize::{OptimizationLevel, PassManager},
+    tacky::{interp::Interpreter, Tacky, TackyProgram},
+    typecheck::typecheck,
+};
+
+const TARGET: TargetPlatform = TargetPlatform::host();
+
+const HELP: &str = "\
+Enter an expression to print its value, or statements ending in `;` to run
+them as the body of `int main(void)` and print what it returns. Input
+continues on the next line while brackets are open or it ends in an operator.
+
+  :ast [input]    show the AST of the input, or of the last one
+  :tacky [input]  show the TACKY
+  :asm [input]    show the assembly
+  :help           show this text
+  :quit           leave, like end of input";
+
+/// Evaluates expressions and statements of the C subset supported by ulang
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct UlangRepl {
+    /// optimization level of the shown TACKY and assembly and of the run code
+    #[arg(short = 'O', value_name = "LEVEL", default_value = "0")]
+    opt_level: OptimizationLevel,
+    /// run natively in process instead of in the TACKY interpreter, a fault like a division by
+    /// zero then ends the session
+    #[cfg(all(feature = "jit", target_arch = "x86_64", unix))]
+    #[arg(long)]
+    jit: bool,
+}
+
+/// Program compiled from one input.
+struct Compiled {
+    ast: AstNode,
+    tacky: TackyProgram,
+}
+
+/// Whether `input` is ready to compile: every bracket is closed and it does
+/// not end in an operator.
+fn is_complete(input: &str) -> bool {
+    let mut depth = 0;
+    for c in input.chars() {
+        match c {
+            '(' | '{' => depth += 1,
+            ')' | '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    let ends_in_operator = input
+        .trim_end()
+        .ends_with(['+', '-', '*', '/', '%', '&', '|', '<', '>', '=', '!', '~']);
+    depth <= 0 && !ends_in_operator
+}
+
+/// `int main` around `input`, statements as they are and an expression
+/// returned.
+fn wrap(input: &str) -> String {
+    let input = input.trim();
+    if input.ends_with(';') || input.ends_with('}') {
+        format!("int main(void) {{\n{}\n}}\n", input)
+    } else {
+        format!("int main(void) {{\n    return {};\n}}\n", input)
+    }
+}
+
+fn compile(input: &str, passes: &PassManager) -> Result<Compiled> {
+    let source = wrap(input);
+    let mut lexer = Lexer::from_content(source.clone());
+    let tokens = lexer
+        .tokenize()
+        .map_err(|e| miette::Report::new(e).with_source_code(source.clone()))?;
+    let ast = ulang::parser::Parser::new(&tokens, &lexer.content)
+        .parse()
+        .map_err(|e| miette::Report::new(e).with_source_code(source.clone()))?;
+    let typed = typecheck(&ast).map_err(|e| miette::Report::new(e).with_source_code(source))?;
+    let mut tacky = Tacky::new(&typed).parse().map_err(|e| miette!(e))?;
+    passes.run_tacky(&mut tacky).map_err(|e| miette!(e))?;
+    Ok(Compiled { ast, tacky })
+}
+
+struct Repl {
+    opt: UlangRepl,
+    last_input: Option<String>,
+}
+
+impl Repl {
+    fn passes(&self) -> PassManager {
+        PassManager::new(self.opt.opt_level)
+    }
+
+    fn run(&self, compiled: &Compiled) -> Result<i32> {
+        #[cfg(all(feature = "jit", target_arch = "x86_64", unix))]
+        if self.opt.jit {
+            return ulang::jit::run(&compiled.tacky, &self.passes()).map_err(|e| miette!(e));
+        }
+        Interpreter::new(&compiled.tacky)
+            .run()
+            .map_err(|e| miette!(e))
+    }
+
+    /// Handles a complete input, returning `false` when the session ends.
+    fn eval(&mut self, input: &str) -> Result<bool> {
+        let input = input.trim();
+        let (command, argument) = match input.strip_prefix(':') {
+            Some(command) => {
+                let (command, argument) = command
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((command, ""));
+                (Some(command), argument.trim())
+            }
+            None => (None, input),
+        };
+        match command {
+            Some("quit" | "q") => return Ok(false),
+            Some("help" | "h") => {
+                println!("{}", HELP);
+                return Ok(true);
+            }
+            Some("ast" | "tacky" | "asm") | None => {}
+            Some(other) => return Err(miette!("unknown command :{}, see :help", other)),
+        }
+        if argument.is_empty() && command.is_none() {
+            return Ok(true);
+        }
+        let input = match (argument, &self.last_input) {
+            ("", Some(last)) => last.clone(),
+            ("", None) => return Err(miette!("nothing was entered yet")),
+            (argument, _) => argument.to_string(),
+        };
+        let compiled = compile(&input, &self.passes())?;
+        self.last_input = Some(input);
+        match command {
+            Some("ast") => println!("{:#?}", compiled.ast),
+            Some("tacky") => print!("{}", compiled.tacky),
+            Some("asm") => {
+                let asm = assembly::generate_optimized_assembly(
+                    &compiled.tacky,
+                    TARGET,
+                    &self.passes(),
+                    &assembly::RuntimeChecks::default(),
+                );
+                print!("{}", asm.0);
+            }
+            _ => println!("{}", self.run(&compiled)?),
+        }
+        Ok(true)
+    }
+}
+
+fn prompt(text: &str) -> Result<()> {
+    print!("{}", text);
+    std::io::stdout().flush().into_diagnostic()
+}
+
+fn main() -> Result<()> {
+    let mut repl = Repl {
+        opt: UlangRepl::parse(),
+        last_input: None,
+    };
+    let mut input = String::new();
+    prompt("ulang> ")?;
+    for line in std::io::stdin().lock().lines() {
+        let line = line.into_diagnostic()?;
+        input.push_str(&line);
+        input.push('\n');
+        if !input.trim_start().starts_with(':') && !is_complete(&input) {
+            prompt("   ... ")?;
+            continue;
+        }
+        match repl.eval(&input) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(report) => eprintln!("{:?}", report),
+        }
+        input.clear();
+        prompt("ulang> ")?;
+    }
+    println!();
+    Ok(())
+}
@@ -0,0 +1,117 @@
+//! On-disk cache of generated assembly, so a build that runs the driver for
+//! every file only compiles the files that changed since the last build.
+//!
+//! An entry is keyed by a hash of the source, the options that change the
+//! output, the target and the build of the compiler, and holds the assembly
+//! `ulang_driver` would otherwise generate for it. The build is the version
+//! and a hash of the sources of the compiler from `build.rs`, so a compiler
+//! changed without a new version doesn't reuse the assembly of the old one.
+
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use crate::assembly::TargetPlatform;
+
+/// Extension of the files holding the entries.
+const EXTENSION: &str = "s";
+
+/// 128-bit FNV-1a of everything that decides the assembly of one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CacheKey(u128);
+
+impl CacheKey {
+    /// `options` stands for every option that changes the output, in any
+    /// layout as long as it is the same for the same options.
+    pub fn new(source: &str, options: &str, target: TargetPlatform) -> Self {
+        const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+        const PRIME: u128 = 0x0000000001000000000000000000013b;
+        let target = format!("{:?}", target);
+        let mut hash = OFFSET;
+        let build = concat!(env!("CARGO_PKG_VERSION"), "+", env!("ULANG_BUILD_ID"));
+        for part in [build, source, options, &target] {
+            // The length keeps the parts from running into each other.
+            let length = (part.len() as u64).to_le_bytes();
+            for byte in length.iter().chain(part.as_bytes()) {
+                hash ^= *byte as u128;
+                hash = hash.wrapping_mul(PRIME);
+            }
+        }
+        Self(hash)
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompilationCache {
+    pub dir: PathBuf,
+}
+
+impl CompilationCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// `$ULANG_CACHE_DIR`, or `ulang` in `$XDG_CACHE_HOME` or `~/.cache`.
+    pub fn default_dir() -> Option<PathBuf> {
+        let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+        if let Some(dir) = var("ULANG_CACHE_DIR") {
+            return Some(PathBuf::from(dir));
+        }
+        let cache = var("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(cache.join("ulang"))
+    }
+
+    pub fn path(&self, key: CacheKey) -> PathBuf {
+        self.dir.join(format!("{}.{}", key, EXTENSION))
+    }
+
+    /// The assembly stored for `key`, `None` when there is none or it can't
+    /// be read.
+    pub fn get(&self, key: CacheKey) -> Option<String> {
+        std::fs::read_to_string(self.path(key)).ok()
+    }
+
+    /// Stores `assembly` for `key`. The entry is written next to its place
+    /// and renamed into it, so a build running at the same time never reads
+    /// half of it.
+    pub fn put(&self, key: CacheKey, assembly: &str) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&partial, assembly)?;
+        std::fs::rename(&partial, &path).inspect_err(|_| {
+            let _ = std::fs::remove_file(&partial);
+        })
+    }
+
+    /// Removes every entry and returns how many there were, a missing
+    /// directory is an empty cache.
+    pub fn clear(&self) -> io::Result<usize> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == EXTENSION)
+            {
+                std::fs::remove_file(path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
@@ -0,0 +1,144 @@
+//! C ABI for tools that run the compiler in process, declared for C in
+//! `include/ulang.h`. Built into the `cdylib` with the `capi` feature:
+//!
+//! ```c
+//! ulang_options options = { .opt_level = 2, .target = ULANG_TARGET_X64_LINUX };
+//! ulang_result result;
+//! if (ulang_compile("int main(void) { return 2; }", &options, &result) == ULANG_OK)
+//!     fputs(result.assembly, stdout);
+//! else
+//!     fputs(result.diagnostics, stderr);
+//! ulang_result_free(&result);
+//! ```
+#![allow(non_camel_case_types)]
+
+use std::{
+    ffi::{c_char, c_int, c_uint, CStr, CString},
+    panic, ptr,
+};
+
+use crate::{assembly::TargetPlatform, embed::assembly_for, json::JsonDiagnostics};
+
+pub const ULANG_OK: c_int = 0;
+/// The source did not compile, `diagnostics` says why.
+pub const ULANG_DIAGNOSTICS: c_int = 1;
+/// `src` or `result` is null, or an option is out of range.
+pub const ULANG_INVALID_ARGUMENT: c_int = 2;
+
+pub const ULANG_TARGET_X64_LINUX: c_uint = 0;
+pub const ULANG_TARGET_MACOS_X64: c_uint = 1;
+
+/// Options of [`ulang_compile`], all zeros picks `-O0` for Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ulang_options {
+    /// 0, 1 or 2, like `-O`.
+    pub opt_level: c_uint,
+    /// `ULANG_TARGET_X64_LINUX` or `ULANG_TARGET_MACOS_X64`.
+    pub target: c_uint,
+}
+
+/// Output of [`ulang_compile`], owned by the library until
+/// [`ulang_result_free`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct ulang_result {
+    /// AT&T assembly, null when the source did not compile.
+    pub assembly: *mut c_char,
+    /// JSON array of diagnostics in the layout of [`JsonDiagnostics`], `[]`
+    /// on success.
+    pub diagnostics: *mut c_char,
+}
+
+/// `text` for the caller, `None` when it has a NUL byte C would cut it at.
+fn c_string(text: String) -> Option<*mut c_char> {
+    CString::new(text).ok().map(CString::into_raw)
+}
+
+/// The diagnostics for the caller. A NUL can only be in a JSON string, which
+/// may hold it escaped instead.
+fn c_diagnostics(JsonDiagnostics(json): JsonDiagnostics) -> *mut c_char {
+    c_string(json.replace('\0', "\\u0000")).unwrap_or_else(ptr::null_mut)
+}
+
+/// Compiles the NUL-terminated UTF-8 source `src` to assembly.
+///
+/// `options` may be null for the defaults. Unless it returns
+/// `ULANG_INVALID_ARGUMENT` for a null `result`, the fields of `result` are
+/// set and must be released with [`ulang_result_free`].
+///
+/// # Safety
+///
+/// `src` must be null or a NUL-terminated string, `options` null or a valid
+/// [`ulang_options`], and `result` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn ulang_compile(
+    src: *const c_char,
+    options: *const ulang_options,
+    result: *mut ulang_result,
+) -> c_int {
+    if result.is_null() {
+        return ULANG_INVALID_ARGUMENT;
+    }
+    let options = options.as_ref().copied().unwrap_or_default();
+    let invalid = |message: String| (ULANG_INVALID_ARGUMENT, JsonDiagnostics::message(&message));
+    let platform = match (src.is_null(), options.opt_level, options.target) {
+        (true, _, _) => Err(invalid("src is null".to_string())),
+        (_, 3.., _) => Err(invalid(format!(
+            "Unknown optimization level {}",
+            options.opt_level
+        ))),
+        (_, _, ULANG_TARGET_X64_LINUX) => Ok(TargetPlatform::X64Linux),
+        (_, _, ULANG_TARGET_MACOS_X64) => Ok(TargetPlatform::MacOsX64),
+        (_, _, target) => Err(invalid(format!("Unknown target {}", target))),
+    };
+    let outcome = platform.and_then(|platform| {
+        let source = CStr::from_ptr(src).to_str().map_err(|e| {
+            let message = format!("The source is not UTF-8: {}", e);
+            (ULANG_DIAGNOSTICS, JsonDiagnostics::message(&message))
+        })?;
+        // A panic must not unwind into the caller, it is reported like any
+        // other failure.
+        panic::catch_unwind(|| assembly_for(source, options.opt_level as u8, platform))
+            .unwrap_or_else(|_| Err(JsonDiagnostics::message("Internal compiler error")))
+            .map_err(|diagnostics| (ULANG_DIAGNOSTICS, diagnostics))
+            .and_then(|assembly| {
+                c_string(assembly).ok_or_else(|| {
+                    let message = "The assembly contains a NUL byte";
+                    (ULANG_DIAGNOSTICS, JsonDiagnostics::message(message))
+                })
+            })
+    });
+    let (status, assembly, diagnostics) = match outcome {
+        Ok(assembly) => (
+            ULANG_OK,
+            assembly,
+            c_diagnostics(JsonDiagnostics::new(&[], "")),
+        ),
+        Err((status, diagnostics)) => (status, ptr::null_mut(), c_diagnostics(diagnostics)),
+    };
+    result.write(ulang_result {
+        assembly,
+        diagnostics,
+    });
+    status
+}
+
+/// Releases the strings of a result filled by [`ulang_compile`] and nulls
+/// them, so freeing twice is harmless.
+///
+/// # Safety
+///
+/// `result` must be null or a result filled by [`ulang_compile`].
+#[no_mangle]
+pub unsafe extern "C" fn ulang_result_free(result: *mut ulang_result) {
+    let Some(result) = result.as_mut() else {
+        return;
+    };
+    for field in [&mut result.assembly, &mut result.diagnostics] {
+        if !field.is_null() {
+            drop(CString::from_raw(*field));
+            *field = ptr::null_mut();
+        }
+    }
+}
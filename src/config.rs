@@ -0,0 +1,122 @@
+//! Project defaults read from a `ulang.toml` in the directory of the source
+//! file or one above it, so the options shared by every file of a project
+//! don't have to be repeated for each one:
+//!
+//! ```toml
+//! target = "x64-linux"
+//! opt-level = 2
+//! cc = "clang"
+//!
+//! [warnings]
+//! allow = ["unreachable_code"]
+//! deny = true
+//! ```
+//!
+//! Every key is optional. The command line wins over the file, and so do the
+//! variables of [`ToolchainEnv`](crate::toolchain::ToolchainEnv).
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use miette::Diagnostic;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{assembly::TargetPlatform, diagnostics::WarningPolicy, optimize::OptimizationLevel};
+
+pub const FILE_NAME: &str = "ulang.toml";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub target: Option<TargetPlatform>,
+    pub opt_level: Option<OptimizationLevel>,
+    /// C compiler assembling and linking the program.
+    pub cc: Option<String>,
+    pub warnings: WarningPolicy,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum ConfigError {
+    #[error("Failed to read {}", path.display())]
+    #[diagnostic(code(error::config))]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Invalid {}: {message}", path.display())]
+    #[diagnostic(code(error::config))]
+    Invalid { path: PathBuf, message: String },
+}
+
+/// The file as written, before the values are checked.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct RawConfig {
+    target: Option<String>,
+    opt_level: Option<u8>,
+    cc: Option<String>,
+    warnings: RawWarnings,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RawWarnings {
+    allow: Vec<String>,
+    deny: bool,
+}
+
+impl Config {
+    /// The `ulang.toml` closest to `file`, looking in its directory and then
+    /// in every directory above it.
+    pub fn find(file: &Path) -> Option<PathBuf> {
+        let file = std::path::absolute(file).ok()?;
+        file.ancestors()
+            .skip(1)
+            .map(|dir| dir.join(FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// The config of the `ulang.toml` closest to `file`, the default one when
+    /// there is none.
+    pub fn for_file(file: &Path) -> Result<Self, ConfigError> {
+        match Self::find(file) {
+            Some(path) => Self::load(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        text.parse().map_err(|message| ConfigError::Invalid {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+}
+
+impl FromStr for Config {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let raw: RawConfig = toml::from_str(s).map_err(|e| e.to_string())?;
+        Ok(Config {
+            target: raw.target.as_deref().map(str::parse).transpose()?,
+            opt_level: raw
+                .opt_level
+                .map(|level| level.to_string().parse())
+                .transpose()?,
+            cc: raw.cc,
+            warnings: WarningPolicy {
+                allow: raw.warnings.allow,
+                deny: raw.warnings.deny,
+            },
+        })
+    }
+}
@@ -0,0 +1,87 @@
+//! Evaluation of integer constant expressions at compile time, for static
+//! initializers, enum values and array sizes.
+//!
+//! The arithmetic is the one of [`TackyProgram::fold_constants`], so folding
+//! and evaluating never disagree. `&&` and `||` don't evaluate their right
+//! operand when the left one decides the result, like at runtime, which makes
+//! `0 && 1 / 0` a constant 0. `&` and `|` are evaluated even though code
+//! generation doesn't support them yet.
+//!
+//! Variables, assignments, `++`, `--` and `&` are never constant. The file-scope declarations
+//! that need this are still to come.
+//!
+//! [`TackyProgram::fold_constants`]: crate::tacky::TackyProgram::fold_constants
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::{
+    ast::{BinaryOperator, Expression, ExpressionKind, Factor},
+    tacky::{
+        constant_folding::{fold_binary, fold_unary},
+        TackyBinaryOperator,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error, Diagnostic)]
+pub enum ConstError {
+    #[error("Division by zero in constant expression")]
+    #[diagnostic(code(error::constant))]
+    DivisionByZero {
+        #[label("divisor is zero")]
+        span: SourceSpan,
+    },
+    #[error("Expression is not a constant")]
+    #[diagnostic(code(error::constant))]
+    NotConstant {
+        #[label("not known at compile time")]
+        span: SourceSpan,
+    },
+    #[error("Integer overflow in constant expression")]
+    #[diagnostic(code(error::constant))]
+    Overflow {
+        #[label("result doesn't fit in an int")]
+        span: SourceSpan,
+    },
+}
+
+/// Value of `expression` as an `int`, the spans of the errors point at the
+/// operator or the variable.
+pub fn evaluate(expression: &Expression) -> Result<i32, ConstError> {
+    match &expression.kind {
+        ExpressionKind::Factor(Factor::Constant(c, _)) => Ok(*c),
+        ExpressionKind::Factor(Factor::Variable(_, span))
+        | ExpressionKind::Assignment(_, _, span)
+        | ExpressionKind::Update(_, _, span)
+        | ExpressionKind::AddressOf(_, span) => Err(ConstError::NotConstant { span: *span }),
+        ExpressionKind::Factor(Factor::ParentedExpression(inner)) => evaluate(inner),
+        ExpressionKind::Factor(Factor::Unary(operator, inner)) => {
+            Ok(fold_unary(operator, evaluate(inner)?))
+        }
+        ExpressionKind::Binary(left, BinaryOperator::And, right, _) => {
+            Ok((evaluate(left)? != 0 && evaluate(right)? != 0) as i32)
+        }
+        ExpressionKind::Binary(left, BinaryOperator::Or, right, _) => {
+            Ok((evaluate(left)? != 0 || evaluate(right)? != 0) as i32)
+        }
+        ExpressionKind::Binary(left, BinaryOperator::BitwiseAnd, right, _) => {
+            Ok(evaluate(left)? & evaluate(right)?)
+        }
+        ExpressionKind::Binary(left, BinaryOperator::BitwiseOr, right, _) => {
+            Ok(evaluate(left)? | evaluate(right)?)
+        }
+        ExpressionKind::Binary(left, operator, right, span) => {
+            let operator = TackyBinaryOperator::try_from(operator)
+                .expect("only the logical and bitwise operators have no TACKY operator");
+            let (left, right) = (evaluate(left)?, evaluate(right)?);
+            let divides = matches!(
+                operator,
+                TackyBinaryOperator::Divide | TackyBinaryOperator::Remainder
+            );
+            if divides && right == 0 {
+                return Err(ConstError::DivisionByZero { span: *span });
+            }
+            fold_binary(&operator, left, right).ok_or(ConstError::Overflow { span: *span })
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Reading the `.ulangcov` files written by programs compiled with
+//! `--coverage` and annotating the source with them.
+//!
+//! The file has a `<line>\t<count>` line for every source line of every basic
+//! block, so a line shared by several blocks appears several times. With the
+//! tabs shown as spaces:
+//!
+//! ```text
+//! 2   1
+//! 3   1
+//! 5   0
+//! ```
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CoverageError {
+    #[error("Line {0} of the coverage file is not `<line>\\t<count>`")]
+    Malformed(usize),
+}
+
+/// How many times each source line ran, keyed by its 1-based number.
+///
+/// A line shared by several blocks gets the count of the one that ran the
+/// most, so a line reads as covered when any of its code ran.
+pub fn parse_counts(coverage: &str) -> Result<BTreeMap<usize, u64>, CoverageError> {
+    let mut counts = BTreeMap::new();
+    for (i, record) in coverage.lines().enumerate() {
+        if record.is_empty() {
+            continue;
+        }
+        let parsed = record
+            .split_once('\t')
+            .and_then(|(line, count)| Some((line.parse().ok()?, count.parse().ok()?)));
+        let Some((line, count)) = parsed else {
+            return Err(CoverageError::Malformed(i + 1));
+        };
+        let entry = counts.entry(line).or_insert(0);
+        *entry = u64::max(*entry, count);
+    }
+    Ok(counts)
+}
+
+/// `source` with the count of every line in front of it, in the layout of
+/// gcov: `-` for lines without code and `#####` for code that never ran.
+///
+/// ```text
+///         -:    1:int main(void) {
+///         1:    2:    return 0;
+///         -:    3:}
+/// ```
+pub fn annotate(source: &str, counts: &BTreeMap<usize, u64>) -> String {
+    let mut annotated = String::with_capacity(source.len() * 2);
+    for (i, text) in source.lines().enumerate() {
+        let count = match counts.get(&(i + 1)) {
+            None => "-".to_string(),
+            Some(0) => "#####".to_string(),
+            Some(count) => count.to_string(),
+        };
+        let _ = writeln!(annotated, "{:>9}:{:>5}:{}", count, i + 1, text);
+    }
+    annotated
+}
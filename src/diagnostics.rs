@@ -0,0 +1,278 @@
+//! Where the stages report their errors and warnings.
+//!
+//! The lexer, the parser and the semantic passes hand every diagnostic to a
+//! [`DiagnosticSink`] with spans into the source but without the source
+//! itself, so a host decides how to show them: [`Collect`] keeps them for
+//! later, for example to turn them into [`JsonDiagnostics`], and [`Stream`]
+//! passes each one on as it arrives, which the driver uses to render them with
+//! miette. In between, [`Deduplicate`] keeps cascading errors from flooding
+//! the output.
+
+use std::collections::HashSet;
+
+use miette::{Diagnostic, Severity};
+
+use crate::json::JsonDiagnostics;
+
+pub type BoxedDiagnostic = Box<dyn Diagnostic + Send + Sync + 'static>;
+
+pub trait DiagnosticSink {
+    fn report(&mut self, diagnostic: BoxedDiagnostic);
+
+    /// How many diagnostics of `severity` were reported so far.
+    fn count(&self, severity: Severity) -> usize;
+
+    fn has_errors(&self) -> bool {
+        self.count(Severity::Error) > 0
+    }
+}
+
+/// Severity of `diagnostic`, errors when it does not say.
+pub fn severity(diagnostic: &dyn Diagnostic) -> Severity {
+    diagnostic.severity().unwrap_or(Severity::Error)
+}
+
+/// Diagnostics counted by severity.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SeverityCounts {
+    pub errors: usize,
+    pub warnings: usize,
+    pub advice: usize,
+}
+
+impl SeverityCounts {
+    pub fn add(&mut self, severity: Severity) {
+        match severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+            Severity::Advice => self.advice += 1,
+        }
+    }
+
+    pub fn get(&self, severity: Severity) -> usize {
+        match severity {
+            Severity::Error => self.errors,
+            Severity::Warning => self.warnings,
+            Severity::Advice => self.advice,
+        }
+    }
+
+    /// A line like "2 errors, 1 warning emitted", `None` when there were
+    /// neither.
+    pub fn summary(&self) -> Option<String> {
+        if self.errors == 0 && self.warnings == 0 {
+            return None;
+        }
+        let plural = |count: usize, noun: &str| match count {
+            1 => format!("1 {}", noun),
+            _ => format!("{} {}s", count, noun),
+        };
+        Some(format!(
+            "{}, {} emitted",
+            plural(self.errors, "error"),
+            plural(self.warnings, "warning")
+        ))
+    }
+}
+
+/// Keeps every diagnostic in the order they were reported.
+#[derive(Default)]
+pub struct Collect {
+    pub diagnostics: Vec<BoxedDiagnostic>,
+}
+
+impl Collect {
+    /// The diagnostics as JSON, with positions resolved against `source`.
+    pub fn to_json(&self, source: &str) -> JsonDiagnostics {
+        let diagnostics: Vec<&dyn Diagnostic> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.as_ref() as &dyn Diagnostic)
+            .collect();
+        JsonDiagnostics::new(&diagnostics, source)
+    }
+}
+
+impl DiagnosticSink for Collect {
+    fn report(&mut self, diagnostic: BoxedDiagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| self::severity(diagnostic.as_ref()) == severity)
+            .count()
+    }
+}
+
+/// Calls a function with each diagnostic as it is reported and only keeps
+/// their counts.
+pub struct Stream<F> {
+    on_report: F,
+    counts: SeverityCounts,
+}
+
+impl<F: FnMut(BoxedDiagnostic)> Stream<F> {
+    pub fn new(on_report: F) -> Self {
+        Self {
+            on_report,
+            counts: SeverityCounts::default(),
+        }
+    }
+
+    pub fn counts(&self) -> SeverityCounts {
+        self.counts
+    }
+}
+
+impl<F: FnMut(BoxedDiagnostic)> DiagnosticSink for Stream<F> {
+    fn report(&mut self, diagnostic: BoxedDiagnostic) {
+        self.counts.add(severity(diagnostic.as_ref()));
+        (self.on_report)(diagnostic);
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.counts.get(severity)
+    }
+}
+
+/// What makes two diagnostics the same: severity, code, message and labels.
+type DiagnosticKey = (
+    u8,
+    Option<String>,
+    String,
+    Vec<(usize, usize, Option<String>)>,
+);
+
+fn key(diagnostic: &dyn Diagnostic) -> DiagnosticKey {
+    let labels = diagnostic
+        .labels()
+        .map(|labels| {
+            labels
+                .map(|label| {
+                    (
+                        label.offset(),
+                        label.len(),
+                        label.label().map(str::to_string),
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    (
+        severity(diagnostic) as u8,
+        diagnostic.code().map(|code| code.to_string()),
+        diagnostic.to_string(),
+        labels,
+    )
+}
+
+/// Drops the diagnostics identical to one reported before, and the errors
+/// past a limit. The counts are of every diagnostic it was given apart from
+/// the duplicates, so with the errors of [`Self::hidden`] the stages still see
+/// that they failed.
+///
+/// The spans are compared without their file, it is meant for the
+/// diagnostics of one file.
+pub struct Deduplicate<'a> {
+    sink: &'a mut dyn DiagnosticSink,
+    seen: HashSet<DiagnosticKey>,
+    limit: Option<usize>,
+    counts: SeverityCounts,
+}
+
+impl<'a> Deduplicate<'a> {
+    pub fn new(sink: &'a mut dyn DiagnosticSink) -> Self {
+        Self {
+            sink,
+            seen: HashSet::new(),
+            limit: None,
+            counts: SeverityCounts::default(),
+        }
+    }
+
+    /// Passes on the first `limit` errors only, all of them with `None`.
+    /// Warnings are never held back.
+    pub fn with_limit(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Errors past the limit that were not passed on.
+    pub fn hidden(&self) -> usize {
+        hidden_errors(self.counts, self.limit)
+    }
+}
+
+/// Errors of `counts` past `limit`.
+pub fn hidden_errors(counts: SeverityCounts, limit: Option<usize>) -> usize {
+    limit.map_or(0, |limit| counts.errors.saturating_sub(limit))
+}
+
+impl DiagnosticSink for Deduplicate<'_> {
+    fn report(&mut self, diagnostic: BoxedDiagnostic) {
+        if !self.seen.insert(key(diagnostic.as_ref())) {
+            return;
+        }
+        let severity = severity(diagnostic.as_ref());
+        self.counts.add(severity);
+        if severity != Severity::Error || self.hidden() == 0 {
+            self.sink.report(diagnostic);
+        }
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.counts.get(severity)
+    }
+}
+
+/// Which warnings get reported, set by `ulang.toml` and the driver flags.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WarningPolicy {
+    /// Codes of the warnings to drop, like `missing_return` or
+    /// `warning::missing_return`.
+    pub allow: Vec<String>,
+    /// Whether the warnings that are reported fail the compilation, the
+    /// stages don't know about it, so the host checks the count.
+    pub deny: bool,
+}
+
+impl WarningPolicy {
+    /// Whether `diagnostic` is a warning this policy drops.
+    pub fn allows(&self, diagnostic: &dyn Diagnostic) -> bool {
+        if severity(diagnostic) != Severity::Warning {
+            return false;
+        }
+        let Some(code) = diagnostic.code().map(|code| code.to_string()) else {
+            return false;
+        };
+        let short = code.strip_prefix("warning::");
+        self.allow
+            .iter()
+            .any(|allowed| *allowed == code || Some(allowed.as_str()) == short)
+    }
+
+    /// Sink passing on to `sink` what this policy doesn't drop.
+    pub fn sink<'a>(&'a self, sink: &'a mut dyn DiagnosticSink) -> Filter<'a> {
+        Filter { policy: self, sink }
+    }
+}
+
+/// See [`WarningPolicy::sink`].
+pub struct Filter<'a> {
+    policy: &'a WarningPolicy,
+    sink: &'a mut dyn DiagnosticSink,
+}
+
+impl DiagnosticSink for Filter<'_> {
+    fn report(&mut self, diagnostic: BoxedDiagnostic) {
+        if !self.policy.allows(diagnostic.as_ref()) {
+            self.sink.report(diagnostic);
+        }
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.sink.count(severity)
+    }
+}
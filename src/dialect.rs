@@ -0,0 +1,190 @@
+//! The rules that differ between the C standards, enforced when one is
+//! selected with `--std`, so a program can be checked against what C89 or
+//! C99 allowed.
+//!
+//! | rule                            | C89   | C99, C11 |
+//! |---------------------------------|-------|----------|
+//! | `//` comments                   | warns | allowed  |
+//! | declarations after statements   | error | allowed  |
+//! | functions without a return type | `int` | error    |
+//!
+//! The missing return type is a grammar rule, the parser checks it, see
+//! [`Parser::with_standard`](crate::parser::Parser::with_standard).
+
+use std::{fmt, str::FromStr};
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::{
+    ast::{AstNode, BlockItem, Statement},
+    diagnostics::DiagnosticSink,
+    lexer::Lexer,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Standard {
+    C89,
+    C99,
+    C11,
+}
+
+impl Standard {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Standard::C89 => "C89",
+            Standard::C99 => "C99",
+            Standard::C11 => "C11",
+        }
+    }
+
+    /// `//` comments came with C99.
+    pub fn line_comments(&self) -> bool {
+        *self >= Standard::C99
+    }
+
+    /// Declarations can follow statements in a block since C99.
+    pub fn mixed_declarations(&self) -> bool {
+        *self >= Standard::C99
+    }
+
+    /// A function declared without a return type returns `int`, C99
+    /// removed the rule.
+    pub fn implicit_int(&self) -> bool {
+        *self == Standard::C89
+    }
+}
+
+impl fmt::Display for Standard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Standard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "c89" | "c90" => Ok(Standard::C89),
+            "c99" => Ok(Standard::C99),
+            "c11" => Ok(Standard::C11),
+            _ => Err(format!(
+                "Unknown standard \"{s}\", expected c89, c99 or c11"
+            )),
+        }
+    }
+}
+
+#[derive(Error, Debug, Diagnostic, Clone)]
+#[error("`//` comments are not part of {standard}")]
+#[diagnostic(
+    code(warning::line_comment),
+    severity(Warning),
+    help("use a /* */ comment, or select C99 or later")
+)]
+pub struct LineComment {
+    pub standard: Standard,
+    #[label("comment starts here")]
+    pub span: SourceSpan,
+}
+
+#[derive(Error, Debug, Diagnostic, Clone)]
+#[error("{standard} doesn't allow declarations after statements")]
+#[diagnostic(
+    code(error::mixed_declarations),
+    help("move the declaration to the start of the block, or select C99 or later")
+)]
+pub struct DeclarationAfterStatement {
+    pub standard: Standard,
+    #[label("declared after a statement")]
+    pub span: SourceSpan,
+    #[label("first statement of the block")]
+    pub statement: SourceSpan,
+}
+
+/// One warning per `//` comment the lexer found.
+pub fn line_comments(lexer: &Lexer, standard: Standard) -> Vec<LineComment> {
+    if standard.line_comments() {
+        return vec![];
+    }
+    lexer
+        .line_comments
+        .iter()
+        .map(|&span| LineComment { standard, span })
+        .collect()
+}
+
+/// One error per declaration that follows a statement of its block.
+pub fn declarations_after_statements(
+    node: &AstNode,
+    standard: Standard,
+) -> Vec<DeclarationAfterStatement> {
+    let mut errors = vec![];
+    if !standard.mixed_declarations() {
+        check_node(node, standard, &mut errors);
+    }
+    errors
+}
+
+fn check_node(node: &AstNode, standard: Standard, errors: &mut Vec<DeclarationAfterStatement>) {
+    match node {
+        AstNode::Program(nodes) => {
+            for node in nodes {
+                check_node(node, standard, errors);
+            }
+        }
+        AstNode::FunctionDeclaration(function) => {
+            check_statement(&function.body, standard, errors);
+        }
+        AstNode::Statement(statement) => check_statement(statement, standard, errors),
+        AstNode::Expression(_) => {}
+    }
+}
+
+fn check_statement(
+    statement: &Statement,
+    standard: Standard,
+    errors: &mut Vec<DeclarationAfterStatement>,
+) {
+    let Statement::Compound(items) = statement else {
+        return;
+    };
+    let mut first_statement = None;
+    for item in items {
+        match item {
+            BlockItem::Statement(statement) => {
+                first_statement = first_statement.or(statement.span());
+                check_statement(statement, standard, errors);
+            }
+            BlockItem::Declaration(declaration) => {
+                if let Some(statement) = first_statement {
+                    errors.push(DeclarationAfterStatement {
+                        standard,
+                        span: declaration.name_span,
+                        statement,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Reports the diagnostics of both checks into `sink`, returns whether
+/// there was no error.
+pub fn check_into(
+    lexer: &Lexer,
+    node: &AstNode,
+    standard: Standard,
+    sink: &mut dyn DiagnosticSink,
+) -> bool {
+    for warning in line_comments(lexer, standard) {
+        sink.report(Box::new(warning));
+    }
+    let errors = declarations_after_statements(node, standard);
+    let valid = errors.is_empty();
+    for error in errors {
+        sink.report(Box::new(error));
+    }
+    valid
+}
@@ -0,0 +1,201 @@
+//! Graphviz DOT rendering of the AST, of the TACKY control-flow graph and of
+//! the call graph.
+
+use std::fmt::Write;
+
+use crate::{
+    ast::{AstNode, BlockItem, Declaration, Expression, ExpressionKind, Factor, Statement},
+    tacky::{call_graph::CallGraph, cfg::ControlFlowGraph, TackyProgram},
+};
+
+#[derive(Default)]
+struct DotWriter {
+    out: String,
+    counter: usize,
+}
+
+impl DotWriter {
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.counter;
+        self.counter += 1;
+        let _ = writeln!(self.out, "    n{} [label=\"{}\"];", id, escape(label));
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize) {
+        let _ = writeln!(self.out, "    n{} -> n{};", from, to);
+    }
+
+    fn finish(self, name: &str, attributes: &str) -> String {
+        format!("digraph {} {{\n{}{}}}\n", name, attributes, self.out)
+    }
+
+    fn ast_node(&mut self, node: &AstNode) -> usize {
+        match node {
+            AstNode::Program(nodes) => {
+                let id = self.node("Program");
+                for child in nodes {
+                    let child = self.ast_node(child);
+                    self.edge(id, child);
+                }
+                id
+            }
+            AstNode::FunctionDeclaration(function) => {
+                let id = self.node(&format!(
+                    "Function {}\\nreturns {:?}",
+                    function.name, function.return_type
+                ));
+                let body = self.statement(&function.body);
+                self.edge(id, body);
+                id
+            }
+            AstNode::Statement(statement) => self.statement(statement),
+            AstNode::Expression(expression) => self.expression(expression),
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> usize {
+        match statement {
+            Statement::ReturnStatement(expression, _) => {
+                let id = self.node("Return");
+                if let Some(expression) = expression {
+                    let child = self.expression(expression);
+                    self.edge(id, child);
+                }
+                id
+            }
+            Statement::Expression(expression) => self.expression(expression),
+            Statement::Compound(items) => {
+                let id = self.node("Block");
+                for item in items {
+                    let child = match item {
+                        BlockItem::Declaration(declaration) => self.declaration(declaration),
+                        BlockItem::Statement(statement) => self.statement(statement),
+                    };
+                    self.edge(id, child);
+                }
+                id
+            }
+        }
+    }
+
+    fn declaration(&mut self, declaration: &Declaration) -> usize {
+        let id = self.node(&format!(
+            "Declare {:?} {}",
+            declaration.var_type, declaration.name
+        ));
+        if let Some(initializer) = &declaration.initializer {
+            let child = self.expression(initializer);
+            self.edge(id, child);
+        }
+        id
+    }
+
+    fn expression(&mut self, expression: &Expression) -> usize {
+        match &expression.kind {
+            ExpressionKind::Binary(left, operator, right, _) => {
+                let id = self.node(&format!("{:?}", operator));
+                let left = self.expression(left);
+                self.edge(id, left);
+                let right = self.expression(right);
+                self.edge(id, right);
+                id
+            }
+            ExpressionKind::Assignment(target, value, _) => {
+                let id = self.node("=");
+                let target = self.expression(target);
+                self.edge(id, target);
+                let value = self.expression(value);
+                self.edge(id, value);
+                id
+            }
+            ExpressionKind::Update(operator, operand, _) => {
+                let id = self.node(&format!("{:?}", operator));
+                let operand = self.expression(operand);
+                self.edge(id, operand);
+                id
+            }
+            ExpressionKind::AddressOf(operand, _) => {
+                let id = self.node("&");
+                let operand = self.expression(operand);
+                self.edge(id, operand);
+                id
+            }
+            ExpressionKind::Factor(Factor::Constant(c, _)) => self.node(&c.to_string()),
+            ExpressionKind::Factor(Factor::Variable(name, _)) => self.node(&name.to_string()),
+            ExpressionKind::Factor(Factor::Unary(operator, inner)) => {
+                let id = self.node(&format!("{:?}", operator));
+                let inner = self.expression(inner);
+                self.edge(id, inner);
+                id
+            }
+            ExpressionKind::Factor(Factor::ParentedExpression(inner)) => {
+                let id = self.node("( )");
+                let inner = self.expression(inner);
+                self.edge(id, inner);
+                id
+            }
+        }
+    }
+}
+
+/// Escapes quotes and backslashes, keeping the `\n`/`\l` line break escapes intact.
+fn escape(label: &str) -> String {
+    let mut result = String::with_capacity(label.len());
+    let mut chars = label.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' if matches!(chars.peek(), Some('n') | Some('l')) => result.push(ch),
+            '\\' => result.push_str("\\\\"),
+            _ => result.push(ch),
+        }
+    }
+    result
+}
+
+pub fn ast_to_dot(node: &AstNode) -> String {
+    let mut writer = DotWriter::default();
+    writer.ast_node(node);
+    writer.finish("ast", "    node [shape=box];\n")
+}
+
+pub fn cfg_to_dot(program: &TackyProgram) -> String {
+    let cfg = ControlFlowGraph::from_instructions(&program.0.instruction);
+    let mut writer = DotWriter::default();
+    let entry = writer.node(&format!("entry: {}", program.0.identifier));
+    for block in &cfg.blocks {
+        let label: String = block
+            .instructions
+            .iter()
+            .map(|instruction| format!("{}\\l", instruction))
+            .collect();
+        writer.node(&label);
+    }
+    if !cfg.blocks.is_empty() {
+        writer.edge(entry, entry + 1);
+    }
+    for (i, block) in cfg.blocks.iter().enumerate() {
+        for successor in &block.successors {
+            writer.edge(entry + 1 + i, entry + 1 + successor);
+        }
+    }
+    writer.finish("cfg", "    node [shape=box, fontname=monospace];\n")
+}
+
+/// One node per function, labelled with the length of its TACKY.
+pub fn call_graph_to_dot(graph: &CallGraph) -> String {
+    let mut writer = DotWriter::default();
+    for function in &graph.functions {
+        let size = match &function.instructions {
+            Ok(1) => "1 instruction".to_string(),
+            Ok(count) => format!("{} instructions", count),
+            Err(_) => "not lowered".to_string(),
+        };
+        writer.node(&format!("{}\\n{}", function.name, size));
+    }
+    for (caller, callee) in &graph.calls {
+        writer.edge(*caller, *callee);
+    }
+    writer.finish("calls", "    node [shape=box];\n")
+}
@@ -0,0 +1,72 @@
+//! Compiling a source held in memory, for hosts that embed the library:
+//! the playground entry points of [`crate::wasm`] and the C ABI of the
+//! `capi` feature. A failure is the JSON of its diagnostics.
+
+use crate::{
+    assembly::{self, TargetPlatform},
+    ast::AstNode,
+    diagnostics::Collect,
+    json::JsonDiagnostics,
+    lexer::{FileToken, Lexer},
+    optimize::{OptimizationLevel, PassManager},
+    parser::Parser,
+    tacky::{Tacky, TackyProgram},
+    typecheck::typecheck,
+};
+
+pub(crate) fn lex(source: &str) -> Result<(Lexer, Vec<FileToken>), JsonDiagnostics> {
+    let mut lexer = Lexer::from_content(source.to_string());
+    let mut sink = Collect::default();
+    match lexer.tokenize_into(&mut sink) {
+        Some(tokens) => Ok((lexer, tokens)),
+        None => Err(sink.to_json(source)),
+    }
+}
+
+pub(crate) fn parse(source: &str) -> Result<AstNode, JsonDiagnostics> {
+    let (lexer, tokens) = lex(source)?;
+    Parser::new(&tokens, &lexer.content)
+        .parse()
+        .map_err(|e| JsonDiagnostics::new(&[&e], source))
+}
+
+pub(crate) fn lower(source: &str, passes: &PassManager) -> Result<TackyProgram, JsonDiagnostics> {
+    let ast = parse(source)?;
+    let typed = typecheck(&ast).map_err(|e| JsonDiagnostics::new(&[&e], source))?;
+    let mut tacky = Tacky::new(&typed)
+        .parse()
+        .map_err(|e| JsonDiagnostics::message(&e))?;
+    passes
+        .run_tacky(&mut tacky)
+        .map_err(|e| JsonDiagnostics::message(&e))?;
+    Ok(tacky)
+}
+
+/// The assembly of `source` for `platform`.
+pub(crate) fn assembly_for(
+    source: &str,
+    opt_level: u8,
+    platform: TargetPlatform,
+) -> Result<String, JsonDiagnostics> {
+    let passes = PassManager::new(opt_level_from(opt_level)?);
+    let tacky = lower(source, &passes)?;
+    let asm = assembly::generate_optimized_assembly(
+        &tacky,
+        platform,
+        &passes,
+        &assembly::RuntimeChecks::default(),
+    );
+    Ok(asm.0)
+}
+
+pub(crate) fn opt_level_from(level: u8) -> Result<OptimizationLevel, JsonDiagnostics> {
+    match level {
+        0 => Ok(OptimizationLevel::O0),
+        1 => Ok(OptimizationLevel::O1),
+        2 => Ok(OptimizationLevel::O2),
+        _ => Err(JsonDiagnostics::message(&format!(
+            "Unknown optimization level {}",
+            level
+        ))),
+    }
+}
@@ -0,0 +1,160 @@
+//! Token based source formatter that keeps comments and directives.
+//!
+//! Works on tokens lexed with [`Lexer::with_trivia`](crate::lexer::Lexer::with_trivia),
+//! so it only decides on whitespace and never changes the token sequence.
+//! A file in the middle of an edit can be lexed
+//! [`with_error_tokens`](crate::lexer::Lexer::with_error_tokens), the text
+//! that doesn't lex is then kept as it is.
+
+use crate::lexer::{FileToken, Token, Trivia};
+
+const INDENT: &str = "    ";
+
+#[derive(Default)]
+struct Formatter {
+    out: String,
+    depth: usize,
+    /// Blank line requested before the next item, e.g. between functions.
+    blank_line: bool,
+}
+
+impl Formatter {
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n')
+    }
+
+    fn newline(&mut self) {
+        if !self.at_line_start() {
+            self.out.push('\n');
+        }
+    }
+
+    fn start_line(&mut self, depth: usize) {
+        self.newline();
+        if self.blank_line && !self.out.is_empty() && !self.out.ends_with("{\n") {
+            self.out.push('\n');
+        }
+        self.blank_line = false;
+        self.out.push_str(&INDENT.repeat(depth));
+    }
+
+    fn own_line_trivia(&mut self, trivia: &[Trivia]) {
+        for item in trivia {
+            match item {
+                Trivia::BlankLines(_) => self.blank_line = true,
+                Trivia::Whitespace(text) if text.matches('\n').count() > 1 => {
+                    self.blank_line = true
+                }
+                Trivia::Whitespace(_) => {}
+                Trivia::Directive(text) => {
+                    self.start_line(0);
+                    self.out.push_str(text.trim_end());
+                    self.out.push('\n');
+                }
+                Trivia::LineComment(text) | Trivia::BlockComment(text) => {
+                    self.start_line(self.depth);
+                    self.out.push_str(text.trim_end());
+                    self.out.push('\n');
+                }
+            }
+        }
+    }
+
+    fn trailing_trivia(&mut self, trivia: &[Trivia]) {
+        for item in trivia {
+            match item {
+                Trivia::LineComment(text) => {
+                    self.out.push(' ');
+                    self.out.push_str(text.trim_end());
+                    self.out.push('\n');
+                }
+                Trivia::BlockComment(text) => {
+                    self.out.push(' ');
+                    self.out.push_str(text);
+                }
+                // Only comments end up after a token on the same line.
+                Trivia::BlankLines(_) | Trivia::Directive(_) | Trivia::Whitespace(_) => {}
+            }
+        }
+    }
+}
+
+fn is_operand_end(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Identifier(_)
+            | Token::Constant(_)
+            | Token::CloseParenthesis
+            // Only a postfix `++` or `--` can be followed by a binary operator.
+            | Token::Increment
+            | Token::Decrement
+    )
+}
+
+/// Formats the tokens of a file, with `end_trivia` being whatever followed the last token.
+pub fn format_tokens(tokens: &[FileToken], end_trivia: &[Trivia]) -> String {
+    let mut f = Formatter::default();
+    let mut previous: Option<&Token> = None;
+    let mut previous_unary = false;
+
+    for file_token in tokens {
+        let token = &file_token.token;
+        f.own_line_trivia(&file_token.leading_trivia);
+        if token == &Token::CloseBrace {
+            f.depth = f.depth.saturating_sub(1);
+            f.blank_line = false;
+        }
+
+        let unary = match token {
+            Token::Tilde | Token::Not => true,
+            // A `*` after a type is part of a declarator like `char **argv`.
+            Token::Hyphen
+            | Token::Ampersand
+            | Token::Asteriks
+            | Token::Increment
+            | Token::Decrement => !previous.is_some_and(is_operand_end),
+            _ => false,
+        };
+
+        if f.at_line_start() || f.blank_line {
+            f.start_line(f.depth);
+        } else if let Some(previous) = previous {
+            let space = match (previous, token) {
+                (_, Token::Semicolon | Token::CloseParenthesis | Token::Comma) => false,
+                (Token::OpenParenthesis, _) => false,
+                (Token::Identifier(_), Token::OpenParenthesis) => false,
+                (Token::Hyphen, Token::Hyphen | Token::Decrement) if previous_unary => true,
+                (Token::Ampersand, Token::Ampersand) if previous_unary => true,
+                // A postfix `++` or `--` sticks to its operand.
+                (_, Token::Increment | Token::Decrement) if !unary => false,
+                _ if previous_unary => false,
+                _ => true,
+            };
+            if space {
+                f.out.push(' ');
+            }
+        }
+        f.out.push_str(&token.to_string());
+
+        let line_ends = matches!(
+            token,
+            Token::Semicolon | Token::OpenBrace | Token::CloseBrace
+        );
+        f.trailing_trivia(&file_token.trailing_trivia);
+        if line_ends {
+            f.newline();
+        }
+        match token {
+            Token::OpenBrace => f.depth += 1,
+            Token::CloseBrace if f.depth == 0 => f.blank_line = true,
+            _ => {}
+        }
+
+        previous_unary = unary;
+        previous = Some(token);
+    }
+
+    f.own_line_trivia(end_trivia);
+    f.newline();
+    f.out
+}
@@ -0,0 +1,338 @@
+//! Editing session that keeps tokens and parsed functions between edits.
+//!
+//! Tokens never span lines, so an edit only re-lexes the lines it touches and
+//! moves the offsets of the tokens below it. Top-level items are split on balanced braces and
+//! only the items whose tokens changed get parsed again. Whenever an edit could
+//! open or close a block comment the whole file is lexed again instead.
+
+use std::{ops::Range, path::PathBuf};
+
+use miette::SourceSpan;
+
+use crate::{
+    ast::{AstNode, BlockItem, Expression, ExpressionKind, Factor, NodeId, Statement},
+    lexer::{FileToken, Lexer, LexerError, Token},
+    parser::{Parser, ParserError},
+};
+
+/// Replaces the bytes in `range` with `replacement`.
+#[derive(Debug, Clone)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+/// Top-level item: the range of its tokens and the result of parsing them.
+#[derive(Debug, Clone)]
+struct Item {
+    tokens: Range<usize>,
+    parsed: Result<Vec<AstNode>, ParserError>,
+}
+
+pub struct Session {
+    pub path: PathBuf,
+    pub content: String,
+    pub tokens: Vec<FileToken>,
+    items: Vec<Item>,
+    /// Number of items parsed by the last update, handy to check reuse.
+    pub reparsed_items: usize,
+    /// First node id not used by any parse of the session, so kept items and
+    /// parsed again ones never share ids.
+    next_id: NodeId,
+}
+
+impl Session {
+    pub fn new(path: PathBuf, content: String) -> Result<Self, LexerError> {
+        let mut session = Self {
+            path,
+            content,
+            tokens: vec![],
+            items: vec![],
+            reparsed_items: 0,
+            next_id: NodeId::default(),
+        };
+        session.reload()?;
+        Ok(session)
+    }
+
+    /// Applies the edit, lexing and parsing only what it touched.
+    pub fn apply_edit(&mut self, edit: &TextEdit) -> Result<(), LexerError> {
+        let start_line = line_of(&self.content, edit.range.start);
+        let old_end_line = line_of(&self.content, edit.range.end);
+        let old_region = line_region(&self.content, start_line, old_end_line);
+        let old_region_text = self.content[old_region.clone()].to_string();
+
+        self.content
+            .replace_range(edit.range.clone(), &edit.replacement);
+        let new_end_line = line_of(&self.content, edit.range.start + edit.replacement.len());
+        let new_region = line_region(&self.content, start_line, new_end_line);
+        let new_region_text = &self.content[new_region.clone()];
+
+        let touches_comment = |text: &str| text.contains("/*") || text.contains("*/");
+        let first_changed = self
+            .tokens
+            .iter()
+            .position(|t| t.offset >= old_region.start)
+            .unwrap_or(self.tokens.len());
+        let last_changed = self
+            .tokens
+            .iter()
+            .position(|t| t.offset > old_region.end)
+            .unwrap_or(self.tokens.len());
+
+        if touches_comment(&old_region_text)
+            || touches_comment(new_region_text)
+            || inside_block_comment(&self.content[..new_region.start])
+        {
+            return self.reload();
+        }
+
+        let mut region_lexer = Lexer::from_content(new_region_text.to_string());
+        let Ok(mut new_tokens) = region_lexer.tokenize() else {
+            // Report the error against the whole file.
+            return self.reload();
+        };
+        for token in new_tokens.iter_mut() {
+            token.offset += new_region.start;
+        }
+        let delta = edit.replacement.len() as isize - edit.range.len() as isize;
+        for token in self.tokens[last_changed..].iter_mut() {
+            token.offset = (token.offset as isize + delta) as usize;
+        }
+        let inserted = new_tokens.len();
+        self.tokens.splice(first_changed..last_changed, new_tokens);
+
+        self.update_items(first_changed..last_changed, inserted, delta);
+        Ok(())
+    }
+
+    /// Program built from the cached items, or the first parse error.
+    pub fn ast(&self) -> Result<AstNode, ParserError> {
+        let mut nodes = vec![];
+        for item in &self.items {
+            nodes.extend(item.parsed.clone()?);
+        }
+        if nodes.is_empty() {
+            return Parser::new(&self.tokens, &self.content).parse();
+        }
+        Ok(AstNode::Program(nodes))
+    }
+
+    fn relex_all(&mut self) -> Result<(), LexerError> {
+        let mut lexer = Lexer::from_content(self.content.clone());
+        lexer.path = self.path.clone();
+        match lexer.tokenize() {
+            Ok(tokens) => {
+                self.tokens = tokens;
+                Ok(())
+            }
+            Err(e) => {
+                self.tokens.clear();
+                self.items.clear();
+                Err(e)
+            }
+        }
+    }
+
+    /// Lexes and parses the whole file again.
+    fn reload(&mut self) -> Result<(), LexerError> {
+        self.relex_all()?;
+        self.items = self.parse_items(0..self.tokens.len());
+        self.reparsed_items = self.items.len();
+        Ok(())
+    }
+
+    /// `removed` old tokens were replaced by `inserted` new ones at the same
+    /// start, moving the text after them by `byte_delta`.
+    fn update_items(&mut self, removed: Range<usize>, inserted: usize, byte_delta: isize) {
+        let delta = inserted as isize - removed.len() as isize;
+        let old_items = std::mem::take(&mut self.items);
+
+        // Items strictly before or after the change survive, everything else
+        // (and every item that failed to parse) gets parsed again.
+        let keep_before: Vec<Item> = old_items
+            .iter()
+            .take_while(|item| item.tokens.end <= removed.start && item.parsed.is_ok())
+            .cloned()
+            .collect();
+        let keep_after: Vec<Item> = old_items
+            .iter()
+            .rev()
+            .take_while(|item| item.tokens.start >= removed.end && item.parsed.is_ok())
+            .map(|item| Item {
+                tokens: shift(&item.tokens, delta),
+                parsed: item.parsed.clone().map(|mut nodes| {
+                    nodes
+                        .iter_mut()
+                        .for_each(|node| shift_spans(node, byte_delta));
+                    nodes
+                }),
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+
+        let start = keep_before.last().map_or(0, |item| item.tokens.end);
+        let end = keep_after
+            .first()
+            .map_or(self.tokens.len(), |item| item.tokens.start);
+        let middle = self.parse_items(start..end);
+        self.reparsed_items = middle.len();
+
+        self.items = keep_before;
+        self.items.extend(middle);
+        self.items.extend(keep_after);
+    }
+
+    fn parse_items(&mut self, range: Range<usize>) -> Vec<Item> {
+        split_items(&self.tokens[range.clone()])
+            .into_iter()
+            .map(|local| {
+                let tokens = shift(&local, range.start as isize);
+                let mut parser = Parser::new(&self.tokens[tokens.clone()], &self.content)
+                    .with_first_id(self.next_id);
+                let parsed = parser.parse().map(|node| match node {
+                    AstNode::Program(nodes) => nodes,
+                    node => vec![node],
+                });
+                self.next_id = parser.next_node_id();
+                Item { tokens, parsed }
+            })
+            .collect()
+    }
+}
+
+fn shift(range: &Range<usize>, delta: isize) -> Range<usize> {
+    (range.start as isize + delta) as usize..(range.end as isize + delta) as usize
+}
+
+/// Moves the source spans stored in the AST of an item that was not parsed again.
+fn shift_spans(node: &mut AstNode, delta: isize) {
+    fn shift_span(span: &mut SourceSpan, delta: isize) {
+        *span = SourceSpan::new(
+            ((span.offset() as isize + delta) as usize).into(),
+            span.len(),
+        );
+    }
+    fn shift_expression(expression: &mut Expression, delta: isize) {
+        match &mut expression.kind {
+            ExpressionKind::Binary(left, _, right, span) => {
+                shift_span(span, delta);
+                shift_expression(left, delta);
+                shift_expression(right, delta);
+            }
+            ExpressionKind::Assignment(target, value, span) => {
+                shift_span(span, delta);
+                shift_expression(target, delta);
+                shift_expression(value, delta);
+            }
+            ExpressionKind::Update(_, operand, span) | ExpressionKind::AddressOf(operand, span) => {
+                shift_span(span, delta);
+                shift_expression(operand, delta);
+            }
+            ExpressionKind::Factor(Factor::Constant(_, span))
+            | ExpressionKind::Factor(Factor::Variable(_, span)) => shift_span(span, delta),
+            ExpressionKind::Factor(Factor::Unary(_, inner))
+            | ExpressionKind::Factor(Factor::ParentedExpression(inner)) => {
+                shift_expression(inner, delta)
+            }
+        }
+    }
+    fn shift_statement(statement: &mut Statement, delta: isize) {
+        match statement {
+            Statement::Expression(expression) => shift_expression(expression, delta),
+            Statement::Compound(items) => {
+                for item in items {
+                    match item {
+                        BlockItem::Declaration(declaration) => {
+                            shift_span(&mut declaration.name_span, delta);
+                            if let Some(initializer) = &mut declaration.initializer {
+                                shift_expression(initializer, delta);
+                            }
+                        }
+                        BlockItem::Statement(statement) => shift_statement(statement, delta),
+                    }
+                }
+            }
+            Statement::ReturnStatement(value, span) => {
+                shift_span(span, delta);
+                if let Some(value) = value {
+                    shift_expression(value, delta);
+                }
+            }
+        }
+    }
+    match node {
+        AstNode::FunctionDeclaration(function) => {
+            shift_span(&mut function.name_span, delta);
+            shift_statement(&mut function.body, delta);
+        }
+        AstNode::Statement(statement) => shift_statement(statement, delta),
+        AstNode::Expression(expression) => shift_expression(expression, delta),
+        AstNode::Program(nodes) => nodes.iter_mut().for_each(|node| shift_spans(node, delta)),
+    }
+}
+
+/// Splits tokens into top-level items ending with the brace that closes depth 1.
+fn split_items(tokens: &[FileToken]) -> Vec<Range<usize>> {
+    let mut items = vec![];
+    let mut start = 0;
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.token {
+            Token::OpenBrace => depth += 1,
+            Token::CloseBrace => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    items.push(start..i + 1);
+                    start = i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    if start < tokens.len() {
+        items.push(start..tokens.len());
+    }
+    items
+}
+
+/// 1-based line containing the byte `offset`.
+fn line_of(content: &str, offset: usize) -> usize {
+    content[..offset].matches('\n').count() + 1
+}
+
+/// Byte range covering the lines `first..=last`, without the final line break.
+fn line_region(content: &str, first: usize, last: usize) -> Range<usize> {
+    let mut starts = std::iter::once(0).chain(content.match_indices('\n').map(|(i, _)| i + 1));
+    let start = starts.nth(first - 1).unwrap_or(content.len());
+    let end = content[start..]
+        .match_indices('\n')
+        .nth(last - first)
+        .map_or(content.len(), |(i, _)| start + i);
+    start..end
+}
+
+/// Whether the text ends inside an unterminated `/* */` comment.
+fn inside_block_comment(text: &str) -> bool {
+    let mut chars = text.chars().peekable();
+    let mut in_comment = false;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' if in_comment && chars.peek() == Some(&'/') => {
+                chars.next();
+                in_comment = false;
+            }
+            '/' if !in_comment && chars.peek() == Some(&'*') => {
+                chars.next();
+                in_comment = true;
+            }
+            '/' if !in_comment && chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            _ => {}
+        }
+    }
+    in_comment
+}
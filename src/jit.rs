@@ -0,0 +1,394 @@
+//! Runs compiled programs in the host process, without an assembler or a
+//! linker.
+//!
+//! The fixed up [`AsmInstruction`]s are encoded to x86-64 machine code, copied
+//! into memory mapped executable and `main` is called like a C function.
+//! Programs with runtime checks or instrumentation call into libc and are
+//! not supported.
+//!
+//! The code runs with the privileges and in the address space of the host, a
+//! fault like a division by zero takes the host down with it.
+
+use std::{collections::HashMap, ptr::NonNull};
+
+use thiserror::Error;
+
+use crate::{
+    assembly::{
+        AsmBinaryOperator, AsmFunctionDef, AsmInstruction, AsmPipeline, AsmProgram, AsmRegistry,
+        AsmType, AsmUnaryOperator, ConditionCode, Operand,
+    },
+    ast::Identifier,
+    optimize::PassManager,
+    tacky::TackyProgram,
+};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum JitError {
+    #[error("{0} can't be run in process")]
+    Unsupported(String),
+    #[error("Instruction `{0}` has operands x86 does not accept")]
+    InvalidOperands(String),
+    #[error("Jump to undefined label {0}")]
+    UndefinedLabel(Identifier),
+    #[error("Failed to map executable memory: {0}")]
+    Memory(String),
+}
+
+/// Machine code of a function in executable memory, unmapped on drop.
+#[derive(Debug)]
+pub struct JitFunction {
+    code: NonNull<u8>,
+    len: usize,
+}
+
+impl JitFunction {
+    /// Maps `bytes` read and executable.
+    pub fn new(bytes: &[u8]) -> Result<Self, JitError> {
+        let len = bytes.len().max(1);
+        // SAFETY: a fresh anonymous mapping is written before it is made
+        // executable, and never writable and executable at once.
+        unsafe {
+            let memory = libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANON,
+                -1,
+                0,
+            );
+            if memory == libc::MAP_FAILED {
+                return Err(JitError::Memory(
+                    std::io::Error::last_os_error().to_string(),
+                ));
+            }
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), memory as *mut u8, bytes.len());
+            if libc::mprotect(memory, len, libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                let error = std::io::Error::last_os_error();
+                libc::munmap(memory, len);
+                return Err(JitError::Memory(error.to_string()));
+            }
+            Ok(Self {
+                code: NonNull::new_unchecked(memory as *mut u8),
+                len,
+            })
+        }
+    }
+
+    /// Calls the function, the whole `int` it returns, not only the low byte
+    /// a process exit status keeps. It gets the `argc` and `argv` of a
+    /// program started without arguments, which `main(void)` ignores.
+    pub fn call(&self) -> i32 {
+        let argv = [c"ulang".as_ptr(), std::ptr::null()];
+        // SAFETY: the memory holds a whole function following the System V
+        // calling convention, encoded by `encode`.
+        unsafe {
+            let function: extern "C" fn(i32, *const *const std::ffi::c_char) -> i32 =
+                std::mem::transmute(self.code.as_ptr());
+            function(1, argv.as_ptr())
+        }
+    }
+}
+
+impl Drop for JitFunction {
+    fn drop(&mut self) {
+        // SAFETY: the mapping is owned by this value and no longer called.
+        unsafe {
+            libc::munmap(self.code.as_ptr() as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// Compiles `tacky` the way [`crate::assembly::generate_optimized_assembly`]
+/// does, without runtime checks, and maps the result.
+pub fn compile(tacky: &TackyProgram, passes: &PassManager) -> Result<JitFunction, JitError> {
+    let asm: AsmProgram = tacky.into();
+    let mut pipeline = AsmPipeline::new(asm);
+    pipeline.run_all(passes).expect("the passes run in order");
+    JitFunction::new(&encode(&pipeline.program().0)?)
+}
+
+/// Compiles and calls `main`, returning its value.
+pub fn run(tacky: &TackyProgram, passes: &PassManager) -> Result<i32, JitError> {
+    Ok(compile(tacky, passes)?.call())
+}
+
+/// Machine code of a function whose instructions are fixed up.
+pub fn encode(function: &AsmFunctionDef) -> Result<Vec<u8>, JitError> {
+    if !function.traps.is_empty() {
+        return Err(JitError::Unsupported(
+            "A function with runtime checks".into(),
+        ));
+    }
+    let mut encoder = Encoder::default();
+    for instruction in &function.instructions {
+        encoder.instruction(instruction)?;
+    }
+    encoder.finish()
+}
+
+/// Size of the operands of the instructions that have one, only longwords
+/// are encoded so far.
+fn operand_size(instruction: &AsmInstruction) -> Option<AsmType> {
+    match instruction {
+        AsmInstruction::Mov { ty, .. }
+        | AsmInstruction::Unary(_, ty, _)
+        | AsmInstruction::Cmp(ty, _, _)
+        | AsmInstruction::Binary(_, ty, _, _)
+        | AsmInstruction::Idiv(ty, _)
+        | AsmInstruction::Cdq(ty) => Some(*ty),
+        _ => None,
+    }
+}
+
+/// Hardware number of a register, the high bit goes into a REX prefix.
+fn register_number(register: &AsmRegistry) -> u8 {
+    match register {
+        AsmRegistry::AX => 0,
+        AsmRegistry::CX => 1,
+        AsmRegistry::DX => 2,
+        AsmRegistry::SP => 4,
+        AsmRegistry::BP => 5,
+        AsmRegistry::SI => 6,
+        AsmRegistry::DI => 7,
+        AsmRegistry::R8 => 8,
+        AsmRegistry::R9 => 9,
+        AsmRegistry::R10 => 10,
+        AsmRegistry::R11 => 11,
+    }
+}
+
+fn condition_number(condition: &ConditionCode) -> u8 {
+    match condition {
+        ConditionCode::O => 0x0,
+        ConditionCode::E => 0x4,
+        ConditionCode::NE => 0x5,
+        ConditionCode::L => 0xc,
+        ConditionCode::GE => 0xd,
+        ConditionCode::LE => 0xe,
+        ConditionCode::G => 0xf,
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    code: Vec<u8>,
+    labels: HashMap<Identifier, usize>,
+    /// Offsets of rel32 fields and the label they point at.
+    fixups: Vec<(usize, Identifier)>,
+}
+
+impl Encoder {
+    /// `opcode` with a ModRM byte, `reg` in its reg field and `rm` as the
+    /// register or `disp32(%rbp)` operand. `wide` selects 64-bit operands and
+    /// `byte_register` asks for a REX prefix so 8-bit registers reach the
+    /// low byte of every register.
+    fn modrm(
+        &mut self,
+        opcode: &[u8],
+        reg: u8,
+        rm: &Operand,
+        wide: bool,
+        byte_register: bool,
+    ) -> Result<(), JitError> {
+        let (rm_number, displacement) = match rm {
+            Operand::Register(register) => (register_number(register), None),
+            Operand::Stack(offset) => (register_number(&AsmRegistry::BP), Some(*offset)),
+            Operand::Imm(_) | Operand::Pseudo(_) => {
+                return Err(JitError::InvalidOperands(format!("{:?}", rm)))
+            }
+        };
+        let rex = 0x40 | (wide as u8) << 3 | (reg >> 3) << 2 | rm_number >> 3;
+        if rex != 0x40 || (byte_register && displacement.is_none()) {
+            self.code.push(rex);
+        }
+        self.code.extend_from_slice(opcode);
+        match displacement {
+            None => self.code.push(0xc0 | (reg & 7) << 3 | (rm_number & 7)),
+            Some(offset) => {
+                self.code.push(0x80 | (reg & 7) << 3 | (rm_number & 7));
+                self.code.extend_from_slice(&offset.to_le_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    /// Register number of `operand`, for instructions that only take a
+    /// register in that place.
+    fn register(operand: &Operand, instruction: &AsmInstruction) -> Result<u8, JitError> {
+        match operand {
+            Operand::Register(register) => Ok(register_number(register)),
+            _ => Err(JitError::InvalidOperands(format!("{:?}", instruction))),
+        }
+    }
+
+    fn jump(&mut self, opcode: &[u8], target: Identifier) {
+        self.code.extend_from_slice(opcode);
+        self.fixups.push((self.code.len(), target));
+        self.code.extend_from_slice(&[0; 4]);
+    }
+
+    fn instruction(&mut self, instruction: &AsmInstruction) -> Result<(), JitError> {
+        let invalid = || JitError::InvalidOperands(format!("{:?}", instruction));
+        if let Some(ty) = operand_size(instruction) {
+            if ty != AsmType::Longword {
+                return Err(JitError::Unsupported(format!("{:?} operands", ty)));
+            }
+        }
+        match instruction {
+            AsmInstruction::Mov { src, dst, .. } => match (src, dst) {
+                (Operand::Imm(value), Operand::Register(register)) => {
+                    let number = register_number(register);
+                    if number >= 8 {
+                        self.code.push(0x41);
+                    }
+                    self.code.push(0xb8 + (number & 7));
+                    self.code.extend_from_slice(&(*value as i32).to_le_bytes());
+                }
+                (Operand::Imm(value), _) => {
+                    self.modrm(&[0xc7], 0, dst, false, false)?;
+                    self.code.extend_from_slice(&(*value as i32).to_le_bytes());
+                }
+                (Operand::Register(register), _) => {
+                    self.modrm(&[0x89], register_number(register), dst, false, false)?
+                }
+                (Operand::Stack(_), Operand::Register(register)) => {
+                    self.modrm(&[0x8b], register_number(register), src, false, false)?
+                }
+                _ => return Err(invalid()),
+            },
+            AsmInstruction::Unary(operator, _, operand) => {
+                let (opcode, extension) = match operator {
+                    AsmUnaryOperator::Neg => (0xf7, 3),
+                    AsmUnaryOperator::Complement => (0xf7, 2),
+                    AsmUnaryOperator::Inc => (0xff, 0),
+                    AsmUnaryOperator::Dec => (0xff, 1),
+                    AsmUnaryOperator::Not => return Err(invalid()),
+                };
+                self.modrm(&[opcode], extension, operand, false, false)?;
+            }
+            // cmpl a, b computes b - a
+            AsmInstruction::Cmp(_, a, b) => match a {
+                Operand::Imm(value) => {
+                    self.modrm(&[0x81], 7, b, false, false)?;
+                    self.code.extend_from_slice(&(*value as i32).to_le_bytes());
+                }
+                Operand::Register(register) => {
+                    self.modrm(&[0x39], register_number(register), b, false, false)?
+                }
+                _ => self.modrm(&[0x3b], Self::register(b, instruction)?, a, false, false)?,
+            },
+            AsmInstruction::Binary(AsmBinaryOperator::Mult, _, src, dst) => {
+                let register = Self::register(dst, instruction)?;
+                match src {
+                    Operand::Imm(value) => {
+                        self.modrm(&[0x69], register, dst, false, false)?;
+                        self.code.extend_from_slice(&(*value as i32).to_le_bytes());
+                    }
+                    _ => self.modrm(&[0x0f, 0xaf], register, src, false, false)?,
+                }
+            }
+            AsmInstruction::Binary(AsmBinaryOperator::Shl, _, src, dst) => {
+                let Operand::Imm(count) = src else {
+                    return Err(invalid());
+                };
+                self.modrm(&[0xc1], 4, dst, false, false)?;
+                self.code.push(*count as u8);
+            }
+            AsmInstruction::Binary(operator, _, src, dst) => {
+                // Opcodes of the register to r/m and r/m to register forms
+                // and the extension of the immediate form.
+                let (to_rm, to_register, extension) = match operator {
+                    AsmBinaryOperator::Add => (0x01, 0x03, 0),
+                    AsmBinaryOperator::Sub => (0x29, 0x2b, 5),
+                    AsmBinaryOperator::Xor => (0x31, 0x33, 6),
+                    AsmBinaryOperator::Mult | AsmBinaryOperator::Shl => {
+                        unreachable!("matched above")
+                    }
+                };
+                match src {
+                    Operand::Imm(value) => {
+                        self.modrm(&[0x81], extension, dst, false, false)?;
+                        self.code.extend_from_slice(&(*value as i32).to_le_bytes());
+                    }
+                    Operand::Register(register) => {
+                        self.modrm(&[to_rm], register_number(register), dst, false, false)?
+                    }
+                    _ => {
+                        let dst = Self::register(dst, instruction)?;
+                        self.modrm(&[to_register], dst, src, false, false)?
+                    }
+                }
+            }
+            AsmInstruction::Idiv(_, operand) => self.modrm(&[0xf7], 7, operand, false, false)?,
+            AsmInstruction::Cdq(_) => self.code.push(0x99),
+            AsmInstruction::AllocateStack(bytes) => {
+                self.modrm(&[0x81], 5, &AsmRegistry::SP.into(), true, false)?;
+                self.code.extend_from_slice(&bytes.to_le_bytes());
+            }
+            AsmInstruction::Push(register) | AsmInstruction::Pop(register) => {
+                let number = register_number(register);
+                if number >= 8 {
+                    self.code.push(0x41);
+                }
+                let base = match instruction {
+                    AsmInstruction::Push(_) => 0x50,
+                    _ => 0x58,
+                };
+                self.code.push(base + (number & 7));
+            }
+            AsmInstruction::MovQ { src, dst } => self.modrm(
+                &[0x89],
+                register_number(src),
+                &dst.clone().into(),
+                true,
+                false,
+            )?,
+            AsmInstruction::Lea { base, offset, dst } => {
+                let (base, dst) = (register_number(base), register_number(dst));
+                let rex = 0x40 | (dst >> 3) << 2 | base >> 3;
+                if rex != 0x40 {
+                    self.code.push(rex);
+                }
+                self.code.push(0x8d);
+                self.code.push(0x80 | (dst & 7) << 3 | (base & 7));
+                // rm 100 means a SIB byte follows, here one without index.
+                if base & 7 == 4 {
+                    self.code.push(0x24);
+                }
+                self.code.extend_from_slice(&offset.to_le_bytes());
+            }
+            AsmInstruction::Jmp(target) => self.jump(&[0xe9], *target),
+            AsmInstruction::JmpCC(condition, target) => {
+                self.jump(&[0x0f, 0x80 + condition_number(condition)], *target)
+            }
+            AsmInstruction::SetCC(condition, operand) => {
+                let opcode = [0x0f, 0x90 + condition_number(condition)];
+                self.modrm(&opcode, 0, operand, false, true)?
+            }
+            AsmInstruction::Label(label) => {
+                self.labels.insert(*label, self.code.len());
+            }
+            AsmInstruction::Location { .. } => {}
+            AsmInstruction::Ret => self.code.push(0xc3),
+            AsmInstruction::IncrementCounter(_) => {
+                return Err(JitError::Unsupported("Instrumented code".into()))
+            }
+            AsmInstruction::Return => {
+                unreachable!("returns are replaced when fixing up the instructions")
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, JitError> {
+        for (at, target) in &self.fixups {
+            let Some(destination) = self.labels.get(target) else {
+                return Err(JitError::UndefinedLabel(*target));
+            };
+            let relative = *destination as i32 - (*at as i32 + 4);
+            self.code[*at..*at + 4].copy_from_slice(&relative.to_le_bytes());
+        }
+        Ok(self.code)
+    }
+}
@@ -0,0 +1,90 @@
+//! JSON output for hosts that embed the compiler and render diagnostics
+//! themselves.
+
+use std::fmt::Write;
+
+use miette::{Diagnostic, Severity};
+
+use crate::lexer::line_column;
+
+/// JSON array with one object per diagnostic, positions resolved against
+/// the source they were reported for:
+///
+/// ```json
+/// [
+///   {"severity": "error", "message": "Failed to parse the code",
+///    "labels": [{"message": "Expected Expression, found Semicolon",
+///                "offset": 23, "length": 1, "line": 2, "column": 12}]}
+/// ]
+/// ```
+///
+/// Offsets and lengths count bytes, lines and columns start at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonDiagnostics(pub String);
+
+impl JsonDiagnostics {
+    pub fn new(diagnostics: &[&dyn Diagnostic], source: &str) -> Self {
+        let objects: Vec<String> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic_object(*diagnostic, source))
+            .collect();
+        Self(format!("[{}]", objects.join(",")))
+    }
+
+    /// A diagnostic without a position, for failures outside the source.
+    pub fn message(message: &str) -> Self {
+        Self(format!(
+            "[{{\"severity\":\"error\",\"message\":{},\"labels\":[]}}]",
+            string(message)
+        ))
+    }
+}
+
+fn diagnostic_object(diagnostic: &dyn Diagnostic, source: &str) -> String {
+    let severity = match crate::diagnostics::severity(diagnostic) {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Advice => "advice",
+    };
+    let labels: Vec<String> = diagnostic
+        .labels()
+        .into_iter()
+        .flatten()
+        .map(|label| {
+            let (line, column) = line_column(source, label.offset());
+            format!(
+                "{{\"message\":{},\"offset\":{},\"length\":{},\"line\":{},\"column\":{}}}",
+                label.label().map_or("null".to_string(), string),
+                label.offset(),
+                label.len(),
+                line,
+                column
+            )
+        })
+        .collect();
+    format!(
+        "{{\"severity\":\"{}\",\"message\":{},\"labels\":[{}]}}",
+        severity,
+        string(&diagnostic.to_string()),
+        labels.join(",")
+    )
+}
+
+/// `text` as a quoted JSON string.
+pub(crate) fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,53 @@
+//! Lines two sequences up on their longest common subsequence, the part of
+//! the text and AST diffs that doesn't depend on what is compared.
+//!
+//! Quadratic in the length of the sequences: fine for the lines of one file
+//! or the items of one block.
+
+/// One step of the walk through both sequences, by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// `old[i]` and `new[j]` are equal and lined up.
+    Same(usize, usize),
+    /// `old[i]` has no counterpart.
+    Removed(usize),
+    /// `new[j]` has no counterpart.
+    Added(usize),
+}
+
+/// Every item of `old` and `new` in order. Between two [`Edit::Same`], the
+/// removed items come before the added ones.
+pub fn lcs<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    // common[i][j]: length of the longest common subsequence of old[i..] and new[j..].
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let (mut removed, mut added) = (vec![], vec![]);
+    let mut edits = vec![];
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.append(&mut removed);
+            edits.append(&mut added);
+            edits.push(Edit::Same(i, j));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            removed.push(Edit::Removed(i));
+            i += 1;
+        } else {
+            added.push(Edit::Added(j));
+            j += 1;
+        }
+    }
+    edits.append(&mut removed);
+    edits.append(&mut added);
+    edits
+}
@@ -1,56 +1,196 @@
-use miette::{Diagnostic, NamedSource, SourceOffset, SourceSpan};
-use std::{
-    fmt::Display,
-    iter::{self, from_fn},
-    path::PathBuf,
-};
+use miette::{Diagnostic, SourceSpan};
+use std::{fmt::Display, path::PathBuf};
 use thiserror::Error;
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum Token {
-    Identifier(String),
-    Constant(i32),
-    IntKeyword,
-    VoidKeyWord,
-    ReturnKeyWord,
-    OpenParenthesis,
-    CloseParenthesis,
-    OpenBrace,
-    CloseBrace,
-    Semicolon,
-    Tilde,
-    Hyphen,
-    Decrement,
-    Plus,
-    Asteriks,
-    Slash,
-    PercentSign,
-    Not,
-    And,
-    Or,
-    EqualTo,
-    NotEqualTo,
-    LessThan,
-    GreaterThan,
-    LessThanEqualTo,
-    GreaterThanEqualTo,
+use crate::{diagnostics::DiagnosticSink, symbol::Symbol};
+
+/// Largest constant the lexer takes, `-2147483648` is the only expression
+/// it may appear in.
+pub const INT_MIN_MAGNITUDE: i64 = 1 << 31;
+
+/// Declares [`Token`] and [`TokenKind`] from the spelling and the code of
+/// every keyword and punctuator, so the variants, [`KEYWORDS`], `Display`,
+/// [`Token::text_length`] and the categories come from one table.
+///
+/// The codes are what [`TokenKind::code`] returns, they never change once
+/// given out, a new token takes an unused one in the range of its group.
+macro_rules! tokens {
+    (
+        keywords { $($keyword:ident => $keyword_text:literal = $keyword_code:literal,)* }
+        delimiters { $($delimiter:ident => $delimiter_text:literal = $delimiter_code:literal,)* }
+        operators { $($operator:ident => $operator_text:literal = $operator_code:literal,)* }
+    ) => {
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum Token {
+            Identifier(Symbol),
+            /// Decimal constant up to `2147483648`, which only fits in an
+            /// `int` once negated, see [`INT_MIN_MAGNITUDE`].
+            Constant(i64),
+            $($keyword,)*
+            $($delimiter,)*
+            $($operator,)*
+            /// Never produced by the lexer, the parser reports it when input ends early.
+            EndOfFile,
+            /// Text that doesn't lex, as written, only produced by lexers
+            /// created with [`Lexer::with_error_tokens`].
+            Error(String),
+        }
+
+        /// [`Token`] without its value, for the tools that only care about
+        /// the kind, like a syntax highlighter.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[repr(u16)]
+        pub enum TokenKind {
+            Identifier = 0x01,
+            Constant = 0x02,
+            EndOfFile = 0x03,
+            Error = 0x04,
+            $($keyword = $keyword_code,)*
+            $($delimiter = $delimiter_code,)*
+            $($operator = $operator_code,)*
+        }
+
+        const KEYWORDS: [(Token, &str); [$($keyword_text),*].len()] =
+            [$((Token::$keyword, $keyword_text),)*];
+
+        impl Token {
+            /// Spelling of a keyword or punctuator.
+            pub fn text(&self) -> Option<&'static str> {
+                self.kind().text()
+            }
+
+            pub fn kind(&self) -> TokenKind {
+                match self {
+                    Token::Identifier(_) => TokenKind::Identifier,
+                    Token::Constant(_) => TokenKind::Constant,
+                    Token::EndOfFile => TokenKind::EndOfFile,
+                    Token::Error(_) => TokenKind::Error,
+                    $(Token::$keyword => TokenKind::$keyword,)*
+                    $(Token::$delimiter => TokenKind::$delimiter,)*
+                    $(Token::$operator => TokenKind::$operator,)*
+                }
+            }
+        }
+
+        impl TokenKind {
+            pub const ALL: [TokenKind; 4 + [$($keyword_text,)* $($delimiter_text,)* $($operator_text,)*].len()] = [
+                TokenKind::Identifier,
+                TokenKind::Constant,
+                TokenKind::EndOfFile,
+                TokenKind::Error,
+                $(TokenKind::$keyword,)*
+                $(TokenKind::$delimiter,)*
+                $(TokenKind::$operator,)*
+            ];
+
+            /// Spelling of a keyword or punctuator.
+            pub fn text(&self) -> Option<&'static str> {
+                match self {
+                    $(TokenKind::$keyword => Some($keyword_text),)*
+                    $(TokenKind::$delimiter => Some($delimiter_text),)*
+                    $(TokenKind::$operator => Some($operator_text),)*
+                    TokenKind::Identifier
+                    | TokenKind::Constant
+                    | TokenKind::EndOfFile
+                    | TokenKind::Error => None,
+                }
+            }
+
+            pub fn is_keyword(&self) -> bool {
+                matches!(self, $(TokenKind::$keyword)|*)
+            }
+
+            /// Whether the token is an operator, the brackets and the
+            /// semicolon aren't.
+            pub fn is_operator(&self) -> bool {
+                matches!(self, $(TokenKind::$operator)|*)
+            }
+        }
+    };
+}
+
+tokens! {
+    keywords {
+        IntKeyword => "int" = 0x100,
+        VoidKeyWord => "void" = 0x101,
+        ReturnKeyWord => "return" = 0x102,
+        IfKeyword => "if" = 0x103,
+        ElseKeyword => "else" = 0x104,
+        WhileKeyword => "while" = 0x105,
+        DoKeyword => "do" = 0x106,
+        ForKeyword => "for" = 0x107,
+        BreakKeyword => "break" = 0x108,
+        ContinueKeyword => "continue" = 0x109,
+        GotoKeyword => "goto" = 0x10a,
+        SwitchKeyword => "switch" = 0x10b,
+        CaseKeyword => "case" = 0x10c,
+        DefaultKeyword => "default" = 0x10d,
+        StaticKeyword => "static" = 0x10e,
+        ExternKeyword => "extern" = 0x10f,
+        LongKeyword => "long" = 0x110,
+        UnsignedKeyword => "unsigned" = 0x111,
+        SignedKeyword => "signed" = 0x112,
+        CharKeyword => "char" = 0x113,
+        DoubleKeyword => "double" = 0x114,
+        StructKeyword => "struct" = 0x115,
+        SizeofKeyword => "sizeof" = 0x116,
+    }
+    delimiters {
+        OpenParenthesis => "(" = 0x200,
+        CloseParenthesis => ")" = 0x201,
+        OpenBrace => "{" = 0x202,
+        CloseBrace => "}" = 0x203,
+        Semicolon => ";" = 0x204,
+        Comma => "," = 0x205,
+    }
+    operators {
+        Tilde => "~" = 0x300,
+        Hyphen => "-" = 0x301,
+        Decrement => "--" = 0x302,
+        Plus => "+" = 0x303,
+        Asteriks => "*" = 0x304,
+        Slash => "/" = 0x305,
+        PercentSign => "%" = 0x306,
+        Not => "!" = 0x307,
+        Ampersand => "&" = 0x308,
+        Pipe => "|" = 0x309,
+        And => "&&" = 0x30a,
+        Or => "||" = 0x30b,
+        Assign => "=" = 0x30c,
+        EqualTo => "==" = 0x30d,
+        NotEqualTo => "!=" = 0x30e,
+        LessThan => "<" = 0x30f,
+        GreaterThan => ">" = 0x310,
+        LessThanEqualTo => "<=" = 0x311,
+        GreaterThanEqualTo => ">=" = 0x312,
+        Increment => "++" = 0x313,
+    }
+}
+
+impl TokenKind {
+    pub fn is_literal(&self) -> bool {
+        *self == TokenKind::Constant
+    }
+
+    /// Stable number of the kind, to store or send it.
+    pub fn code(&self) -> u16 {
+        *self as u16
+    }
+
+    /// The kind with the [`TokenKind::code`] `code`.
+    pub fn from_code(code: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.code() == code)
+    }
 }
 
 impl Token {
-    fn text_length(&self) -> usize {
+    /// Length in bytes of the text of the token, `None` for a constant, which
+    /// can be written with leading zeros, and the end of file.
+    pub fn text_length(&self) -> Option<usize> {
         match self {
-            Token::Identifier(s) => s.len(),
-            Token::Constant(i) => i.to_string().len(),
-            Token::IntKeyword => 3,
-            Token::VoidKeyWord => 4,
-            Token::ReturnKeyWord => 6,
-            Token::Decrement
-            | Token::And
-            | Token::Or
-            | Token::NotEqualTo
-            | Self::LessThanEqualTo
-            | Token::GreaterThanEqualTo => 2,
-            _ => 1,
+            Token::Identifier(id) => Some(id.as_str().len()),
+            Token::Error(text) => Some(text.len()),
+            token => token.text().map(str::len),
         }
     }
 }
@@ -58,48 +198,19 @@ impl Token {
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Token::Identifier(id) => f.write_str(id),
+            Token::Identifier(id) => write!(f, "{}", id),
             Token::Constant(i) => f.write_str(&i.to_string()),
-            Token::IntKeyword => f.write_str("int"),
-            Token::VoidKeyWord => f.write_str("void"),
-            Token::ReturnKeyWord => f.write_str("return"),
-            Token::OpenParenthesis => f.write_str("("),
-            Token::CloseParenthesis => f.write_str(")"),
-            Token::OpenBrace => f.write_str("{"),
-            Token::CloseBrace => f.write_str("}"),
-            Token::Semicolon => f.write_str(";"),
-            Token::Hyphen => f.write_str("-"),
-            Token::Decrement => f.write_str("--"),
-            Token::Tilde => f.write_str("~"),
-            Token::Plus => f.write_str("+"),
-            Token::Asteriks => f.write_str("*"),
-            Token::Slash => f.write_str("/"),
-            Token::PercentSign => f.write_str("%"),
-            Token::And => f.write_str("&&"),
-            Token::Not => f.write_str("!"),
-            Token::Or => f.write_str("||"),
-            Token::EqualTo => f.write_str("=="),
-            Token::NotEqualTo => f.write_str("!="),
-            Token::LessThan => f.write_str("<"),
-            Token::GreaterThan => f.write_str(">"),
-            Token::GreaterThanEqualTo => f.write_str(">="),
-            Token::LessThanEqualTo => f.write_str("<="),
+            Token::EndOfFile => f.write_str("end of file"),
+            Token::Error(text) => f.write_str(text),
+            token => f.write_str(token.text().expect("keywords and punctuators have a text")),
         }
     }
 }
 
-const KEYWORDS: [(Token, &str); 3] = [
-    (Token::IntKeyword, "int"),
-    (Token::ReturnKeyWord, "return"),
-    (Token::VoidKeyWord, "void"),
-];
-
 #[derive(Error, Debug, Diagnostic, Clone)]
 #[error("Failed to parse the code")]
 #[diagnostic(code(error::on::base))]
 pub struct LexerError {
-    #[source_code]
-    pub src: NamedSource<String>,
     #[label = "{error}"]
     pub span: SourceSpan,
     pub error: LexerErrorType,
@@ -111,30 +222,118 @@ pub enum LexerErrorType {
     InvalidCharInDigitalConstant,
     #[error("Unrecognized char")]
     UnexpectedChar,
+    #[error("Integer constant is too large for its type")]
+    ConstantTooLarge,
+    #[error("Block comment is never closed")]
+    UnterminatedComment,
+    #[error("`*/` outside of a block comment")]
+    StrayCommentEnd,
+}
+
+/// Source text that carries no meaning for the parser but matters to tools
+/// that need to reproduce the file, like the formatter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trivia {
+    /// `// ...` comment without the line break
+    LineComment(String),
+    /// `/* ... */` comment including the delimiters
+    BlockComment(String),
+    /// Preprocessor line like `#include <stdio.h>`
+    Directive(String),
+    /// Number of empty lines
+    BlankLines(usize),
+    /// Whitespace and line continuations as written, only collected by
+    /// lexers created with [`Lexer::with_whitespace`], which then leave out
+    /// the [`Trivia::BlankLines`].
+    Whitespace(String),
+}
+
+impl Trivia {
+    /// The trivia as it is written in the file. Without
+    /// [`Lexer::with_whitespace`] the line break after a line comment or a
+    /// directive and the whitespace are not kept, so this is the text of the
+    /// comments and directives and a line break for each blank line.
+    pub fn text(&self) -> String {
+        match self {
+            Trivia::LineComment(text)
+            | Trivia::BlockComment(text)
+            | Trivia::Directive(text)
+            | Trivia::Whitespace(text) => text.clone(),
+            Trivia::BlankLines(lines) => "\n".repeat(*lines),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct FileToken {
     pub token: Token,
-    pub line: usize,
-    pub start_char_in_line: usize,
+    /// Byte offset of the token in the source.
+    pub offset: usize,
+    /// Length of the token text in bytes.
+    pub len: usize,
+    /// Trivia between the previous token and this one, only collected by
+    /// lexers created with [`Lexer::with_trivia`].
+    pub leading_trivia: Vec<Trivia>,
+    /// Comments that follow the token on the same line.
+    pub trailing_trivia: Vec<Trivia>,
+    /// Text of the token as written when it prints differently, like `007`,
+    /// a digraph or a keyword split by a line continuation. Only kept by
+    /// lexers created with [`Lexer::with_whitespace`].
+    pub spelling: Option<String>,
 }
 
 impl FileToken {
-    pub fn source_span(&self, source: impl AsRef<str>) -> SourceSpan {
-        SourceSpan::new(
-            SourceOffset::from_location(source, self.line, self.start_char_in_line),
-            self.token.text_length(),
-        )
+    pub fn source_span(&self) -> SourceSpan {
+        SourceSpan::new(self.offset.into(), self.len)
+    }
+
+    /// 1-based line and column (in chars) of the token, computed on demand.
+    pub fn line_column(&self, source: &str) -> (usize, usize) {
+        line_column(source, self.offset)
     }
 }
 
+/// 1-based line and column (in chars) of the byte `offset` in `source`.
+///
+/// Only `\n` starts a line, so a `\r\n` is one line break, and a tab or a
+/// multibyte char is one column like for miette. An offset past the end or
+/// inside a char is moved back to the start of that char.
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let mut end = offset.min(source.len());
+    while !source.is_char_boundary(end) {
+        end -= 1;
+    }
+    let before = &source[..end];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
 pub struct Lexer {
     pub path: PathBuf,
     pub content: String,
-    pub tokens: Vec<FileToken>,
-    line_nr: usize,
-    nr_in_line: usize,
+    /// Trivia after the last token, collected when trivia is enabled.
+    pub end_trivia: Vec<Trivia>,
+    /// Spans of the `//` of every line comment, which not every standard
+    /// allows, see [`dialect::line_comments`](crate::dialect::line_comments).
+    pub line_comments: Vec<SourceSpan>,
+    /// Errors behind the [`Token::Error`] tokens, when they are enabled.
+    pub errors: Vec<LexerError>,
+    /// Byte offset of the next char to read.
+    offset: usize,
+    trivia: bool,
+    trigraphs: bool,
+    error_tokens: bool,
+    whitespace: bool,
+    /// Start of the whitespace before the next item, when it is kept.
+    whitespace_start: Option<usize>,
+    pending_trivia: Vec<Trivia>,
+    newlines_since_item: usize,
+    /// Last lexed token, held back until its trailing comments are known.
+    held: Option<FileToken>,
+    ready: Option<FileToken>,
 }
 
 impl Lexer {
@@ -142,188 +341,542 @@ impl Lexer {
         Self {
             path: "main.c".into(),
             content,
-            tokens: Vec::new(),
-            line_nr: 1,
-            nr_in_line: 0,
+            end_trivia: Vec::new(),
+            line_comments: Vec::new(),
+            errors: Vec::new(),
+            offset: 0,
+            trivia: false,
+            trigraphs: false,
+            error_tokens: false,
+            whitespace: false,
+            whitespace_start: None,
+            pending_trivia: Vec::new(),
+            newlines_since_item: 0,
+            held: None,
+            ready: None,
         }
     }
+    /// Not available in the browser, where there is no filesystem.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
     pub fn from_path(path: PathBuf) -> Result<Self, std::io::Error> {
         let content = std::fs::read_to_string(path.clone())?;
-        Ok(Self {
-            path,
-            content,
-            tokens: Vec::new(),
-            line_nr: 1,
-            nr_in_line: 0,
+        let mut lexer = Self::from_content(content);
+        lexer.path = path;
+        Ok(lexer)
+    }
+    /// Keep comments, preprocessor lines and blank lines as trivia on the tokens.
+    pub fn with_trivia(mut self, trivia: bool) -> Self {
+        self.trivia = trivia;
+        self
+    }
+
+    /// Also keep the whitespace as trivia, and the comments and directives
+    /// as written, so [`Lexer::detokenize`] gives back the file. Turns on the
+    /// trivia.
+    pub fn with_whitespace(mut self, whitespace: bool) -> Self {
+        self.whitespace = whitespace;
+        self.trivia |= whitespace;
+        self
+    }
+
+    /// The file the tokens were lexed from, with the trivia and
+    /// [`Lexer::end_trivia`]. Byte for byte when the lexer was created
+    /// [`with_whitespace`](Lexer::with_whitespace) and, for a file that
+    /// doesn't lex, [`with_error_tokens`](Lexer::with_error_tokens).
+    pub fn detokenize(&self, tokens: &[FileToken]) -> String {
+        let mut text = String::new();
+        for token in tokens {
+            text.extend(token.leading_trivia.iter().map(Trivia::text));
+            match &token.spelling {
+                Some(spelling) => text.push_str(spelling),
+                None => text.push_str(&token.token.to_string()),
+            }
+            text.extend(token.trailing_trivia.iter().map(Trivia::text));
+        }
+        text.extend(self.end_trivia.iter().map(Trivia::text));
+        text
+    }
+
+    /// Translate the trigraphs like `??<` to the char they stand for and lex
+    /// the digraphs `<%`, `%>` and `%:` as `{`, `}` and `#`. The spans still
+    /// cover the chars as they are written.
+    pub fn with_trigraphs(mut self, trigraphs: bool) -> Self {
+        self.trigraphs = trigraphs;
+        self
+    }
+
+    /// Never fail: the text of an error becomes a [`Token::Error`] and
+    /// lexing goes on after it, the error is kept in [`Lexer::errors`]. For
+    /// the tools working on a file while it is edited.
+    pub fn with_error_tokens(mut self, error_tokens: bool) -> Self {
+        self.error_tokens = error_tokens;
+        self
+    }
+
+    /// Adds a token spanning from `start` to the current offset.
+    fn add_token(&mut self, token: Token, start: usize) {
+        debug_assert!(token
+            .text_length()
+            .is_none_or(|len| len == self.text(start).len()));
+        self.push_token(FileToken {
+            token,
+            offset: start,
+            len: self.offset - start,
+            leading_trivia: Vec::new(),
+            trailing_trivia: Vec::new(),
+            spelling: None,
         })
     }
-    pub fn add_token(&mut self, token: Token) {
-        self.tokens.push(FileToken {
+
+    /// Adds a token written as a digraph, which is longer than its text.
+    fn add_digraph(&mut self, token: Token, start: usize) {
+        debug_assert!(self.trigraphs);
+        self.push_token(FileToken {
             token,
-            line: self.line_nr,
-            start_char_in_line: self.nr_in_line,
+            offset: start,
+            len: self.offset - start,
+            leading_trivia: Vec::new(),
+            trailing_trivia: Vec::new(),
+            spelling: None,
         })
     }
 
+    fn push_token(&mut self, mut token: FileToken) {
+        if self.whitespace {
+            let written = &self.content[token.offset..token.offset + token.len];
+            if token.token.to_string() != written {
+                token.spelling = Some(written.to_string());
+            }
+        }
+        if self.trivia {
+            self.flush_blank_lines();
+            token.leading_trivia = std::mem::take(&mut self.pending_trivia);
+        }
+        self.newlines_since_item = 0;
+        self.ready = self.held.replace(token);
+    }
+
+    fn flush_blank_lines(&mut self) {
+        if self.newlines_since_item > 1 && !self.whitespace {
+            self.pending_trivia
+                .push(Trivia::BlankLines(self.newlines_since_item - 1));
+        }
+    }
+
+    /// Stores a comment or directive; `ends_line` is set when it consumed the line break.
+    fn push_trivia(&mut self, trivia: Trivia, ends_line: bool) {
+        if !self.trivia {
+            return;
+        }
+        let same_line = self.newlines_since_item == 0 && self.pending_trivia.is_empty();
+        match self.held.as_mut() {
+            Some(last) if same_line => last.trailing_trivia.push(trivia),
+            _ => {
+                self.flush_blank_lines();
+                self.pending_trivia.push(trivia);
+            }
+        }
+        self.newlines_since_item = ends_line as usize;
+    }
+
+    /// Stores the whitespace between the last item and `end`, with the
+    /// comments after a token when it is on the same line.
+    fn flush_whitespace(&mut self, end: usize) {
+        let Some(start) = self.whitespace_start.take().filter(|_| self.whitespace) else {
+            return;
+        };
+        if start == end {
+            return;
+        }
+        let trivia = Trivia::Whitespace(self.content[start..end].to_string());
+        match self.held.as_mut() {
+            Some(last) if self.newlines_since_item == 0 && self.pending_trivia.is_empty() => {
+                last.trailing_trivia.push(trivia)
+            }
+            _ => self.pending_trivia.push(trivia),
+        }
+    }
+
+    /// Char at `offset` and the number of bytes it is written with, a
+    /// trigraph is the char it stands for when they are translated.
+    fn source_char(&self, offset: usize) -> Option<(char, usize)> {
+        let rest = &self.content[offset..];
+        if let Some(ch) = trigraph(rest).filter(|_| self.trigraphs) {
+            return Some((ch, 3));
+        }
+        rest.chars().next().map(|ch| (ch, ch.len_utf8()))
+    }
+
+    /// Offset of the first char at or after `offset` that is not part of a
+    /// line continuation, a `\` right before a line break.
+    fn skip_continuations(&self, mut offset: usize) -> usize {
+        while let Some(('\\', len)) = self.source_char(offset) {
+            let rest = &self.content[offset + len..];
+            if rest.starts_with('\n') {
+                offset += len + 1;
+            } else if rest.starts_with("\r\n") {
+                offset += len + 2;
+            } else {
+                break;
+            }
+        }
+        offset
+    }
+
+    /// Next char with line continuations spliced out and trigraphs
+    /// translated, like the C preprocessor does before anything else.
+    fn peek_char(&self) -> Option<char> {
+        self.source_char(self.skip_continuations(self.offset))
+            .map(|(ch, _)| ch)
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let start = self.skip_continuations(self.offset);
+        let (ch, len) = self.source_char(start)?;
+        self.offset = start + len;
+        Some(ch)
+    }
+
+    fn next_char_if(&mut self, expected: char) -> bool {
+        if self.peek_char() == Some(expected) {
+            self.next_char();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes chars while `predicate` holds and returns the text read since
+    /// `start`.
+    fn take_while(&mut self, start: usize, predicate: impl Fn(char) -> bool) -> String {
+        while self.peek_char().is_some_and(&predicate) {
+            self.next_char();
+        }
+        self.text(start)
+    }
+
+    /// Text from `start` to the current offset without line continuations
+    /// and with trigraphs translated, the span of a token still covers them.
+    fn text(&self, start: usize) -> String {
+        let mut text = String::new();
+        let mut offset = self.skip_continuations(start);
+        while offset < self.offset {
+            let Some((ch, len)) = self.source_char(offset) else {
+                break;
+            };
+            text.push(ch);
+            offset = self.skip_continuations(offset + len);
+        }
+        text
+    }
+
+    /// Consumes the line including its line break and returns the text read
+    /// since `start`, without the `\r` of a `\r\n`. Keeping the whitespace,
+    /// the text is as written and the line break is left for the whitespace.
+    fn rest_of_line(&mut self, start: usize) -> String {
+        let mut text = self.take_while(start, |c| c != '\n');
+        if self.whitespace {
+            return self.content[start..self.offset].to_string();
+        }
+        if text.ends_with('\r') {
+            text.pop();
+        }
+        self.next_char();
+        text
+    }
+
+    /// Starts lexing from the beginning of the content again.
+    fn reset(&mut self) {
+        self.end_trivia.clear();
+        self.line_comments.clear();
+        self.errors.clear();
+        self.offset = 0;
+        self.whitespace_start = None;
+        self.pending_trivia.clear();
+        self.newlines_since_item = 0;
+        self.held = None;
+        self.ready = None;
+    }
+
     pub fn tokenize(&mut self) -> Result<Vec<FileToken>, LexerError> {
-        let mut errors = Vec::<LexerError>::new();
-        let content = self.content.clone();
-        let mut iter = content.chars().peekable();
-        self.line_nr = 1;
-        self.nr_in_line = 0;
-
-        while let Some(ch) = iter.next() {
-            self.nr_in_line += 1;
-            if ch.eq(&'\n') {
-                self.nr_in_line = 0;
-                self.line_nr += 1;
+        self.reset();
+        let mut tokens = Vec::new();
+        let mut first_error = None;
+        for result in self.by_ref() {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
             }
-            match ch {
-                ch if ch.is_whitespace() => continue,
-                '(' => self.add_token(Token::OpenParenthesis),
-                ')' => self.add_token(Token::CloseParenthesis),
-                '{' => self.add_token(Token::OpenBrace),
-                '}' => self.add_token(Token::CloseBrace),
-                ';' => self.add_token(Token::Semicolon),
-                '~' => self.add_token(Token::Tilde),
-                '+' => self.add_token(Token::Plus),
-                '*' => self.add_token(Token::Asteriks),
-                '%' => self.add_token(Token::PercentSign),
-                '=' if iter.next_if_eq(&'=').is_some() => {
-                    self.add_token(Token::EqualTo);
-                    self.nr_in_line += 1;
+        }
+
+        match first_error {
+            None => Ok(tokens),
+            Some(e) => Err(e),
+        }
+    }
+
+    /// Like [`Lexer::tokenize`], but reports every error into `sink` instead
+    /// of returning the first one. The tokens are only returned without errors.
+    pub fn tokenize_into(&mut self, sink: &mut dyn DiagnosticSink) -> Option<Vec<FileToken>> {
+        self.reset();
+        let mut tokens = Vec::new();
+        let mut failed = false;
+        for result in self.by_ref() {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(e) => {
+                    failed = true;
+                    sink.report(Box::new(e));
                 }
-                '&' if iter.next_if_eq(&'&').is_some() => {
-                    self.add_token(Token::And);
-                    self.nr_in_line += 1;
+            }
+        }
+        (!failed).then_some(tokens)
+    }
+
+    /// Lexes the next char or group of chars, which may or may not produce a token.
+    /// Returns `None` at the end of the content.
+    fn lex_item(&mut self) -> Option<Result<(), LexerError>> {
+        // A continuation before the item belongs to no token.
+        let before = self.offset;
+        self.offset = self.skip_continuations(self.offset);
+        let item_start = self.offset;
+        self.whitespace_start.get_or_insert(before);
+        let Some(ch) = self.next_char() else {
+            self.flush_whitespace(item_start);
+            return None;
+        };
+        if ch.eq(&'\n') {
+            self.newlines_since_item += 1;
+        }
+        if !ch.is_whitespace() {
+            self.flush_whitespace(item_start);
+        }
+        match ch {
+            ch if ch.is_whitespace() => {}
+            '(' => self.add_token(Token::OpenParenthesis, item_start),
+            ')' => self.add_token(Token::CloseParenthesis, item_start),
+            '{' => self.add_token(Token::OpenBrace, item_start),
+            '}' => self.add_token(Token::CloseBrace, item_start),
+            ';' => self.add_token(Token::Semicolon, item_start),
+            ',' => self.add_token(Token::Comma, item_start),
+            '~' => self.add_token(Token::Tilde, item_start),
+            '+' => {
+                if self.next_char_if('+') {
+                    self.add_token(Token::Increment, item_start);
+                } else {
+                    self.add_token(Token::Plus, item_start);
                 }
-                '|' if iter.next_if_eq(&'|').is_some() => {
-                    self.add_token(Token::Or);
-                    self.nr_in_line += 1;
+            }
+            '*' => {
+                // `*/*` is a multiplication followed by a comment.
+                let asterisk_end = self.offset;
+                if self.next_char_if('/') && self.peek_char() != Some('*') {
+                    return Some(Err(self.error(LexerErrorType::StrayCommentEnd, item_start)));
                 }
-                '-' => {
-                    if iter.next_if_eq(&'-').is_some() {
-                        self.add_token(Token::Decrement);
-                        self.nr_in_line += 1;
-                    } else {
-                        self.add_token(Token::Hyphen);
+                self.offset = asterisk_end;
+                self.add_token(Token::Asteriks, item_start);
+            }
+            '%' => {
+                if self.trigraphs && self.next_char_if('>') {
+                    self.add_digraph(Token::CloseBrace, item_start);
+                } else if self.trigraphs && self.next_char_if(':') {
+                    let mut text = self.rest_of_line(item_start);
+                    if !self.whitespace {
+                        text = format!("#{}", &text["%:".len()..]);
                     }
+                    self.push_trivia(Trivia::Directive(text), !self.whitespace);
+                } else {
+                    self.add_token(Token::PercentSign, item_start);
                 }
-                '>' => {
-                    if iter.next_if_eq(&'=').is_some() {
-                        self.add_token(Token::GreaterThanEqualTo);
-                        self.nr_in_line += 1;
-                    } else {
-                        self.add_token(Token::GreaterThan);
-                    }
+            }
+            '=' => {
+                if self.next_char_if('=') {
+                    self.add_token(Token::EqualTo, item_start);
+                } else {
+                    self.add_token(Token::Assign, item_start);
                 }
-                '<' => {
-                    if iter.next_if_eq(&'=').is_some() {
-                        self.add_token(Token::LessThanEqualTo);
-                        self.nr_in_line += 1;
-                    } else {
-                        self.add_token(Token::LessThan);
-                    }
+            }
+            '&' => {
+                if self.next_char_if('&') {
+                    self.add_token(Token::And, item_start);
+                } else {
+                    self.add_token(Token::Ampersand, item_start);
                 }
-                '!' => {
-                    if iter.next_if_eq(&'=').is_some() {
-                        self.add_token(Token::NotEqualTo);
-                        self.nr_in_line += 1;
-                    } else {
-                        self.add_token(Token::Not);
-                    }
+            }
+            '|' => {
+                if self.next_char_if('|') {
+                    self.add_token(Token::Or, item_start);
+                } else {
+                    self.add_token(Token::Pipe, item_start);
                 }
-                '0'..='9' => {
-                    let value = iter::once(ch)
-                        .chain(from_fn(|| iter.by_ref().next_if(|s| s.is_ascii_digit())))
-                        .collect::<String>();
-                    let n: i32 = value.parse().unwrap();
-
-                    self.add_token(Token::Constant(n));
-                    self.nr_in_line += value.len();
-                    if let Some(next_ch) = iter.peek() {
-                        if next_ch.is_alphabetic() {
-                            errors.push(self.error(LexerErrorType::InvalidCharInDigitalConstant));
-                            iter.next();
-                            self.nr_in_line += 1;
-                        }
-                    }
+            }
+            '-' => {
+                if self.next_char_if('-') {
+                    self.add_token(Token::Decrement, item_start);
+                } else {
+                    self.add_token(Token::Hyphen, item_start);
                 }
-                // AT LEAST FOR NOW
-                '#' => {
-                    iter.by_ref().find(|&c| c == '\n'); // Skip until end of the line
-                    self.nr_in_line = 0;
-                    self.line_nr += 1;
+            }
+            '>' => {
+                if self.next_char_if('=') {
+                    self.add_token(Token::GreaterThanEqualTo, item_start);
+                } else {
+                    self.add_token(Token::GreaterThan, item_start);
                 }
-                '/' => {
-                    if iter.next_if(|s| s.eq(&'/')).is_some() {
-                        // Single line comment (//)
-                        iter.by_ref().find(|&c| c == '\n'); // Skip until end of the line
-                        self.nr_in_line = 0;
-                        self.line_nr += 1;
-                    } else if iter.next_if(|s| s.eq(&'*')).is_some() {
-                        self.nr_in_line += 1;
-                        // Multiline comment (/* */)
-                        while let Some(ch) = iter.next() {
-                            self.nr_in_line += 1;
-                            if ch.eq(&'\n') {
-                                self.nr_in_line = 0;
-                                self.line_nr += 1;
-                            }
-                            if ch == '*' && iter.next_if(|s| s.eq(&'/')).is_some() {
-                                self.nr_in_line += 1;
-                                break; // End of the multiline comment
-                            }
-                        }
-                    } else {
-                        self.add_token(Token::Slash);
-                    }
+            }
+            '<' => {
+                if self.trigraphs && self.next_char_if('%') {
+                    self.add_digraph(Token::OpenBrace, item_start);
+                } else if self.next_char_if('=') {
+                    self.add_token(Token::LessThanEqualTo, item_start);
+                } else {
+                    self.add_token(Token::LessThan, item_start);
                 }
-                ch if ch.is_ascii_alphabetic() => {
-                    let n: String = iter::once(ch)
-                        .chain(from_fn(|| {
-                            iter.by_ref().next_if(|s| s.is_ascii_alphanumeric())
-                        }))
-                        .collect::<String>()
-                        .parse()
-                        .unwrap();
-                    let length = n.len();
-                    let token = KEYWORDS
-                        .iter()
-                        .find(|(_, s)| s.eq(&n))
-                        .map_or(Token::Identifier(n), |(t, _)| t.clone());
-                    self.tokens.push(FileToken {
-                        line: self.line_nr,
-                        start_char_in_line: self.nr_in_line,
-                        token,
-                    });
-                    self.nr_in_line += length;
+            }
+            '!' => {
+                if self.next_char_if('=') {
+                    self.add_token(Token::NotEqualTo, item_start);
+                } else {
+                    self.add_token(Token::Not, item_start);
                 }
-                _ => {
-                    errors.push(self.error(LexerErrorType::UnexpectedChar));
+            }
+            '0'..='9' => {
+                self.take_while(item_start, |s| s.is_ascii_digit());
+                // `12ab` is one malformed word, not a constant followed by a name.
+                if self.peek_char().is_some_and(char::is_alphanumeric) {
+                    self.take_while(item_start, char::is_alphanumeric);
+                    return Some(Err(
+                        self.error(LexerErrorType::InvalidCharInDigitalConstant, item_start)
+                    ));
                 }
+                // Only digits were taken, so the parse can only fail on overflow.
+                let Some(n) = self
+                    .text(item_start)
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|n| *n <= INT_MIN_MAGNITUDE)
+                else {
+                    return Some(Err(self.error(LexerErrorType::ConstantTooLarge, item_start)));
+                };
+                self.add_token(Token::Constant(n), item_start);
+            }
+            // AT LEAST FOR NOW
+            '#' => {
+                let text = self.rest_of_line(item_start);
+                self.push_trivia(Trivia::Directive(text), !self.whitespace);
+            }
+            '/' => {
+                if self.next_char_if('/') {
+                    // Single line comment (//)
+                    self.line_comments
+                        .push(SourceSpan::new(item_start.into(), self.offset - item_start));
+                    let text = self.rest_of_line(item_start);
+                    self.push_trivia(Trivia::LineComment(text), !self.whitespace);
+                } else if self.next_char_if('*') {
+                    // Multiline comment (/* */)
+                    let opener_end = self.offset;
+                    let mut closed = false;
+                    while let Some(ch) = self.next_char() {
+                        if ch == '*' && self.next_char_if('/') {
+                            closed = true;
+                            break;
+                        }
+                    }
+                    if !closed {
+                        return Some(Err(LexerError {
+                            error: LexerErrorType::UnterminatedComment,
+                            span: SourceSpan::new(item_start.into(), opener_end - item_start),
+                        }));
+                    }
+                    let text = self.content[item_start..self.offset].to_string();
+                    self.push_trivia(Trivia::BlockComment(text), false);
+                } else {
+                    self.add_token(Token::Slash, item_start);
+                }
+            }
+            ch if ch.is_ascii_alphabetic() => {
+                let n = self.take_while(item_start, |s| s.is_ascii_alphanumeric());
+                let token = KEYWORDS
+                    .iter()
+                    .find(|(_, s)| s.eq(&n))
+                    .map_or_else(|| Token::Identifier(Symbol::intern(&n)), |(t, _)| t.clone());
+                self.add_token(token, item_start);
+            }
+            _ => {
+                return Some(Err(self.error(LexerErrorType::UnexpectedChar, item_start)));
             }
         }
-
-        if errors.is_empty() {
-            Ok(self.tokens.clone())
-        } else {
-            Err(errors.first().unwrap().clone())
-        }
+        Some(Ok(()))
     }
 
-    pub fn error(&self, error: LexerErrorType) -> LexerError {
+    /// Error covering the text from `start` up to the current offset.
+    pub fn error(&self, error: LexerErrorType, start: usize) -> LexerError {
         LexerError {
-            src: NamedSource::new(self.path.to_str().unwrap(), self.content.clone()),
             error,
-            span: self.source_span(),
+            span: SourceSpan::new(start.into(), self.offset - start),
         }
     }
+}
 
-    pub fn source_span(&self) -> SourceSpan {
-        SourceSpan::new(
-            SourceOffset::from_location(&self.content, self.line_nr, self.nr_in_line),
-            1,
-        )
+/// The chars after `??` that make a trigraph, and the char it stands for.
+const TRIGRAPHS: [(char, char); 9] = [
+    ('=', '#'),
+    ('(', '['),
+    ('/', '\\'),
+    (')', ']'),
+    ('\'', '^'),
+    ('<', '{'),
+    ('!', '|'),
+    ('>', '}'),
+    ('-', '~'),
+];
+
+/// Char the trigraph at the start of `text` stands for.
+fn trigraph(text: &str) -> Option<char> {
+    let third = text.strip_prefix("??")?.chars().next()?;
+    TRIGRAPHS
+        .iter()
+        .find(|(after, _)| *after == third)
+        .map(|(_, ch)| *ch)
+}
+
+/// Pulls tokens lazily. A token is handed out once the next token is lexed, so
+/// comments on the same line can still be attached to it as trailing trivia.
+impl Iterator for Lexer {
+    type Item = Result<FileToken, LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.ready.is_none() {
+            match self.lex_item() {
+                Some(Ok(())) => {}
+                Some(Err(e)) if self.error_tokens => {
+                    // The text that was skipped, an unclosed comment runs to
+                    // the end while its error only points at the `/*`.
+                    let start = e.span.offset();
+                    self.errors.push(e);
+                    self.push_token(FileToken {
+                        token: Token::Error(self.content[start..self.offset].to_string()),
+                        offset: start,
+                        len: self.offset - start,
+                        leading_trivia: Vec::new(),
+                        trailing_trivia: Vec::new(),
+                        spelling: None,
+                    });
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    if self.trivia {
+                        self.flush_blank_lines();
+                        let rest = std::mem::take(&mut self.pending_trivia);
+                        self.end_trivia.extend(rest);
+                        self.newlines_since_item = 0;
+                    }
+                    return self.held.take().map(Ok);
+                }
+            }
+        }
+        self.ready.take().map(Ok)
     }
 }
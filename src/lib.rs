@@ -1,5 +1,40 @@
 pub mod assembly;
 pub mod ast;
+pub mod ast_diff;
+pub mod cache;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod const_eval;
+pub mod coverage;
+pub mod diagnostics;
+pub mod dialect;
+pub mod dot;
+mod embed;
+pub mod format;
+pub mod incremental;
+#[cfg(all(feature = "jit", target_arch = "x86_64", unix))]
+pub mod jit;
+pub mod json;
+pub mod lcs;
 pub mod lexer;
+pub mod names;
+pub mod optimize;
+pub mod options;
 pub mod parser;
+pub mod printer;
+pub mod reachability;
+pub mod runner;
+pub mod side_table;
+pub mod source_manager;
+pub mod symbol;
+pub mod symbol_index;
+pub mod synth;
 pub mod tacky;
+pub mod text_diff;
+pub mod token_stream;
+pub mod toolchain;
+pub mod typecheck;
+pub mod typed_ast;
+pub mod wasm;
@@ -0,0 +1,58 @@
+//! Names of compiler generated temporaries and labels.
+//!
+//! Every name starts with the function it belongs to, so functions lowered
+//! one after another never share a name, and numbering restarts for each
+//! function so the output of one does not shift when another one changes.
+
+use std::collections::HashMap;
+
+use crate::ast::Identifier;
+
+#[derive(Debug, Clone)]
+pub struct NameGenerator {
+    function: Identifier,
+    counters: HashMap<&'static str, usize>,
+}
+
+impl NameGenerator {
+    pub fn new(function: Identifier) -> Self {
+        Self {
+            function,
+            counters: HashMap::new(),
+        }
+    }
+
+    /// Next `<function>.<kind>.<n>` name, counted separately for each kind.
+    ///
+    /// The dots keep the names apart from anything a C identifier can spell.
+    pub fn fresh(&mut self, kind: &'static str) -> Identifier {
+        let counter = self.counters.entry(kind).or_default();
+        let name = format!("{}.{}.{}", self.function, kind, counter);
+        *counter += 1;
+        Identifier::from(name.as_str())
+    }
+
+    /// Next `<function>.<kind>.<name>.<n>` name, for a label made from
+    /// something in the source like a case value. `name` may hold chars an
+    /// assembler doesn't take, [`crate::assembly::Mangler`] escapes them.
+    pub fn derived(&mut self, kind: &'static str, name: &str) -> Identifier {
+        let counter = self.counters.entry(kind).or_default();
+        let name = format!("{}.{}.{}.{}", self.function, kind, name, counter);
+        *counter += 1;
+        Identifier::from(name.as_str())
+    }
+
+    pub fn temporary(&mut self) -> Identifier {
+        self.fresh("tmp")
+    }
+
+    pub fn label(&mut self) -> Identifier {
+        self.fresh("label")
+    }
+}
+
+/// Whether `name` was made by the compiler, a temporary or a new version of
+/// a variable, rather than written in the source.
+pub fn is_generated(name: Identifier) -> bool {
+    name.0.as_str().contains('.')
+}
@@ -0,0 +1,238 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    assembly::{AsmPipeline, AsmPipelineError},
+    tacky::TackyProgram,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum OptimizationLevel {
+    #[default]
+    O0,
+    O1,
+    O2,
+}
+
+impl OptimizationLevel {
+    pub fn passes(&self) -> Vec<Pass> {
+        match self {
+            OptimizationLevel::O0 => vec![],
+            OptimizationLevel::O1 => vec![
+                Pass::ConstantFolding,
+                Pass::DeadCodeElimination,
+                Pass::JumpCleanup,
+            ],
+            OptimizationLevel::O2 => vec![
+                Pass::Ssa,
+                Pass::ConstantFolding,
+                Pass::CopyPropagation,
+                Pass::LoopInvariantCodeMotion,
+                Pass::StrengthReduction,
+                Pass::DeadCodeElimination,
+                Pass::JumpCleanup,
+                Pass::CoalesceSlots,
+                Pass::Peephole,
+            ],
+        }
+    }
+}
+
+impl FromStr for OptimizationLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(OptimizationLevel::O0),
+            "1" => Ok(OptimizationLevel::O1),
+            "2" => Ok(OptimizationLevel::O2),
+            _ => Err(format!(
+                "Unknown optimization level \"{s}\", expected 0, 1 or 2"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OptimizationLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            OptimizationLevel::O0 => "0",
+            OptimizationLevel::O1 => "1",
+            OptimizationLevel::O2 => "2",
+        };
+        f.write_str(level)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Round trip through SSA form, later passes working on SSA go between.
+    Ssa,
+    ConstantFolding,
+    CopyPropagation,
+    /// Moves the computations a loop repeats with the same result out of it.
+    LoopInvariantCodeMotion,
+    /// Turns multiplications by a power of two into shifts.
+    StrengthReduction,
+    DeadCodeElimination,
+    JumpCleanup,
+    /// Shares stack slots between pseudo registers never live together.
+    CoalesceSlots,
+    Peephole,
+}
+
+pub const ALL_PASSES: [Pass; 9] = [
+    Pass::Ssa,
+    Pass::ConstantFolding,
+    Pass::CopyPropagation,
+    Pass::LoopInvariantCodeMotion,
+    Pass::StrengthReduction,
+    Pass::DeadCodeElimination,
+    Pass::JumpCleanup,
+    Pass::CoalesceSlots,
+    Pass::Peephole,
+];
+
+impl Pass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pass::Ssa => "ssa",
+            Pass::ConstantFolding => "fold",
+            Pass::CopyPropagation => "copy-prop",
+            Pass::LoopInvariantCodeMotion => "licm",
+            Pass::StrengthReduction => "strength-reduce",
+            Pass::DeadCodeElimination => "dce",
+            Pass::JumpCleanup => "jump-cleanup",
+            Pass::CoalesceSlots => "coalesce-slots",
+            Pass::Peephole => "peephole",
+        }
+    }
+
+    /// Whether the pass works on assembly rather than on TACKY.
+    pub fn is_assembly_pass(&self) -> bool {
+        matches!(self, Pass::CoalesceSlots | Pass::Peephole)
+    }
+
+    /// Whether the assembly pass runs before the pseudo registers get their
+    /// stack slots.
+    pub fn on_pseudo_registers(&self) -> bool {
+        *self == Pass::CoalesceSlots
+    }
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Pass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ALL_PASSES
+            .iter()
+            .find(|pass| pass.name() == s)
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<_> = ALL_PASSES.iter().map(Pass::name).collect();
+                format!(
+                    "Unknown pass \"{s}\", expected one of: {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Runs a sequence of optimization passes over TACKY and assembly.
+#[derive(Debug, Clone, Default)]
+pub struct PassManager {
+    pub passes: Vec<Pass>,
+    /// Passes after which the resulting IR gets printed.
+    pub print_after: Vec<Pass>,
+}
+
+impl PassManager {
+    pub fn new(level: OptimizationLevel) -> Self {
+        Self::with_passes(level.passes())
+    }
+
+    pub fn with_passes(passes: Vec<Pass>) -> Self {
+        Self {
+            passes,
+            print_after: vec![],
+        }
+    }
+
+    /// In debug builds the program is verified before the first pass and after every pass.
+    pub fn run_tacky(&self, program: &mut TackyProgram) -> Result<(), String> {
+        if cfg!(debug_assertions) && !self.passes.is_empty() {
+            program
+                .verify()
+                .map_err(|e| format!("Invalid TACKY before optimizing: {e}"))?;
+        }
+        for pass in self.passes.iter().filter(|p| !p.is_assembly_pass()) {
+            match pass {
+                Pass::Ssa => {
+                    let ssa = program.to_ssa();
+                    if cfg!(debug_assertions) {
+                        ssa.verify().map_err(|e| format!("Invalid SSA form: {e}"))?;
+                    }
+                    *program = ssa.into_tacky();
+                }
+                Pass::ConstantFolding => program.fold_constants(),
+                Pass::CopyPropagation => program.propagate_copies(),
+                Pass::LoopInvariantCodeMotion => program.hoist_loop_invariants(),
+                Pass::StrengthReduction => program.reduce_strength(),
+                Pass::DeadCodeElimination => program.eliminate_dead_code(),
+                Pass::JumpCleanup => program.cleanup_jumps(),
+                Pass::CoalesceSlots | Pass::Peephole => unreachable!(),
+            }
+            if cfg!(debug_assertions) {
+                program
+                    .verify()
+                    .map_err(|e| format!("Invalid TACKY after {pass}: {e}"))?;
+            }
+            if self.print_after.contains(pass) {
+                println!("\n*** IR after {} ***\n{}", pass, program);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the assembly passes working on pseudo registers on `pipeline`,
+    /// before they are replaced.
+    pub fn run_assembly_on_pseudos(
+        &self,
+        pipeline: &mut AsmPipeline,
+    ) -> Result<(), AsmPipelineError> {
+        self.run_assembly_passes(pipeline, true)
+    }
+
+    /// Runs the other assembly passes on `pipeline`, whose instructions have
+    /// to be fixed up already.
+    pub fn run_assembly(&self, pipeline: &mut AsmPipeline) -> Result<(), AsmPipelineError> {
+        self.run_assembly_passes(pipeline, false)
+    }
+
+    fn run_assembly_passes(
+        &self,
+        pipeline: &mut AsmPipeline,
+        on_pseudo_registers: bool,
+    ) -> Result<(), AsmPipelineError> {
+        let passes = self
+            .passes
+            .iter()
+            .filter(|p| p.is_assembly_pass() && p.on_pseudo_registers() == on_pseudo_registers);
+        for pass in passes {
+            let program = match pass {
+                Pass::CoalesceSlots => pipeline.coalesce_slots()?,
+                Pass::Peephole => pipeline.peephole()?,
+                _ => unreachable!(),
+            };
+            if self.print_after.contains(pass) {
+                println!("\n*** IR after {} ***\n{:#?}", pass, program);
+            }
+        }
+        Ok(())
+    }
+}
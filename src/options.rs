@@ -0,0 +1,161 @@
+//! What a compilation does, from the stage it stops after to the code it
+//! compiles into the program, in one value the stages read their settings
+//! from.
+//!
+//! The driver builds it from its flags and the `ulang.toml` of the file, a
+//! host embedding the compiler can fill it in directly.
+
+use std::path::Path;
+
+use crate::{
+    assembly::{FunctionSections, Instrumentation, RuntimeChecks, TargetPlatform},
+    ast::Identifier,
+    diagnostics::WarningPolicy,
+    dialect::Standard,
+    optimize::{OptimizationLevel, Pass, PassManager},
+};
+
+/// Stage after which a compilation stops, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Stage {
+    Lex,
+    Parse,
+    /// Type checking and lowering to TACKY, before the optimization passes.
+    Semantic,
+    Tacky,
+    #[default]
+    Codegen,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileOptions {
+    pub target: TargetPlatform,
+    pub opt_level: OptimizationLevel,
+    /// The last stage to run, whose output is what the compilation emits.
+    pub emit: Stage,
+    pub warnings: WarningPolicy,
+    /// Standard whose rules the program is checked against, see
+    /// [`crate::dialect`].
+    pub standard: Option<Standard>,
+    /// Translate trigraphs and digraphs, see [`crate::lexer::Lexer::with_trigraphs`].
+    pub trigraphs: bool,
+    /// Record where the statements and operators come from, for a source
+    /// map of the assembly.
+    pub debug_info: bool,
+    /// Stop the program with a message on division by zero and on signed
+    /// overflow of `+`, `-` and `*`.
+    pub checked_arithmetic: bool,
+    /// Count the calls of every function, see [`Instrumentation::functions`].
+    pub instrument_functions: bool,
+    /// Count the runs of every basic block, see [`Instrumentation::coverage`].
+    pub coverage: bool,
+    /// Function compiled instead of the first one.
+    pub entry: Option<Identifier>,
+    /// The program is linked without the C runtime.
+    pub freestanding: bool,
+    /// Also emit a `_start` calling the entry point, for a freestanding
+    /// program.
+    pub start: bool,
+    /// Put every function in a section of its own, see
+    /// [`FunctionSections::Separate`].
+    pub function_sections: bool,
+    /// Passes after which the IR gets printed.
+    pub print_after: Vec<Pass>,
+    /// C compiler assembling and linking the program.
+    pub cc: String,
+    /// Extra flags for linking, after the ones of the compiler.
+    pub ldflags: Vec<String>,
+    /// Reuse and store the assembly in the compilation cache.
+    pub cache: bool,
+    /// Print the commands of the toolchain instead of running them.
+    pub dry_run: bool,
+    /// How many errors are shown, see
+    /// [`crate::diagnostics::Deduplicate::with_limit`].
+    pub error_limit: Option<usize>,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            target: TargetPlatform::host(),
+            opt_level: OptimizationLevel::default(),
+            emit: Stage::default(),
+            warnings: WarningPolicy::default(),
+            standard: None,
+            trigraphs: false,
+            debug_info: false,
+            checked_arithmetic: false,
+            instrument_functions: false,
+            coverage: false,
+            entry: None,
+            freestanding: false,
+            start: false,
+            function_sections: false,
+            print_after: vec![],
+            cc: "gcc".to_string(),
+            ldflags: vec![],
+            cache: false,
+            dry_run: false,
+            error_limit: Some(20),
+        }
+    }
+}
+
+impl CompileOptions {
+    /// Whether the TACKY needs the position of the statements and operators,
+    /// for the runtime error messages, the source map or the coverage report.
+    pub fn source_locations(&self) -> bool {
+        self.checked_arithmetic || self.debug_info || self.coverage
+    }
+
+    /// The passes of the optimization level. With the overflow checks on,
+    /// the ones moving or replacing arithmetic are left out, the checks
+    /// would no longer fire where the source overflows.
+    pub fn passes(&self) -> PassManager {
+        let mut passes = PassManager::new(self.opt_level);
+        if self.checked_arithmetic {
+            passes.passes.retain(|pass| {
+                !matches!(
+                    pass,
+                    Pass::LoopInvariantCodeMotion | Pass::StrengthReduction
+                )
+            });
+        }
+        passes.print_after = self.print_after.clone();
+        passes
+    }
+
+    /// The checks compiled in, naming `file_name` in their messages.
+    pub fn runtime_checks(&self, file_name: &str) -> RuntimeChecks {
+        RuntimeChecks {
+            division_by_zero: self.checked_arithmetic,
+            overflow: self.checked_arithmetic,
+            file_name: file_name.to_string(),
+        }
+    }
+
+    /// Sections the code of the functions goes into.
+    pub fn function_sections(&self) -> FunctionSections {
+        if self.function_sections {
+            FunctionSections::Separate
+        } else {
+            FunctionSections::Shared
+        }
+    }
+
+    /// The instrumentation compiled in, the counts of the coverage go next
+    /// to `file` with the `.ulangcov` extension. The path is absolute, so
+    /// the program finds the file wherever it runs from.
+    pub fn instrumentation(&self, file: &Path) -> std::io::Result<Instrumentation> {
+        let coverage = if self.coverage {
+            let path = std::fs::canonicalize(file)?;
+            Some(path.with_extension("ulangcov").display().to_string())
+        } else {
+            None
+        };
+        Ok(Instrumentation {
+            functions: self.instrument_functions,
+            coverage,
+        })
+    }
+}
@@ -1,26 +1,33 @@
-use std::path::PathBuf;
-
-use miette::{Diagnostic, NamedSource, SourceSpan};
+use miette::{Diagnostic, SourceSpan};
 use thiserror::Error;
 
 use crate::{
     ast::*,
-    lexer::{FileToken, Token},
+    diagnostics::DiagnosticSink,
+    dialect::Standard,
+    lexer::{FileToken, Token, INT_MIN_MAGNITUDE},
+    token_stream::TokenStream,
 };
 
-pub struct Parser {
-    file: String,
-    file_name: PathBuf,
-    tokens: Vec<FileToken>,
-    pos: usize,
+/// Binds looser than every binary operator, `a || b = c` assigns to `a || b`
+/// and fails in the type checker.
+const ASSIGNMENT_PRECEDENCE: i32 = 1;
+
+/// Parses borrowed tokens of `file`, which is only needed for the position
+/// of the end of file.
+pub struct Parser<'a> {
+    tokens: TokenStream<'a>,
+    /// Id of the next node.
+    next_id: NodeId,
+    /// Standard whose grammar rules apply, the ones of every standard when
+    /// there is none.
+    standard: Option<Standard>,
 }
 
 #[derive(Error, Debug, Diagnostic, Clone)]
 #[error("Failed to parse the code")]
 #[diagnostic(code(error::on::base))]
 pub struct ParserError {
-    #[source_code]
-    pub src: NamedSource<String>,
     #[label = "{error}, found {token}"]
     pub span: SourceSpan,
     pub error: ParserErrorType,
@@ -45,273 +52,487 @@ pub enum ParserErrorType {
     ExpectedExpression,
     #[error("Missing return value")]
     MissingReturnValue,
+    #[error("Unexpected tokens after the end of the program")]
+    UnexpectedAfterProgram,
+    #[error("Unexpected tokens after the expression")]
+    UnexpectedAfterExpression,
+    #[error("Repeated or conflicting type specifier")]
+    ConflictingTypeSpecifier,
+    #[error("Missing return type, {0} has no implicit int")]
+    ImplicitInt(Standard),
+    #[error("Integer constant is too large for its type")]
+    ConstantTooLarge,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<FileToken>, file_name: PathBuf, file: String) -> Self {
+impl<'a> Parser<'a> {
+    pub fn new(tokens: &'a [FileToken], file: &'a str) -> Self {
         Parser {
-            tokens,
-            pos: 0,
-            file_name,
-            file,
+            tokens: TokenStream::new(tokens, file.len()),
+            next_id: NodeId::default(),
+            standard: None,
+        }
+    }
+
+    /// Applies the grammar rules of `standard`, like the implicit `int`
+    /// return type of C89.
+    pub fn with_standard(mut self, standard: Standard) -> Self {
+        self.standard = Some(standard);
+        self
+    }
+
+    /// Numbers the nodes from `first` on, so the ids of a partial parse don't
+    /// collide with the ones of other parses of the same file.
+    pub fn with_first_id(mut self, first: NodeId) -> Self {
+        self.next_id = first;
+        self
+    }
+
+    /// Id the next node will get, the first one free after the parse.
+    pub fn next_node_id(&self) -> NodeId {
+        self.next_id
+    }
+
+    fn next_id(&mut self) -> NodeId {
+        let id = self.next_id;
+        self.next_id.0 += 1;
+        id
+    }
+
+    fn node(&mut self, kind: ExpressionKind) -> Expression {
+        Expression {
+            id: self.next_id(),
+            kind,
         }
     }
 
     pub fn error(&self, token: FileToken, error: ParserErrorType) -> ParserError {
         ParserError {
-            src: NamedSource::new(self.file_name.to_str().unwrap(), self.file.clone()),
             error,
-            span: token.source_span(&self.file),
+            span: token.source_span(),
             token: token.token.clone(),
         }
     }
 
+    /// Parses the program, failing with the first error.
     pub fn parse(&mut self) -> Result<AstNode, ParserError> {
+        self.parse_program()
+            .map_err(|errors| errors.into_iter().next().unwrap())
+    }
+
+    /// Like [`Parser::parse`], reporting every error into `sink`.
+    pub fn parse_into(&mut self, sink: &mut dyn DiagnosticSink) -> Option<AstNode> {
+        self.parse_program()
+            .map_err(|errors| {
+                for e in errors {
+                    sink.report(Box::new(e));
+                }
+            })
+            .ok()
+    }
+
+    /// Functions until the end of the tokens. Tokens after a function that
+    /// can't start another one are reported as one error and skipped, so the
+    /// functions after them are still checked. An error inside a function
+    /// stops the parse.
+    fn parse_program(&mut self) -> Result<AstNode, Vec<ParserError>> {
         let mut nodes = Vec::new();
-        while self.pos < self.tokens.len() {
+        let mut errors = Vec::new();
+        while !self.tokens.is_at_end() {
+            if !nodes.is_empty() {
+                if let Some(error) = self.skip_trailing_tokens() {
+                    errors.push(error);
+                    continue;
+                }
+            }
             match self.parse_function() {
-                Ok(fun) => nodes.push(AstNode::FunctionDeclaration(fun)),
+                Ok(function) => nodes.push(AstNode::FunctionDeclaration(function)),
                 Err(e) => {
-                    return Err(e);
+                    errors.push(e);
+                    return Err(errors);
                 }
             }
         }
         if nodes.is_empty() {
-            Err(self.error(self.last().clone(), ParserErrorType::NoValidFunctions))
-        } else {
+            errors.push(self.tokens.error(ParserErrorType::NoValidFunctions));
+        }
+        if errors.is_empty() {
             Ok(AstNode::Program(nodes))
+        } else {
+            Err(errors)
         }
     }
 
+    /// Skips the tokens up to the next function outside of braces, and
+    /// returns an error covering them when there were any.
+    fn skip_trailing_tokens(&mut self) -> Option<ParserError> {
+        let mut error = self.tokens.error(ParserErrorType::UnexpectedAfterProgram);
+        let start = self.tokens.peek().offset;
+        let mut depth = 0usize;
+        let mut end = None;
+        while !self.tokens.is_at_end() {
+            let token = &self.tokens.peek().token;
+            if depth == 0 && (starts_type(token) || self.implicit_int()) {
+                break;
+            }
+            match token {
+                Token::OpenBrace => depth += 1,
+                Token::CloseBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            let token = self.tokens.advance();
+            end = Some(token.offset + token.len);
+        }
+        error.span = SourceSpan::new(start.into(), end? - start);
+        Some(error)
+    }
+
     fn parse_function(&mut self) -> Result<FunctionDecl, ParserError> {
-        let return_type = if self.match_token(&Token::IntKeyword) {
-            VarType::Int
-        } else if self.match_token(&Token::VoidKeyWord) {
+        let return_type = if self.tokens.eat(&Token::VoidKeyWord) {
             VarType::Void
+        } else if let Some(integer) = self.integer_type()? {
+            integer
+        } else if self.implicit_int() {
+            VarType::Int
+        } else if let Some(standard) = self.standard.filter(|_| self.missing_return_type()) {
+            return Err(self.tokens.error(ParserErrorType::ImplicitInt(standard)));
         } else {
-            let file_token = self.peek().unwrap();
-            return Err(self.error(file_token.clone(), ParserErrorType::ExpectedTypeKeyword));
+            return Err(self.tokens.error(ParserErrorType::ExpectedTypeKeyword));
         };
 
-        let name = if let Some(Token::Identifier(name)) = self.advance().map(|t| t.token.clone()) {
-            name.clone()
-        } else {
-            let file_token = self.peek().unwrap();
+        let (name, name_span) = self.identifier(ParserErrorType::ExpectedFunctionName)?;
 
-            return Err(self.error(file_token.clone(), ParserErrorType::ExpectedFunctionName));
-        };
+        self.tokens
+            .expect(&Token::OpenParenthesis, ParserErrorType::ExpectedChar('('))?;
+        let parameters = self.parse_parameters()?;
 
-        if !self.match_token(&Token::OpenParenthesis) {
-            let file_token = self.peek().unwrap();
-            return Err(self.error(file_token.clone(), ParserErrorType::ExpectedChar('(')));
-        }
+        self.tokens
+            .expect(&Token::OpenBrace, ParserErrorType::ExpectedChar('{'))?;
+        let body = self.parse_compound_statement()?;
+        self.tokens
+            .expect(&Token::CloseBrace, ParserErrorType::ExpectedChar('}'))?;
+
+        Ok(FunctionDecl {
+            id: self.next_id(),
+            return_type,
+            name,
+            name_span,
+            parameters,
+            body,
+        })
+    }
 
-        // Parse parameters (ignoring for simplicity in this example)
-        while !self.match_token(&Token::CloseParenthesis) {
-            let is_some = self.advance().is_some(); // Skip until ')'
-            if !is_some {
-                return Err(self.error(self.last().clone(), ParserErrorType::ExpectedChar(')')));
+    /// The parameters after the `(` of a function, up to and including the
+    /// `)`. Both `()` and `(void)` declare none.
+    fn parse_parameters(&mut self) -> Result<Vec<Parameter>, ParserError> {
+        let mut parameters = Vec::new();
+        if self.tokens.eat(&Token::CloseParenthesis) {
+            return Ok(parameters);
+        }
+        if self.tokens.peek().token == Token::VoidKeyWord
+            && self.tokens.peek_n(1).token == Token::CloseParenthesis
+        {
+            self.tokens.advance();
+            self.tokens.advance();
+            return Ok(parameters);
+        }
+        loop {
+            let mut var_type = if self.tokens.eat(&Token::CharKeyword) {
+                VarType::Char
+            } else if let Some(integer) = self.integer_type()? {
+                integer
+            } else {
+                return Err(self.tokens.error(ParserErrorType::ExpectedTypeKeyword));
+            };
+            while self.tokens.eat(&Token::Asteriks) {
+                var_type = VarType::Pointer(Box::new(var_type));
+            }
+            let (name, name_span) = self.identifier(ParserErrorType::ExpectedVariableName)?;
+            parameters.push(Parameter {
+                var_type,
+                name,
+                name_span,
+            });
+            if self.tokens.eat(&Token::CloseParenthesis) {
+                return Ok(parameters);
             }
+            self.tokens
+                .expect(&Token::Comma, ParserErrorType::ExpectedChar(','))?;
         }
+    }
 
-        if !self.match_token(&Token::OpenBrace) {
-            let file_token = self.peek().unwrap();
-            return Err(self.error(file_token.clone(), ParserErrorType::ExpectedChar('{')));
-        }
+    /// Whether the next tokens are a function name and `(`, a function
+    /// declared without a return type.
+    fn missing_return_type(&self) -> bool {
+        matches!(self.tokens.peek().token, Token::Identifier(_))
+            && self.tokens.peek_n(1).token == Token::OpenParenthesis
+    }
 
-        let body = match self.parse_compound_statement() {
-            Ok(b) => b,
-            Err(e) => return Err(e),
+    /// Whether a function without a return type starts here, and returns
+    /// `int` in the selected standard.
+    fn implicit_int(&self) -> bool {
+        self.standard
+            .is_some_and(|standard| standard.implicit_int())
+            && self.missing_return_type()
+    }
+
+    /// Consumes an identifier, or fails with `error` at the token found instead.
+    fn identifier(
+        &mut self,
+        error: ParserErrorType,
+    ) -> Result<(Identifier, SourceSpan), ParserError> {
+        let Token::Identifier(name) = self.tokens.peek().token else {
+            return Err(self.tokens.error(error));
         };
+        let span = self.tokens.advance().source_span();
+        Ok((Identifier(name), span))
+    }
 
-        if !self.match_token(&Token::CloseBrace) {
-            let file_token = self.peek().unwrap();
-            return Err(self.error(file_token.clone(), ParserErrorType::ExpectedChar('}')));
+    fn parse_compound_statement(&mut self) -> Result<Statement, ParserError> {
+        let mut items = Vec::new();
+        while !self.tokens.check(&Token::CloseBrace) && !self.tokens.is_at_end() {
+            items.push(self.parse_block_item()?);
         }
-
-        Ok(FunctionDecl {
-            return_type,
-            name,
-            parameters: Vec::new(), // Skipping parameter parsing for now
-            body,
-        })
+        Ok(Statement::Compound(items))
     }
 
-    fn parse_compound_statement(&mut self) -> Result<Statement, ParserError> {
-        let mut statements = Vec::new();
-        // println!("ONE");
-        while !self.check_token(&Token::CloseBrace) && self.pos < self.tokens.len() {
-            // println!(" Try");
-            match self.parse_statement() {
-                Ok(s) => statements.push(s),
-                Err(e) => return Err(e),
-            };
+    /// A declaration when the item starts with a type, a statement otherwise.
+    fn parse_block_item(&mut self) -> Result<BlockItem, ParserError> {
+        if let Some(var_type) = self.integer_type()? {
+            self.parse_declaration(var_type).map(BlockItem::Declaration)
+        } else {
+            self.parse_statement().map(BlockItem::Statement)
         }
-        // println!("TWO");
-        Ok(Statement::Compound(statements))
     }
 
     fn parse_statement(&mut self) -> Result<Statement, ParserError> {
-        if self.match_token(&Token::IntKeyword) {
-            return self.parse_variable_declaration();
-        } else if self.match_token(&Token::ReturnKeyWord) {
+        if self.tokens.eat(&Token::ReturnKeyWord) {
             return self.parse_return_statement();
         }
-        let file_token = self.peek().unwrap();
+        let start = self.tokens.checkpoint();
+        let expression = match self.parse_expression() {
+            Ok(expression) => expression,
+            // Nothing here starts a statement.
+            Err(_) if self.tokens.checkpoint() == start => {
+                return Err(self.tokens.error(ParserErrorType::ExpectedStatement))
+            }
+            Err(e) => return Err(e),
+        };
+        self.tokens
+            .expect(&Token::Semicolon, ParserErrorType::ExpectedChar(';'))?;
+        Ok(Statement::Expression(expression))
+    }
 
-        Err(self.error(file_token.clone(), ParserErrorType::ExpectedStatement))
+    /// The `int`, `long`, `signed` and `unsigned` keywords naming an integer
+    /// type, in any order like in C, `None` when the next token is none of
+    /// them.
+    fn integer_type(&mut self) -> Result<Option<VarType>, ParserError> {
+        let (mut int, mut long, mut signed, mut unsigned) = (false, false, false, false);
+        let mut any = false;
+        loop {
+            let (specifier, conflict) = match self.tokens.peek().token {
+                Token::IntKeyword => (&mut int, false),
+                Token::LongKeyword => (&mut long, false),
+                Token::SignedKeyword => (&mut signed, unsigned),
+                Token::UnsignedKeyword => (&mut unsigned, signed),
+                _ => break,
+            };
+            if *specifier || conflict {
+                return Err(self.tokens.error(ParserErrorType::ConflictingTypeSpecifier));
+            }
+            *specifier = true;
+            any = true;
+            self.tokens.advance();
+        }
+        Ok(any.then_some(match (long, unsigned) {
+            (false, false) => VarType::Int,
+            (false, true) => VarType::UInt,
+            (true, false) => VarType::Long,
+            (true, true) => VarType::ULong,
+        }))
     }
 
-    fn parse_variable_declaration(&mut self) -> Result<Statement, ParserError> {
-        let name = if let Some(Token::Identifier(name)) = self.advance().map(|t| t.token.clone()) {
-            name.clone()
-        } else {
-            let file_token = self.peek().unwrap();
-            return Err(self.error(file_token.clone(), ParserErrorType::ExpectedVariableName));
-        };
+    /// The rest of a declaration after its type.
+    fn parse_declaration(&mut self, var_type: VarType) -> Result<Declaration, ParserError> {
+        let (name, name_span) = self.identifier(ParserErrorType::ExpectedVariableName)?;
 
-        let initializer = if self.match_token(&Token::Semicolon) {
+        let initializer = if self.tokens.eat(&Token::Semicolon) {
             None
-        } else if let Ok(expression) = self.parse_expression() {
-            if self.match_token(&Token::Semicolon) {
-                Some(expression)
-            } else {
-                let file_token = self.previous().unwrap();
-                return Err(self.error(file_token.clone(), ParserErrorType::ExpectedChar(';')));
-            }
         } else {
-            let file_token = self.peek().unwrap();
-            return Err(self.error(file_token.clone(), ParserErrorType::ExpectedExpression));
+            self.tokens
+                .expect(&Token::Assign, ParserErrorType::ExpectedChar('='))?;
+            let expression = self.parse_expression()?;
+            self.tokens
+                .expect(&Token::Semicolon, ParserErrorType::ExpectedChar(';'))?;
+            Some(expression)
         };
 
-        Ok(Statement::VariableDeclaration {
-            var_type: VarType::Int,
+        Ok(Declaration {
+            id: self.next_id(),
+            var_type,
             name,
+            name_span,
             initializer,
         })
     }
 
     fn parse_return_statement(&mut self) -> Result<Statement, ParserError> {
-        let expr = if !self.check_token(&Token::Semicolon) {
-            self.parse_expression()
+        let start = self.tokens.previous().map_or(0, |keyword| keyword.offset);
+        let value = if self.tokens.check(&Token::Semicolon) {
+            None
         } else {
-            Err("TT".into())
-        };
-
-        println!("{:?}", expr);
-        match expr {
-            Ok(val) if self.check_token(&Token::Semicolon) => {
-                self.advance();
-                Ok(Statement::ReturnStatement(Some(val)))
+            match self.parse_expression() {
+                Ok(value) if self.tokens.check(&Token::Semicolon) => Some(value),
+                Err(
+                    error @ ParserError {
+                        error: ParserErrorType::ConstantTooLarge,
+                        ..
+                    },
+                ) => return Err(error),
+                _ => return Err(self.tokens.error(ParserErrorType::MissingReturnValue)),
             }
-            _ => Err(self.error(
-                self.peek().unwrap().clone(),
-                ParserErrorType::MissingReturnValue,
-            )),
-        }
+        };
+        let semicolon = self.tokens.advance();
+        let end = semicolon.offset + semicolon.len;
+        Ok(Statement::ReturnStatement(
+            value,
+            SourceSpan::new(start.into(), end - start),
+        ))
     }
 
     /// Entry point for parsing expressions, with initial minimum precedence 0.
-    pub fn parse_expression(&mut self) -> Result<Expression, String> {
+    pub fn parse_expression(&mut self) -> Result<Expression, ParserError> {
         self.parse_binary_expression(0)
     }
 
-    pub fn parse_binary_expression(&mut self, min_precedence: i32) -> Result<Expression, String> {
+    /// An expression that takes all of the tokens, like the input of
+    /// `ulang_driver --eval`.
+    pub fn parse_whole_expression(&mut self) -> Result<Expression, ParserError> {
+        let expression = self.parse_expression()?;
+        if !self.tokens.is_at_end() {
+            return Err(self
+                .tokens
+                .error(ParserErrorType::UnexpectedAfterExpression));
+        }
+        Ok(expression)
+    }
+
+    pub fn parse_binary_expression(
+        &mut self,
+        min_precedence: i32,
+    ) -> Result<Expression, ParserError> {
         let mut left = self.parse_factor()?;
-        println!("{:?}", left);
         loop {
-            let Some(operator) = self.peek_binary_operator() else {
+            if self.tokens.check(&Token::Assign) && min_precedence <= ASSIGNMENT_PRECEDENCE {
+                let span = self.tokens.advance().source_span();
+                // Same precedence for the right side, so `a = b = c` is `a = (b = c)`
+                let right = self.parse_binary_expression(ASSIGNMENT_PRECEDENCE)?;
+                left = self.node(ExpressionKind::Assignment(
+                    Box::new(left),
+                    Box::new(right),
+                    span,
+                ));
+                continue;
+            }
+            let Ok(operator) = BinaryOperator::try_from(self.tokens.peek().token.clone()) else {
                 return Ok(left);
             };
             let precedence = operator.precedence();
-            println!(
-                "{:?} with {} vs min {}",
-                operator, precedence, min_precedence
-            );
             if min_precedence > precedence {
                 return Ok(left);
             }
-            self.advance();
+            let span = self.tokens.advance().source_span();
             let right = self.parse_binary_expression(precedence + 1)?;
-            left = Expression::Binary(Box::new(left), operator, Box::new(right))
+            left = self.node(ExpressionKind::Binary(
+                Box::new(left),
+                operator,
+                Box::new(right),
+                span,
+            ))
         }
     }
 
-    pub fn parse_factor(&mut self) -> Result<Expression, String> {
-        let Some(token) = self.peek() else {
-            return Err("".into());
-        };
-        let t = token.token.clone();
-        println!("Parsing factor: {:?}", &t);
-
-        if let Some(operator) = UnaryOperator::from_token(&t) {
-            self.advance();
+    pub fn parse_factor(&mut self) -> Result<Expression, ParserError> {
+        let token = self.tokens.peek();
+        let span = token.source_span();
+        // `2147483648` doesn't fit in an `int`, the negated one does, so
+        // `-2147483648` is one constant instead of a negation.
+        if token.token == Token::Hyphen
+            && self.tokens.peek_n(1).token == Token::Constant(INT_MIN_MAGNITUDE)
+        {
+            self.tokens.advance();
+            let constant = self.tokens.advance();
+            let len = constant.offset + constant.len - span.offset();
+            let span = SourceSpan::new(span.offset().into(), len);
+            return Ok(self.node(ExpressionKind::Factor(Factor::Constant(i32::MIN, span))));
+        }
+        if let Some(operator) = UnaryOperator::from_token(&token.token) {
+            self.tokens.advance();
             let inner = self.parse_factor()?;
-            return Ok(Expression::Factor(Factor::Unary(operator, Box::new(inner))));
+            return Ok(self.node(ExpressionKind::Factor(Factor::Unary(
+                operator,
+                Box::new(inner),
+            ))));
         }
-        match t {
-            Token::Constant(c) => {
-                self.advance();
-                return Ok(Expression::Factor(Factor::Constant(c)));
-            }
-            Token::OpenParenthesis => {
-                self.advance();
-                let inner = self
-                    .parse_expression()
-                    .map(|i| Expression::Factor(Factor::ParentedExpression(Box::new(i))));
-                if self.check_token(&Token::CloseParenthesis) {
-                    self.advance();
-                    return inner;
-                } else {
-                    return Err("".into());
-                    // return Err(self.error(self.peek().unwrap().clone(), ParserErrorType::ExpectedChar('})))
-                }
-            }
-            _ => {}
-        };
-        Err("".into())
-    }
-
-    /// Peek to see if the next token is a binary operator
-    fn peek_binary_operator(&self) -> Option<BinaryOperator> {
-        self.peek()
-            .and_then(|file_token| BinaryOperator::try_from(file_token.token.clone()).ok())
-    }
-
-    fn match_token(&mut self, token: &Token) -> bool {
-        if self.check_token(token) {
-            self.advance();
-            true
-        } else {
-            false
+        if let Some(operator) = UpdateOperator::prefix(&token.token) {
+            self.tokens.advance();
+            let operand = self.parse_factor()?;
+            return Ok(self.node(ExpressionKind::Update(operator, Box::new(operand), span)));
         }
-    }
-
-    fn check_token(&self, token: &Token) -> bool {
-        if let Some(current_token) = self.tokens.get(self.pos) {
-            &current_token.token == token
-        } else {
-            false
+        if token.token == Token::Ampersand {
+            self.tokens.advance();
+            let operand = self.parse_factor()?;
+            return Ok(self.node(ExpressionKind::AddressOf(Box::new(operand), span)));
         }
+        let mut expression = self.parse_primary()?;
+        // Postfix operators bind tighter than the prefix ones, `-a++` is `-(a++)`.
+        while let Some(operator) = UpdateOperator::postfix(&self.tokens.peek().token) {
+            let span = self.tokens.advance().source_span();
+            expression = self.node(ExpressionKind::Update(operator, Box::new(expression), span));
+        }
+        Ok(expression)
     }
 
-    fn peek(&self) -> Option<&FileToken> {
-        self.tokens.get(self.pos)
-    }
-    fn previous(&self) -> Option<&FileToken> {
-        self.tokens.get(self.pos - 1)
-    }
-
-    fn advance(&mut self) -> Option<&FileToken> {
-        if self.pos < self.tokens.len() {
-            self.pos += 1;
-            self.tokens.get(self.pos - 1)
-        } else {
-            None
+    /// A constant, a variable or an expression in parentheses.
+    fn parse_primary(&mut self) -> Result<Expression, ParserError> {
+        let token = self.tokens.peek();
+        let span = token.source_span();
+        match token.token {
+            Token::Constant(c) => {
+                let Ok(c) = i32::try_from(c) else {
+                    return Err(self.tokens.error(ParserErrorType::ConstantTooLarge));
+                };
+                self.tokens.advance();
+                Ok(self.node(ExpressionKind::Factor(Factor::Constant(c, span))))
+            }
+            Token::Identifier(name) => {
+                self.tokens.advance();
+                Ok(self.node(ExpressionKind::Factor(Factor::Variable(
+                    Identifier(name),
+                    span,
+                ))))
+            }
+            Token::OpenParenthesis => {
+                self.tokens.advance();
+                let inner = self.parse_expression()?;
+                self.tokens
+                    .expect(&Token::CloseParenthesis, ParserErrorType::ExpectedChar(')'))?;
+                Ok(self.node(ExpressionKind::Factor(Factor::ParentedExpression(
+                    Box::new(inner),
+                ))))
+            }
+            _ => Err(self.tokens.error(ParserErrorType::ExpectedExpression)),
         }
     }
+}
 
-    fn last(&self) -> &FileToken {
-        self.tokens.last().expect("msg")
-    }
+/// Whether `token` is a keyword a type can start with.
+fn starts_type(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::IntKeyword
+            | Token::VoidKeyWord
+            | Token::LongKeyword
+            | Token::SignedKeyword
+            | Token::UnsignedKeyword
+    )
 }
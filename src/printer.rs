@@ -0,0 +1,163 @@
+//! Turns the AST back into C source.
+
+use crate::ast::{
+    AstNode, BlockItem, Declaration, Expression, ExpressionKind, Factor, FunctionDecl, Identifier,
+    Statement, VarType,
+};
+
+const INDENT: &str = "    ";
+
+pub fn to_c_source(node: &AstNode) -> String {
+    let mut out = String::new();
+    print_node(&mut out, node, 0);
+    out
+}
+
+fn print_node(out: &mut String, node: &AstNode, depth: usize) {
+    match node {
+        AstNode::Program(nodes) => {
+            for (i, node) in nodes.iter().enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                }
+                print_node(out, node, depth);
+            }
+        }
+        AstNode::FunctionDeclaration(function) => print_function(out, function, depth),
+        AstNode::Statement(statement) => print_statement(out, statement, depth),
+        AstNode::Expression(expression) => out.push_str(&expression_to_c(expression)),
+    }
+}
+
+/// `ty name`, without a space between the name and the `*` of a pointer.
+fn declarator(ty: &VarType, name: Identifier) -> String {
+    let ty = ty.to_string();
+    if ty.ends_with('*') {
+        format!("{}{}", ty, name)
+    } else {
+        format!("{} {}", ty, name)
+    }
+}
+
+fn print_function(out: &mut String, function: &FunctionDecl, depth: usize) {
+    let parameters = if function.parameters.is_empty() {
+        "void".to_string()
+    } else {
+        function
+            .parameters
+            .iter()
+            .map(|parameter| declarator(&parameter.var_type, parameter.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    out.push_str(&INDENT.repeat(depth));
+    out.push_str(&format!(
+        "{} {}({}) ",
+        function.return_type, function.name, parameters
+    ));
+    print_block(out, &function.body, depth);
+    out.push('\n');
+}
+
+/// Prints a statement as a braced block, starting at the current line.
+fn print_block(out: &mut String, statement: &Statement, depth: usize) {
+    out.push_str("{\n");
+    match statement {
+        Statement::Compound(items) => {
+            for item in items {
+                match item {
+                    BlockItem::Declaration(declaration) => {
+                        print_declaration(out, declaration, depth + 1)
+                    }
+                    BlockItem::Statement(statement) => print_statement(out, statement, depth + 1),
+                }
+            }
+        }
+        statement => print_statement(out, statement, depth + 1),
+    }
+    out.push_str(&INDENT.repeat(depth));
+    out.push('}');
+}
+
+fn print_declaration(out: &mut String, declaration: &Declaration, depth: usize) {
+    out.push_str(&INDENT.repeat(depth));
+    out.push_str(&declarator(&declaration.var_type, declaration.name));
+    if let Some(initializer) = &declaration.initializer {
+        out.push_str(&format!(" = {}", expression_to_c(initializer)));
+    }
+    out.push_str(";\n");
+}
+
+fn print_statement(out: &mut String, statement: &Statement, depth: usize) {
+    out.push_str(&INDENT.repeat(depth));
+    match statement {
+        Statement::ReturnStatement(Some(expression), _) => {
+            out.push_str(&format!("return {};\n", expression_to_c(expression)));
+        }
+        Statement::ReturnStatement(None, _) => out.push_str("return;\n"),
+        Statement::Expression(expression) => {
+            out.push_str(&format!("{};\n", expression_to_c(expression)));
+        }
+        Statement::Compound(_) => {
+            print_block(out, statement, depth);
+            out.push('\n');
+        }
+    }
+}
+
+/// A statement on its own line, a block over several lines.
+pub fn statement_to_c(statement: &Statement) -> String {
+    let mut out = String::new();
+    print_statement(&mut out, statement, 0);
+    out
+}
+
+pub fn declaration_to_c(declaration: &Declaration) -> String {
+    let mut out = String::new();
+    print_declaration(&mut out, declaration, 0);
+    out
+}
+
+pub fn expression_to_c(expression: &Expression) -> String {
+    match &expression.kind {
+        ExpressionKind::Binary(left, operator, right, _) => format!(
+            "{} {} {}",
+            expression_to_c(left),
+            operator,
+            expression_to_c(right)
+        ),
+        ExpressionKind::Assignment(target, value, _) => {
+            format!("{} = {}", expression_to_c(target), expression_to_c(value))
+        }
+        ExpressionKind::Update(operator, operand, _) if operator.is_prefix() => {
+            format!("{}{}", operator, expression_to_c(operand))
+        }
+        ExpressionKind::Update(operator, operand, _) => {
+            format!("{}{}", expression_to_c(operand), operator)
+        }
+        ExpressionKind::AddressOf(operand, _) => {
+            let operand = expression_to_c(operand);
+            // `& &x` must not turn into the `&&` token
+            if operand.starts_with('&') {
+                format!("& {}", operand)
+            } else {
+                format!("&{}", operand)
+            }
+        }
+        ExpressionKind::Factor(Factor::Constant(c, _)) => c.to_string(),
+        ExpressionKind::Factor(Factor::Variable(name, _)) => name.to_string(),
+        ExpressionKind::Factor(Factor::Unary(operator, inner)) => {
+            let inner = expression_to_c(inner);
+            let operator = operator.to_string();
+            // `- -x` must not turn into the `--` token
+            if inner.starts_with(&operator) && operator == "-" {
+                format!("{} {}", operator, inner)
+            } else {
+                format!("{}{}", operator, inner)
+            }
+        }
+        ExpressionKind::Factor(Factor::ParentedExpression(inner)) => {
+            format!("({})", expression_to_c(inner))
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Finds statements that can never run because an earlier statement of the
+//! same block always leaves it, and functions that can run off their end.
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::ast::{AstNode, BlockItem, Identifier, Statement, VarType};
+
+#[derive(Error, Debug, Diagnostic, Clone)]
+#[error("Unreachable code")]
+#[diagnostic(code(warning::unreachable_code), severity(Warning))]
+pub struct UnreachableCode {
+    #[label("this statement is never executed")]
+    pub span: SourceSpan,
+}
+
+#[derive(Error, Debug, Diagnostic, Clone)]
+#[error("control reaches the end of non-void function `{0}`")]
+#[diagnostic(code(warning::missing_return), severity(Warning))]
+pub struct MissingReturn(pub Identifier);
+
+/// One warning per block, pointing at its first dead statement.
+pub fn unreachable_code(node: &AstNode) -> Vec<UnreachableCode> {
+    let mut warnings = vec![];
+    match node {
+        AstNode::Program(nodes) => {
+            for node in nodes {
+                warnings.extend(unreachable_code(node));
+            }
+        }
+        AstNode::FunctionDeclaration(function) => {
+            check_statement(&function.body, &mut warnings);
+        }
+        AstNode::Statement(statement) => {
+            check_statement(statement, &mut warnings);
+        }
+        AstNode::Expression(_) => {}
+    }
+    warnings
+}
+
+/// The functions returning a value that can run off their end, except
+/// `entry`, which returns 0 then like `main`.
+pub fn missing_returns(node: &AstNode, entry: Identifier) -> Vec<MissingReturn> {
+    let AstNode::Program(nodes) = node else {
+        return vec![];
+    };
+    nodes
+        .iter()
+        .filter_map(|node| match node {
+            AstNode::FunctionDeclaration(function)
+                if function.return_type != VarType::Void
+                    && function.name != entry
+                    && !check_statement(&function.body, &mut vec![]) =>
+            {
+                Some(MissingReturn(function.name))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns whether control never continues past `statement`.
+fn check_statement(statement: &Statement, warnings: &mut Vec<UnreachableCode>) -> bool {
+    match statement {
+        Statement::ReturnStatement(..) => true,
+        Statement::Expression(_) => false,
+        Statement::Compound(items) => {
+            let mut exits = false;
+            for item in items {
+                if exits {
+                    if let Some(span) = item.span() {
+                        warnings.push(UnreachableCode { span });
+                        break;
+                    }
+                } else if let BlockItem::Statement(statement) = item {
+                    exits = check_statement(statement, warnings);
+                }
+            }
+            exits
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! Running compiled programs, directly or under an emulator like
+//! `qemu-x86_64` or `wine`, so a program built for another platform can
+//! still be run and checked on the machine that built it.
+
+use std::{path::Path, process::Command};
+
+/// Command running `program` under `runner`, a command line whose words are
+/// separated by whitespace, or directly without one or with an empty one.
+pub fn command(runner: Option<&str>, program: &Path) -> Command {
+    let mut words = runner.into_iter().flat_map(str::split_whitespace);
+    match words.next() {
+        Some(emulator) => {
+            let mut command = Command::new(emulator);
+            command.args(words).arg(program);
+            command
+        }
+        None => Command::new(program),
+    }
+}
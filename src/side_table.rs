@@ -0,0 +1,62 @@
+//! Analysis results stored next to the AST, keyed by [`NodeId`].
+//!
+//! The parser output is never changed after parsing: every pass that learns
+//! something about a node records it in a table of its own, so a tool can
+//! hold on to the tree and ask for the type or the symbol of the node under
+//! the cursor.
+
+use std::collections::HashMap;
+
+use crate::{
+    ast::{NodeId, VarType},
+    symbol_index::SymbolId,
+};
+
+#[derive(Debug, Clone)]
+pub struct SideTable<T> {
+    values: HashMap<NodeId, T>,
+}
+
+impl<T> Default for SideTable<T> {
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+        }
+    }
+}
+
+impl<T> SideTable<T> {
+    /// Records `value` for `id`, replacing the one recorded before.
+    pub fn insert(&mut self, id: NodeId, value: T) {
+        self.values.insert(id, value);
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.values.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Entries in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &T)> {
+        self.values.iter().map(|(id, value)| (*id, value))
+    }
+}
+
+/// What the type checker found out about the nodes of a program.
+#[derive(Debug, Clone, Default)]
+pub struct Analysis {
+    /// Type of every expression, before any implicit conversion.
+    pub types: SideTable<VarType>,
+    /// Symbol declared by a function or declaration, or used by a variable.
+    pub resolutions: SideTable<SymbolId>,
+    /// Value of the full expressions that are integer constant expressions:
+    /// initializers, return values and expression statements.
+    pub constants: SideTable<i32>,
+}
@@ -0,0 +1,270 @@
+//! Every file loaded for a compilation, so several files can share one
+//! [`DiagnosticSink`] and positions name the file they are in.
+//!
+//! The stages only see the text of one file and report spans into it. The
+//! sink returned by [`SourceManager::sink_for`] wraps their diagnostics in
+//! [`InFile`], which carries the [`FileId`] and the source, so a host can
+//! tell the files apart and render any diagnostic on its own.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use miette::{Diagnostic, LabeledSpan, NamedSource, Severity, SourceCode};
+
+use crate::{
+    diagnostics::{BoxedDiagnostic, DiagnosticSink},
+    lexer::Lexer,
+};
+
+/// Index of a file in its [`SourceManager`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+
+/// Position of a byte in a loaded file, lines and columns (in chars) start
+/// at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub file: FileId,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub content: Arc<str>,
+    /// Byte offset of the start of every line, for resolving offsets
+    /// without scanning the file.
+    line_starts: Vec<usize>,
+}
+
+#[derive(Default)]
+pub struct SourceManager {
+    files: Vec<SourceFile>,
+    /// See [`SourceManager::with_hyperlinks`].
+    hyperlinks: bool,
+}
+
+impl SourceManager {
+    /// Names the files in diagnostics with a terminal hyperlink to them, so a
+    /// terminal that supports the links opens the file on a click and the
+    /// others show the path.
+    pub fn with_hyperlinks(mut self, hyperlinks: bool) -> Self {
+        self.hyperlinks = hyperlinks;
+        self
+    }
+
+    /// Adds a file with the given content, it gets a new id even when a file
+    /// with the same path was added before.
+    pub fn add(&mut self, path: PathBuf, content: String) -> FileId {
+        let line_starts = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        self.files.push(SourceFile {
+            path,
+            content: content.into(),
+            line_starts,
+        });
+        FileId(self.files.len() as u32 - 1)
+    }
+
+    /// Reads the file at `path`, or returns the id it was loaded with before.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn load(&mut self, path: PathBuf) -> Result<FileId, std::io::Error> {
+        if let Some(id) = self.find(&path) {
+            return Ok(id);
+        }
+        let content = std::fs::read_to_string(&path)?;
+        Ok(self.add(path, content))
+    }
+
+    /// Id of the file added with `path`, the first one if there are several.
+    pub fn find(&self, path: &Path) -> Option<FileId> {
+        self.files
+            .iter()
+            .position(|file| file.path == path)
+            .map(|i| FileId(i as u32))
+    }
+
+    /// # Panics
+    ///
+    /// When `id` comes from another manager and is out of range.
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0 as usize]
+    }
+
+    pub fn path(&self, id: FileId) -> &Path {
+        &self.file(id).path
+    }
+
+    pub fn content(&self, id: FileId) -> &str {
+        &self.file(id).content
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| (FileId(i as u32), file))
+    }
+
+    /// Lexer over a copy of the content of `id`.
+    pub fn lexer(&self, id: FileId) -> Lexer {
+        let mut lexer = Lexer::from_content(self.content(id).to_string());
+        lexer.path = self.path(id).to_path_buf();
+        lexer
+    }
+
+    /// Location of the byte at `offset` in `id`, offsets past the end resolve
+    /// to the end of the file.
+    pub fn resolve(&self, id: FileId, offset: usize) -> Location {
+        let file = self.file(id);
+        let mut offset = offset.min(file.content.len());
+        while !file.content.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = file.line_starts.partition_point(|&start| start <= offset);
+        let line_start = file.line_starts[line - 1];
+        Location {
+            file: id,
+            line,
+            column: file.content[line_start..offset].chars().count() + 1,
+        }
+    }
+
+    /// The file as miette source code, sharing the content.
+    pub fn named_source(&self, id: FileId) -> NamedSource<Arc<str>> {
+        let file = self.file(id);
+        let name = file.path.to_string_lossy();
+        let name = if self.hyperlinks {
+            hyperlink(&file.path, &name)
+        } else {
+            name.into_owned()
+        };
+        NamedSource::new(name, file.content.clone())
+    }
+
+    /// Sink reporting into `sink` with every diagnostic wrapped in [`InFile`]
+    /// for the file `id`.
+    pub fn sink_for<'a>(&self, id: FileId, sink: &'a mut dyn DiagnosticSink) -> FileSink<'a> {
+        FileSink {
+            file: id,
+            source: self.named_source(id),
+            sink,
+        }
+    }
+}
+
+/// `text` as an OSC 8 terminal hyperlink to the `file://` URL of `path`.
+fn hyperlink(path: &Path, text: &str) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut url = String::from("file://");
+    if !path.starts_with('/') {
+        // A Windows path with its drive letter, `file:///C:/...`.
+        url.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+/// Sink for the diagnostics of one file, see [`SourceManager::sink_for`].
+pub struct FileSink<'a> {
+    file: FileId,
+    source: NamedSource<Arc<str>>,
+    sink: &'a mut dyn DiagnosticSink,
+}
+
+impl DiagnosticSink for FileSink<'_> {
+    fn report(&mut self, diagnostic: BoxedDiagnostic) {
+        self.sink.report(Box::new(InFile {
+            file: self.file,
+            source: self.source.clone(),
+            diagnostic,
+        }));
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.sink.count(severity)
+    }
+}
+
+/// A diagnostic of a stage together with the file its spans point into.
+pub struct InFile {
+    pub file: FileId,
+    source: NamedSource<Arc<str>>,
+    pub diagnostic: BoxedDiagnostic,
+}
+
+impl InFile {
+    /// File of a diagnostic reported through a [`FileSink`].
+    pub fn file_of(diagnostic: &(dyn Diagnostic + 'static)) -> Option<FileId> {
+        (diagnostic as &dyn std::error::Error)
+            .downcast_ref::<InFile>()
+            .map(|in_file| in_file.file)
+    }
+}
+
+impl fmt::Debug for InFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InFile")
+            .field("file", &self.file)
+            .field("diagnostic", &self.diagnostic)
+            .finish()
+    }
+}
+
+impl fmt::Display for InFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.diagnostic.fmt(f)
+    }
+}
+
+impl std::error::Error for InFile {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.diagnostic.source()
+    }
+}
+
+impl Diagnostic for InFile {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.diagnostic.code()
+    }
+
+    fn severity(&self) -> Option<Severity> {
+        self.diagnostic.severity()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.diagnostic.help()
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.diagnostic.url()
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(&self.source)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        self.diagnostic.labels()
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        self.diagnostic.related()
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        self.diagnostic.diagnostic_source()
+    }
+}
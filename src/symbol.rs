@@ -0,0 +1,107 @@
+//! Interned strings for identifiers.
+//!
+//! Names are stored once in a process wide [`SymbolTable`], every later stage
+//! only copies and compares the `u32` handle.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt,
+    sync::{Mutex, OnceLock},
+};
+
+/// Handle to an interned string. The default symbol is the empty string.
+#[derive(Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub struct Symbol(u32);
+
+#[derive(Debug)]
+pub struct SymbolTable {
+    strings: Vec<&'static str>,
+    lookup: HashMap<&'static str, Symbol>,
+}
+
+impl SymbolTable {
+    fn new() -> Self {
+        let mut table = Self {
+            strings: vec![],
+            lookup: HashMap::new(),
+        };
+        table.intern("");
+        table
+    }
+
+    /// Returns the symbol of `text`, storing it on first use.
+    pub fn intern(&mut self, text: &str) -> Symbol {
+        if let Some(symbol) = self.lookup.get(text) {
+            return *symbol;
+        }
+        // Symbols live for the whole compilation, so the text is never freed.
+        let text: &'static str = Box::leak(text.to_owned().into_boxed_str());
+        let symbol = Symbol(self.strings.len() as u32);
+        self.strings.push(text);
+        self.lookup.insert(text, symbol);
+        symbol
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &'static str {
+        self.strings[symbol.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// The table shared by all stages.
+    pub fn global() -> &'static Mutex<SymbolTable> {
+        static TABLE: OnceLock<Mutex<SymbolTable>> = OnceLock::new();
+        TABLE.get_or_init(|| Mutex::new(SymbolTable::new()))
+    }
+}
+
+impl Symbol {
+    pub fn intern(text: &str) -> Self {
+        SymbolTable::global().lock().unwrap().intern(text)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        SymbolTable::global().lock().unwrap().resolve(self)
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(text: &str) -> Self {
+        Symbol::intern(text)
+    }
+}
+
+/// Ordered by text so sorted output does not depend on interning order.
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            return Ordering::Equal;
+        }
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
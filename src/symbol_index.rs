@@ -0,0 +1,93 @@
+//! Declarations found by the type checker and the places they are used.
+//!
+//! Tools look symbols up by source position here instead of walking the AST
+//! themselves: go to definition takes [`SymbolInfo::declaration`], a rename
+//! rewrites [`SymbolInfo::spans`] and [`SymbolIndex::unused`] lists what is
+//! never read.
+
+use miette::SourceSpan;
+
+use crate::ast::{Identifier, VarType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Parameter,
+    Variable,
+}
+
+#[derive(Debug, Clone)]
+pub struct SymbolInfo {
+    pub name: Identifier,
+    pub kind: SymbolKind,
+    /// Type of the variable, or the return type of a function.
+    pub ty: VarType,
+    /// Span of the name in the declaration.
+    pub declaration: SourceSpan,
+    /// Spans of every later use of the name.
+    pub references: Vec<SourceSpan>,
+}
+
+impl SymbolInfo {
+    /// The declaration followed by all references.
+    pub fn spans(&self) -> impl Iterator<Item = SourceSpan> + '_ {
+        std::iter::once(self.declaration).chain(self.references.iter().copied())
+    }
+}
+
+/// Index into [`SymbolIndex::symbols`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SymbolId(pub usize);
+
+#[derive(Debug, Clone, Default)]
+pub struct SymbolIndex {
+    symbols: Vec<SymbolInfo>,
+}
+
+impl SymbolIndex {
+    pub fn declare(
+        &mut self,
+        name: Identifier,
+        kind: SymbolKind,
+        ty: VarType,
+        declaration: SourceSpan,
+    ) -> SymbolId {
+        self.symbols.push(SymbolInfo {
+            name,
+            kind,
+            ty,
+            declaration,
+            references: vec![],
+        });
+        SymbolId(self.symbols.len() - 1)
+    }
+
+    pub fn add_reference(&mut self, id: SymbolId, span: SourceSpan) {
+        self.symbols[id.0].references.push(span);
+    }
+
+    pub fn get(&self, id: SymbolId) -> &SymbolInfo {
+        &self.symbols[id.0]
+    }
+
+    /// All symbols in declaration order.
+    pub fn symbols(&self) -> &[SymbolInfo] {
+        &self.symbols
+    }
+
+    /// Symbol whose declaration or one of its references covers byte `offset`.
+    pub fn at(&self, offset: usize) -> Option<&SymbolInfo> {
+        self.symbols.iter().find(|symbol| {
+            symbol
+                .spans()
+                .any(|span| (span.offset()..span.offset() + span.len()).contains(&offset))
+        })
+    }
+
+    /// Variables and parameters that are declared but never referenced.
+    pub fn unused(&self) -> impl Iterator<Item = &SymbolInfo> {
+        self.symbols
+            .iter()
+            .filter(|symbol| symbol.kind != SymbolKind::Function && symbol.references.is_empty())
+    }
+}
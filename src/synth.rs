@@ -0,0 +1,91 @@
+//! Deterministic generator of C programs in the subset ulang accepts.
+//!
+//! Used by the benchmarks to get inputs of any size; the same seed always
+//! gives the same program so numbers stay comparable between runs.
+
+/// Statements put into every generated function.
+const STATEMENTS_PER_FUNCTION: usize = 20;
+
+const BINARY_OPERATORS: [&str; 13] = [
+    "+", "-", "*", "/", "%", "&&", "||", "==", "!=", "<", "<=", ">", ">=",
+];
+const UNARY_OPERATORS: [&str; 3] = ["-", "~", "!"];
+
+pub struct Generator {
+    state: u64,
+    /// Nesting limit of generated expressions.
+    pub max_depth: usize,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        // Spread the seed bits so that close seeds give unrelated programs.
+        let mut state = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Self {
+            // xorshift gets stuck on zero
+            state: (state ^ (state >> 31)).max(1),
+            max_depth: 4,
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Random expression; divisors are always non-zero constants.
+    pub fn expression(&mut self, depth: usize) -> String {
+        if depth >= self.max_depth || (depth > 0 && self.below(3) == 0) {
+            return self.below(1000).to_string();
+        }
+        match self.below(4) {
+            0 => {
+                let operator = UNARY_OPERATORS[self.below(UNARY_OPERATORS.len())];
+                // `- -1` and not `--1`, which would lex as a decrement
+                format!("{} {}", operator, self.expression(depth + 1))
+            }
+            1 => format!("({})", self.expression(depth + 1)),
+            _ => {
+                let operator = BINARY_OPERATORS[self.below(BINARY_OPERATORS.len())];
+                let left = self.expression(depth + 1);
+                let right = if operator == "/" || operator == "%" {
+                    (self.below(9) + 1).to_string()
+                } else {
+                    self.expression(depth + 1)
+                };
+                format!("{} {} {}", left, operator, right)
+            }
+        }
+    }
+
+    /// Function returning `int` with `statements` return statements, one per line.
+    pub fn function(&mut self, name: &str, statements: usize) -> String {
+        let mut out = format!("int {}(void) {{\n", name);
+        for _ in 0..statements {
+            out += &format!("    return {};\n", self.expression(0));
+        }
+        out += "}\n";
+        out
+    }
+
+    /// Program of about `lines` lines split into many small functions.
+    pub fn program(&mut self, lines: usize) -> String {
+        let functions = lines.div_ceil(STATEMENTS_PER_FUNCTION + 3).max(1);
+        let mut out = String::new();
+        for i in 0..functions {
+            if i > 0 {
+                out.push('\n');
+            }
+            out += &self.function(&format!("f{}", i), STATEMENTS_PER_FUNCTION);
+        }
+        out
+    }
+}
@@ -0,0 +1,69 @@
+//! Which functions of a program call which, over the TACKY of each.
+//!
+//! TACKY has no call instruction yet, so the graph has no edges and every
+//! function only reaches itself. Dropping the functions `main` doesn't
+//! reach waits for them, see `ToDo.md`.
+
+use crate::{ast::Identifier, typed_ast::TypedProgram};
+
+use super::Tacky;
+
+#[derive(Debug, Clone)]
+pub struct CallGraph {
+    pub functions: Vec<FunctionNode>,
+    /// Caller and callee, as indices into `functions`.
+    pub calls: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionNode {
+    pub name: Identifier,
+    /// Length of the TACKY before the optimization passes, or why the
+    /// function can't be lowered.
+    pub instructions: Result<usize, String>,
+}
+
+impl CallGraph {
+    /// Lowers every function of `program` on its own.
+    pub fn new(program: &TypedProgram) -> Self {
+        let functions = program
+            .functions
+            .iter()
+            .map(|function| FunctionNode {
+                name: function.name,
+                instructions: Tacky::new(program)
+                    .with_entry(function.name)
+                    .parse()
+                    .map(|tacky| tacky.0.instruction.len()),
+            })
+            .collect();
+        Self {
+            functions,
+            calls: vec![],
+        }
+    }
+
+    pub fn index_of(&self, name: Identifier) -> Option<usize> {
+        self.functions.iter().position(|f| f.name == name)
+    }
+
+    /// Marks the functions `root` calls, directly or through others, and
+    /// `root` itself.
+    pub fn reachable(&self, root: usize) -> Vec<bool> {
+        let mut visited = vec![false; self.functions.len()];
+        let mut stack = vec![root];
+        while let Some(i) = stack.pop() {
+            if visited[i] {
+                continue;
+            }
+            visited[i] = true;
+            stack.extend(
+                self.calls
+                    .iter()
+                    .filter(|(caller, _)| *caller == i)
+                    .map(|(_, callee)| *callee),
+            );
+        }
+        visited
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use crate::ast::Identifier;
+
+use super::Instruction;
+
+/// Straight-line run of instructions with a single entry and a single exit.
+#[derive(Debug, Clone, Default)]
+pub struct BasicBlock {
+    pub instructions: Vec<Instruction>,
+    pub successors: Vec<usize>,
+    pub predecessors: Vec<usize>,
+}
+
+/// Control-flow graph over a flat list of TACKY instructions.
+///
+/// Blocks keep the order of the original instruction list, so flattening
+/// the graph back preserves fallthrough edges. The first block is the entry.
+#[derive(Debug, Clone, Default)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+}
+
+impl ControlFlowGraph {
+    pub fn from_instructions(instructions: &[Instruction]) -> Self {
+        let mut blocks = vec![];
+        let mut current = BasicBlock::default();
+        for instruction in instructions {
+            match instruction {
+                Instruction::Label(_) => {
+                    if !current.instructions.is_empty() {
+                        blocks.push(std::mem::take(&mut current));
+                    }
+                    current.instructions.push(instruction.clone());
+                }
+                Instruction::Jump(_)
+                | Instruction::JumpIfZero(_, _)
+                | Instruction::JumpIfNotZero(_, _)
+                | Instruction::Return(_) => {
+                    current.instructions.push(instruction.clone());
+                    blocks.push(std::mem::take(&mut current));
+                }
+                _ => current.instructions.push(instruction.clone()),
+            }
+        }
+        if !current.instructions.is_empty() {
+            blocks.push(current);
+        }
+
+        let mut cfg = ControlFlowGraph { blocks };
+        cfg.connect();
+        cfg
+    }
+
+    /// Recomputes successor and predecessor edges from the block contents.
+    pub fn connect(&mut self) {
+        let labels: HashMap<Identifier, usize> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, block)| match block.instructions.first() {
+                Some(Instruction::Label(id)) => Some((*id, i)),
+                _ => None,
+            })
+            .collect();
+        let next = |i: usize| (i + 1 < self.blocks.len()).then_some(i + 1);
+
+        let mut successors = vec![];
+        for (i, block) in self.blocks.iter().enumerate() {
+            let edges: Vec<usize> = match block.instructions.last() {
+                Some(Instruction::Return(_)) => vec![],
+                Some(Instruction::Jump(id)) => labels.get(id).copied().into_iter().collect(),
+                Some(Instruction::JumpIfZero(_, id)) | Some(Instruction::JumpIfNotZero(_, id)) => {
+                    let mut edges: Vec<usize> = next(i).into_iter().collect();
+                    if let Some(target) = labels.get(id) {
+                        if !edges.contains(target) {
+                            edges.push(*target);
+                        }
+                    }
+                    edges
+                }
+                _ => next(i).into_iter().collect(),
+            };
+            successors.push(edges);
+        }
+
+        for block in self.blocks.iter_mut() {
+            block.predecessors.clear();
+        }
+        for (i, edges) in successors.iter().enumerate() {
+            for target in edges {
+                self.blocks[*target].predecessors.push(i);
+            }
+        }
+        for (block, edges) in self.blocks.iter_mut().zip(successors) {
+            block.successors = edges;
+        }
+    }
+
+    /// Marks every block reachable from the entry block.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut stack = vec![];
+        if !self.blocks.is_empty() {
+            stack.push(0);
+        }
+        while let Some(i) = stack.pop() {
+            if visited[i] {
+                continue;
+            }
+            visited[i] = true;
+            stack.extend(self.blocks[i].successors.iter().copied());
+        }
+        visited
+    }
+
+    /// Drops blocks that can't be reached from the entry block.
+    pub fn remove_unreachable(&mut self) -> bool {
+        let reachable = self.reachable();
+        if reachable.iter().all(|r| *r) {
+            return false;
+        }
+        let mut flags = reachable.into_iter();
+        self.blocks.retain(|_| flags.next().unwrap_or(false));
+        self.connect();
+        true
+    }
+
+    pub fn into_instructions(self) -> Vec<Instruction> {
+        self.blocks
+            .into_iter()
+            .flat_map(|block| block.instructions)
+            .collect()
+    }
+}
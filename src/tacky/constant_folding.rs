@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use crate::ast::{Identifier, UnaryOperator};
+
+use super::{Instruction, TackyBinaryOperator, TackyProgram, Value};
+
+impl TackyProgram {
+    /// Evaluates unary and binary instructions whose operands are known constants
+    /// and replaces them with a `Copy` of the result.
+    ///
+    /// Constants assigned to temporaries are propagated until the next label,
+    /// so chains like `5 + 2 * 3` collapse into a single value.
+    ///
+    /// A division by zero or a result that doesn't fit stays as it is: it may
+    /// never run, and when it does its behavior is the one of the target.
+    pub fn fold_constants(&mut self) {
+        let mut known: HashMap<Identifier, i32> = HashMap::new();
+
+        for instruction in self.0.instruction.iter_mut() {
+            if let Instruction::Label(_) = instruction {
+                // Another path can reach this point with different values.
+                known.clear();
+                continue;
+            }
+            for value in instruction.sources_mut() {
+                if let Value::Var(id) = value {
+                    if let Some(c) = known.get(id) {
+                        *value = Value::Constant(*c);
+                    }
+                }
+            }
+
+            let folded = match instruction {
+                Instruction::Unary {
+                    operator,
+                    src: Value::Constant(c),
+                    dest,
+                } => Some((fold_unary(operator, *c), dest.clone())),
+                Instruction::Binary {
+                    operator,
+                    src1: Value::Constant(c1),
+                    src2: Value::Constant(c2),
+                    dest,
+                } => fold_binary(operator, *c1, *c2).map(|result| (result, dest.clone())),
+                _ => None,
+            };
+            if let Some((result, dest)) = folded {
+                *instruction = Instruction::Copy {
+                    src: Value::Constant(result),
+                    dest,
+                };
+            }
+
+            match instruction {
+                Instruction::Copy {
+                    src: Value::Constant(c),
+                    dest: Value::Var(id),
+                } => {
+                    known.insert(*id, *c);
+                }
+                Instruction::Unary {
+                    dest: Value::Var(id),
+                    ..
+                }
+                | Instruction::Binary {
+                    dest: Value::Var(id),
+                    ..
+                }
+                | Instruction::Copy {
+                    dest: Value::Var(id),
+                    ..
+                } => {
+                    known.remove(id);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+pub(crate) fn fold_unary(operator: &UnaryOperator, value: i32) -> i32 {
+    match operator {
+        UnaryOperator::Complement => !value,
+        UnaryOperator::Negate => value.wrapping_neg(),
+        UnaryOperator::Not => (value == 0) as i32,
+    }
+}
+
+/// Returns `None` when the result is not representable, dividing by zero
+/// included, and must be left to runtime.
+pub(crate) fn fold_binary(operator: &TackyBinaryOperator, left: i32, right: i32) -> Option<i32> {
+    match operator {
+        TackyBinaryOperator::Add => left.checked_add(right),
+        TackyBinaryOperator::Substract => left.checked_sub(right),
+        TackyBinaryOperator::Multiply => left.checked_mul(right),
+        TackyBinaryOperator::Divide => left.checked_div(right),
+        TackyBinaryOperator::Remainder => left.checked_rem(right),
+        TackyBinaryOperator::ShiftLeft => (0..32)
+            .contains(&right)
+            .then(|| left.checked_mul(1 << right))
+            .flatten(),
+        TackyBinaryOperator::Equal => Some((left == right) as i32),
+        TackyBinaryOperator::NotEqual => Some((left != right) as i32),
+        TackyBinaryOperator::LessThan => Some((left < right) as i32),
+        TackyBinaryOperator::LessOrEqual => Some((left <= right) as i32),
+        TackyBinaryOperator::GreaterThan => Some((left > right) as i32),
+        TackyBinaryOperator::GreaterOrEqual => Some((left >= right) as i32),
+    }
+}
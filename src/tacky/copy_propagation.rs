@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+use crate::ast::Identifier;
+
+use super::{Instruction, TackyProgram, Value};
+
+impl TackyProgram {
+    /// Replaces reads of variables that were just copied from another value
+    /// with that value. Works within straight-line code, forgetting every
+    /// copy at labels where other paths join.
+    pub fn propagate_copies(&mut self) {
+        let mut copies: HashMap<Identifier, Value> = HashMap::new();
+
+        for instruction in self.0.instruction.iter_mut() {
+            if let Instruction::Label(_) = instruction {
+                copies.clear();
+                continue;
+            }
+            for value in instruction.sources_mut() {
+                if let Value::Var(id) = value {
+                    if let Some(src) = copies.get(id) {
+                        *value = src.clone();
+                    }
+                }
+            }
+
+            if let Some(Value::Var(dest)) = instruction.destination() {
+                let dest = *dest;
+                copies.retain(|id, src| {
+                    id != &dest && !matches!(src, Value::Var(src_id) if src_id == &dest)
+                });
+                if let Instruction::Copy { src, .. } = instruction {
+                    if !matches!(src, Value::Var(src_id) if src_id == &dest) {
+                        copies.insert(dest, src.clone());
+                    }
+                }
+            }
+        }
+    }
+}
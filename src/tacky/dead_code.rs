@@ -0,0 +1,104 @@
+use std::collections::HashSet;
+
+use crate::ast::Identifier;
+
+use super::{cfg::ControlFlowGraph, Instruction, TackyProgram, Value};
+
+impl TackyProgram {
+    /// Removes unreachable blocks and instructions whose results are never read.
+    pub fn eliminate_dead_code(&mut self) {
+        let mut cfg = ControlFlowGraph::from_instructions(&self.0.instruction);
+        loop {
+            let removed_blocks = cfg.remove_unreachable();
+            let removed_stores = remove_dead_stores(&mut cfg);
+            if !removed_blocks && !removed_stores {
+                break;
+            }
+        }
+        self.0.instruction = cfg.into_instructions();
+    }
+}
+
+fn variable(value: &Value) -> Option<&Identifier> {
+    match value {
+        Value::Var(id) => Some(id),
+        Value::Constant(_) => None,
+    }
+}
+
+/// Walks a block backwards from `live`, returning the variables live at its start.
+fn live_before(instructions: &[Instruction], mut live: HashSet<Identifier>) -> HashSet<Identifier> {
+    for instruction in instructions.iter().rev() {
+        if let Some(id) = instruction.destination().and_then(variable) {
+            live.remove(id);
+        }
+        live.extend(
+            instruction
+                .sources()
+                .into_iter()
+                .filter_map(variable)
+                .cloned(),
+        );
+    }
+    live
+}
+
+/// Variables live at the start of every block.
+pub(super) fn live_in(cfg: &ControlFlowGraph) -> Vec<HashSet<Identifier>> {
+    let mut live_in: Vec<HashSet<Identifier>> = vec![HashSet::new(); cfg.blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in (0..cfg.blocks.len()).rev() {
+            let live = live_before(&cfg.blocks[i].instructions, live_out(cfg, &live_in, i));
+            if live != live_in[i] {
+                live_in[i] = live;
+                changed = true;
+            }
+        }
+    }
+    live_in
+}
+
+fn live_out(
+    cfg: &ControlFlowGraph,
+    live_in: &[HashSet<Identifier>],
+    i: usize,
+) -> HashSet<Identifier> {
+    cfg.blocks[i]
+        .successors
+        .iter()
+        .flat_map(|s| live_in[*s].iter().cloned())
+        .collect()
+}
+
+fn remove_dead_stores(cfg: &mut ControlFlowGraph) -> bool {
+    let live_in = live_in(cfg);
+    let live_outs: Vec<_> = (0..cfg.blocks.len())
+        .map(|i| live_out(cfg, &live_in, i))
+        .collect();
+    let mut removed = false;
+    for (block, mut live) in cfg.blocks.iter_mut().zip(live_outs) {
+        let mut kept = Vec::with_capacity(block.instructions.len());
+        for instruction in block.instructions.drain(..).rev() {
+            if let Some(id) = instruction.destination().and_then(variable) {
+                if !live.contains(id) {
+                    removed = true;
+                    continue;
+                }
+                live.remove(id);
+            }
+            live.extend(
+                instruction
+                    .sources()
+                    .into_iter()
+                    .filter_map(variable)
+                    .cloned(),
+            );
+            kept.push(instruction);
+        }
+        kept.reverse();
+        block.instructions = kept;
+    }
+    removed
+}
@@ -0,0 +1,194 @@
+//! Reference interpreter for TACKY.
+//!
+//! Runs a program without going through the assembly backend, which makes it
+//! usable on any host and gives the semantics the generated code is compared against.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::ast::{Identifier, UnaryOperator};
+
+use super::{Instruction, TackyBinaryOperator, TackyProgram, Value};
+
+/// Upper bound of executed instructions before the program is considered stuck.
+pub const DEFAULT_STEP_LIMIT: usize = 10_000_000;
+
+#[derive(Debug, Clone, Error)]
+pub enum InterpretError {
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Signed integer overflow")]
+    Overflow,
+    #[error("{0} is read before it is assigned")]
+    UninitializedVariable(Identifier),
+    #[error("Jump to undefined label {0}")]
+    UndefinedLabel(Identifier),
+    #[error("Control reached the end of {0} without a return")]
+    MissingReturn(Identifier),
+    #[error("Step limit of {0} instructions exceeded")]
+    StepLimitExceeded(usize),
+}
+
+pub struct Interpreter<'a> {
+    program: &'a TackyProgram,
+    labels: HashMap<&'a Identifier, usize>,
+    variables: HashMap<&'a Identifier, i32>,
+    pub step_limit: usize,
+    /// Values of the parameters, in order. The ones left out are read
+    /// before they are assigned.
+    pub arguments: Vec<i32>,
+    /// Report signed overflow of additions, subtractions and multiplications
+    /// instead of wrapping, like code built with overflow checks.
+    pub trap_overflow: bool,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(program: &'a TackyProgram) -> Self {
+        let labels = program
+            .0
+            .instruction
+            .iter()
+            .enumerate()
+            .filter_map(|(i, instruction)| match instruction {
+                Instruction::Label(id) => Some((id, i)),
+                _ => None,
+            })
+            .collect();
+        Self {
+            program,
+            labels,
+            variables: HashMap::new(),
+            step_limit: DEFAULT_STEP_LIMIT,
+            arguments: vec![],
+            trap_overflow: false,
+        }
+    }
+
+    /// Executes the function and returns the value it returned, 0 when it
+    /// returns nothing.
+    pub fn run(&mut self) -> Result<i32, InterpretError> {
+        let function = &self.program.0;
+        for (parameter, value) in function.parameters.iter().zip(&self.arguments) {
+            self.variables.insert(parameter, *value);
+        }
+        let instructions = &function.instruction;
+        let mut pc = 0;
+        let mut steps = 0;
+        while let Some(instruction) = instructions.get(pc) {
+            steps += 1;
+            if steps > self.step_limit {
+                return Err(InterpretError::StepLimitExceeded(self.step_limit));
+            }
+            pc += 1;
+            match instruction {
+                Instruction::Return(Some(value)) => return self.value(value),
+                Instruction::Return(None) => return Ok(0),
+                Instruction::Unary {
+                    operator,
+                    src,
+                    dest,
+                } => {
+                    let src = self.value(src)?;
+                    let result = match operator {
+                        UnaryOperator::Complement => !src,
+                        UnaryOperator::Negate => src.wrapping_neg(),
+                        UnaryOperator::Not => (src == 0) as i32,
+                    };
+                    self.assign(dest, result);
+                }
+                Instruction::Binary {
+                    operator,
+                    src1,
+                    src2,
+                    dest,
+                } => {
+                    let (left, right) = (self.value(src1)?, self.value(src2)?);
+                    if self.trap_overflow && overflows(operator, left, right) {
+                        return Err(InterpretError::Overflow);
+                    }
+                    let result = binary(operator, left, right)?;
+                    self.assign(dest, result);
+                }
+                Instruction::Copy { src, dest } => {
+                    let src = self.value(src)?;
+                    self.assign(dest, src);
+                }
+                Instruction::Jump(id) => pc = self.label(id)?,
+                Instruction::JumpIfZero(value, id) => {
+                    if self.value(value)? == 0 {
+                        pc = self.label(id)?;
+                    }
+                }
+                Instruction::JumpIfNotZero(value, id) => {
+                    if self.value(value)? != 0 {
+                        pc = self.label(id)?;
+                    }
+                }
+                Instruction::Label(_) | Instruction::Location { .. } => {}
+            }
+        }
+        Err(InterpretError::MissingReturn(self.program.0.identifier))
+    }
+
+    fn value(&self, value: &Value) -> Result<i32, InterpretError> {
+        match value {
+            Value::Constant(c) => Ok(*c),
+            Value::Var(id) => self
+                .variables
+                .get(id)
+                .copied()
+                .ok_or(InterpretError::UninitializedVariable(*id)),
+        }
+    }
+
+    fn assign(&mut self, dest: &'a Value, result: i32) {
+        if let Value::Var(id) = dest {
+            self.variables.insert(id, result);
+        }
+    }
+
+    fn label(&self, id: &Identifier) -> Result<usize, InterpretError> {
+        self.labels
+            .get(id)
+            .copied()
+            .ok_or(InterpretError::UndefinedLabel(*id))
+    }
+}
+
+fn overflows(operator: &TackyBinaryOperator, left: i32, right: i32) -> bool {
+    match operator {
+        TackyBinaryOperator::Add => left.checked_add(right).is_none(),
+        TackyBinaryOperator::Substract => left.checked_sub(right).is_none(),
+        TackyBinaryOperator::Multiply => left.checked_mul(right).is_none(),
+        _ => false,
+    }
+}
+
+fn binary(operator: &TackyBinaryOperator, left: i32, right: i32) -> Result<i32, InterpretError> {
+    let result = match operator {
+        TackyBinaryOperator::Add => left.wrapping_add(right),
+        TackyBinaryOperator::Substract => left.wrapping_sub(right),
+        TackyBinaryOperator::Multiply => left.wrapping_mul(right),
+        TackyBinaryOperator::Divide | TackyBinaryOperator::Remainder if right == 0 => {
+            return Err(InterpretError::DivisionByZero);
+        }
+        TackyBinaryOperator::Divide => left.wrapping_div(right),
+        TackyBinaryOperator::Remainder => left.wrapping_rem(right),
+        // Like `sall`, which only looks at the low 5 bits of the count.
+        TackyBinaryOperator::ShiftLeft => left.wrapping_shl(right as u32),
+        TackyBinaryOperator::Equal => (left == right) as i32,
+        TackyBinaryOperator::NotEqual => (left != right) as i32,
+        TackyBinaryOperator::LessThan => (left < right) as i32,
+        TackyBinaryOperator::LessOrEqual => (left <= right) as i32,
+        TackyBinaryOperator::GreaterThan => (left > right) as i32,
+        TackyBinaryOperator::GreaterOrEqual => (left >= right) as i32,
+    };
+    Ok(result)
+}
+
+impl TackyProgram {
+    pub fn interpret(&self) -> Result<i32, InterpretError> {
+        Interpreter::new(self).run()
+    }
+}
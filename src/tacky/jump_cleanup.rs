@@ -0,0 +1,66 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::Identifier;
+
+use super::{Instruction, TackyProgram};
+
+impl TackyProgram {
+    /// Merges adjacent labels, drops jumps to the instruction that follows
+    /// anyway and removes labels nothing jumps to.
+    pub fn cleanup_jumps(&mut self) {
+        while self.cleanup_jumps_once() {}
+    }
+
+    fn cleanup_jumps_once(&mut self) -> bool {
+        let instructions = &mut self.0.instruction;
+        let before = instructions.len();
+
+        // Every label in a run of adjacent labels is replaced by the first one.
+        let mut canonical: HashMap<Identifier, Identifier> = HashMap::new();
+        let mut run_start: Option<Identifier> = None;
+        for instruction in instructions.iter() {
+            if let Instruction::Label(id) = instruction {
+                let first = run_start.get_or_insert(*id);
+                canonical.insert(*id, *first);
+            } else {
+                run_start = None;
+            }
+        }
+        for instruction in instructions.iter_mut() {
+            if let Some(target) = instruction.jump_target_mut() {
+                if let Some(first) = canonical.get(target) {
+                    *target = *first;
+                }
+            }
+        }
+
+        // A jump is redundant when only labels separate it from its target.
+        let mut redundant = vec![false; instructions.len()];
+        for (i, instruction) in instructions.iter().enumerate() {
+            let Some(target) = instruction.jump_target() else {
+                continue;
+            };
+            redundant[i] = instructions[i + 1..]
+                .iter()
+                .map_while(|next| match next {
+                    Instruction::Label(id) => Some(id),
+                    _ => None,
+                })
+                .any(|id| id == target);
+        }
+        let mut flags = redundant.into_iter();
+        instructions.retain(|_| !flags.next().unwrap_or(false));
+
+        let referenced: HashSet<Identifier> = instructions
+            .iter()
+            .filter_map(Instruction::jump_target)
+            .cloned()
+            .collect();
+        instructions.retain(|instruction| match instruction {
+            Instruction::Label(id) => referenced.contains(id),
+            _ => true,
+        });
+
+        instructions.len() != before
+    }
+}
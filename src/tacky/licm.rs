@@ -0,0 +1,285 @@
+//! Loop-invariant code motion.
+//!
+//! A loop is found from a back edge, a jump to a block that dominates the
+//! jumping one, its header. An instruction of the loop moves to a preheader,
+//! a new block run once right before the header, when
+//!
+//! - its operands are constants, variables the loop doesn't write, or
+//!   variables written by instructions that move as well,
+//! - it is the only write of its destination in the loop and dominates
+//!   every read of it in the loop,
+//! - it runs before every exit of the loop anyway, or its destination is
+//!   dead once the loop is left,
+//! - it can't trap, so divisions stay where they are.
+//!
+//! ```text
+//!   main.label.0:                   main.preheader.0:
+//!     ifz i goto main.label.1         main.tmp.1 = n * 4
+//!     main.tmp.1 = n * 4      =>    main.label.0:
+//!     sum = sum + main.tmp.1          ifz i goto main.label.1
+//!     ...                             sum = sum + main.tmp.1
+//! ```
+
+use std::collections::HashSet;
+
+use crate::{ast::Identifier, names::NameGenerator};
+
+use super::{
+    cfg::{BasicBlock, ControlFlowGraph},
+    dead_code::live_in,
+    ssa::immediate_dominators,
+    Instruction, TackyBinaryOperator, TackyProgram, Value,
+};
+
+impl TackyProgram {
+    /// Hoists the invariant computations out of every loop, inner loops
+    /// first so what they hoist can move on out of the loops around them.
+    /// Unreachable blocks are dropped on the way.
+    pub fn hoist_loop_invariants(&mut self) {
+        let function = &mut self.0;
+        let mut names = NameGenerator::new(function.identifier);
+        let mut cfg = ControlFlowGraph::from_instructions(&function.instruction);
+        cfg.remove_unreachable();
+        while hoist_once(&mut cfg, &mut names) {}
+        function.instruction = cfg.into_instructions();
+    }
+}
+
+struct Loop {
+    header: usize,
+    /// Blocks of the loop, the header included, in ascending order.
+    blocks: Vec<usize>,
+}
+
+/// Hoists out of the innermost loop with anything to hoist, returns whether
+/// there was one.
+fn hoist_once(cfg: &mut ControlFlowGraph, names: &mut NameGenerator) -> bool {
+    let idom = immediate_dominators(cfg);
+    let mut loops = natural_loops(cfg, &idom);
+    loops.sort_by_key(|l| l.blocks.len());
+    let live_in = live_in(cfg);
+    for l in &loops {
+        let Some(Instruction::Label(header_label)) = cfg.blocks[l.header].instructions.first()
+        else {
+            continue;
+        };
+        let header_label = *header_label;
+        let hoisted = invariants(cfg, &idom, &live_in, l);
+        if hoisted.is_empty() {
+            continue;
+        }
+
+        let mut moved = Vec::with_capacity(hoisted.len());
+        for (block, index) in &hoisted {
+            moved.push(cfg.blocks[*block].instructions[*index].clone());
+        }
+        for (i, block) in cfg.blocks.iter_mut().enumerate() {
+            let mut index = 0;
+            block.instructions.retain(|_| {
+                index += 1;
+                !hoisted.contains(&(i, index - 1))
+            });
+        }
+
+        let preheader = fresh_label(cfg, names);
+        for &predecessor in &cfg.blocks[l.header].predecessors.clone() {
+            let block = &mut cfg.blocks[predecessor];
+            if l.blocks.binary_search(&predecessor).is_err() {
+                if let Some(target) = block
+                    .instructions
+                    .last_mut()
+                    .and_then(Instruction::jump_target_mut)
+                {
+                    if *target == header_label {
+                        *target = preheader;
+                    }
+                }
+            } else if predecessor + 1 == l.header && falls_through(block) {
+                // The preheader goes in between.
+                block.instructions.push(Instruction::Jump(header_label));
+            }
+        }
+        let mut instructions = vec![Instruction::Label(preheader)];
+        instructions.extend(moved);
+        cfg.blocks.insert(
+            l.header,
+            BasicBlock {
+                instructions,
+                ..BasicBlock::default()
+            },
+        );
+        *cfg = ControlFlowGraph::from_instructions(&std::mem::take(cfg).into_instructions());
+        return true;
+    }
+    false
+}
+
+/// Loops of the back edges, the ones sharing a header merged into one.
+fn natural_loops(cfg: &ControlFlowGraph, idom: &[usize]) -> Vec<Loop> {
+    let mut loops: Vec<Loop> = vec![];
+    for (latch, block) in cfg.blocks.iter().enumerate() {
+        for &header in &block.successors {
+            if !dominates(idom, header, latch) {
+                continue;
+            }
+            let mut body = vec![header];
+            let mut stack = vec![latch];
+            while let Some(i) = stack.pop() {
+                if !body.contains(&i) {
+                    body.push(i);
+                    stack.extend(cfg.blocks[i].predecessors.iter().copied());
+                }
+            }
+            match loops.iter_mut().find(|l| l.header == header) {
+                Some(l) => l.blocks.extend(body),
+                None => loops.push(Loop {
+                    header,
+                    blocks: body,
+                }),
+            }
+        }
+    }
+    for l in loops.iter_mut() {
+        l.blocks.sort_unstable();
+        l.blocks.dedup();
+    }
+    loops
+}
+
+/// Positions of the instructions of `l` that can move to its preheader,
+/// each after the ones writing its operands.
+fn invariants(
+    cfg: &ControlFlowGraph,
+    idom: &[usize],
+    live_in: &[HashSet<Identifier>],
+    l: &Loop,
+) -> Vec<(usize, usize)> {
+    let in_loop = |block: &usize| l.blocks.binary_search(block).is_ok();
+    let instructions = || {
+        l.blocks.iter().flat_map(|&block| {
+            cfg.blocks[block]
+                .instructions
+                .iter()
+                .enumerate()
+                .map(move |(index, instruction)| (block, index, instruction))
+        })
+    };
+    let written = |id: &Identifier| {
+        instructions()
+            .filter(|(_, _, instruction)| writes(instruction, id))
+            .count()
+    };
+    let exits: Vec<usize> = l
+        .blocks
+        .iter()
+        .flat_map(|&block| cfg.blocks[block].successors.iter().copied())
+        .filter(|block| !in_loop(block))
+        .collect();
+    let exiting: Vec<usize> = l
+        .blocks
+        .iter()
+        .copied()
+        .filter(|&block| cfg.blocks[block].successors.iter().any(|s| !in_loop(s)))
+        .collect();
+
+    let mut hoisted: Vec<(usize, usize)> = vec![];
+    let mut invariant: HashSet<Identifier> = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (block, index, instruction) in instructions() {
+            let Some(Value::Var(dest)) = instruction.destination() else {
+                continue;
+            };
+            if invariant.contains(dest) || !can_move(instruction) || written(dest) != 1 {
+                continue;
+            }
+            let operands_invariant = instruction
+                .sources()
+                .into_iter()
+                .all(|source| match source {
+                    Value::Constant(_) => true,
+                    Value::Var(id) => invariant.contains(id) || written(id) == 0,
+                });
+            let dominates_reads = instructions()
+                .filter(|(_, _, other)| reads(other, dest))
+                .all(|(b, i, _)| {
+                    if b == block {
+                        i > index
+                    } else {
+                        dominates(idom, block, b)
+                    }
+                });
+            let runs_anyway = exiting.iter().all(|&exit| dominates(idom, block, exit));
+            let dead_after = exits.iter().all(|&exit| !live_in[exit].contains(dest));
+            if operands_invariant && dominates_reads && (runs_anyway || dead_after) {
+                invariant.insert(*dest);
+                hoisted.push((block, index));
+                changed = true;
+            }
+        }
+    }
+    hoisted
+}
+
+fn writes(instruction: &Instruction, id: &Identifier) -> bool {
+    matches!(instruction.destination(), Some(Value::Var(dest)) if dest == id)
+}
+
+fn reads(instruction: &Instruction, id: &Identifier) -> bool {
+    instruction
+        .sources()
+        .into_iter()
+        .any(|source| matches!(source, Value::Var(source) if source == id))
+}
+
+fn can_move(instruction: &Instruction) -> bool {
+    match instruction {
+        Instruction::Binary { operator, .. } => !matches!(
+            operator,
+            TackyBinaryOperator::Divide | TackyBinaryOperator::Remainder
+        ),
+        Instruction::Unary { .. } | Instruction::Copy { .. } => true,
+        _ => false,
+    }
+}
+
+/// Whether `a` dominates `b`, every block dominating itself.
+fn dominates(idom: &[usize], a: usize, mut b: usize) -> bool {
+    loop {
+        if a == b {
+            return true;
+        }
+        if idom[b] == b {
+            return false;
+        }
+        b = idom[b];
+    }
+}
+
+fn falls_through(block: &BasicBlock) -> bool {
+    !matches!(
+        block.instructions.last(),
+        Some(Instruction::Jump(_)) | Some(Instruction::Return(_))
+    )
+}
+
+/// A `preheader` label the function doesn't use yet, the pass may run more
+/// than once.
+fn fresh_label(cfg: &ControlFlowGraph, names: &mut NameGenerator) -> Identifier {
+    let used: HashSet<Identifier> = cfg
+        .blocks
+        .iter()
+        .flat_map(|block| &block.instructions)
+        .filter_map(|instruction| match instruction {
+            Instruction::Label(id) => Some(*id),
+            _ => None,
+        })
+        .collect();
+    loop {
+        let label = names.fresh("preheader");
+        if !used.contains(&label) {
+            return label;
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use miette::{MietteDiagnostic, SourceSpan};
+
+use crate::{
+    ast::{BinaryOperator, Identifier, UnaryOperator, VarType},
+    diagnostics::DiagnosticSink,
+    lexer::line_column,
+    names::NameGenerator,
+    typed_ast::{TypedExpression, TypedExpressionKind, TypedProgram, TypedStatement},
+};
+
+pub mod call_graph;
+pub mod cfg;
+pub(crate) mod constant_folding;
+mod copy_propagation;
+mod dead_code;
+pub mod interp;
+mod jump_cleanup;
+mod licm;
+pub mod ssa;
+mod strength_reduction;
+mod text;
+mod verify;
+
+pub use verify::VerifyError;
+
+#[derive(Debug, Clone)]
+pub struct TackyProgram(pub FunctionDefinition);
+
+#[derive(Debug, Clone, Default)]
+pub struct FunctionDefinition {
+    pub identifier: Identifier,
+    /// Variables holding the arguments on entry, in order.
+    pub parameters: Vec<Identifier>,
+    pub instruction: Vec<Instruction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TackyBinaryOperator {
+    Add,
+    Substract,
+    Multiply,
+    Divide,
+    Remainder,
+    /// Made by strength reduction only, the count is a constant below 32.
+    ShiftLeft,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessOrEqual,
+    GreaterThan,
+    GreaterOrEqual,
+}
+
+impl TryFrom<&BinaryOperator> for TackyBinaryOperator {
+    type Error = ();
+
+    fn try_from(value: &BinaryOperator) -> Result<Self, Self::Error> {
+        match value {
+            BinaryOperator::And
+            | BinaryOperator::Or
+            | BinaryOperator::BitwiseAnd
+            | BinaryOperator::BitwiseOr => Err(()),
+            BinaryOperator::Add => Ok(TackyBinaryOperator::Add),
+            BinaryOperator::Substract => Ok(TackyBinaryOperator::Substract),
+            BinaryOperator::Multiply => Ok(TackyBinaryOperator::Multiply),
+            BinaryOperator::Divide => Ok(TackyBinaryOperator::Divide),
+            BinaryOperator::Remainder => Ok(TackyBinaryOperator::Remainder),
+            BinaryOperator::Equal => Ok(TackyBinaryOperator::Equal),
+            BinaryOperator::NotEqual => Ok(TackyBinaryOperator::NotEqual),
+            BinaryOperator::LessThan => Ok(TackyBinaryOperator::LessThan),
+            BinaryOperator::LessOrEqual => Ok(TackyBinaryOperator::LessOrEqual),
+            BinaryOperator::GreaterThan => Ok(TackyBinaryOperator::GreaterThan),
+            BinaryOperator::GreaterOrEqual => Ok(TackyBinaryOperator::GreaterOrEqual),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Instruction {
+    /// The value is `None` in a function returning void.
+    Return(Option<Value>),
+    Unary {
+        operator: UnaryOperator,
+        src: Value,
+        dest: Value,
+    },
+    Binary {
+        operator: TackyBinaryOperator,
+        src1: Value,
+        src2: Value,
+        dest: Value,
+    },
+    Copy {
+        src: Value,
+        dest: Value,
+    },
+    Jump(Identifier),
+    JumpIfZero(Value, Identifier),
+    JumpIfNotZero(Value, Identifier),
+    Label(Identifier),
+    /// 1-based source line and column of the instructions that follow, only
+    /// emitted when lowering with [`Tacky::with_source_locations`].
+    Location {
+        line: usize,
+        column: usize,
+    },
+}
+
+impl Instruction {
+    /// Values read by the instruction.
+    pub fn sources(&self) -> Vec<&Value> {
+        match self {
+            Instruction::Return(value) => value.iter().collect(),
+            Instruction::JumpIfZero(value, _)
+            | Instruction::JumpIfNotZero(value, _)
+            | Instruction::Unary { src: value, .. }
+            | Instruction::Copy { src: value, .. } => vec![value],
+            Instruction::Binary { src1, src2, .. } => vec![src1, src2],
+            Instruction::Jump(_) | Instruction::Label(_) | Instruction::Location { .. } => vec![],
+        }
+    }
+
+    /// Mutable access to the values read by the instruction.
+    pub fn sources_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Instruction::Return(value) => value.iter_mut().collect(),
+            Instruction::JumpIfZero(value, _)
+            | Instruction::JumpIfNotZero(value, _)
+            | Instruction::Unary { src: value, .. }
+            | Instruction::Copy { src: value, .. } => vec![value],
+            Instruction::Binary { src1, src2, .. } => vec![src1, src2],
+            Instruction::Jump(_) | Instruction::Label(_) | Instruction::Location { .. } => vec![],
+        }
+    }
+
+    /// Label the instruction may transfer control to.
+    pub fn jump_target(&self) -> Option<&Identifier> {
+        match self {
+            Instruction::Jump(id)
+            | Instruction::JumpIfZero(_, id)
+            | Instruction::JumpIfNotZero(_, id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Value written by the instruction, if any.
+    pub fn destination(&self) -> Option<&Value> {
+        match self {
+            Instruction::Unary { dest, .. }
+            | Instruction::Binary { dest, .. }
+            | Instruction::Copy { dest, .. } => Some(dest),
+            _ => None,
+        }
+    }
+
+    /// Mutable access to the value written by the instruction.
+    pub fn destination_mut(&mut self) -> Option<&mut Value> {
+        match self {
+            Instruction::Unary { dest, .. }
+            | Instruction::Binary { dest, .. }
+            | Instruction::Copy { dest, .. } => Some(dest),
+            _ => None,
+        }
+    }
+
+    /// Label the instruction may transfer control to, for retargeting it.
+    pub fn jump_target_mut(&mut self) -> Option<&mut Identifier> {
+        match self {
+            Instruction::Jump(id)
+            | Instruction::JumpIfZero(_, id)
+            | Instruction::JumpIfNotZero(_, id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Constant(i32),
+    Var(Identifier),
+}
+
+pub struct Tacky<'a> {
+    pub program: &'a TypedProgram,
+    pub result: FunctionDefinition,
+    names: NameGenerator,
+    /// Source text the spans of the typed AST point into, set to emit
+    /// [`Instruction::Location`] markers.
+    source: Option<&'a str>,
+    /// Line and column of the last marker emitted.
+    location: Option<(usize, usize)>,
+    /// Function to lower instead of the first one.
+    entry: Option<Identifier>,
+    /// Whether the function being lowered returns a value.
+    returns_value: bool,
+}
+impl<'a> Tacky<'a> {
+    pub fn new(program: &'a TypedProgram) -> Self {
+        Self {
+            program,
+            names: NameGenerator::new(Identifier::default()),
+            result: FunctionDefinition::default(),
+            source: None,
+            location: None,
+            entry: None,
+            returns_value: false,
+        }
+    }
+
+    /// Lowers the function `entry` instead of the first one. Like `main`
+    /// without an entry, it returns 0 when it runs off its end.
+    pub fn with_entry(mut self, entry: Identifier) -> Self {
+        self.entry = Some(entry);
+        self
+    }
+
+    /// Records the position of every statement and binary operator, so later
+    /// stages can point at them, for example in runtime error messages or in
+    /// a source map of the assembly.
+    pub fn with_source_locations(mut self, source: &'a str) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn parse(&mut self) -> Result<TackyProgram, String> {
+        let function = match self.entry {
+            Some(entry) => self.program.functions.iter().find(|f| f.name == entry),
+            None => self.program.functions.first(),
+        };
+        let Some(function) = function else {
+            return Err(match self.entry {
+                Some(entry) => format!("No entry function `{}`", entry),
+                None => "Expected a function".to_string(),
+            });
+        };
+        self.result = FunctionDefinition {
+            identifier: function.name,
+            parameters: function.parameters.iter().map(|(_, name)| *name).collect(),
+            instruction: vec![],
+        };
+        if !matches!(function.return_type, VarType::Int | VarType::Void) {
+            return Err(unsupported_type(&function.return_type));
+        }
+        self.names = NameGenerator::new(function.name);
+        self.location = None;
+        self.returns_value = function.return_type != VarType::Void;
+
+        for statement in &function.body {
+            self.parse_statement(statement)?;
+        }
+
+        if !matches!(self.result.instruction.last(), Some(Instruction::Return(_))) {
+            // The entry point returns 0 implicitly, other functions get the same
+            // instead of running into whatever follows them.
+            let value = self.returns_value.then_some(Value::Constant(0));
+            self.result.instruction.push(Instruction::Return(value));
+        }
+
+        Ok(TackyProgram(std::mem::take(&mut self.result)))
+    }
+
+    /// Like [`Tacky::parse`], reporting the error into `sink`.
+    pub fn parse_into(&mut self, sink: &mut dyn DiagnosticSink) -> Option<TackyProgram> {
+        self.parse()
+            .map_err(|e| sink.report(Box::new(MietteDiagnostic::new(e))))
+            .ok()
+    }
+
+    /// Emits a marker for `span` when lowering with source locations and the
+    /// previous marker points elsewhere.
+    fn mark_location(&mut self, span: SourceSpan) {
+        let Some(source) = self.source else {
+            return;
+        };
+        let location = line_column(source, span.offset());
+        if self.location == Some(location) {
+            return;
+        }
+        self.location = Some(location);
+        let (line, column) = location;
+        self.result
+            .instruction
+            .push(Instruction::Location { line, column });
+    }
+
+    fn parse_statement(&mut self, statement: &TypedStatement) -> Result<(), String> {
+        match statement {
+            TypedStatement::Return(expression, span) => {
+                self.mark_location(*span);
+                let result = match expression {
+                    Some(expression) => Some(self.parse_node(expression)?),
+                    // The caller of a function returning a value gets a 0
+                    // from a `return;`, like from running off the end.
+                    None => self.returns_value.then_some(Value::Constant(0)),
+                };
+                // Operators in the value moved the location away from the statement.
+                self.mark_location(*span);
+                self.result.instruction.push(Instruction::Return(result));
+            }
+            TypedStatement::Declaration {
+                name,
+                span,
+                initializer,
+            } => {
+                if let Some(initializer) = initializer {
+                    self.mark_location(*span);
+                    let src = self.parse_node(initializer)?;
+                    self.result.instruction.push(Instruction::Copy {
+                        src,
+                        dest: Value::Var(*name),
+                    });
+                }
+            }
+            TypedStatement::Expression(expression, span) => {
+                self.mark_location(*span);
+                self.parse_node(expression)?;
+            }
+            TypedStatement::Compound(statements) => {
+                for statement in statements {
+                    self.parse_statement(statement)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_node(&mut self, expression: &TypedExpression) -> Result<Value, String> {
+        if expression.ty != VarType::Int {
+            return Err(unsupported_type(&expression.ty));
+        }
+        match &expression.kind {
+            TypedExpressionKind::Binary(expr, oper, expr_2, span) => {
+                if matches!(oper, BinaryOperator::And | BinaryOperator::Or) {
+                    return self.parse_logical(expr, oper, expr_2);
+                }
+                let v1 = self.parse_node(expr)?;
+                let v2 = self.parse_node(expr_2)?;
+                let dst = self.names.temporary();
+                let Ok(operator) = oper.try_into() else {
+                    return Err(format!("`{}` can't be lowered yet", oper));
+                };
+                self.mark_location(*span);
+                self.result.instruction.push(Instruction::Binary {
+                    operator,
+                    src1: v1,
+                    src2: v2,
+                    dest: Value::Var(dst),
+                });
+                Ok(Value::Var(dst))
+            }
+            TypedExpressionKind::Constant(c) => Ok(Value::Constant(*c)),
+            TypedExpressionKind::Variable(name) => Ok(Value::Var(*name)),
+            TypedExpressionKind::Assignment(target, value) => {
+                let TypedExpressionKind::Variable(name) = &target.kind else {
+                    return Err("Only variables can be assigned".into());
+                };
+                let src = self.parse_node(value)?;
+                self.result.instruction.push(Instruction::Copy {
+                    src,
+                    dest: Value::Var(*name),
+                });
+                Ok(Value::Var(*name))
+            }
+            TypedExpressionKind::Update(operator, operand, span) => {
+                let TypedExpressionKind::Variable(name) = &operand.kind else {
+                    return Err("Only variables can be incremented".into());
+                };
+                let variable = Value::Var(*name);
+                // A postfix operator evaluates to the value from before.
+                let result = if operator.is_prefix() {
+                    variable.clone()
+                } else {
+                    let old = Value::Var(self.names.temporary());
+                    self.result.instruction.push(Instruction::Copy {
+                        src: variable.clone(),
+                        dest: old.clone(),
+                    });
+                    old
+                };
+                self.mark_location(*span);
+                self.result.instruction.push(Instruction::Binary {
+                    operator: if operator.is_increment() {
+                        TackyBinaryOperator::Add
+                    } else {
+                        TackyBinaryOperator::Substract
+                    },
+                    src1: variable.clone(),
+                    src2: Value::Constant(1),
+                    dest: variable,
+                });
+                Ok(result)
+            }
+            TypedExpressionKind::Unary(operator, expression) => {
+                let src = self.parse_node(expression)?;
+                let dest = self.names.temporary();
+                self.result.instruction.push(Instruction::Unary {
+                    operator: operator.clone(),
+                    src,
+                    dest: Value::Var(dest),
+                });
+                Ok(Value::Var(dest))
+            }
+            // Only int values get here, so there is nothing to convert yet.
+            TypedExpressionKind::Convert(inner) => self.parse_node(inner),
+        }
+    }
+
+    /// `&&` and `||`, which only evaluate `right` when `left` doesn't decide
+    /// the result already.
+    fn parse_logical(
+        &mut self,
+        left: &TypedExpression,
+        operator: &BinaryOperator,
+        right: &TypedExpression,
+    ) -> Result<Value, String> {
+        let and = operator == &BinaryOperator::And;
+        let jump = |value, label| {
+            if and {
+                Instruction::JumpIfZero(value, label)
+            } else {
+                Instruction::JumpIfNotZero(value, label)
+            }
+        };
+        let v1 = self.parse_node(left)?;
+        let assign_label = self.names.label();
+        let end_label = self.names.label();
+        self.result.instruction.push(jump(v1, assign_label));
+        let v2 = self.parse_node(right)?;
+        self.result.instruction.push(jump(v2, assign_label));
+        let dst = Value::Var(self.names.temporary());
+        // The value when no jump was taken, then the one when a jump was.
+        let (first_result, second_result) = if and { (1, 0) } else { (0, 1) };
+        self.result.instruction.extend([
+            Instruction::Copy {
+                src: Value::Constant(first_result),
+                dest: dst.clone(),
+            },
+            Instruction::Jump(end_label),
+            Instruction::Label(assign_label),
+            Instruction::Copy {
+                src: Value::Constant(second_result),
+                dest: dst.clone(),
+            },
+            Instruction::Label(end_label),
+        ]);
+        Ok(dst)
+    }
+}
+
+/// TACKY values are 32 bit and its operators signed, the other integer types
+/// pass the type checker but can't be lowered yet, see `ToDo.md`.
+fn unsupported_type(ty: &VarType) -> String {
+    format!(
+        "Values of type `{}` are not supported by the code generator yet",
+        ty
+    )
+}
@@ -0,0 +1,550 @@
+//! Static single assignment form of TACKY.
+//!
+//! [`TackyProgram::to_ssa`] renames every variable so it is written exactly
+//! once, adding phi nodes where definitions from different paths meet. Phis
+//! are placed at the dominance frontiers of the definitions (Cytron et al.),
+//! but only for variables read in a block other than the one writing them,
+//! and only where the variable is live: a dead phi would read it on paths
+//! that never wrote it.
+//! [`SsaProgram::into_tacky`] turns the phis back into copies at the end of
+//! the predecessors, so the rest of the pipeline and codegen never see them.
+//!
+//! ```text
+//! function main {
+//!     ifz a goto main.label.0
+//!     x.0 = 1
+//!     goto main.label.1
+//!   main.label.0:
+//!     x.1 = 2
+//!   main.label.1:
+//!     x.2 = phi x.0, x.1
+//!     return x.2
+//! }
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{ast::Identifier, names::NameGenerator};
+
+use super::{
+    cfg::{BasicBlock, ControlFlowGraph},
+    dead_code::live_in,
+    text::header,
+    FunctionDefinition, Instruction, TackyProgram, Value, VerifyError,
+};
+
+/// Value of `dest` chosen by the edge control arrived from.
+#[derive(Debug, Clone)]
+pub struct Phi {
+    pub dest: Identifier,
+    /// One value per predecessor, in the order of [`BasicBlock::predecessors`].
+    pub sources: Vec<Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SsaProgram {
+    pub identifier: Identifier,
+    pub parameters: Vec<Identifier>,
+    /// Only blocks reachable from the entry are kept.
+    pub cfg: ControlFlowGraph,
+    /// Phis at the start of each block, indexed like `cfg.blocks`.
+    pub phis: Vec<Vec<Phi>>,
+}
+
+impl TackyProgram {
+    pub fn to_ssa(&self) -> SsaProgram {
+        let mut cfg = ControlFlowGraph::from_instructions(&self.0.instruction);
+        cfg.remove_unreachable();
+        let idom = immediate_dominators(&cfg);
+        let frontiers = dominance_frontiers(&cfg, &idom);
+        let live_in = live_in(&cfg);
+
+        // Variables written in one block and read in another, only those
+        // can ever need a phi.
+        let mut globals = HashSet::new();
+        let mut definitions: HashMap<Identifier, Vec<usize>> = HashMap::new();
+        for (i, block) in cfg.blocks.iter().enumerate() {
+            let mut written = HashSet::new();
+            for instruction in &block.instructions {
+                for source in instruction.sources() {
+                    if let Value::Var(id) = source {
+                        if !written.contains(id) {
+                            globals.insert(*id);
+                        }
+                    }
+                }
+                if let Some(Value::Var(id)) = instruction.destination() {
+                    written.insert(*id);
+                    definitions.entry(*id).or_default().push(i);
+                }
+            }
+        }
+
+        let mut variables: Vec<Identifier> = globals.into_iter().collect();
+        variables.sort();
+        let mut phi_variables: Vec<Vec<Identifier>> = vec![vec![]; cfg.blocks.len()];
+        for variable in variables {
+            let Some(blocks) = definitions.get(&variable) else {
+                continue;
+            };
+            let mut worklist = blocks.clone();
+            while let Some(block) = worklist.pop() {
+                for &frontier in &frontiers[block] {
+                    if live_in[frontier].contains(&variable)
+                        && !phi_variables[frontier].contains(&variable)
+                    {
+                        phi_variables[frontier].push(variable);
+                        worklist.push(frontier);
+                    }
+                }
+            }
+        }
+
+        let mut children = vec![vec![]; cfg.blocks.len()];
+        for (block, dominator) in idom.iter().enumerate().skip(1) {
+            children[*dominator].push(block);
+        }
+        let phis = phi_variables
+            .iter()
+            .zip(&cfg.blocks)
+            .map(|(variables, block)| {
+                variables
+                    .iter()
+                    .map(|variable| Phi {
+                        dest: *variable,
+                        sources: vec![Value::Var(*variable); block.predecessors.len()],
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut renamer = Renamer {
+            cfg,
+            phis,
+            phi_variables,
+            children,
+            versions: HashMap::new(),
+            stacks: HashMap::new(),
+        };
+        if !renamer.cfg.blocks.is_empty() {
+            renamer.rename(0);
+        }
+        SsaProgram {
+            identifier: self.0.identifier,
+            parameters: self.0.parameters.clone(),
+            cfg: renamer.cfg,
+            phis: renamer.phis,
+        }
+    }
+}
+
+/// Walks the dominator tree, giving every definition a new version and every
+/// use the version of the closest dominating definition.
+struct Renamer {
+    cfg: ControlFlowGraph,
+    phis: Vec<Vec<Phi>>,
+    /// Variable each phi was placed for, parallel to `phis`.
+    phi_variables: Vec<Vec<Identifier>>,
+    children: Vec<Vec<usize>>,
+    versions: HashMap<Identifier, usize>,
+    stacks: HashMap<Identifier, Vec<Identifier>>,
+}
+
+impl Renamer {
+    fn define(&mut self, variable: Identifier) -> Identifier {
+        let version = self.versions.entry(variable).or_default();
+        let name = Identifier::from(format!("{}.{}", variable, version).as_str());
+        *version += 1;
+        self.stacks.entry(variable).or_default().push(name);
+        name
+    }
+
+    /// Current version of `variable`, reads without a definition on the way
+    /// keep the original name.
+    fn current(&self, variable: Identifier) -> Identifier {
+        self.stacks
+            .get(&variable)
+            .and_then(|stack| stack.last())
+            .copied()
+            .unwrap_or(variable)
+    }
+
+    fn rename(&mut self, block: usize) {
+        let mut defined = vec![];
+        for i in 0..self.phis[block].len() {
+            let variable = self.phi_variables[block][i];
+            self.phis[block][i].dest = self.define(variable);
+            defined.push(variable);
+        }
+
+        let mut instructions = std::mem::take(&mut self.cfg.blocks[block].instructions);
+        for instruction in instructions.iter_mut() {
+            for source in instruction.sources_mut() {
+                if let Value::Var(id) = source {
+                    *id = self.current(*id);
+                }
+            }
+            if let Some(Value::Var(id)) = instruction.destination_mut() {
+                let variable = *id;
+                *id = self.define(variable);
+                defined.push(variable);
+            }
+        }
+        self.cfg.blocks[block].instructions = instructions;
+
+        for successor in self.cfg.blocks[block].successors.clone() {
+            let edge = self.cfg.blocks[successor]
+                .predecessors
+                .iter()
+                .position(|p| *p == block)
+                .expect("successor lists its predecessor");
+            for i in 0..self.phis[successor].len() {
+                let current = self.current(self.phi_variables[successor][i]);
+                self.phis[successor][i].sources[edge] = Value::Var(current);
+            }
+        }
+
+        for child in self.children[block].clone() {
+            self.rename(child);
+        }
+        for variable in defined {
+            if let Some(stack) = self.stacks.get_mut(&variable) {
+                stack.pop();
+            }
+        }
+    }
+}
+
+/// Blocks in reverse postorder of a depth-first walk from the entry.
+fn reverse_postorder(cfg: &ControlFlowGraph) -> Vec<usize> {
+    let mut order = vec![];
+    if cfg.blocks.is_empty() {
+        return order;
+    }
+    let mut visited = vec![false; cfg.blocks.len()];
+    let mut stack = vec![(0, 0)];
+    visited[0] = true;
+    while let Some((block, next)) = stack.last_mut() {
+        let block = *block;
+        if let Some(&successor) = cfg.blocks[block].successors.get(*next) {
+            *next += 1;
+            if !visited[successor] {
+                visited[successor] = true;
+                stack.push((successor, 0));
+            }
+        } else {
+            order.push(block);
+            stack.pop();
+        }
+    }
+    order.reverse();
+    order
+}
+
+/// Immediate dominator of every block, the entry being its own, computed as
+/// in "A Simple, Fast Dominance Algorithm" by Cooper, Harvey and Kennedy.
+///
+/// Expects every block to be reachable from the entry.
+pub(super) fn immediate_dominators(cfg: &ControlFlowGraph) -> Vec<usize> {
+    let order = reverse_postorder(cfg);
+    let mut position = vec![0; cfg.blocks.len()];
+    for (i, block) in order.iter().enumerate() {
+        position[*block] = i;
+    }
+    let mut idom: Vec<Option<usize>> = vec![None; cfg.blocks.len()];
+    if let Some(entry) = idom.first_mut() {
+        *entry = Some(0);
+    }
+
+    let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            while position[a] > position[b] {
+                a = idom[a].expect("processed blocks have a dominator");
+            }
+            while position[b] > position[a] {
+                b = idom[b].expect("processed blocks have a dominator");
+            }
+        }
+        a
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &block in order.iter().skip(1) {
+            let mut new_idom = None;
+            for &predecessor in &cfg.blocks[block].predecessors {
+                if idom[predecessor].is_none() {
+                    continue;
+                }
+                new_idom = Some(match new_idom {
+                    None => predecessor,
+                    Some(other) => intersect(&idom, predecessor, other),
+                });
+            }
+            if new_idom != idom[block] {
+                idom[block] = new_idom;
+                changed = true;
+            }
+        }
+    }
+    idom.into_iter()
+        .map(|dominator| dominator.expect("every block is reachable"))
+        .collect()
+}
+
+fn dominance_frontiers(cfg: &ControlFlowGraph, idom: &[usize]) -> Vec<Vec<usize>> {
+    let mut frontiers: Vec<Vec<usize>> = vec![vec![]; cfg.blocks.len()];
+    for (block, node) in cfg.blocks.iter().enumerate() {
+        if node.predecessors.len() < 2 {
+            continue;
+        }
+        for &predecessor in &node.predecessors {
+            let mut runner = predecessor;
+            while runner != idom[block] {
+                if !frontiers[runner].contains(&block) {
+                    frontiers[runner].push(block);
+                }
+                runner = idom[runner];
+            }
+        }
+    }
+    frontiers
+}
+
+impl SsaProgram {
+    /// Checks that every variable is written by a single phi or instruction.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        let mut written = HashSet::new();
+        let phi_destinations = self.phis.iter().flatten().map(|phi| &phi.dest);
+        let destinations = self
+            .cfg
+            .blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+            .filter_map(|instruction| match instruction.destination() {
+                Some(Value::Var(id)) => Some(id),
+                _ => None,
+            });
+        for id in phi_destinations.chain(destinations) {
+            if !written.insert(id) {
+                return Err(VerifyError::MultipleDefinitions(*id));
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces every phi with copies on the incoming edges.
+    ///
+    /// Copies go at the end of the predecessor, before its jump. When the
+    /// predecessor ends in a conditional jump, the copies of each edge get a
+    /// block of their own right after it and the condition is inverted to
+    /// skip the copies meant for the jump target, so only the copies of the
+    /// edge actually taken run.
+    pub fn into_tacky(self) -> TackyProgram {
+        let SsaProgram {
+            identifier,
+            parameters,
+            mut cfg,
+            phis,
+        } = self;
+        let mut names = FreshNames::new(identifier, &cfg, &phis);
+
+        let mut edges: HashMap<(usize, usize), Vec<Instruction>> = HashMap::new();
+        for (block, phis) in phis.iter().enumerate() {
+            if phis.is_empty() {
+                continue;
+            }
+            for (edge, predecessor) in cfg.blocks[block].predecessors.iter().enumerate() {
+                let moves = phis
+                    .iter()
+                    .map(|phi| (phi.dest, phi.sources[edge].clone()))
+                    .collect();
+                edges.insert((*predecessor, block), sequentialize(moves, &mut names));
+            }
+        }
+
+        let mut split_blocks: Vec<Vec<Instruction>> = vec![vec![]; cfg.blocks.len()];
+        for (block, split_block) in split_blocks.iter_mut().enumerate() {
+            match cfg.blocks[block].successors[..] {
+                [successor] => {
+                    let Some(copies) = edges.remove(&(block, successor)) else {
+                        continue;
+                    };
+                    let instructions = &mut cfg.blocks[block].instructions;
+                    let at = match instructions.last() {
+                        Some(last) if last.jump_target().is_some() => instructions.len() - 1,
+                        _ => instructions.len(),
+                    };
+                    instructions.splice(at..at, copies);
+                }
+                [next, target] => {
+                    let fallthrough = edges.remove(&(block, next));
+                    let Some(copies) = edges.remove(&(block, target)) else {
+                        *split_block = fallthrough.unwrap_or_default();
+                        continue;
+                    };
+                    let Some(Instruction::Label(target_label)) =
+                        cfg.blocks[target].instructions.first()
+                    else {
+                        unreachable!("jump targets start with their label");
+                    };
+                    let mut split = copies;
+                    split.push(Instruction::Jump(*target_label));
+                    let next_label = match fallthrough {
+                        Some(copies) => {
+                            let label = names.label();
+                            split.push(Instruction::Label(label));
+                            split.extend(copies);
+                            label
+                        }
+                        None => block_label(&mut cfg.blocks[next], &mut names),
+                    };
+                    if let Some(jump) = cfg.blocks[block].instructions.last_mut() {
+                        invert_jump(jump, next_label);
+                    }
+                    *split_block = split;
+                }
+                _ => {}
+            }
+        }
+
+        let instruction = cfg
+            .blocks
+            .into_iter()
+            .zip(split_blocks)
+            .flat_map(|(block, split)| block.instructions.into_iter().chain(split))
+            .collect();
+        TackyProgram(FunctionDefinition {
+            identifier,
+            parameters,
+            instruction,
+        })
+    }
+}
+
+/// Label the block starts with, adding a fresh one when it has none.
+fn block_label(block: &mut BasicBlock, names: &mut FreshNames) -> Identifier {
+    if let Some(Instruction::Label(label)) = block.instructions.first() {
+        return *label;
+    }
+    let label = names.label();
+    block.instructions.insert(0, Instruction::Label(label));
+    label
+}
+
+/// Turns a conditional jump into one taken in exactly the other cases.
+fn invert_jump(jump: &mut Instruction, target: Identifier) {
+    *jump = match jump {
+        Instruction::JumpIfZero(value, _) => Instruction::JumpIfNotZero(value.clone(), target),
+        Instruction::JumpIfNotZero(value, _) => Instruction::JumpIfZero(value.clone(), target),
+        _ => unreachable!("only conditional jumps have two successors"),
+    };
+}
+
+/// Orders the parallel copies of one edge. When a copy reads what another
+/// one writes, every value goes through a temporary first.
+fn sequentialize(moves: Vec<(Identifier, Value)>, names: &mut FreshNames) -> Vec<Instruction> {
+    let reads_destination = moves
+        .iter()
+        .any(|(_, src)| matches!(src, Value::Var(id) if moves.iter().any(|(dest, _)| dest == id)));
+    if !reads_destination {
+        return moves
+            .into_iter()
+            .map(|(dest, src)| Instruction::Copy {
+                src,
+                dest: Value::Var(dest),
+            })
+            .collect();
+    }
+
+    let temporaries: Vec<Identifier> = moves.iter().map(|_| names.temporary()).collect();
+    let load = moves
+        .iter()
+        .zip(&temporaries)
+        .map(|((_, src), tmp)| Instruction::Copy {
+            src: src.clone(),
+            dest: Value::Var(*tmp),
+        });
+    let store = moves
+        .iter()
+        .zip(&temporaries)
+        .map(|((dest, _), tmp)| Instruction::Copy {
+            src: Value::Var(*tmp),
+            dest: Value::Var(*dest),
+        });
+    load.chain(store).collect()
+}
+
+/// Hands out generated names the program does not use yet, lowering already
+/// took the first ones of the [`NameGenerator`] sequence.
+struct FreshNames {
+    generator: NameGenerator,
+    used: HashSet<Identifier>,
+}
+
+impl FreshNames {
+    fn new(identifier: Identifier, cfg: &ControlFlowGraph, phis: &[Vec<Phi>]) -> Self {
+        let mut used: HashSet<Identifier> = phis.iter().flatten().map(|phi| phi.dest).collect();
+        for instruction in cfg.blocks.iter().flat_map(|block| &block.instructions) {
+            if let Instruction::Label(id) = instruction {
+                used.insert(*id);
+            }
+            if let Some(Value::Var(id)) = instruction.destination() {
+                used.insert(*id);
+            }
+            for source in instruction.sources() {
+                if let Value::Var(id) = source {
+                    used.insert(*id);
+                }
+            }
+        }
+        Self {
+            generator: NameGenerator::new(identifier),
+            used,
+        }
+    }
+
+    fn next(&mut self, kind: &'static str) -> Identifier {
+        loop {
+            let name = self.generator.fresh(kind);
+            if self.used.insert(name) {
+                return name;
+            }
+        }
+    }
+
+    fn temporary(&mut self) -> Identifier {
+        self.next("tmp")
+    }
+
+    fn label(&mut self) -> Identifier {
+        self.next("label")
+    }
+}
+
+impl fmt::Display for SsaProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", header(&self.identifier, &self.parameters))?;
+        for (block, phis) in self.cfg.blocks.iter().zip(&self.phis) {
+            let mut instructions = block.instructions.iter().peekable();
+            if let Some(label @ Instruction::Label(_)) = instructions.peek() {
+                writeln!(f, "  {}", label)?;
+                instructions.next();
+            }
+            for phi in phis {
+                let sources: Vec<String> = phi.sources.iter().map(Value::to_string).collect();
+                writeln!(f, "    {} = phi {}", phi.dest, sources.join(", "))?;
+            }
+            for instruction in instructions {
+                match instruction {
+                    Instruction::Label(_) => writeln!(f, "  {}", instruction)?,
+                    _ => writeln!(f, "    {}", instruction)?,
+                }
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
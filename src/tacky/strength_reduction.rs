@@ -0,0 +1,44 @@
+use super::{Instruction, TackyBinaryOperator, TackyProgram, Value};
+
+impl TackyProgram {
+    /// Replaces multiplications by a constant power of two with a left shift,
+    /// `x * 8` becoming `x << 3`.
+    ///
+    /// Both give the same result, the shift just doesn't set the overflow
+    /// flag the checks of `--checked-arithmetic` read, see
+    /// [`crate::options::CompileOptions::passes`].
+    pub fn reduce_strength(&mut self) {
+        for instruction in self.0.instruction.iter_mut() {
+            let Instruction::Binary {
+                operator: operator @ TackyBinaryOperator::Multiply,
+                src1,
+                src2,
+                ..
+            } = instruction
+            else {
+                continue;
+            };
+            let count = if let Some(count) = shift_count(src2) {
+                count
+            } else if let Some(count) = shift_count(src1) {
+                *src1 = src2.clone();
+                count
+            } else {
+                continue;
+            };
+            *operator = TackyBinaryOperator::ShiftLeft;
+            *src2 = Value::Constant(count);
+        }
+    }
+}
+
+/// The `n` of a constant multiplier `2^n`, from 2 up, multiplying by 1 is
+/// left to the other passes.
+fn shift_count(value: &Value) -> Option<i32> {
+    match value {
+        Value::Constant(c) if *c > 1 && (*c as u32).is_power_of_two() => {
+            Some(c.trailing_zeros() as i32)
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,276 @@
+//! Stable textual form of TACKY.
+//!
+//! ```text
+//! function main {
+//!     main.tmp.0 = 2 * 3
+//!     main.tmp.1 = - main.tmp.0
+//!     ifz main.tmp.1 goto main.label.0
+//!     main.tmp.2 = main.tmp.1
+//!   main.label.0:
+//!     return main.tmp.1
+//! }
+//! ```
+//!
+//! Every token is separated by whitespace, so identifiers may contain dots and
+//! negative constants are written without a space (`-5`). Text after `//` is
+//! a comment, like the source positions of [`TackyProgram::to_annotated_string`].
+//! Only the header differs: parameters are listed after the name, as in
+//! `function main(argc, argv) {`.
+
+use std::{fmt, str::FromStr};
+
+use crate::ast::{Identifier, UnaryOperator};
+
+use super::{FunctionDefinition, Instruction, TackyBinaryOperator, TackyProgram, Value};
+
+const BINARY_OPERATORS: [(TackyBinaryOperator, &str); 12] = [
+    (TackyBinaryOperator::Add, "+"),
+    (TackyBinaryOperator::Substract, "-"),
+    (TackyBinaryOperator::Multiply, "*"),
+    (TackyBinaryOperator::Divide, "/"),
+    (TackyBinaryOperator::Remainder, "%"),
+    (TackyBinaryOperator::ShiftLeft, "<<"),
+    (TackyBinaryOperator::Equal, "=="),
+    (TackyBinaryOperator::NotEqual, "!="),
+    (TackyBinaryOperator::LessThan, "<"),
+    (TackyBinaryOperator::LessOrEqual, "<="),
+    (TackyBinaryOperator::GreaterThan, ">"),
+    (TackyBinaryOperator::GreaterOrEqual, ">="),
+];
+
+const UNARY_OPERATORS: [(UnaryOperator, &str); 3] = [
+    (UnaryOperator::Negate, "-"),
+    (UnaryOperator::Complement, "~"),
+    (UnaryOperator::Not, "!"),
+];
+
+impl fmt::Display for TackyBinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, s) = BINARY_OPERATORS
+            .iter()
+            .find(|(o, _)| o == self)
+            .expect("every operator has a symbol");
+        f.write_str(s)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Constant(c) => write!(f, "{}", c),
+            Value::Var(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Return(Some(value)) => write!(f, "return {}", value),
+            Instruction::Return(None) => f.write_str("return"),
+            Instruction::Unary {
+                operator,
+                src,
+                dest,
+            } => {
+                let (_, s) = UNARY_OPERATORS
+                    .iter()
+                    .find(|(o, _)| o == operator)
+                    .expect("every operator has a symbol");
+                write!(f, "{} = {} {}", dest, s, src)
+            }
+            Instruction::Binary {
+                operator,
+                src1,
+                src2,
+                dest,
+            } => write!(f, "{} = {} {} {}", dest, src1, operator, src2),
+            Instruction::Copy { src, dest } => write!(f, "{} = {}", dest, src),
+            Instruction::Jump(id) => write!(f, "goto {}", id),
+            Instruction::JumpIfZero(value, id) => write!(f, "ifz {} goto {}", value, id),
+            Instruction::JumpIfNotZero(value, id) => write!(f, "ifnz {} goto {}", value, id),
+            Instruction::Label(id) => write!(f, "{}:", id),
+            Instruction::Location { line, column } => write!(f, "@ {}:{}", line, column),
+        }
+    }
+}
+
+impl fmt::Display for TackyProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", header(&self.0.identifier, &self.0.parameters))?;
+        for instruction in &self.0.instruction {
+            match instruction {
+                Instruction::Label(_) => writeln!(f, "  {}", instruction)?,
+                _ => writeln!(f, "    {}", instruction)?,
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// Column the position comments of [`TackyProgram::to_annotated_string`]
+/// start at, unless the instruction is longer.
+const ANNOTATION_COLUMN: usize = 36;
+
+impl TackyProgram {
+    /// The text form with the `line:column` of the code every instruction
+    /// but the labels was lowered from in a comment after it, taken from the
+    /// [`Instruction::Location`] markers, which are left out.
+    ///
+    /// ```text
+    /// function main {
+    ///     main.tmp.0 = 2 * 3              // 2:15
+    ///     return main.tmp.0               // 2:5
+    /// }
+    /// ```
+    pub fn to_annotated_string(&self) -> String {
+        let mut result = format!("{}\n", header(&self.0.identifier, &self.0.parameters));
+        let mut location = None;
+        for instruction in &self.0.instruction {
+            let line = match instruction {
+                Instruction::Location { line, column } => {
+                    location = Some((*line, *column));
+                    continue;
+                }
+                Instruction::Label(_) => {
+                    result += &format!("  {}\n", instruction);
+                    continue;
+                }
+                _ => format!("    {}", instruction),
+            };
+            result += &match location {
+                Some((l, c)) => format!("{line:<ANNOTATION_COLUMN$} // {l}:{c}\n"),
+                None => format!("{line}\n"),
+            };
+        }
+        result + "}\n"
+    }
+}
+
+impl FromStr for TackyProgram {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(nr, line)| (nr + 1, line.split("//").next().unwrap_or_default().trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let Some((nr, header)) = lines.next() else {
+            return Err("Expected a function".to_string());
+        };
+        let (identifier, parameters) =
+            parse_header(header).ok_or(format!("line {nr}: expected `function <name> {{`"))?;
+
+        let mut instruction = vec![];
+        let mut closed = false;
+        for (nr, line) in lines {
+            if closed {
+                return Err(format!("line {nr}: unexpected text after the function"));
+            }
+            if line == "}" {
+                closed = true;
+                continue;
+            }
+            instruction.push(parse_instruction(line).map_err(|e| format!("line {nr}: {e}"))?);
+        }
+        if !closed {
+            return Err("Missing closing `}`".to_string());
+        }
+
+        Ok(TackyProgram(FunctionDefinition {
+            identifier,
+            parameters,
+            instruction,
+        }))
+    }
+}
+
+/// First line of a function, `function main {` or with the parameters
+/// `function main(argc, argv) {`.
+pub(crate) fn header(identifier: &Identifier, parameters: &[Identifier]) -> String {
+    if parameters.is_empty() {
+        return format!("function {} {{", identifier);
+    }
+    let parameters = parameters
+        .iter()
+        .map(Identifier::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("function {}({}) {{", identifier, parameters)
+}
+
+fn parse_header(header: &str) -> Option<(Identifier, Vec<Identifier>)> {
+    let signature = header.strip_prefix("function")?.strip_suffix('{')?.trim();
+    let Some((name, parameters)) = signature.split_once('(') else {
+        return (!signature.is_empty() && !signature.contains(char::is_whitespace))
+            .then(|| (Identifier::from(signature), vec![]));
+    };
+    let parameters = parameters
+        .strip_suffix(')')?
+        .split(',')
+        .map(str::trim)
+        .filter(|parameter| !parameter.is_empty())
+        .map(Identifier::from)
+        .collect();
+    Some((Identifier::from(name.trim()), parameters))
+}
+
+fn parse_value(s: &str) -> Value {
+    match s.parse::<i32>() {
+        Ok(c) => Value::Constant(c),
+        Err(_) => Value::Var(Identifier::from(s)),
+    }
+}
+
+fn parse_instruction(line: &str) -> Result<Instruction, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let label = Identifier::from;
+    let instruction = match words[..] {
+        [word] if word.len() > 1 && word.ends_with(':') => {
+            Instruction::Label(label(word.trim_end_matches(':')))
+        }
+        ["return", value] => Instruction::Return(Some(parse_value(value))),
+        ["return"] => Instruction::Return(None),
+        ["@", location] => {
+            let parsed = location
+                .split_once(':')
+                .and_then(|(line, column)| Some((line.parse().ok()?, column.parse().ok()?)));
+            let Some((line, column)) = parsed else {
+                return Err(format!("expected `@ <line>:<column>`, found `{line}`"));
+            };
+            Instruction::Location { line, column }
+        }
+        ["goto", id] => Instruction::Jump(label(id)),
+        ["ifz", value, "goto", id] => Instruction::JumpIfZero(parse_value(value), label(id)),
+        ["ifnz", value, "goto", id] => Instruction::JumpIfNotZero(parse_value(value), label(id)),
+        [dest, "=", src] => Instruction::Copy {
+            src: parse_value(src),
+            dest: parse_value(dest),
+        },
+        [dest, "=", operator, src] => {
+            let Some((operator, _)) = UNARY_OPERATORS.iter().find(|(_, s)| *s == operator) else {
+                return Err(format!("unknown unary operator `{operator}`"));
+            };
+            Instruction::Unary {
+                operator: operator.clone(),
+                src: parse_value(src),
+                dest: parse_value(dest),
+            }
+        }
+        [dest, "=", src1, operator, src2] => {
+            let Some((operator, _)) = BINARY_OPERATORS.iter().find(|(_, s)| *s == operator) else {
+                return Err(format!("unknown binary operator `{operator}`"));
+            };
+            Instruction::Binary {
+                operator: operator.clone(),
+                src1: parse_value(src1),
+                src2: parse_value(src2),
+                dest: parse_value(dest),
+            }
+        }
+        _ => return Err(format!("can't parse instruction `{line}`")),
+    };
+    Ok(instruction)
+}
@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::{ast::Identifier, names};
+
+use super::{cfg::ControlFlowGraph, Instruction, TackyBinaryOperator, TackyProgram, Value};
+
+#[derive(Debug, Clone, Error)]
+pub enum VerifyError {
+    #[error("Jump to undefined label {0}")]
+    UndefinedLabel(Identifier),
+    #[error("Label {0} is defined more than once")]
+    DuplicateLabel(Identifier),
+    /// Only for the names the compiler made: C lets a program read a
+    /// variable it never wrote.
+    #[error("{0} is used before it is defined")]
+    UseBeforeDefinition(Identifier),
+    #[error("Instruction `{0}` writes to a constant")]
+    ConstantDestination(Instruction),
+    #[error("Control falls off the end of the function after `{0}`")]
+    UnterminatedBlock(Instruction),
+    #[error("Function {0} does not end with a return")]
+    MissingReturn(Identifier),
+    #[error("Instruction `{0}` shifts by something else than a constant below 32")]
+    ShiftCount(Instruction),
+    #[error("{0} is written more than once in SSA form")]
+    MultipleDefinitions(Identifier),
+}
+
+impl TackyProgram {
+    /// Checks the structural invariants every pass has to preserve.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        let function = &self.0;
+        let instructions = &function.instruction;
+
+        let mut labels = HashSet::new();
+        for instruction in instructions {
+            if let Instruction::Label(id) = instruction {
+                if !labels.insert(id) {
+                    return Err(VerifyError::DuplicateLabel(*id));
+                }
+            }
+            if let Some(Value::Constant(_)) = instruction.destination() {
+                return Err(VerifyError::ConstantDestination(instruction.clone()));
+            }
+            if let Instruction::Binary {
+                operator: TackyBinaryOperator::ShiftLeft,
+                src2,
+                ..
+            } = instruction
+            {
+                if !matches!(src2, Value::Constant(0..=31)) {
+                    return Err(VerifyError::ShiftCount(instruction.clone()));
+                }
+            }
+        }
+        for instruction in instructions {
+            if let Some(target) = instruction.jump_target() {
+                if !labels.contains(target) {
+                    return Err(VerifyError::UndefinedLabel(*target));
+                }
+            }
+        }
+
+        match instructions.last() {
+            Some(Instruction::Return(_)) => {}
+            Some(Instruction::Jump(_)) | None => {
+                return Err(VerifyError::MissingReturn(function.identifier));
+            }
+            Some(last) => return Err(VerifyError::UnterminatedBlock(last.clone())),
+        }
+
+        check_definitions(
+            &ControlFlowGraph::from_instructions(instructions),
+            &function.parameters,
+        )
+    }
+}
+
+/// Forward "defined on every path" analysis; unvisited blocks start as `None`.
+/// The parameters are defined on entry. Only reads of generated names are
+/// checked, see [`names::is_generated`].
+fn check_definitions(cfg: &ControlFlowGraph, parameters: &[Identifier]) -> Result<(), VerifyError> {
+    let defs = |instructions: &[Instruction], mut defined: HashSet<Identifier>| {
+        for instruction in instructions {
+            if let Some(Value::Var(id)) = instruction.destination() {
+                defined.insert(*id);
+            }
+        }
+        defined
+    };
+
+    let mut defined_out: Vec<Option<HashSet<Identifier>>> = vec![None; cfg.blocks.len()];
+    let defined_in = |defined_out: &Vec<Option<HashSet<Identifier>>>, i: usize| {
+        if i == 0 {
+            return Some(parameters.iter().copied().collect());
+        }
+        cfg.blocks[i]
+            .predecessors
+            .iter()
+            .filter_map(|p| defined_out[*p].as_ref())
+            .fold(None, |acc: Option<HashSet<Identifier>>, set| match acc {
+                None => Some(set.clone()),
+                Some(acc) => Some(acc.intersection(set).cloned().collect()),
+            })
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in 0..cfg.blocks.len() {
+            let Some(input) = defined_in(&defined_out, i) else {
+                continue;
+            };
+            let output = Some(defs(&cfg.blocks[i].instructions, input));
+            if output != defined_out[i] {
+                defined_out[i] = output;
+                changed = true;
+            }
+        }
+    }
+
+    for (i, block) in cfg.blocks.iter().enumerate() {
+        let Some(mut defined) = defined_in(&defined_out, i) else {
+            continue;
+        };
+        for instruction in &block.instructions {
+            for source in instruction.sources() {
+                if let Value::Var(id) = source {
+                    if !defined.contains(id) && names::is_generated(*id) {
+                        return Err(VerifyError::UseBeforeDefinition(*id));
+                    }
+                }
+            }
+            if let Some(Value::Var(id)) = instruction.destination() {
+                defined.insert(*id);
+            }
+        }
+    }
+    Ok(())
+}
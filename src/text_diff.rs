@@ -0,0 +1,106 @@
+//! Line differences between two texts, printed as a unified diff like
+//! `diff -u` does, to compare what the compiler generates with different
+//! options. The lines are lined up by [`lcs`].
+
+use crate::lcs::{lcs, Edit};
+
+/// A line of the diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    /// In both texts.
+    Same(&'a str),
+    /// Only in the old text.
+    Removed(&'a str),
+    /// Only in the new text.
+    Added(&'a str),
+}
+
+impl DiffLine<'_> {
+    fn prefix(&self) -> char {
+        match self {
+            DiffLine::Same(_) => ' ',
+            DiffLine::Removed(_) => '-',
+            DiffLine::Added(_) => '+',
+        }
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            DiffLine::Same(text) | DiffLine::Removed(text) | DiffLine::Added(text) => text,
+        }
+    }
+}
+
+/// Every line of `old` and `new` in order, the removed ones of a change
+/// before the added ones.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    lcs(&old, &new)
+        .into_iter()
+        .map(|edit| match edit {
+            Edit::Same(i, _) => DiffLine::Same(old[i]),
+            Edit::Removed(i) => DiffLine::Removed(old[i]),
+            Edit::Added(j) => DiffLine::Added(new[j]),
+        })
+        .collect()
+}
+
+/// The unified diff from `old`, named `old_name` in the header, to `new`,
+/// with `context` unchanged lines around the changes. Empty when the texts
+/// have the same lines.
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str, context: usize) -> String {
+    let lines = diff_lines(old, new);
+    let changed: Vec<usize> = (0..lines.len())
+        .filter(|&index| !matches!(lines[index], DiffLine::Same(_)))
+        .collect();
+    if changed.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    // Hunks as ranges of `lines`, merged when their context would overlap.
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for &index in &changed {
+        let start = index.saturating_sub(context);
+        let end = (index + 1 + context).min(lines.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    for (start, end) in hunks {
+        // Lines of each text before the hunk.
+        let before = |in_text: fn(&DiffLine) -> bool| {
+            lines[..start].iter().filter(|line| in_text(line)).count()
+        };
+        let count = |in_text: fn(&DiffLine) -> bool| {
+            lines[start..end]
+                .iter()
+                .filter(|line| in_text(line))
+                .count()
+        };
+        let in_old = |line: &DiffLine| !matches!(line, DiffLine::Added(_));
+        let in_new = |line: &DiffLine| !matches!(line, DiffLine::Removed(_));
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(before(in_old), count(in_old)),
+            range(before(in_new), count(in_new)),
+        ));
+        for line in &lines[start..end] {
+            out.push(line.prefix());
+            out.push_str(line.text());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// A range of a hunk header: the first line, counted from 1, and the number
+/// of lines when it isn't 1. An empty range starts at the line before it.
+fn range(before: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", before),
+        1 => format!("{}", before + 1),
+        _ => format!("{},{}", before + 1, count),
+    }
+}
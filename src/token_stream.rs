@@ -0,0 +1,114 @@
+//! Cursor over the tokens of one file, for the parser.
+//!
+//! Reading past the last token gives an empty [`Token::EndOfFile`] token at
+//! the end of the source instead of `None`, so errors always have a span.
+//! A [`Checkpoint`] lets the parser try one reading of the tokens and go back
+//! when it doesn't fit, like a parenthesized type against a parenthesized
+//! expression.
+
+use crate::{
+    lexer::{FileToken, Token},
+    parser::{ParserError, ParserErrorType},
+};
+
+/// Position to go back to with [`TokenStream::rollback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint(usize);
+
+#[derive(Debug, Clone)]
+pub struct TokenStream<'a> {
+    tokens: &'a [FileToken],
+    pos: usize,
+    end_of_file: FileToken,
+}
+
+impl<'a> TokenStream<'a> {
+    /// `source_len` is the length of the file, where the end of file token
+    /// points.
+    pub fn new(tokens: &'a [FileToken], source_len: usize) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            end_of_file: FileToken {
+                token: Token::EndOfFile,
+                offset: source_len,
+                len: 0,
+                leading_trivia: Vec::new(),
+                trailing_trivia: Vec::new(),
+                spelling: None,
+            },
+        }
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    /// Token at the cursor.
+    pub fn peek(&self) -> &FileToken {
+        self.peek_n(0)
+    }
+
+    /// Token `k` places after the cursor, `peek_n(0)` is [`TokenStream::peek`].
+    pub fn peek_n(&self, k: usize) -> &FileToken {
+        self.tokens.get(self.pos + k).unwrap_or(&self.end_of_file)
+    }
+
+    /// Last token consumed, `None` before the first one.
+    pub fn previous(&self) -> Option<&'a FileToken> {
+        self.pos.checked_sub(1).and_then(|pos| self.tokens.get(pos))
+    }
+
+    /// Moves past the token at the cursor and returns it, the end of file
+    /// token stays where it is.
+    pub fn advance(&mut self) -> &FileToken {
+        if self.is_at_end() {
+            return &self.end_of_file;
+        }
+        self.pos += 1;
+        &self.tokens[self.pos - 1]
+    }
+
+    pub fn check(&self, token: &Token) -> bool {
+        &self.peek().token == token
+    }
+
+    /// Consumes the token at the cursor when it is `token`.
+    pub fn eat(&mut self, token: &Token) -> bool {
+        let found = self.check(token);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    /// Consumes `token`, or fails with `error` at the token found instead.
+    pub fn expect(
+        &mut self,
+        token: &Token,
+        error: ParserErrorType,
+    ) -> Result<&FileToken, ParserError> {
+        if !self.check(token) {
+            return Err(self.error(error));
+        }
+        Ok(self.advance())
+    }
+
+    /// `error` pointing at the token at the cursor.
+    pub fn error(&self, error: ParserErrorType) -> ParserError {
+        let token = self.peek();
+        ParserError {
+            error,
+            span: token.source_span(),
+            token: token.token.clone(),
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.pos)
+    }
+
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.pos = checkpoint.0;
+    }
+}
@@ -0,0 +1,188 @@
+//! Running the external tools a program is built with: the C compiler that
+//! preprocesses, assembles and links, and `ar`.
+//!
+//! The functions named after a step only build the [`Command`], so it can be
+//! shown before it runs. [`run`] captures everything the tool prints and
+//! returns it as a [`ToolOutput`], or a [`ToolchainError`] with the stderr of
+//! the tool when it couldn't be started or failed.
+//!
+//! The defaults of a build environment are read from the usual variables, see
+//! [`ToolchainEnv`].
+
+use std::{io, path::Path, process::Command};
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::assembly::TargetPlatform;
+
+/// What a finished tool printed, and how it exited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolOutput {
+    /// Exit status, `None` when a signal ended the tool.
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Error, Debug, Diagnostic)]
+pub enum ToolchainError {
+    #[error("Failed to run `{command}`")]
+    #[diagnostic(
+        code(error::toolchain),
+        help("check that the tool is installed, or pick another C compiler with --cc")
+    )]
+    Spawn {
+        command: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("`{command}` failed with {}", describe_status(output.status))]
+    #[diagnostic(code(error::toolchain))]
+    Failed {
+        command: String,
+        output: ToolOutput,
+        /// The stderr of the tool, when it printed anything.
+        #[help]
+        stderr: Option<String>,
+    },
+    #[error("Invalid ${name}: {message}")]
+    #[diagnostic(code(error::toolchain))]
+    InvalidVariable { name: &'static str, message: String },
+}
+
+impl ToolchainError {
+    /// Output of the tool, when it ran.
+    pub fn output(&self) -> Option<&ToolOutput> {
+        match self {
+            ToolchainError::Spawn { .. } | ToolchainError::InvalidVariable { .. } => None,
+            ToolchainError::Failed { output, .. } => Some(output),
+        }
+    }
+}
+
+fn describe_status(status: Option<i32>) -> String {
+    match status {
+        Some(status) => format!("exit status {}", status),
+        None => "a signal".to_string(),
+    }
+}
+
+/// Defaults taken from the environment the way `make` and most build systems
+/// pass them on: the C compiler in `CC`, the target in `ULANG_TARGET` and the
+/// extra flags for linking in `LDFLAGS`. An empty variable counts as unset,
+/// the options given on the command line win over these.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolchainEnv {
+    pub cc: Option<String>,
+    pub target: Option<TargetPlatform>,
+    /// Added after the arguments of the link command.
+    pub ldflags: Vec<String>,
+}
+
+impl ToolchainEnv {
+    /// The defaults of the environment of this process.
+    pub fn from_env() -> Result<Self, ToolchainError> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// The defaults from the variables `var` looks up.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, ToolchainError> {
+        let set = |name| var(name).filter(|value| !value.trim().is_empty());
+        let target = set("ULANG_TARGET")
+            .map(|target| target.trim().parse())
+            .transpose()
+            .map_err(|message| ToolchainError::InvalidVariable {
+                name: "ULANG_TARGET",
+                message,
+            })?;
+        Ok(Self {
+            cc: set("CC"),
+            target,
+            ldflags: set("LDFLAGS")
+                .map(|flags| flags.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Command running `tool`, a program followed by its own arguments and
+/// separated by whitespace, like `gcc -m64`.
+pub fn command(tool: &str) -> Command {
+    let mut words = tool.split_whitespace();
+    let mut command = Command::new(words.next().unwrap_or_default());
+    command.args(words);
+    command
+}
+
+/// Command line of `command` as it would be typed in a shell, arguments with
+/// whitespace or quotes in single quotes.
+pub fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|word| {
+            let word = word.to_string_lossy();
+            if !word.is_empty() && !word.contains(|c: char| c.is_whitespace() || "'\"".contains(c))
+            {
+                return word.into_owned();
+            }
+            format!("'{}'", word.replace('\'', r"'\''"))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Runs `command` to its end with the output captured, failing unless it
+/// exits with 0.
+pub fn run(command: &mut Command) -> Result<ToolOutput, ToolchainError> {
+    let output = command.output().map_err(|source| ToolchainError::Spawn {
+        command: command_line(command),
+        source,
+    })?;
+    let result = ToolOutput {
+        status: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    };
+    if output.status.success() {
+        return Ok(result);
+    }
+    let stderr = Some(result.stderr.trim_end().to_string()).filter(|stderr| !stderr.is_empty());
+    Err(ToolchainError::Failed {
+        command: command_line(command),
+        output: result,
+        stderr,
+    })
+}
+
+/// `cc` preprocessing `file` into `output`, without line markers.
+pub fn preprocess(cc: &str, file: &Path, output: &Path) -> Command {
+    let mut command = command(cc);
+    command.args(["-E", "-P"]).arg(file).arg("-o").arg(output);
+    command
+}
+
+/// `cc` assembling `assembly` into `object`.
+pub fn assemble(cc: &str, assembly: &Path, object: &Path) -> Command {
+    let mut command = command(cc);
+    command.arg("-c").arg(assembly).arg("-o").arg(object);
+    command
+}
+
+/// `cc` assembling and linking `assembly` into `program`, with `flags` last.
+pub fn link(cc: &str, assembly: &Path, program: &Path, flags: &[&str]) -> Command {
+    let mut command = command(cc);
+    command.arg(assembly).arg("-o").arg(program).args(flags);
+    command
+}
+
+/// `ar` bundling `objects` into the static library `library`, adding to the
+/// members it already has.
+pub fn archive(library: &Path, objects: &[impl AsRef<Path>]) -> Command {
+    let mut command = Command::new("ar");
+    command
+        .arg("rcs")
+        .arg(library)
+        .args(objects.iter().map(AsRef::as_ref));
+    command
+}
@@ -0,0 +1,509 @@
+//! Semantic analysis turning the parsed AST into a [`TypedProgram`].
+
+use std::{collections::HashMap, fmt};
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::{
+    ast::{
+        AstNode, BinaryOperator, BlockItem, Declaration, Expression, ExpressionKind, Factor,
+        FunctionDecl, Identifier, Statement, UnaryOperator, VarType,
+    },
+    const_eval::evaluate,
+    diagnostics::DiagnosticSink,
+    reachability::unreachable_code,
+    side_table::Analysis,
+    symbol_index::{SymbolId, SymbolIndex, SymbolKind},
+    typed_ast::{
+        TypedExpression, TypedExpressionKind, TypedFunction, TypedProgram, TypedStatement,
+        ValueCategory,
+    },
+};
+
+#[derive(Debug, Clone, Error, Diagnostic)]
+pub enum TypeError {
+    #[error("Expected a program")]
+    NotAProgram,
+    #[error("`{function}` returns void but a return statement has a value")]
+    #[diagnostic(help("remove the value, or declare `{function}` to return int"))]
+    ReturnValueInVoidFunction {
+        function: Identifier,
+        #[label("this value")]
+        span: SourceSpan,
+    },
+    #[error("Expression of type {0} can't be converted to {1}")]
+    InvalidConversion(VarType, VarType),
+    #[error("`{name}` is already declared")]
+    Redeclaration {
+        name: Identifier,
+        #[label("declared again here")]
+        span: SourceSpan,
+        #[label("first declared here")]
+        previous: SourceSpan,
+    },
+    #[error("`{name}` is not declared")]
+    UndeclaredVariable {
+        name: Identifier,
+        #[label("not found in this function")]
+        span: SourceSpan,
+    },
+    #[error("Cannot {operation} this expression")]
+    #[diagnostic(help("only variables, maybe in parentheses, designate an object"))]
+    NotAnLvalue {
+        operation: LvalueUse,
+        #[label("this is a value, not an object")]
+        target: SourceSpan,
+        #[label("the operator needs an object")]
+        span: SourceSpan,
+    },
+    #[error("Taking the address of a variable is not supported yet")]
+    #[diagnostic(help("there are no pointer types yet"))]
+    UnsupportedAddressOf {
+        #[label("this operator")]
+        span: SourceSpan,
+    },
+    #[error("Bitwise {name} is not supported yet")]
+    #[diagnostic(help("only the logical `&&` and `||` are implemented"))]
+    UnsupportedOperator {
+        name: &'static str,
+        #[label("this operator")]
+        span: SourceSpan,
+    },
+}
+
+/// `return;` in a function that returns a value, which C allows as long as
+/// the caller doesn't use the value.
+#[derive(Debug, Clone, Error, Diagnostic)]
+#[error("`{function}` returns a value but a return statement has none")]
+#[diagnostic(code(warning::return_without_value), severity(Warning))]
+pub struct ReturnWithoutValue {
+    pub function: Identifier,
+    #[label("returns nothing")]
+    pub span: SourceSpan,
+}
+
+/// Division or remainder by a literal zero, which C only leaves undefined
+/// when it runs, as it doesn't in `0 && 1 / 0`.
+#[derive(Debug, Clone, Error, Diagnostic)]
+#[error("Division by zero")]
+#[diagnostic(
+    code(warning::division_by_zero),
+    severity(Warning),
+    help("the program stops with a signal if this runs")
+)]
+pub struct DivisionByZero {
+    #[label("divisor is zero")]
+    pub span: SourceSpan,
+}
+
+/// Problems that don't stop the compilation.
+#[derive(Debug, Clone, Error, Diagnostic)]
+pub enum TypeWarning {
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    ReturnWithoutValue(ReturnWithoutValue),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    DivisionByZero(DivisionByZero),
+}
+
+/// What an operand has to be an lvalue for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LvalueUse {
+    Assign,
+    Increment,
+    Decrement,
+    AddressOf,
+}
+
+impl fmt::Display for LvalueUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LvalueUse::Assign => "assign to",
+            LvalueUse::Increment => "increment",
+            LvalueUse::Decrement => "decrement",
+            LvalueUse::AddressOf => "take the address of",
+        })
+    }
+}
+
+pub fn typecheck(node: &AstNode) -> Result<TypedProgram, TypeError> {
+    let AstNode::Program(nodes) = node else {
+        return Err(TypeError::NotAProgram);
+    };
+    let mut checker = Checker::default();
+    let functions = nodes
+        .iter()
+        .filter_map(|node| match node {
+            AstNode::FunctionDeclaration(function) => Some(checker.check_function(function)),
+            _ => None,
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(TypedProgram {
+        functions,
+        symbols: checker.symbols,
+        analysis: checker.analysis,
+        warnings: checker.warnings,
+    })
+}
+
+/// [`typecheck`] reporting into `sink`, after the warnings of
+/// [`unreachable_code`].
+pub fn typecheck_into(node: &AstNode, sink: &mut dyn DiagnosticSink) -> Option<TypedProgram> {
+    for warning in unreachable_code(node) {
+        sink.report(Box::new(warning));
+    }
+    let program = typecheck(node).map_err(|e| sink.report(Box::new(e))).ok()?;
+    for warning in &program.warnings {
+        sink.report(Box::new(warning.clone()));
+    }
+    Some(program)
+}
+
+#[derive(Default)]
+struct Checker {
+    symbols: SymbolIndex,
+    functions: HashMap<Identifier, SymbolId>,
+    /// Variables of the function being checked, there are no nested scopes yet.
+    variables: HashMap<Identifier, SymbolId>,
+    analysis: Analysis,
+    warnings: Vec<TypeWarning>,
+}
+
+impl Checker {
+    /// The name of `previous` declared again at `span`.
+    fn redeclaration(&self, previous: SymbolId, span: SourceSpan) -> TypeError {
+        let previous = self.symbols.get(previous);
+        TypeError::Redeclaration {
+            name: previous.name,
+            span,
+            previous: previous.declaration,
+        }
+    }
+
+    fn check_function(&mut self, function: &FunctionDecl) -> Result<TypedFunction, TypeError> {
+        if let Some(previous) = self.functions.get(&function.name) {
+            return Err(self.redeclaration(*previous, function.name_span));
+        }
+        let id = self.symbols.declare(
+            function.name,
+            SymbolKind::Function,
+            function.return_type.clone(),
+            function.name_span,
+        );
+        self.functions.insert(function.name, id);
+        self.analysis.resolutions.insert(function.id, id);
+        self.variables.clear();
+        for parameter in &function.parameters {
+            if let Some(previous) = self.variables.get(&parameter.name) {
+                return Err(self.redeclaration(*previous, parameter.name_span));
+            }
+            let id = self.symbols.declare(
+                parameter.name,
+                SymbolKind::Parameter,
+                parameter.var_type.clone(),
+                parameter.name_span,
+            );
+            self.variables.insert(parameter.name, id);
+        }
+
+        let body = match self.check_statement(function, &function.body)? {
+            TypedStatement::Compound(statements) => statements,
+            statement => vec![statement],
+        };
+        Ok(TypedFunction {
+            name: function.name,
+            return_type: function.return_type.clone(),
+            parameters: function
+                .parameters
+                .iter()
+                .map(|parameter| (parameter.var_type.clone(), parameter.name))
+                .collect(),
+            body,
+        })
+    }
+
+    fn check_statement(
+        &mut self,
+        function: &FunctionDecl,
+        statement: &Statement,
+    ) -> Result<TypedStatement, TypeError> {
+        match statement {
+            Statement::ReturnStatement(value, span) => {
+                let value = match (value, &function.return_type) {
+                    (Some(value), VarType::Void) => {
+                        return Err(TypeError::ReturnValueInVoidFunction {
+                            function: function.name,
+                            span: value.span(),
+                        })
+                    }
+                    (None, VarType::Void) => None,
+                    (None, _) => {
+                        self.warnings
+                            .push(TypeWarning::ReturnWithoutValue(ReturnWithoutValue {
+                                function: function.name,
+                                span: *span,
+                            }));
+                        None
+                    }
+                    (Some(value), return_type) => {
+                        Some(convert(self.check_full_expression(value)?, return_type)?)
+                    }
+                };
+                Ok(TypedStatement::Return(value, *span))
+            }
+            Statement::Compound(items) => items
+                .iter()
+                .map(|item| match item {
+                    BlockItem::Declaration(declaration) => self.check_declaration(declaration),
+                    BlockItem::Statement(statement) => self.check_statement(function, statement),
+                })
+                .collect::<Result<_, _>>()
+                .map(TypedStatement::Compound),
+            Statement::Expression(expression) => Ok(TypedStatement::Expression(
+                self.check_full_expression(expression)?,
+                expression.span(),
+            )),
+        }
+    }
+
+    fn check_declaration(
+        &mut self,
+        declaration: &Declaration,
+    ) -> Result<TypedStatement, TypeError> {
+        let Declaration {
+            id: node,
+            var_type,
+            name,
+            name_span,
+            initializer,
+        } = declaration;
+        if let Some(previous) = self.variables.get(name) {
+            return Err(self.redeclaration(*previous, *name_span));
+        }
+        // The variable is in scope in its own initializer, like in C.
+        let id = self
+            .symbols
+            .declare(*name, SymbolKind::Variable, var_type.clone(), *name_span);
+        self.variables.insert(*name, id);
+        self.analysis.resolutions.insert(*node, id);
+        let initializer = initializer
+            .as_ref()
+            .map(|value| convert(self.check_full_expression(value)?, var_type))
+            .transpose()?;
+        Ok(TypedStatement::Declaration {
+            name: *name,
+            span: *name_span,
+            initializer,
+        })
+    }
+
+    /// An expression that is not part of another one, its value is recorded
+    /// when it is constant.
+    fn check_full_expression(
+        &mut self,
+        expression: &Expression,
+    ) -> Result<TypedExpression, TypeError> {
+        let typed = self.check_expression(expression)?;
+        if let Ok(value) = evaluate(expression) {
+            self.analysis.constants.insert(expression.id, value);
+        }
+        Ok(typed)
+    }
+
+    fn check_expression(&mut self, expression: &Expression) -> Result<TypedExpression, TypeError> {
+        let typed = match &expression.kind {
+            ExpressionKind::Factor(Factor::Constant(c, _)) => {
+                TypedExpression::rvalue(TypedExpressionKind::Constant(*c), VarType::Int)
+            }
+            ExpressionKind::Factor(Factor::Variable(name, span)) => {
+                let Some(&id) = self.variables.get(name) else {
+                    return Err(TypeError::UndeclaredVariable {
+                        name: *name,
+                        span: *span,
+                    });
+                };
+                self.symbols.add_reference(id, *span);
+                self.analysis.resolutions.insert(expression.id, id);
+                TypedExpression {
+                    kind: TypedExpressionKind::Variable(*name),
+                    ty: self.symbols.get(id).ty.clone(),
+                    category: ValueCategory::LValue,
+                }
+            }
+            ExpressionKind::Factor(Factor::ParentedExpression(inner)) => {
+                self.check_expression(inner)?
+            }
+            ExpressionKind::Factor(Factor::Unary(operator, inner)) => {
+                let inner = promote(self.check_expression(inner)?)?;
+                // `!` yields int whatever its operand, `-` and `~` the promoted type.
+                let ty = match operator {
+                    UnaryOperator::Not => VarType::Int,
+                    UnaryOperator::Negate | UnaryOperator::Complement => inner.ty.clone(),
+                };
+                TypedExpression::rvalue(
+                    TypedExpressionKind::Unary(operator.clone(), Box::new(inner)),
+                    ty,
+                )
+            }
+            ExpressionKind::Binary(left, operator, right, span) => {
+                let name = match operator {
+                    BinaryOperator::BitwiseAnd => Some("AND"),
+                    BinaryOperator::BitwiseOr => Some("OR"),
+                    _ => None,
+                };
+                if let Some(name) = name {
+                    return Err(TypeError::UnsupportedOperator { name, span: *span });
+                }
+                if matches!(operator, BinaryOperator::Divide | BinaryOperator::Remainder) {
+                    if let Some(span) = zero_literal(right) {
+                        self.warnings
+                            .push(TypeWarning::DivisionByZero(DivisionByZero { span }));
+                    }
+                }
+                let left = promote(self.check_expression(left)?)?;
+                let right = promote(self.check_expression(right)?)?;
+                let (left, right, ty) = match operator {
+                    // Each operand is only compared to zero.
+                    BinaryOperator::And | BinaryOperator::Or => (left, right, VarType::Int),
+                    _ => {
+                        let common = common_type(&left.ty, &right.ty);
+                        let ty = if operator.is_comparison() {
+                            VarType::Int
+                        } else {
+                            common.clone()
+                        };
+                        (convert(left, &common)?, convert(right, &common)?, ty)
+                    }
+                };
+                TypedExpression::rvalue(
+                    TypedExpressionKind::Binary(
+                        Box::new(left),
+                        operator.clone(),
+                        Box::new(right),
+                        *span,
+                    ),
+                    ty,
+                )
+            }
+            ExpressionKind::Assignment(target, value, span) => {
+                let typed_target = self.check_lvalue(target, LvalueUse::Assign, *span)?;
+                let value = convert(self.check_expression(value)?, &typed_target.ty)?;
+                // The result is the new value of the target, not the object itself.
+                let ty = typed_target.ty.clone();
+                TypedExpression::rvalue(
+                    TypedExpressionKind::Assignment(Box::new(typed_target), Box::new(value)),
+                    ty,
+                )
+            }
+            ExpressionKind::Update(operator, operand, span) => {
+                let operation = if operator.is_increment() {
+                    LvalueUse::Increment
+                } else {
+                    LvalueUse::Decrement
+                };
+                let operand = self.check_lvalue(operand, operation, *span)?;
+                if !operand.ty.is_integer() {
+                    return Err(TypeError::InvalidConversion(operand.ty, VarType::Int));
+                }
+                let ty = operand.ty.clone();
+                TypedExpression::rvalue(
+                    TypedExpressionKind::Update(*operator, Box::new(operand), *span),
+                    ty,
+                )
+            }
+            ExpressionKind::AddressOf(operand, span) => {
+                self.check_lvalue(operand, LvalueUse::AddressOf, *span)?;
+                return Err(TypeError::UnsupportedAddressOf { span: *span });
+            }
+        };
+        self.analysis.types.insert(expression.id, typed.ty.clone());
+        Ok(typed)
+    }
+
+    /// Checks the operand of an operator that needs an object, like the
+    /// target of `=`. `span` is the one of the operator.
+    fn check_lvalue(
+        &mut self,
+        operand: &Expression,
+        operation: LvalueUse,
+        span: SourceSpan,
+    ) -> Result<TypedExpression, TypeError> {
+        let typed = self.check_expression(operand)?;
+        if typed.category != ValueCategory::LValue {
+            return Err(TypeError::NotAnLvalue {
+                operation,
+                target: operand.span(),
+                span,
+            });
+        }
+        Ok(typed)
+    }
+}
+
+/// Span of `expression` when it is a literal zero, maybe in parentheses.
+fn zero_literal(expression: &Expression) -> Option<SourceSpan> {
+    match &expression.kind {
+        ExpressionKind::Factor(Factor::Constant(0, span)) => Some(*span),
+        ExpressionKind::Factor(Factor::ParentedExpression(inner)) => zero_literal(inner),
+        _ => None,
+    }
+}
+
+/// Applies the integer promotions: a type ranking below `int` becomes `int`,
+/// fails for void.
+fn promote(expression: TypedExpression) -> Result<TypedExpression, TypeError> {
+    let ty = if expression.ty.rank() < VarType::Int.rank() {
+        VarType::Int
+    } else {
+        expression.ty.clone()
+    };
+    convert(expression, &ty)
+}
+
+/// Type the operands of an arithmetic or comparison operator are converted
+/// to, by the usual arithmetic conversions of C on promoted types: the type
+/// of higher rank when the signedness is the same, else the unsigned one
+/// unless it ranks lower and the signed one can hold all its values.
+pub fn common_type(left: &VarType, right: &VarType) -> VarType {
+    if left == right {
+        return left.clone();
+    }
+    if left.is_signed() == right.is_signed() {
+        return if left.rank() >= right.rank() {
+            left.clone()
+        } else {
+            right.clone()
+        };
+    }
+    let (signed, unsigned) = if left.is_signed() {
+        (left, right)
+    } else {
+        (right, left)
+    };
+    if unsigned.rank() >= signed.rank() {
+        unsigned.clone()
+    } else if signed.size() > unsigned.size() {
+        signed.clone()
+    } else {
+        signed.to_unsigned()
+    }
+}
+
+/// Wraps the expression in a conversion to `ty` when its type differs.
+fn convert(expression: TypedExpression, ty: &VarType) -> Result<TypedExpression, TypeError> {
+    if &expression.ty == ty {
+        return Ok(expression);
+    }
+    if !expression.ty.is_integer() || !ty.is_integer() {
+        return Err(TypeError::InvalidConversion(
+            expression.ty.clone(),
+            ty.clone(),
+        ));
+    }
+    Ok(TypedExpression::rvalue(
+        TypedExpressionKind::Convert(Box::new(expression)),
+        ty.clone(),
+    ))
+}
@@ -0,0 +1,96 @@
+//! AST after type checking.
+//!
+//! Every expression carries the type it evaluates to and whether it designates
+//! an object (lvalue) or just a value, and implicit conversions appear as
+//! [`TypedExpressionKind::Convert`] nodes. Parentheses are gone, the tree
+//! only keeps what lowering needs.
+
+use miette::SourceSpan;
+
+use crate::{
+    ast::{BinaryOperator, Identifier, UnaryOperator, UpdateOperator, VarType},
+    side_table::Analysis,
+    symbol_index::SymbolIndex,
+    typecheck::TypeWarning,
+};
+
+#[derive(Debug, Clone)]
+pub struct TypedProgram {
+    pub functions: Vec<TypedFunction>,
+    pub symbols: SymbolIndex,
+    /// Results keyed by the nodes of the checked AST, for tools that work on
+    /// the AST instead of this tree.
+    pub analysis: Analysis,
+    /// Reported by [`typecheck_into`](crate::typecheck::typecheck_into).
+    pub warnings: Vec<TypeWarning>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypedFunction {
+    pub name: Identifier,
+    pub return_type: VarType,
+    pub parameters: Vec<(VarType, Identifier)>,
+    pub body: Vec<TypedStatement>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TypedStatement {
+    /// The value is already converted to the return type of the function,
+    /// the span covers the whole statement.
+    Return(Option<TypedExpression>, SourceSpan),
+    /// The initializer is already converted to the type of the variable.
+    Declaration {
+        name: Identifier,
+        /// Span of the name.
+        span: SourceSpan,
+        initializer: Option<TypedExpression>,
+    },
+    /// Expression evaluated for its side effects, with its span.
+    Expression(TypedExpression, SourceSpan),
+    Compound(Vec<TypedStatement>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueCategory {
+    /// Designates an object, like a variable
+    LValue,
+    /// Plain value, like a constant or the result of an operator
+    RValue,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypedExpression {
+    pub kind: TypedExpressionKind,
+    pub ty: VarType,
+    pub category: ValueCategory,
+}
+
+#[derive(Debug, Clone)]
+pub enum TypedExpressionKind {
+    Constant(i32),
+    Variable(Identifier),
+    Unary(UnaryOperator, Box<TypedExpression>),
+    /// Operands, the operator and the span of the operator.
+    Binary(
+        Box<TypedExpression>,
+        BinaryOperator,
+        Box<TypedExpression>,
+        SourceSpan,
+    ),
+    /// Target, an lvalue, and the value already converted to its type.
+    Assignment(Box<TypedExpression>, Box<TypedExpression>),
+    /// `++` or `--` of an lvalue, with the span of the operator.
+    Update(UpdateOperator, Box<TypedExpression>, SourceSpan),
+    /// Conversion of the inner expression to the type of this node.
+    Convert(Box<TypedExpression>),
+}
+
+impl TypedExpression {
+    pub fn rvalue(kind: TypedExpressionKind, ty: VarType) -> Self {
+        Self {
+            kind,
+            ty,
+            category: ValueCategory::RValue,
+        }
+    }
+}
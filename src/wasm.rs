@@ -0,0 +1,65 @@
+//! Entry points for a browser playground showing every stage of the compiler
+//! as the source is edited.
+//!
+//! With the `wasm` feature on `wasm32-unknown-unknown` the functions are
+//! exported through wasm-bindgen and a failure becomes a JSON string, on other
+//! targets they are plain functions for hosts that embed the library:
+//!
+//! ```sh
+//! wasm-pack build --target web -- --features wasm
+//! ```
+//!
+//! Each one takes the whole source of the program and runs the stages up to
+//! the one it shows, without touching the filesystem or spawning processes.
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    assembly::TargetPlatform,
+    embed::{assembly_for, lex, lower, opt_level_from, parse},
+    json::JsonDiagnostics,
+    optimize::PassManager,
+};
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+impl From<JsonDiagnostics> for JsValue {
+    fn from(diagnostics: JsonDiagnostics) -> Self {
+        JsValue::from_str(&diagnostics.0)
+    }
+}
+
+/// One line per token: its byte offset, length and the token itself.
+#[cfg_attr(all(feature = "wasm", target_arch = "wasm32"), wasm_bindgen)]
+pub fn tokens(source: &str) -> Result<String, JsonDiagnostics> {
+    let (_, tokens) = lex(source)?;
+    Ok(tokens
+        .iter()
+        .map(|t| format!("{}+{}\t{:?}\n", t.offset, t.len, t.token))
+        .collect())
+}
+
+/// The syntax tree in the layout of `{:#?}`.
+#[cfg_attr(all(feature = "wasm", target_arch = "wasm32"), wasm_bindgen)]
+pub fn ast(source: &str) -> Result<String, JsonDiagnostics> {
+    Ok(format!("{:#?}", parse(source)?))
+}
+
+/// The TACKY after the passes of `-O<opt_level>`.
+#[cfg_attr(all(feature = "wasm", target_arch = "wasm32"), wasm_bindgen)]
+pub fn tacky(source: &str, opt_level: u8) -> Result<String, JsonDiagnostics> {
+    let passes = PassManager::new(opt_level_from(opt_level)?);
+    Ok(lower(source, &passes)?.to_string())
+}
+
+/// The x86-64 assembly for Linux at `-O0`, as `ulang_driver -S` writes it.
+#[cfg_attr(all(feature = "wasm", target_arch = "wasm32"), wasm_bindgen)]
+pub fn compile_to_asm(source: &str) -> Result<String, JsonDiagnostics> {
+    compile_to_asm_with(source, 0)
+}
+
+/// [`compile_to_asm`] at `-O<opt_level>`.
+#[cfg_attr(all(feature = "wasm", target_arch = "wasm32"), wasm_bindgen)]
+pub fn compile_to_asm_with(source: &str, opt_level: u8) -> Result<String, JsonDiagnostics> {
+    assembly_for(source, opt_level, TargetPlatform::X64Linux)
+}
@@ -0,0 +1,107 @@
+//! Running the assembly passes one at a time and in the wrong order.
+
+mod common;
+
+use ulang::{
+    assembly::{
+        AsmInstruction, AsmPass, AsmPipeline, AsmPipelineError, AsmProgram, Operand, TargetPlatform,
+    },
+    optimize::{OptimizationLevel, Pass, PassManager},
+};
+
+fn has_pseudos(program: &AsmProgram) -> bool {
+    let mut instructions = program.0.instructions.clone();
+    instructions
+        .iter_mut()
+        .flat_map(AsmInstruction::operands_mut)
+        .any(|operand| matches!(operand, Operand::Pseudo(_)))
+}
+
+#[test]
+fn exposes_the_program_after_every_pass() {
+    let program = common::lower("int main(void) {\n    int a = 2;\n    return -a;\n}\n");
+    let mut pipeline = AsmPipeline::new(AsmProgram::from(&program));
+    assert!(has_pseudos(pipeline.program()));
+
+    assert!(has_pseudos(pipeline.coalesce_slots().unwrap()));
+    assert!(!has_pseudos(pipeline.replace_pseudos().unwrap()));
+    let fixed = pipeline.fix_instructions().unwrap();
+    assert!(matches!(fixed.0.instructions[0], AsmInstruction::Push(_)));
+    pipeline.peephole().unwrap();
+    assert_eq!(
+        pipeline.completed(),
+        [
+            AsmPass::CoalesceSlots,
+            AsmPass::ReplacePseudos,
+            AsmPass::FixInstructions,
+            AsmPass::Peephole
+        ]
+    );
+
+    let mut all = AsmPipeline::new(AsmProgram::from(&program));
+    all.run_all(&PassManager::new(OptimizationLevel::O2))
+        .unwrap();
+    assert_eq!(
+        pipeline.program().generate(TargetPlatform::X64Linux).0,
+        all.program().generate(TargetPlatform::X64Linux).0
+    );
+}
+
+#[test]
+fn rejects_passes_out_of_order() {
+    let program = common::lower("int main(void) {\n    return 1;\n}\n");
+    let mut pipeline = AsmPipeline::new(AsmProgram::from(&program));
+    assert_eq!(
+        pipeline.fix_instructions().unwrap_err(),
+        AsmPipelineError::OutOfOrder {
+            pass: AsmPass::FixInstructions,
+            requires: AsmPass::ReplacePseudos,
+        }
+    );
+    assert!(pipeline.run(AsmPass::Peephole).is_err());
+    assert!(pipeline.completed().is_empty());
+
+    pipeline.replace_pseudos().unwrap();
+    let error = pipeline.replace_pseudos().unwrap_err();
+    assert_eq!(error, AsmPipelineError::AlreadyRan(AsmPass::ReplacePseudos));
+    assert_eq!(error.to_string(), "The replace-pseudos pass already ran");
+    pipeline.fix_instructions().unwrap();
+    pipeline.peephole().unwrap();
+    pipeline.peephole().unwrap();
+}
+
+#[test]
+fn coalesces_slots_before_they_are_assigned() {
+    let program = common::lower("int main(void) {\n    return 1 + 2 * 3 - 4 * 5 + 6;\n}\n");
+    let frame = |passes: Vec<Pass>| {
+        let mut pipeline = AsmPipeline::new(AsmProgram::from(&program));
+        let program = pipeline.run_all(&PassManager::with_passes(passes)).unwrap();
+        program
+            .0
+            .instructions
+            .iter()
+            .find_map(|instruction| match instruction {
+                AsmInstruction::AllocateStack(bytes) => Some(*bytes),
+                _ => None,
+            })
+            .unwrap()
+    };
+    // Each temporary is read right after it is written.
+    assert_eq!(frame(vec![]), 20);
+    assert_eq!(frame(vec![Pass::CoalesceSlots]), 8);
+
+    let mut pipeline = AsmPipeline::new(AsmProgram::from(&program));
+    pipeline.replace_pseudos().unwrap();
+    let error = pipeline.coalesce_slots().unwrap_err();
+    assert_eq!(
+        error,
+        AsmPipelineError::TooLate {
+            pass: AsmPass::CoalesceSlots,
+            precedes: AsmPass::ReplacePseudos,
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "The coalesce-slots pass has to run before replace-pseudos"
+    );
+}
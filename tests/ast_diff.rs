@@ -0,0 +1,103 @@
+//! Structural differences between two versions of a program: formatting,
+//! comments and redundant parentheses don't count, block items are lined up,
+//! and the differences name where they are.
+
+mod common;
+
+use ulang::ast_diff::{diff, AstDifference};
+
+fn differences(old: &str, new: &str) -> Vec<String> {
+    diff(&common::parse(old), &common::parse(new))
+        .iter()
+        .map(AstDifference::to_string)
+        .collect()
+}
+
+const PROGRAM: &str = "int main(void) {\n    int a = 1;\n    a = a * (2 + 3);\n    return -a;\n}\n";
+
+#[test]
+fn ignores_formatting_comments_and_parentheses() {
+    let reformatted = "// same\nint main(void){int a=1;a=(a*((2+3)));\n/* x */ return (-(a)) ;}";
+    assert!(differences(PROGRAM, reformatted).is_empty());
+    assert!(differences(PROGRAM, PROGRAM).is_empty());
+}
+
+#[test]
+fn names_where_the_structure_changed() {
+    let regrouped = PROGRAM.replace("a * (2 + 3)", "a * 2 + 3");
+    assert_eq!(
+        differences(PROGRAM, &regrouped),
+        ["main > body > item 2 > expression > value: `a * (2 + 3)` became `a * 2 + 3`"]
+    );
+    let changed = PROGRAM
+        .replace("int a = 1", "int a = 2")
+        .replace("-a", "~a");
+    assert_eq!(
+        differences(PROGRAM, &changed),
+        [
+            "main > body > item 1 > initializer: `1` became `2`",
+            "main > body > item 3 > value: `-a` became `~a`",
+        ]
+    );
+    let void = PROGRAM.replace("return -a;", "return;");
+    assert_eq!(
+        differences(PROGRAM, &void),
+        ["main > body > item 3 > value: `-a` was removed"]
+    );
+}
+
+#[test]
+fn lines_up_inserted_and_removed_items() {
+    let inserted = PROGRAM.replace("    a = a", "    int b = 0;\n    a = a");
+    let found = diff(&common::parse(PROGRAM), &common::parse(&inserted));
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].path, "main > body > item 2");
+    assert!(found[0].old.is_none());
+    let added = found[0].new.as_ref().unwrap();
+    assert_eq!(added.text, "int b = 0;");
+    assert_eq!(
+        added.span.unwrap().offset(),
+        inserted.find("b = 0").unwrap()
+    );
+
+    assert_eq!(
+        differences(&inserted, PROGRAM),
+        ["main > body > item 2: `int b = 0;` was removed"]
+    );
+    let two = format!("{}int f(void) {{\n    return 0;\n}}\n", PROGRAM);
+    assert_eq!(differences(&two, PROGRAM), ["f: `int f(void)` was removed"]);
+    assert_eq!(
+        differences(
+            PROGRAM,
+            &PROGRAM.replace("int main(void)", "int main(int argc)")
+        ),
+        ["main: `int main(void)` became `int main(int argc)`"]
+    );
+}
+
+#[cfg(feature = "build-binary")]
+#[test]
+fn exits_with_one_when_the_files_differ() {
+    let dir = std::env::temp_dir().join(format!("ulang-astdiff-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (old, new) = (dir.join("old.c"), dir.join("new.c"));
+    std::fs::write(&old, PROGRAM).unwrap();
+    std::fs::write(&new, PROGRAM.replace("return -a", "return a")).unwrap();
+    let run = |new: &std::path::Path| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_ulang-astdiff"))
+            .arg(&old)
+            .arg(new)
+            .output()
+            .unwrap()
+    };
+    let output = run(&new);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("old.c:4:13  -a\n"), "{}", stdout);
+    assert!(stdout.contains("new.c:4:12  a\n"), "{}", stdout);
+    assert!(stdout.ends_with("1 difference\n"), "{}", stdout);
+    assert_eq!(run(&old).status.code(), Some(0));
+    std::fs::write(&new, "int main(void) {").unwrap();
+    assert_eq!(run(&new).status.code(), Some(2));
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,57 @@
+//! Keys of the compilation cache and its entries on disk.
+
+use std::path::PathBuf;
+
+use ulang::{
+    assembly::TargetPlatform,
+    cache::{CacheKey, CompilationCache},
+};
+
+fn cache(name: &str) -> CompilationCache {
+    let dir: PathBuf =
+        std::env::temp_dir().join(format!("ulang-cache-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    CompilationCache::new(dir)
+}
+
+#[test]
+fn keys_change_with_every_input() {
+    let source = "int main(void) { return 0; }";
+    let key = CacheKey::new(source, "O0", TargetPlatform::X64Linux);
+    assert_eq!(key, CacheKey::new(source, "O0", TargetPlatform::X64Linux));
+    assert_ne!(
+        key,
+        CacheKey::new(
+            "int main(void) { return 1; }",
+            "O0",
+            TargetPlatform::X64Linux
+        )
+    );
+    assert_ne!(key, CacheKey::new(source, "O1", TargetPlatform::X64Linux));
+    assert_ne!(key, CacheKey::new(source, "O0", TargetPlatform::MacOsX64));
+    // Moving text from one part to the other is a different key.
+    assert_ne!(
+        CacheKey::new("ab", "c", TargetPlatform::X64Linux),
+        CacheKey::new("a", "bc", TargetPlatform::X64Linux)
+    );
+    assert_eq!(key.to_string().len(), 32);
+}
+
+#[test]
+fn stores_and_clears_entries() {
+    let cache = cache("entries");
+    let first = CacheKey::new("int a;", "", TargetPlatform::X64Linux);
+    let second = CacheKey::new("int b;", "", TargetPlatform::X64Linux);
+    assert_eq!(cache.get(first), None);
+    assert_eq!(cache.clear().unwrap(), 0);
+
+    cache.put(first, "first").unwrap();
+    cache.put(second, "second").unwrap();
+    cache.put(second, "second again").unwrap();
+    assert_eq!(cache.get(first).as_deref(), Some("first"));
+    assert_eq!(cache.get(second).as_deref(), Some("second again"));
+
+    assert_eq!(cache.clear().unwrap(), 2);
+    assert_eq!(cache.get(first), None);
+    let _ = std::fs::remove_dir_all(&cache.dir);
+}
@@ -0,0 +1,50 @@
+//! The call graph over the TACKY of every function of a program.
+
+mod common;
+
+use ulang::{ast::Identifier, dot::call_graph_to_dot, tacky::call_graph::CallGraph};
+
+const SOURCE: &str = "int helper(void) {
+    return 2;
+}
+
+long wide(void) {
+    return 1;
+}
+
+int main(void) {
+    int a = 1;
+    return a + 2;
+}
+";
+
+fn call_graph(source: &str) -> CallGraph {
+    CallGraph::new(&common::check(source))
+}
+
+#[test]
+fn lowers_every_function_on_its_own() {
+    let graph = call_graph(SOURCE);
+    let names: Vec<String> = graph.functions.iter().map(|f| f.name.to_string()).collect();
+    assert_eq!(names, ["helper", "wide", "main"]);
+    assert_eq!(graph.functions[0].instructions, Ok(1));
+    assert!(graph.functions[1].instructions.is_err());
+    assert_eq!(graph.functions[2].instructions, Ok(3));
+    // Without calls, `main` only reaches itself.
+    let main = graph.index_of(Identifier::from("main")).unwrap();
+    assert_eq!(graph.reachable(main), [false, false, true]);
+}
+
+#[test]
+fn draws_a_node_per_function() {
+    assert_eq!(
+        call_graph_to_dot(&call_graph(SOURCE)),
+        "digraph calls {
+    node [shape=box];
+    n0 [label=\"helper\\n1 instruction\"];
+    n1 [label=\"wide\\nnot lowered\"];
+    n2 [label=\"main\\n3 instructions\"];
+}
+"
+    );
+}
@@ -0,0 +1,100 @@
+//! The C ABI of the `capi` feature, called the way a C host would.
+#![cfg(feature = "capi")]
+
+use std::{
+    ffi::{CStr, CString},
+    ptr,
+};
+
+use ulang::capi::{
+    ulang_compile, ulang_options, ulang_result, ulang_result_free, ULANG_DIAGNOSTICS,
+    ULANG_INVALID_ARGUMENT, ULANG_OK, ULANG_TARGET_MACOS_X64,
+};
+
+fn empty_result() -> ulang_result {
+    ulang_result {
+        assembly: ptr::null_mut(),
+        diagnostics: ptr::null_mut(),
+    }
+}
+
+/// Status, assembly and diagnostics of compiling `source`.
+fn compile(source: &str, options: Option<ulang_options>) -> (i32, Option<String>, String) {
+    let source = CString::new(source).unwrap();
+    let options = options.as_ref().map_or(ptr::null(), |o| o as *const _);
+    let mut result = empty_result();
+    unsafe {
+        let status = ulang_compile(source.as_ptr(), options, &mut result);
+        let assembly = (!result.assembly.is_null()).then(|| {
+            CStr::from_ptr(result.assembly)
+                .to_str()
+                .unwrap()
+                .to_string()
+        });
+        let diagnostics = CStr::from_ptr(result.diagnostics)
+            .to_str()
+            .unwrap()
+            .to_string();
+        ulang_result_free(&mut result);
+        assert!(result.assembly.is_null() && result.diagnostics.is_null());
+        (status, assembly, diagnostics)
+    }
+}
+
+#[test]
+fn compiles_with_options() {
+    let source = "int main(void) {\n    return 1 + 2;\n}\n";
+    let (status, assembly, diagnostics) = compile(source, None);
+    assert_eq!(status, ULANG_OK);
+    assert!(assembly.unwrap().contains("addl"));
+    assert_eq!(diagnostics, "[]");
+
+    let options = ulang_options {
+        opt_level: 2,
+        target: ULANG_TARGET_MACOS_X64,
+    };
+    let assembly = compile(source, Some(options)).1.unwrap();
+    assert!(assembly.contains("_main:"), "{}", assembly);
+    assert!(assembly.contains("movl\t$3, %eax"), "{}", assembly);
+}
+
+#[test]
+fn reports_diagnostics_as_json() {
+    let (status, assembly, diagnostics) = compile("int main(void) { return +; }", None);
+    assert_eq!(status, ULANG_DIAGNOSTICS);
+    assert_eq!(assembly, None);
+    assert!(
+        diagnostics.contains("\"offset\":24,\"length\":1,\"line\":1,\"column\":25"),
+        "{}",
+        diagnostics
+    );
+}
+
+#[test]
+fn rejects_invalid_arguments() {
+    let options = ulang_options {
+        opt_level: 0,
+        target: 7,
+    };
+    let (status, _, diagnostics) = compile("int main(void) { return 0; }", Some(options));
+    assert_eq!(status, ULANG_INVALID_ARGUMENT);
+    assert!(diagnostics.contains("Unknown target 7"), "{}", diagnostics);
+
+    let mut result = empty_result();
+    unsafe {
+        assert_eq!(
+            ulang_compile(ptr::null(), ptr::null(), &mut result),
+            ULANG_INVALID_ARGUMENT
+        );
+        ulang_result_free(&mut result);
+        assert_eq!(
+            ulang_compile(
+                c"int main(void) { return 0; }".as_ptr(),
+                ptr::null(),
+                ptr::null_mut()
+            ),
+            ULANG_INVALID_ARGUMENT
+        );
+        ulang_result_free(ptr::null_mut());
+    }
+}
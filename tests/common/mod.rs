@@ -0,0 +1,47 @@
+//! Fixtures shared by the integration tests, running the front end up to one
+//! stage on a source that is valid up to it.
+
+// Every test crate includes the module and only uses some of them.
+#![allow(dead_code)]
+
+use ulang::{
+    ast::AstNode,
+    lexer::{FileToken, Lexer},
+    parser::Parser,
+    tacky::{Tacky, TackyProgram},
+    typecheck::{typecheck, TypeError},
+    typed_ast::TypedProgram,
+};
+
+/// The tokens, and the lexer holding the text they point into.
+pub fn lex(source: &str) -> (Lexer, Vec<FileToken>) {
+    let mut lexer = Lexer::from_content(source.to_string());
+    let tokens = lexer.tokenize().unwrap();
+    (lexer, tokens)
+}
+
+pub fn parse(source: &str) -> AstNode {
+    let (lexer, tokens) = lex(source);
+    Parser::new(&tokens, &lexer.content).parse().unwrap()
+}
+
+pub fn try_check(source: &str) -> Result<TypedProgram, TypeError> {
+    typecheck(&parse(source))
+}
+
+pub fn check(source: &str) -> TypedProgram {
+    try_check(source).unwrap()
+}
+
+/// The TACKY of the first function.
+pub fn lower(source: &str) -> TackyProgram {
+    Tacky::new(&check(source)).parse().unwrap()
+}
+
+/// Like [`lower`], with the markers of the source positions.
+pub fn lower_with_locations(source: &str) -> TackyProgram {
+    Tacky::new(&check(source))
+        .with_source_locations(source)
+        .parse()
+        .unwrap()
+}
@@ -0,0 +1,98 @@
+//! `ulang.toml` files and the warning policy they set.
+#![cfg(feature = "config")]
+
+use std::path::PathBuf;
+
+use miette::Severity;
+use ulang::{
+    assembly::TargetPlatform,
+    config::{Config, FILE_NAME},
+    diagnostics::{Collect, DiagnosticSink, WarningPolicy},
+    lexer::Lexer,
+    optimize::OptimizationLevel,
+    parser::Parser,
+    tacky::Tacky,
+    typecheck::typecheck_into,
+};
+
+#[test]
+fn parses_every_key() {
+    let config: Config = r#"
+        target = "macos-x64"
+        opt-level = 2
+        cc = "clang"
+
+        [warnings]
+        allow = ["unreachable_code"]
+        deny = true
+    "#
+    .parse()
+    .unwrap();
+    assert_eq!(config.target, Some(TargetPlatform::MacOsX64));
+    assert_eq!(config.opt_level, Some(OptimizationLevel::O2));
+    assert_eq!(config.cc.as_deref(), Some("clang"));
+    assert_eq!(config.warnings.allow, ["unreachable_code"]);
+    assert!(config.warnings.deny);
+
+    assert_eq!("".parse::<Config>().unwrap(), Config::default());
+}
+
+#[test]
+fn rejects_unknown_keys_and_values() {
+    let error = "opt-level = 3".parse::<Config>().unwrap_err();
+    assert!(
+        error.contains("Unknown optimization level \"3\""),
+        "{}",
+        error
+    );
+    let error = "target = \"arm\"".parse::<Config>().unwrap_err();
+    assert!(error.contains("Unknown target \"arm\""), "{}", error);
+    let error = "optlevel = 1".parse::<Config>().unwrap_err();
+    assert!(error.contains("unknown field `optlevel`"), "{}", error);
+}
+
+#[test]
+fn finds_the_closest_file() {
+    let root: PathBuf = std::env::temp_dir().join(format!("ulang-config-{}", std::process::id()));
+    let nested = root.join("project/src/deep");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::write(root.join(FILE_NAME), "opt-level = 1").unwrap();
+    let file = nested.join("main.c");
+
+    let outer = Config::for_file(&file).unwrap();
+    std::fs::write(root.join("project").join(FILE_NAME), "opt-level = 2").unwrap();
+    let inner = Config::for_file(&file).unwrap();
+    std::fs::write(root.join("project").join(FILE_NAME), "opt-level =").unwrap();
+    let invalid = Config::for_file(&file);
+    let _ = std::fs::remove_dir_all(&root);
+
+    assert_eq!(outer.opt_level, Some(OptimizationLevel::O1));
+    assert_eq!(inner.opt_level, Some(OptimizationLevel::O2));
+    assert!(invalid.is_err());
+}
+
+#[test]
+fn drops_allowed_warnings() {
+    let source = "int f(void) {\n    return 1;\n    return 2;\n}\nint g(void) {\n}\n";
+    let warnings = |allow: &[&str]| {
+        let policy = WarningPolicy {
+            allow: allow.iter().map(|code| code.to_string()).collect(),
+            deny: false,
+        };
+        let mut collected = Collect::default();
+        let mut sink = policy.sink(&mut collected);
+        let mut lexer = Lexer::from_content(source.to_string());
+        let tokens = lexer.tokenize_into(&mut sink).unwrap();
+        let ast = Parser::new(&tokens, &lexer.content)
+            .parse_into(&mut sink)
+            .unwrap();
+        let typed = typecheck_into(&ast, &mut sink).unwrap();
+        Tacky::new(&typed).parse_into(&mut sink).unwrap();
+        assert_eq!(sink.count(Severity::Error), 0);
+        sink.count(Severity::Warning)
+    };
+    assert_eq!(warnings(&[]), 1);
+    assert_eq!(warnings(&["unreachable_code"]), 0);
+    assert_eq!(warnings(&["warning::unreachable_code"]), 0);
+    assert_eq!(warnings(&["missing_return"]), 1);
+}
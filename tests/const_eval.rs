@@ -0,0 +1,84 @@
+//! Constant expressions evaluated at compile time.
+
+mod common;
+
+use ulang::{
+    ast::{AstNode, BlockItem, Expression, Statement},
+    const_eval::{evaluate, ConstError},
+};
+
+/// The expression of `return <source>;` evaluated.
+fn value(source: &str) -> Result<i32, ConstError> {
+    let program = format!("int main(void) {{ return {}; }}", source);
+    let AstNode::Program(nodes) = common::parse(&program) else {
+        panic!("not a program");
+    };
+    let AstNode::FunctionDeclaration(function) = &nodes[0] else {
+        panic!("not a function");
+    };
+    let Statement::Compound(statements) = &function.body else {
+        panic!("no body");
+    };
+    let BlockItem::Statement(Statement::ReturnStatement(Some(expression), _)) = &statements[0]
+    else {
+        panic!("no return value");
+    };
+    let expression: &Expression = expression;
+    evaluate(expression)
+}
+
+#[test]
+fn evaluates_every_operator() {
+    assert_eq!(value("1 + 2 * 3 - 4"), Ok(3));
+    assert_eq!(value("-7 / 2"), Ok(-3));
+    assert_eq!(value("-7 % 2"), Ok(-1));
+    assert_eq!(value("~0"), Ok(-1));
+    assert_eq!(value("!5"), Ok(0));
+    assert_eq!(value("(1 < 2) + (2 <= 2) + (3 > 4) + (4 >= 5)"), Ok(2));
+    assert_eq!(value("1 == 1 && 2 != 2"), Ok(0));
+    assert_eq!(value("0 || 3"), Ok(1));
+    assert_eq!(value("6 & 3 | 8"), Ok(10));
+}
+
+#[test]
+fn short_circuits_logical_operators() {
+    assert_eq!(value("0 && 1 / 0"), Ok(0));
+    assert_eq!(value("2 || 1 / 0"), Ok(1));
+    assert!(matches!(
+        value("1 && 1 / 0"),
+        Err(ConstError::DivisionByZero { .. })
+    ));
+}
+
+#[test]
+fn reports_what_is_not_an_int() {
+    let Err(ConstError::DivisionByZero { span }) = value("1 + 6 % (2 - 2)") else {
+        panic!("expected a division by zero");
+    };
+    // The `%` in `int main(void) { return 1 + 6 % (2 - 2); }`.
+    assert_eq!((span.offset(), span.len()), (30, 1));
+    assert!(matches!(
+        value("2147483647 + 1"),
+        Err(ConstError::Overflow { .. })
+    ));
+}
+
+#[test]
+fn reads_the_negated_boundary_as_int_min() {
+    assert_eq!(value("-2147483648"), Ok(i32::MIN));
+    assert_eq!(value("-2147483648 + 1"), Ok(-2147483647));
+    // Negation wraps like the generated `negl`.
+    assert_eq!(value("- -2147483648"), Ok(i32::MIN));
+}
+
+#[test]
+fn rejects_variables() {
+    let Err(ConstError::NotConstant { span }) = value("0 || main") else {
+        panic!("expected a variable to be rejected");
+    };
+    assert_eq!((span.offset(), span.len()), (29, 4));
+    assert!(matches!(
+        value("1 && (main = 1)"),
+        Err(ConstError::NotConstant { .. })
+    ));
+}
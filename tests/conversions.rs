@@ -0,0 +1,137 @@
+//! Integer types, the integer promotions and the usual arithmetic
+//! conversions of the type checker.
+
+mod common;
+
+use ulang::{
+    ast::VarType,
+    parser::{Parser, ParserErrorType},
+    tacky::Tacky,
+    typecheck::common_type,
+    typed_ast::{TypedExpression, TypedExpressionKind, TypedProgram, TypedStatement},
+};
+
+/// The value returned by the last statement of the first function.
+fn returned(program: &TypedProgram) -> &TypedExpression {
+    let Some(TypedStatement::Return(Some(value), _)) = program.functions[0].body.last() else {
+        panic!("no returned value");
+    };
+    value
+}
+
+fn converted(expression: &TypedExpression) -> Option<&VarType> {
+    match &expression.kind {
+        TypedExpressionKind::Convert(inner) => Some(&inner.ty),
+        _ => None,
+    }
+}
+
+#[test]
+fn parses_the_integer_type_specifiers() {
+    let source = "long f(void) {\n    unsigned u;\n    long int l;\n    int unsigned long ul;\n    signed s;\n    return 0;\n}\n";
+    let program = common::check(source);
+    assert_eq!(program.functions[0].return_type, VarType::Long);
+    let types: Vec<_> = program
+        .symbols
+        .symbols()
+        .iter()
+        .map(|symbol| symbol.ty.clone())
+        .collect();
+    assert_eq!(
+        types,
+        [
+            VarType::Long,
+            VarType::UInt,
+            VarType::Long,
+            VarType::ULong,
+            VarType::Int
+        ]
+    );
+
+    for source in [
+        "int main(void) { long long a; }",
+        "int main(void) { signed unsigned a; }",
+    ] {
+        let (lexer, tokens) = common::lex(source);
+        let error = Parser::new(&tokens, &lexer.content).parse().unwrap_err();
+        assert!(matches!(
+            error.error,
+            ParserErrorType::ConflictingTypeSpecifier
+        ));
+    }
+}
+
+#[test]
+fn finds_the_common_type() {
+    use VarType::*;
+    assert_eq!(common_type(&Int, &Int), Int);
+    assert_eq!(common_type(&Int, &Long), Long);
+    assert_eq!(common_type(&Int, &UInt), UInt);
+    // long holds every unsigned int, but not every unsigned long.
+    assert_eq!(common_type(&UInt, &Long), Long);
+    assert_eq!(common_type(&ULong, &Long), ULong);
+    assert_eq!(common_type(&ULong, &Int), ULong);
+}
+
+#[test]
+fn converts_operands_to_the_common_type() {
+    let program =
+        common::check("int main(void) {\n    unsigned u = 1;\n    long l;\n    return l + u;\n}\n");
+    let value = returned(&program);
+    // The sum is converted to the return type.
+    assert_eq!(value.ty, VarType::Int);
+    assert_eq!(converted(value), Some(&VarType::Long));
+    let TypedExpressionKind::Convert(sum) = &value.kind else {
+        unreachable!();
+    };
+    let TypedExpressionKind::Binary(left, _, right, _) = &sum.kind else {
+        panic!("expected the sum");
+    };
+    assert_eq!(converted(left), None);
+    assert_eq!(right.ty, VarType::Long);
+    assert_eq!(converted(right), Some(&VarType::UInt));
+
+    // The constant 1 is an int converted to the type of `u`.
+    let TypedStatement::Declaration {
+        initializer: Some(initializer),
+        ..
+    } = &program.functions[0].body[0]
+    else {
+        panic!("expected the declaration of u");
+    };
+    assert_eq!(initializer.ty, VarType::UInt);
+    assert_eq!(converted(initializer), Some(&VarType::Int));
+}
+
+#[test]
+fn comparisons_and_logical_operators_yield_int() {
+    let program =
+        common::check("long main(void) {\n    unsigned long a;\n    return a < -1 && !a;\n}\n");
+    let value = returned(&program);
+    assert_eq!(converted(value), Some(&VarType::Int));
+    let TypedExpressionKind::Convert(and) = &value.kind else {
+        unreachable!();
+    };
+    let TypedExpressionKind::Binary(comparison, _, not, _) = &and.kind else {
+        panic!("expected &&");
+    };
+    assert_eq!((&comparison.ty, &not.ty), (&VarType::Int, &VarType::Int));
+    let TypedExpressionKind::Binary(_, _, minus_one, _) = &comparison.kind else {
+        panic!("expected <");
+    };
+    // -1 becomes a huge unsigned long, as in C.
+    assert_eq!(minus_one.ty, VarType::ULong);
+    assert_eq!(converted(minus_one), Some(&VarType::Int));
+}
+
+#[test]
+fn lowering_refuses_the_types_it_cannot_represent() {
+    let program = common::check("int main(void) {\n    long l = 2;\n    return 1;\n}\n");
+    let error = Tacky::new(&program).parse().unwrap_err();
+    assert_eq!(
+        error,
+        "Values of type `long` are not supported by the code generator yet"
+    );
+    let program = common::check("int main(void) {\n    int a = 2;\n    return -a + 1;\n}\n");
+    assert!(Tacky::new(&program).parse().is_ok());
+}
@@ -0,0 +1,146 @@
+//! Lexing with the whitespace kept and detokenizing gives back the file byte
+//! for byte, for generated programs and for random text made of the pieces
+//! the lexer treats specially: line continuations, trigraphs, digraphs,
+//! comments, directives and text that doesn't lex.
+
+use ulang::{
+    lexer::{Lexer, Token, Trivia},
+    synth::Generator,
+};
+
+fn round_trip(source: &str, trigraphs: bool) -> String {
+    let mut lexer = Lexer::from_content(source.to_string())
+        .with_whitespace(true)
+        .with_error_tokens(true)
+        .with_trigraphs(trigraphs);
+    let tokens = lexer.tokenize().unwrap();
+    for token in &tokens {
+        let written = &source[token.offset..token.offset + token.len];
+        let text = token
+            .spelling
+            .clone()
+            .unwrap_or_else(|| token.token.to_string());
+        assert_eq!(text, written, "in {:?}", source);
+    }
+    lexer.detokenize(&tokens)
+}
+
+#[test]
+fn gives_back_a_formatted_file() {
+    let source = "#include <stdio.h>\r\n\r\n// Answer.\nint main(void) {\n\t/* the */ return 0042; // x\n}\n";
+    assert_eq!(round_trip(source, false), source);
+
+    let mut lexer = Lexer::from_content(source.to_string()).with_whitespace(true);
+    let tokens = lexer.tokenize().unwrap();
+    assert_eq!(tokens[0].token, Token::IntKeyword);
+    assert_eq!(
+        tokens[0].leading_trivia,
+        [
+            Trivia::Directive("#include <stdio.h>\r".to_string()),
+            Trivia::Whitespace("\n\r\n".to_string()),
+            Trivia::LineComment("// Answer.".to_string()),
+            Trivia::Whitespace("\n".to_string()),
+        ]
+    );
+    assert_eq!(tokens[7].spelling.as_deref(), Some("0042"));
+    assert_eq!(
+        tokens[6].trailing_trivia,
+        [Trivia::Whitespace(" ".to_string())]
+    );
+    assert_eq!(
+        tokens[8].trailing_trivia,
+        [
+            Trivia::Whitespace(" ".to_string()),
+            Trivia::LineComment("// x".to_string())
+        ]
+    );
+    assert_eq!(lexer.end_trivia, [Trivia::Whitespace("\n".to_string())]);
+}
+
+#[test]
+fn keeps_the_spelling_of_spliced_and_translated_tokens() {
+    let source = "re\\\nturn ??< <% %> %:define X\n??/\n ;  ";
+    assert_eq!(round_trip(source, true), source);
+    // Without trigraphs the same text is mostly errors, which are kept too.
+    assert_eq!(round_trip(source, false), source);
+}
+
+#[test]
+fn gives_back_generated_programs() {
+    for seed in 0..50 {
+        let source = Generator::new(seed).program(3);
+        assert_eq!(round_trip(&source, false), source, "seed {}", seed);
+    }
+}
+
+const PIECES: [&str; 40] = [
+    "int",
+    "return",
+    "x1",
+    " ",
+    "  ",
+    "\t",
+    "\n",
+    "\r\n",
+    "\n\n",
+    "\\\n",
+    "\\\r\n",
+    "??/\n",
+    "??<",
+    "??=",
+    "<%",
+    "%>",
+    "%:",
+    "#define A 1",
+    "// c",
+    "/* b */",
+    "/*",
+    "*/",
+    "*",
+    "/",
+    "007",
+    "12ab",
+    "99999999999",
+    "1",
+    "@",
+    "$",
+    "\u{e9}",
+    "\u{a0}",
+    "+",
+    "++",
+    "=",
+    "==",
+    ";",
+    "{",
+    "}",
+    "'",
+];
+
+/// `count` random pieces glued together, the same for the same seed.
+fn random_text(seed: u64, count: usize) -> String {
+    let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    (0..count)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            PIECES[(state % PIECES.len() as u64) as usize]
+        })
+        .collect()
+}
+
+#[test]
+fn gives_back_any_text() {
+    for seed in 0..500 {
+        let source = random_text(seed, (seed % 40) as usize);
+        for trigraphs in [false, true] {
+            assert_eq!(
+                round_trip(&source, trigraphs),
+                source,
+                "seed {} with trigraphs {}",
+                seed,
+                trigraphs
+            );
+        }
+    }
+}
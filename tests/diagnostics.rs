@@ -0,0 +1,191 @@
+//! The stages reporting into collecting and streaming sinks.
+
+mod common;
+
+use miette::{Diagnostic, Severity};
+use ulang::{
+    ast::Identifier,
+    diagnostics::{Collect, Deduplicate, DiagnosticSink, SeverityCounts, Stream},
+    lexer::Lexer,
+    parser::Parser,
+    reachability::missing_returns,
+    tacky::Tacky,
+    typecheck::typecheck_into,
+};
+
+fn lexed(source: &str, sink: &mut dyn DiagnosticSink) -> Option<Lexer> {
+    let mut lexer = Lexer::from_content(source.to_string());
+    lexer.tokenize_into(sink).map(|_| lexer)
+}
+
+#[test]
+fn collects_every_lexer_error() {
+    let source = "int main(void) {\n    return 1 @ 2 $;\n}\n";
+    let mut sink = Collect::default();
+    assert!(lexed(source, &mut sink).is_none());
+    assert_eq!(sink.count(Severity::Error), 2);
+    assert!(sink.has_errors());
+
+    let json = sink.to_json(source).0;
+    assert!(json.contains("\"line\":2,\"column\":14"), "{}", json);
+    assert!(json.contains("\"line\":2,\"column\":18"), "{}", json);
+}
+
+#[test]
+fn reports_warnings_before_the_error() {
+    let source = "int main(void) {\n    return 1;\n    return 2 / x;\n}\n";
+    let mut lexer = Lexer::from_content(source.to_string());
+    let mut sink = Collect::default();
+    let tokens = lexer.tokenize_into(&mut sink).unwrap();
+    let ast = Parser::new(&tokens, &lexer.content)
+        .parse_into(&mut sink)
+        .unwrap();
+    assert!(typecheck_into(&ast, &mut sink).is_none());
+
+    let messages: Vec<String> = sink.diagnostics.iter().map(|d| d.to_string()).collect();
+    assert_eq!(messages, ["Unreachable code", "`x` is not declared"]);
+    assert_eq!(sink.count(Severity::Warning), 1);
+    assert_eq!(sink.count(Severity::Error), 1);
+}
+
+#[test]
+fn reports_errors_after_trailing_tokens() {
+    let source = "int main(void) {\n    return 0;\n}\n}\nint f(void) {\n    return 1 +;\n}\n";
+    let mut lexer = Lexer::from_content(source.to_string());
+    let mut sink = Collect::default();
+    let tokens = lexer.tokenize_into(&mut sink).unwrap();
+    assert!(Parser::new(&tokens, &lexer.content)
+        .parse_into(&mut sink)
+        .is_none());
+
+    let json = sink.to_json(source).0;
+    assert!(
+        json.contains("Unexpected tokens after the end of the program, found }"),
+        "{}",
+        json
+    );
+    assert!(json.contains("Missing return value, found ;"), "{}", json);
+    assert_eq!(sink.count(Severity::Error), 2);
+}
+
+#[test]
+fn streams_with_counts() {
+    let mut seen = vec![];
+    let mut sink = Stream::new(|diagnostic| seen.push(diagnostic.to_string()));
+
+    let source = "int f(void) {\n}\n";
+    let mut lexer = Lexer::from_content(source.to_string());
+    let tokens = lexer.tokenize_into(&mut sink).unwrap();
+    let ast = Parser::new(&tokens, &lexer.content)
+        .parse_into(&mut sink)
+        .unwrap();
+    let typed = typecheck_into(&ast, &mut sink).unwrap();
+    for warning in missing_returns(&ast, Identifier::from("main")) {
+        sink.report(Box::new(warning));
+    }
+    assert!(Tacky::new(&typed).parse_into(&mut sink).is_some());
+    assert!(lexed("int main(void) { return 0 }", &mut sink).is_some());
+    assert!(Parser::new(&[], "").parse_into(&mut sink).is_none());
+
+    assert_eq!(
+        sink.counts(),
+        SeverityCounts {
+            errors: 1,
+            warnings: 1,
+            advice: 0,
+        }
+    );
+    assert!(sink.has_errors());
+    assert_eq!(
+        seen,
+        [
+            "control reaches the end of non-void function `f`",
+            "Failed to parse the code"
+        ]
+    );
+}
+
+#[test]
+fn drops_the_same_diagnostic_at_the_same_span() {
+    let mut stream = Stream::new(|_| {});
+    let mut sink = Deduplicate::new(&mut stream);
+    assert!(lexed("int main(void) { return 1 @ 2 $; }", &mut sink).is_none());
+    assert!(lexed("int main(void) { return 1 @ 2 $; }", &mut sink).is_none());
+    // The same label, at another span.
+    assert!(lexed("int main(void) { return 1 @ 2 $$; }", &mut sink).is_none());
+    assert_eq!(sink.count(Severity::Error), 3);
+    assert_eq!(stream.counts().errors, 3);
+}
+
+#[test]
+fn holds_back_the_errors_past_the_limit() {
+    let source = "int f(void) {\n}\nint main(void) { return 1 @ 2 @ 3 @ 4; }\n";
+    let mut seen = vec![];
+    let mut stream = Stream::new(|diagnostic| seen.push(diagnostic.to_string()));
+    let mut sink = Deduplicate::new(&mut stream).with_limit(Some(1));
+    let mut lexer = Lexer::from_content(source.replace('@', "+"));
+    let tokens = lexer.tokenize_into(&mut sink).unwrap();
+    let ast = Parser::new(&tokens, &lexer.content)
+        .parse_into(&mut sink)
+        .unwrap();
+    let typed = typecheck_into(&ast, &mut sink).unwrap();
+    for warning in missing_returns(&ast, Identifier::from("main")) {
+        sink.report(Box::new(warning));
+    }
+    assert!(Tacky::new(&typed).parse_into(&mut sink).is_some());
+    assert!(lexed(source, &mut sink).is_none());
+    assert_eq!(sink.count(Severity::Error), 3);
+    assert_eq!(sink.hidden(), 2);
+    assert_eq!(
+        seen,
+        [
+            "control reaches the end of non-void function `f`",
+            "Failed to parse the code"
+        ]
+    );
+}
+
+#[test]
+fn sums_up_the_errors_and_warnings() {
+    let counts = |errors, warnings| SeverityCounts {
+        errors,
+        warnings,
+        advice: 2,
+    };
+    assert_eq!(counts(0, 0).summary(), None);
+    assert_eq!(
+        counts(1, 0).summary().unwrap(),
+        "1 error, 0 warnings emitted"
+    );
+    assert_eq!(
+        counts(3, 1).summary().unwrap(),
+        "3 errors, 1 warning emitted"
+    );
+}
+
+#[test]
+fn points_at_both_declarations_of_a_name() {
+    for (source, declaration) in [
+        ("int main(void) {\n    int a;\n    int a;\n}\n", "int a"),
+        ("int f(int a, int a) {\n    return a;\n}\n", "int a"),
+        ("int f(void) {\n}\nint f(void) {\n}\n", "int f"),
+    ] {
+        let error = common::try_check(source).unwrap_err();
+        let labels: Vec<(usize, String)> = error
+            .labels()
+            .unwrap()
+            .map(|label| (label.offset(), label.label().unwrap().to_string()))
+            .collect();
+        let first = source.find(declaration).unwrap() + "int ".len();
+        let again = source.rfind(declaration).unwrap() + "int ".len();
+        assert_eq!(
+            labels,
+            [
+                (again, "declared again here".to_string()),
+                (first, "first declared here".to_string())
+            ],
+            "{}",
+            source
+        );
+    }
+}
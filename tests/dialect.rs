@@ -0,0 +1,77 @@
+//! The rules of `--std`, checked on the tokens and the syntax tree.
+
+mod common;
+
+use ulang::{
+    ast::{AstNode, VarType},
+    dialect::{self, Standard},
+    lexer::Lexer,
+    parser::{Parser, ParserErrorType},
+};
+
+fn parse(source: &str, standard: Standard) -> (Lexer, Result<AstNode, ParserErrorType>) {
+    let (lexer, tokens) = common::lex(source);
+    let ast = Parser::new(&tokens, &lexer.content)
+        .with_standard(standard)
+        .parse()
+        .map_err(|e| e.error);
+    (lexer, ast)
+}
+
+#[test]
+fn parses_the_standard_names() {
+    assert_eq!("c89".parse(), Ok(Standard::C89));
+    assert_eq!("c90".parse(), Ok(Standard::C89));
+    assert_eq!("c11".parse(), Ok(Standard::C11));
+    assert!("gnu99".parse::<Standard>().is_err());
+    assert_eq!(Standard::C99.to_string(), "C99");
+}
+
+#[test]
+fn warns_about_line_comments_before_c99() {
+    let source = "// first\nint main(void) {\n    /* block */ return 2; // second\n}\n";
+    let (lexer, _) = parse(source, Standard::C89);
+    let warnings = dialect::line_comments(&lexer, Standard::C89);
+    let offsets: Vec<usize> = warnings.iter().map(|w| w.span.offset()).collect();
+    assert_eq!(offsets, [0, source.find("// second").unwrap()]);
+    assert_eq!(warnings[0].to_string(), "`//` comments are not part of C89");
+    assert!(dialect::line_comments(&lexer, Standard::C99).is_empty());
+}
+
+#[test]
+fn rejects_declarations_after_statements_before_c99() {
+    let source = "int main(void) {\n    int a = 1;\n    a = 2;\n    int b = a;\n    return b;\n}\n";
+    let (_, ast) = parse(source, Standard::C89);
+    let ast = ast.unwrap();
+    let errors = dialect::declarations_after_statements(&ast, Standard::C89);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].span.offset(), source.find("b =").unwrap());
+    assert_eq!(errors[0].statement.offset(), source.find("a = 2").unwrap());
+    assert_eq!(
+        errors[0].to_string(),
+        "C89 doesn't allow declarations after statements"
+    );
+    assert!(dialect::declarations_after_statements(&ast, Standard::C11).is_empty());
+}
+
+#[test]
+fn returns_int_without_a_return_type_only_in_c89() {
+    let source = "main(void) {\n    return 2;\n}\nsecond(void) {\n    return 3;\n}\n";
+    let (_, ast) = parse(source, Standard::C89);
+    let AstNode::Program(nodes) = ast.unwrap() else {
+        panic!("not a program");
+    };
+    assert_eq!(nodes.len(), 2);
+    let AstNode::FunctionDeclaration(function) = &nodes[0] else {
+        panic!("not a function");
+    };
+    assert_eq!(function.return_type, VarType::Int);
+
+    let (_, ast) = parse(source, Standard::C99);
+    let error = ast.unwrap_err();
+    assert!(matches!(error, ParserErrorType::ImplicitInt(Standard::C99)));
+    assert_eq!(
+        error.to_string(),
+        "Missing return type, C99 has no implicit int"
+    );
+}
@@ -0,0 +1,484 @@
+//! The `ulang_driver` subcommands, and its exit status for each stage that can
+//! fail.
+#![cfg(feature = "build-binary")]
+
+use std::{
+    process::{Command, Output},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static CASES: AtomicUsize = AtomicUsize::new(0);
+
+fn status(args: &[&str], source: &str) -> Option<i32> {
+    status_with_program_args(args, source, &[])
+}
+
+/// Like [`status`], with `program_args` after the file.
+fn status_with_program_args(args: &[&str], source: &str, program_args: &[&str]) -> Option<i32> {
+    output(args, source, program_args, &[]).status.code()
+}
+
+/// Runs the driver on `source` with `args` before the file, `program_args`
+/// after it and `envs` set, the toolchain variables of the test run unset.
+fn output(args: &[&str], source: &str, program_args: &[&str], envs: &[(&str, &str)]) -> Output {
+    let case = CASES.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("ulang-driver-{}-{}", std::process::id(), case));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("case.c");
+    std::fs::write(&file, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_ulang_driver"))
+        .args(args)
+        .arg(&file)
+        .args(program_args)
+        .env("ULANG_CACHE_DIR", dir.join("cache"))
+        .env_remove("CC")
+        .env_remove("LDFLAGS")
+        .env_remove("ULANG_TARGET")
+        .envs(envs.iter().copied())
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    output
+}
+
+#[test]
+fn exits_with_the_failing_stage() {
+    let valid = "int main(void) {\n    return 2;\n}\n";
+    assert_eq!(status(&["--tacky"], valid), Some(0));
+    assert_eq!(status(&["--tacky"], "int main(void) { $ }"), Some(2));
+    assert_eq!(status(&["--tacky"], "int main(void) { return 2 }"), Some(3));
+    assert_eq!(
+        status(&["--tacky"], "int main(void) { return x; }"),
+        Some(4)
+    );
+}
+
+#[test]
+fn warns_about_a_division_by_zero() {
+    // Only undefined if it runs, which it doesn't.
+    let source = "int main(void) {\n    return 0 && 1 / 0;\n}\n";
+    let checked = output(&["check", "--color", "never"], source, &[], &[]);
+    assert_eq!(checked.status.code(), Some(0));
+    let stderr = String::from_utf8_lossy(&checked.stderr);
+    assert!(stderr.contains("warning::division_by_zero"), "{}", stderr);
+    assert!(stderr.contains("divisor is zero"), "{}", stderr);
+    assert_eq!(status(&["check", "--deny-warnings"], source), Some(4));
+}
+
+#[test]
+fn checks_the_selected_standard() {
+    let commented = "int main(void) {\n    return 2; // two\n}\n";
+    assert_eq!(
+        status(&["--tacky", "--std=c99", "--deny-warnings"], commented),
+        Some(0)
+    );
+    assert_eq!(
+        status(&["--tacky", "--std=c89", "--deny-warnings"], commented),
+        Some(4)
+    );
+    let mixed = "int main(void) {\n    int a = 1;\n    a = 2;\n    int b = a;\n    return b;\n}\n";
+    assert_eq!(status(&["--tacky"], mixed), Some(0));
+    assert_eq!(status(&["--tacky", "--std=c89"], mixed), Some(3));
+    let implicit = "main(void) {\n    return 2;\n}\n";
+    assert_eq!(status(&["--tacky", "--std=c89"], implicit), Some(0));
+    assert_eq!(status(&["--tacky", "--std=c11"], implicit), Some(3));
+    assert_eq!(status(&["--tacky", "--std=c23"], implicit), Some(1));
+}
+
+#[test]
+fn translates_trigraphs_with_the_flag() {
+    let source = "int main(void) ??<\n    return 2;\n%>\n";
+    assert_eq!(status(&["--tacky"], source), Some(2));
+    assert_eq!(status(&["--tacky", "--trigraphs"], source), Some(0));
+}
+
+#[test]
+fn lexes_with_the_subcommand() {
+    let file = std::env::temp_dir().join(format!("ulang-driver-lex-{}.c", std::process::id()));
+    std::fs::write(&file, "int main(void) {\n    return 2;\n}\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_ulang_driver"))
+        .arg("lex")
+        .arg(&file)
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(&file);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "1:1\tIntKeyword");
+    assert_eq!(lines[7], "2:12\tConstant(2)");
+
+    assert_eq!(status(&["lex"], "int main(void) { $ }"), Some(2));
+}
+
+#[test]
+fn checks_without_generating_code() {
+    assert_eq!(status(&["check"], "int main(void) { return 2; }"), Some(0));
+    assert_eq!(status(&["check"], "int main(void) { return x; }"), Some(4));
+    assert_eq!(
+        status(
+            &["check", "--freestanding", "--entry", "kmain"],
+            "int main(void) { return 2; }"
+        ),
+        Some(4)
+    );
+    // A hosted build can pick the function to compile too.
+    let two = "int f(void) { return 1; }\nint main(void) { return 2; }";
+    let output = output(&["emit", "tacky", "--entry", "main"], two, &[], &[]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("function main"), "{}", stdout);
+    // Types the code generator can't lower yet only fail when generating code.
+    let long = "long main(void) { return 2; }";
+    assert_eq!(status(&["check"], long), Some(0));
+    assert_eq!(status(&["emit", "asm", "--no-cache"], long), Some(5));
+    let warns = "int f(void) {\n}\n";
+    assert_eq!(status(&["check"], warns), Some(0));
+    assert_eq!(status(&["check", "--deny-warnings"], warns), Some(4));
+    assert_eq!(
+        status(&["check", "--deny-warnings", "-A", "missing_return"], warns),
+        Some(0)
+    );
+}
+
+#[test]
+fn emits_to_stdout() {
+    let file = std::env::temp_dir().join(format!("ulang-driver-emit-{}.c", std::process::id()));
+    std::fs::write(&file, "int main(void) {\n    return 1 + 2;\n}\n").unwrap();
+    let emit = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_ulang_driver"))
+            .arg("emit")
+            .args(args)
+            .arg(&file)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let unoptimized = emit(&["tacky"]);
+    let folded = emit(&["tacky", "-O1"]);
+    let annotated = emit(&["tacky-text"]);
+    let printed = emit(&["c"]);
+    let calls = emit(&["callgraph-dot"]);
+    let sectioned = emit(&["asm", "--target", "x64-linux", "--function-sections"]);
+    let _ = std::fs::remove_file(&file);
+    assert!(unoptimized.contains("= 1 + 2"), "{}", unoptimized);
+    assert!(folded.contains("return 3"), "{}", folded);
+    assert!(annotated.contains("= 1 + 2  "), "{}", annotated);
+    assert!(annotated.contains("// 2:14\n"), "{}", annotated);
+    assert!(printed.starts_with("int main(void)"), "{}", printed);
+    assert!(
+        calls.contains("[label=\"main\\n2 instructions\"]"),
+        "{}",
+        calls
+    );
+    assert!(
+        sectioned.starts_with("\t.section\t.text.main,\"ax\",@progbits\n"),
+        "{}",
+        sectioned
+    );
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[test]
+fn runs_the_program() {
+    if !Command::new("gcc")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+    {
+        return;
+    }
+    assert_eq!(
+        status(&["run", "--no-cache"], "int main(void) { return 6 * 7; }"),
+        Some(42)
+    );
+    assert_eq!(
+        status(&["run"], "int main(void) { return 6 * 7 + 1; }"),
+        Some(43)
+    );
+    // `env` runs the program it is given, `false` ignores it.
+    let answer = "int main(void) { return 42; }";
+    assert_eq!(
+        status(&["run", "--runner", "env ULANG=1"], answer),
+        Some(42)
+    );
+    assert_eq!(status(&["run", "--runner", "false"], answer), Some(1));
+    assert_eq!(
+        status(&["run", "--no-cache", "--function-sections"], answer),
+        Some(42)
+    );
+    // Whatever follows `--` goes to the program.
+    let argc = "int main(int argc, char **argv) { return argc; }";
+    assert_eq!(
+        status_with_program_args(&["run", "--no-cache"], argc, &["--", "a", "--b"]),
+        Some(3)
+    );
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[test]
+fn archives_objects_into_a_static_library() {
+    let has = |tool: &str| {
+        Command::new(tool)
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    };
+    if !has("gcc") || !has("ar") {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("ulang-driver-archive-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("answer.c"), "int answer(void) { return 40; }").unwrap();
+    std::fs::write(dir.join("two.c"), "int two(void) { return 2; }").unwrap();
+    std::fs::write(
+        dir.join("main.c"),
+        "int answer(void); int two(void); int main(void) { return answer() + two(); }",
+    )
+    .unwrap();
+    let library = dir.join("libsupport.a");
+    let archived = Command::new(env!("CARGO_BIN_EXE_ulang_driver"))
+        .args(["archive", "--no-cache", "-o"])
+        .arg(&library)
+        .arg(dir.join("answer.c"))
+        .arg(dir.join("two.c"))
+        .status()
+        .unwrap();
+    assert!(archived.success());
+    let program = dir.join("main");
+    let linked = Command::new("gcc")
+        .arg(dir.join("main.c"))
+        .arg(&library)
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .unwrap();
+    assert!(linked.success());
+    let status = Command::new(&program).status().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert_eq!(status.code(), Some(42));
+}
+
+#[cfg(unix)]
+#[test]
+fn links_with_the_selected_compiler() {
+    let fake_cc = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_cc.sh");
+    let log = std::env::temp_dir().join(format!("ulang-driver-cc-{}.log", std::process::id()));
+    let answer = "int main(void) { return 42; }";
+    let args = ["build", "--no-cache", "--freestanding", "--cc", fake_cc];
+    let built = output(
+        &args,
+        answer,
+        &[],
+        &[("FAKE_CC_LOG", log.to_str().unwrap())],
+    );
+    assert_eq!(built.status.code(), Some(0));
+    // Warnings of the compiler are passed on.
+    assert!(String::from_utf8_lossy(&built.stderr).contains("fake cc: warning on stderr"));
+    let logged = std::fs::read_to_string(&log).unwrap();
+    let _ = std::fs::remove_file(&log);
+    let logged: Vec<&str> = logged.lines().collect();
+    assert!(logged[0].ends_with("case.s"), "{:?}", logged);
+    assert_eq!(logged[1], "-o");
+    assert_eq!(logged[3..], ["-nostdlib"]);
+
+    let failed = output(&args, answer, &[], &[("FAKE_CC_STATUS", "3")]);
+    assert_eq!(failed.status.code(), Some(6));
+    let stderr = String::from_utf8_lossy(&failed.stderr);
+    assert!(stderr.contains("failed with exit status 3"), "{}", stderr);
+    assert!(stderr.contains("fake cc: warning on stderr"), "{}", stderr);
+
+    let missing = ["build", "--no-cache", "--cc", "ulang-no-such-cc"];
+    let missing = output(&missing, answer, &[], &[]);
+    assert_eq!(missing.status.code(), Some(6));
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("Failed to run `ulang-no-such-cc"));
+}
+
+#[cfg(unix)]
+#[test]
+fn prints_the_commands_without_running_them() {
+    let fake_cc = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_cc.sh");
+    let log = std::env::temp_dir().join(format!("ulang-driver-dry-{}.log", std::process::id()));
+    let envs = [("FAKE_CC_LOG", log.to_str().unwrap())];
+    let answer = "int main(void) { return 42; }";
+    let lines = |output: Output| {
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        stdout.lines().map(str::to_string).collect::<Vec<_>>()
+    };
+
+    let args = ["build", "--no-cache", "--dry-run", "--cc", fake_cc];
+    let printed = lines(output(&args, answer, &[], &envs));
+    assert_eq!(printed.len(), 1, "{:?}", printed);
+    assert!(printed[0].starts_with(fake_cc));
+    assert!(printed[0].ends_with("/case"), "{:?}", printed);
+    assert!(!log.exists(), "the compiler ran");
+
+    // Only before `--`, the rest goes to the program.
+    let args = ["run", "--no-cache", "-###", "--cc", fake_cc];
+    let printed = lines(output(&args, answer, &["--", "-###"], &envs));
+    assert_eq!(printed.len(), 2, "{:?}", printed);
+    assert!(printed[1].ends_with("/case -###"), "{:?}", printed);
+    assert!(!log.exists(), "the compiler ran");
+}
+
+#[cfg(unix)]
+#[test]
+fn takes_the_toolchain_defaults_from_the_environment() {
+    let fake_cc = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fake_cc.sh");
+    let answer = "int main(void) { return 42; }";
+    let stdout = |args: &[&str], envs: &[(&str, &str)]| {
+        let output = output(args, answer, &[], envs);
+        assert_eq!(output.status.code(), Some(0));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let envs = [("CC", fake_cc), ("LDFLAGS", " -L/opt/lib  -lm ")];
+    let build = ["build", "--no-cache", "--dry-run"];
+    let printed = stdout(&build, &envs);
+    assert!(printed.starts_with(fake_cc), "{}", printed);
+    assert!(printed.ends_with("/case -L/opt/lib -lm\n"), "{}", printed);
+    // The command line wins.
+    let printed = stdout(&["build", "--no-cache", "--dry-run", "--cc", "cc"], &envs);
+    assert!(printed.starts_with("cc "), "{}", printed);
+
+    let asm = ["emit", "asm", "--no-cache"];
+    assert!(stdout(&asm, &[("ULANG_TARGET", "macos-x64")]).contains("_main:"));
+    let asm = ["emit", "asm", "--no-cache", "--target", "x64-linux"];
+    assert!(!stdout(&asm, &[("ULANG_TARGET", "macos-x64")]).contains("_main:"));
+    assert_eq!(
+        output(&["check"], answer, &[], &[("ULANG_TARGET", "vax")])
+            .status
+            .code(),
+        Some(1)
+    );
+}
+
+#[test]
+fn colors_the_diagnostics_when_asked() {
+    let undeclared = "int main(void) { return x; }";
+    let stderr = |args: &[&str]| {
+        let output = output(args, undeclared, &[], &[]);
+        assert_eq!(output.status.code(), Some(4));
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+    // Piped, `auto` falls back to plain ASCII like `never`.
+    for args in [&["check"][..], &["check", "--color", "never"]] {
+        let plain = stderr(args);
+        assert!(plain.is_ascii(), "{}", plain);
+        assert!(plain.contains("`x` is not declared"), "{}", plain);
+        assert!(plain.contains(",-["), "{}", plain);
+    }
+    let fancy = stderr(&["check", "--color=always"]);
+    assert!(fancy.contains("\x1b["), "{}", fancy);
+    assert!(fancy.contains("\x1b]8;;file://"), "{}", fancy);
+    assert!(fancy.contains("╭─["), "{}", fancy);
+}
+
+#[test]
+fn limits_the_errors_and_sums_them_up() {
+    let stray = format!("int main(void) {{ return 1 {}; }}", "@ ".repeat(25));
+    let stderr = |args: &[&str]| {
+        let output = output(args, &stray, &[], &[]);
+        assert_eq!(output.status.code(), Some(2));
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+    let errors = |stderr: &str| stderr.matches("Unrecognized char").count();
+    let limited = stderr(&["check"]);
+    assert_eq!(errors(&limited), 20);
+    assert!(limited
+        .ends_with("20 errors, 0 warnings emitted, 5 more errors not shown, see --error-limit\n"));
+    let limited = stderr(&["check", "--error-limit", "1"]);
+    assert_eq!(errors(&limited), 1);
+    let all = stderr(&["check", "--error-limit", "0"]);
+    assert_eq!(errors(&all), 25);
+    assert!(
+        all.ends_with("\n25 errors, 0 warnings emitted\n"),
+        "{}",
+        all
+    );
+
+    let missing_return = "int f(void) {\n}\nint main(void) { return 0; }\n";
+    let output = output(&["check"], missing_return, &[], &[]);
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.ends_with("\n0 errors, 1 warning emitted\n"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn evaluates_an_expression() {
+    let eval = |expression: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_ulang_driver"))
+            .args(["--color", "never", "--eval", expression])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        (output.status.code(), stdout)
+    };
+    assert_eq!(eval("1 + 2 * (3 - 1)"), (Some(0), "5\n".to_string()));
+    assert_eq!(
+        eval("-2147483648 / 2"),
+        (Some(0), "-1073741824\n".to_string())
+    );
+    assert_eq!(eval("(1 < 2) == !0 || 1 / 0"), (Some(0), "1\n".to_string()));
+    assert_eq!(eval("1 @ 2").0, Some(2));
+    assert_eq!(eval("1 +").0, Some(3));
+    assert_eq!(eval("1 2").0, Some(3));
+    assert_eq!(eval("x + 1").0, Some(4));
+    assert_eq!(eval("1 / 0").0, Some(4));
+    assert_eq!(
+        status(&["--eval", "1"], "int main(void) { return 0; }"),
+        Some(1)
+    );
+}
+
+#[test]
+fn compares_the_assembly_of_two_optimization_levels() {
+    let source = "int main(void) {\n    int x = 2 * 3 + 4;\n    return x - 1;\n}\n";
+    let diff = output(
+        &["emit", "asm", "--compare", "-O0", "-O1"],
+        source,
+        &[],
+        &[],
+    );
+    assert_eq!(diff.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&diff.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].starts_with("--- ") && lines[0].ends_with("case.c -O0"));
+    assert!(lines[1].starts_with("+++ ") && lines[1].ends_with("case.c -O1"));
+    assert!(lines[2].starts_with("@@ -"), "{}", stdout);
+    // The constants are folded into the return value.
+    assert!(lines.contains(&"-\timull\t$3, %r11d"), "{}", stdout);
+    assert!(lines.contains(&"+\tmovl\t$9, %eax"), "{}", stdout);
+    assert!(lines.contains(&" main:"), "{}", stdout);
+
+    let same = output(&["emit", "asm", "--compare", "O1", "1"], source, &[], &[]);
+    assert_eq!(same.status.code(), Some(0));
+    assert!(same.stdout.is_empty());
+    assert_eq!(
+        status(&["emit", "asm", "--compare", "-O0", "-O3"], source),
+        Some(1)
+    );
+    assert_eq!(
+        status(&["emit", "asm", "-O1", "--compare", "-O0", "-O2"], source),
+        Some(1)
+    );
+}
+
+#[test]
+fn exits_with_one_on_usage_errors() {
+    let valid = "int main(void) {\n    return 2;\n}\n";
+    assert_eq!(status(&["--no-such-flag"], valid), Some(1));
+    assert_eq!(status(&["--lex", "--parse"], valid), Some(1));
+
+    let driver = env!("CARGO_BIN_EXE_ulang_driver");
+    let missing = Command::new(driver).arg("missing.c").output().unwrap();
+    assert_eq!(missing.status.code(), Some(1));
+    let help = Command::new(driver).arg("--help").output().unwrap();
+    assert_eq!(help.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&help.stdout).contains("Exit status:"));
+}
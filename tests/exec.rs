@@ -0,0 +1,240 @@
+//! Execution tests: compiles the programs listed in `tests/exec/manifest.txt`,
+//! runs them and checks their exit status.
+//!
+//! A case may list `--checked-arithmetic` after the status to be compiled with
+//! runtime checks, the interpreter then reports a division by zero or an
+//! overflow with the trap exit status, and `-O1` or `-O2` to be optimized,
+//! with the TACKY verified around every pass.
+//!
+//! Every case is also run through the TACKY interpreter, so the lowering is
+//! checked even on hosts without a C toolchain to assemble and link with, in
+//! which case the native runs are skipped. The interpreter runs the program a
+//! second time after a round trip through SSA form. With the `jit` feature
+//! the cases without runtime checks also run in process.
+//!
+//! The native programs run under the emulator given with `--runner=<cmd>`,
+//! as in `cargo test --test exec -- --runner=qemu-x86_64`, or in
+//! `ULANG_RUNNER`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, ExitCode},
+};
+
+use ulang::{
+    assembly::{generate_optimized_assembly, RuntimeChecks, TargetPlatform, TRAP_EXIT_STATUS},
+    lexer::Lexer,
+    optimize::{OptimizationLevel, PassManager},
+    parser::Parser,
+    runner,
+    tacky::{
+        interp::{InterpretError, Interpreter},
+        Tacky, TackyProgram,
+    },
+    typecheck::typecheck,
+};
+
+#[cfg(target_os = "linux")]
+const TARGET: TargetPlatform = TargetPlatform::X64Linux;
+#[cfg(not(target_os = "linux"))]
+const TARGET: TargetPlatform = TargetPlatform::MacOsX64;
+
+struct Case {
+    source: PathBuf,
+    expected_status: i32,
+    checked_arithmetic: bool,
+    opt_level: OptimizationLevel,
+}
+
+fn read_manifest(dir: &Path) -> Vec<Case> {
+    let manifest = fs::read_to_string(dir.join("manifest.txt")).unwrap();
+    manifest
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (source, status, flags) = match &words[..] {
+                [source, status, flags @ ..] => (source, status, flags),
+                _ => panic!("expected `<source> <status> [flags]`, found `{}`", line),
+            };
+            let mut case = Case {
+                source: dir.join(source),
+                expected_status: status.parse().unwrap(),
+                checked_arithmetic: false,
+                opt_level: OptimizationLevel::O0,
+            };
+            for flag in flags {
+                match flag.strip_prefix("-O") {
+                    _ if *flag == "--checked-arithmetic" => case.checked_arithmetic = true,
+                    Some(level) => case.opt_level = level.parse().unwrap(),
+                    None => panic!("unknown flag `{}` in `{}`", flag, line),
+                }
+            }
+            case
+        })
+        .collect()
+}
+
+fn compile_to_tacky(source: &Path, checked_arithmetic: bool) -> Result<TackyProgram, String> {
+    let mut lexer = Lexer::from_path(source.to_path_buf()).map_err(|e| e.to_string())?;
+    let tokens = lexer.tokenize().map_err(|e| e.error.to_string())?;
+    let ast = Parser::new(&tokens, &lexer.content)
+        .parse()
+        .map_err(|e| e.error.to_string())?;
+    let typed = typecheck(&ast).map_err(|e| e.to_string())?;
+    let mut tacky = Tacky::new(&typed);
+    if checked_arithmetic {
+        tacky = tacky.with_source_locations(&lexer.content);
+    }
+    tacky.parse()
+}
+
+fn interpret(tacky: &TackyProgram, checked_arithmetic: bool) -> Result<i32, String> {
+    let mut interpreter = Interpreter::new(tacky);
+    interpreter.trap_overflow = checked_arithmetic;
+    // The `argc` of the native runs, which get no arguments.
+    interpreter.arguments = vec![1];
+    // The parent only sees the low byte of the value returned from main.
+    match interpreter.run() {
+        Ok(value) => Ok(value & 0xff),
+        Err(InterpretError::DivisionByZero | InterpretError::Overflow) if checked_arithmetic => {
+            Ok(TRAP_EXIT_STATUS)
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// C compiler driver used to assemble and link, if there is one.
+fn find_linker() -> Option<&'static str> {
+    ["cc", "gcc", "clang"].into_iter().find(|cc| {
+        Command::new(cc)
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    })
+}
+
+fn run_native(
+    linker: &str,
+    runner: Option<&str>,
+    tacky: &TackyProgram,
+    passes: &PassManager,
+    checks: &RuntimeChecks,
+    work_dir: &Path,
+    name: &str,
+) -> Result<i32, String> {
+    let assembly = work_dir.join(name).with_extension("s");
+    let binary = work_dir.join(name);
+    let generated = generate_optimized_assembly(tacky, TARGET, passes, checks);
+    fs::write(&assembly, generated.0).map_err(|e| e.to_string())?;
+    let output = Command::new(linker)
+        .arg(&assembly)
+        .arg("-o")
+        .arg(&binary)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed:\n{}",
+            linker,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    let status = runner::command(runner, &binary)
+        .status()
+        .map_err(|e| e.to_string())?;
+    status
+        .code()
+        .ok_or_else(|| format!("terminated by a signal: {}", status))
+}
+
+fn main() -> ExitCode {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/exec");
+    let cases = read_manifest(&dir);
+    let runner = std::env::args()
+        .find_map(|arg| arg.strip_prefix("--runner=").map(str::to_string))
+        .or_else(|| std::env::var("ULANG_RUNNER").ok());
+    let linker = find_linker();
+    if linker.is_none() {
+        println!("no C compiler found, only the interpreter runs the programs");
+    }
+    let work_dir = std::env::temp_dir().join(format!("ulang-exec-{}", std::process::id()));
+    fs::create_dir_all(&work_dir).unwrap();
+
+    let mut failures = 0;
+    for case in &cases {
+        let name = case.source.file_stem().unwrap().to_str().unwrap();
+        let level = case.opt_level;
+        let mut check = |runner: &str, result: Result<i32, String>| match result {
+            Ok(status) if status == case.expected_status => {}
+            Ok(status) => {
+                failures += 1;
+                println!(
+                    "FAILED {} -O{} ({}): expected status {}, got {}",
+                    name, level, runner, case.expected_status, status
+                );
+            }
+            Err(e) => {
+                failures += 1;
+                println!("FAILED {} -O{} ({}): {}", name, level, runner, e);
+            }
+        };
+
+        let passes = PassManager::new(case.opt_level);
+        let optimized =
+            compile_to_tacky(&case.source, case.checked_arithmetic).and_then(|mut tacky| {
+                passes.run_tacky(&mut tacky)?;
+                Ok(tacky)
+            });
+        let tacky = match optimized {
+            Ok(tacky) => tacky,
+            Err(e) => {
+                check("compile", Err(e));
+                continue;
+            }
+        };
+        check("interpreter", interpret(&tacky, case.checked_arithmetic));
+        let ssa = tacky.to_ssa();
+        let round_trip = match ssa.verify() {
+            Ok(()) => interpret(&ssa.into_tacky(), case.checked_arithmetic),
+            Err(e) => Err(e.to_string()),
+        };
+        check("ssa", round_trip);
+        #[cfg(all(feature = "jit", target_arch = "x86_64", unix))]
+        if !case.checked_arithmetic {
+            let result = ulang::jit::run(&tacky, &passes);
+            check(
+                "jit",
+                result.map(|value| value & 0xff).map_err(|e| e.to_string()),
+            );
+        }
+        if let Some(linker) = linker {
+            let checks = RuntimeChecks {
+                division_by_zero: case.checked_arithmetic,
+                overflow: case.checked_arithmetic,
+                file_name: name.to_string(),
+            };
+            check(
+                "native",
+                run_native(
+                    linker,
+                    runner.as_deref(),
+                    &tacky,
+                    &passes,
+                    &checks,
+                    &work_dir,
+                    name,
+                ),
+            );
+        }
+    }
+    let _ = fs::remove_dir_all(&work_dir);
+
+    println!("exec: {} cases, {} failures", cases.len(), failures);
+    if failures > 0 {
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,4 @@
+int main(int argc, char **argv) {
+    // Run without arguments, only the program name is counted.
+    return argc + 40;
+}
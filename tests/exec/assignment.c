@@ -0,0 +1,7 @@
+int main(void) {
+    int a;
+    int b = 2;
+    a = b = b * 3;
+    (a) = a + 1;
+    return a + b;
+}
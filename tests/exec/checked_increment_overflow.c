@@ -0,0 +1,5 @@
+int main(void) {
+    int a = 2147483647;
+    a++;
+    return 0;
+}
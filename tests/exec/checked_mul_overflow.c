@@ -0,0 +1,3 @@
+int main(void) {
+    return 65536 * 65536;
+}
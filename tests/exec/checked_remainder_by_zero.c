@@ -0,0 +1,3 @@
+int main(void) {
+    return 10 % (3 - 3 * 1);
+}
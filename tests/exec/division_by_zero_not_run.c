@@ -0,0 +1,4 @@
+int main(void) {
+    // A warning only: the division never runs.
+    return 0 && 1 / 0;
+}
@@ -0,0 +1,5 @@
+int main(void) {
+    int small = 1;
+    int large = 2;
+    return (small <= large) + (large <= small) * 2 + (small <= small) * 4;
+}
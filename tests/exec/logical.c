@@ -0,0 +1,3 @@
+int main(void) {
+    return (0 && 1) + (0 || 0) * 2 + (3 && 4) * 4 + (0 || 5) * 8;
+}
@@ -0,0 +1,7 @@
+int main(void) {
+    int a = 5;
+    int b = a;
+    a = 7;
+    b = b + (a = a * 2);
+    return a * 10 + b;
+}
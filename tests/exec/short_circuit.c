@@ -0,0 +1,13 @@
+int main(void) {
+    int a = 0;
+    int b = 0;
+    int one = 1;
+    // The right operand only runs when the left one doesn't decide.
+    int c = a && (b = 1);
+    int d = one || (b = b + 2);
+    int e = one && (b = b + 4);
+    int f = a || (b = b + 8);
+    // Never divides.
+    int g = a && (one / a);
+    return b * 10 + c + d + e + f + g;
+}
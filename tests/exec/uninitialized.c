@@ -0,0 +1,6 @@
+int main(void) {
+    // Reading a variable nothing wrote is valid, only its value isn't known.
+    int a;
+    a = a + 1;
+    return 3;
+}
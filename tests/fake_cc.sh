@@ -0,0 +1,10 @@
+#!/bin/sh
+# Stands in for the C compiler in the toolchain tests: logs its arguments one
+# per line to $FAKE_CC_LOG, prints to both streams and exits with
+# $FAKE_CC_STATUS, 0 by default.
+if [ -n "$FAKE_CC_LOG" ]; then
+    printf '%s\n' "$@" > "$FAKE_CC_LOG"
+fi
+echo "fake cc ran"
+echo "fake cc: warning on stderr" >&2
+exit "${FAKE_CC_STATUS:-0}"
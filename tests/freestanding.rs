@@ -0,0 +1,115 @@
+//! Compiling a chosen entry point, and programs linked without the C runtime
+//! that start in the `_start` of `--freestanding --start`.
+
+mod common;
+
+use ulang::{
+    assembly::{generate_assembly, generate_start, TargetPlatform},
+    ast::Identifier,
+    reachability::missing_returns,
+    tacky::{interp::Interpreter, Tacky, TackyProgram},
+    typecheck::typecheck,
+};
+
+const SOURCE: &str = "int helper(void) {
+    return 1;
+}
+int kmain(void) {
+}
+";
+
+fn lower(source: &str, entry: Option<&str>) -> (Result<TackyProgram, String>, usize) {
+    let ast = common::parse(source);
+    let typed = typecheck(&ast).unwrap();
+    let mut tacky = Tacky::new(&typed);
+    if let Some(entry) = entry {
+        tacky = tacky.with_entry(Identifier::from(entry));
+    }
+    let warnings = missing_returns(&ast, Identifier::from(entry.unwrap_or("main")));
+    (tacky.parse(), warnings.len())
+}
+
+/// Lines of the assembly with whitespace normalized.
+fn lines(assembly: &str) -> Vec<String> {
+    assembly
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect()
+}
+
+#[test]
+fn lowers_the_entry_point() {
+    let (tacky, warnings) = lower(SOURCE, Some("kmain"));
+    let tacky = tacky.unwrap();
+    assert_eq!(tacky.0.identifier, Identifier::from("kmain"));
+    assert_eq!(Interpreter::new(&tacky).run().unwrap(), 0);
+    // Running off the end of the entry point is like `main`.
+    assert_eq!(warnings, 0);
+
+    let (tacky, _) = lower(SOURCE, None);
+    assert_eq!(tacky.unwrap().0.identifier, Identifier::from("helper"));
+
+    let (tacky, _) = lower(SOURCE, Some("nope"));
+    assert_eq!(tacky.unwrap_err(), "No entry function `nope`");
+}
+
+#[test]
+fn starts_with_the_exit_system_call() {
+    let entry = Identifier::from("kmain");
+    assert_eq!(
+        lines(&generate_start(&entry, TargetPlatform::X64Linux)),
+        [
+            ".text",
+            ".globl _start",
+            ".type _start, @function",
+            "_start:",
+            "xorl %ebp, %ebp",
+            "call kmain",
+            "movl %eax, %edi",
+            "movl $60, %eax",
+            "syscall"
+        ]
+    );
+    let macos = lines(&generate_start(&entry, TargetPlatform::MacOsX64));
+    assert!(macos.contains(&"start:".to_string()));
+    assert!(macos.contains(&"call _kmain".to_string()));
+    assert!(macos.contains(&"movl $33554433, %eax".to_string()));
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[test]
+fn runs_without_the_c_runtime() {
+    use std::process::Command;
+
+    let Some(cc) = ["cc", "gcc", "clang"].into_iter().find(|cc| {
+        Command::new(cc)
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }) else {
+        return;
+    };
+    let source = "int kmain(void) {\n    return 6 * 7;\n}\n";
+    let tacky = lower(source, Some("kmain")).0.unwrap();
+    let mut assembly = generate_assembly(&tacky, TargetPlatform::X64Linux).0;
+    assembly += &generate_start(&tacky.0.identifier, TargetPlatform::X64Linux);
+
+    let dir = std::env::temp_dir().join(format!("ulang-freestanding-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("kmain.s"), assembly).unwrap();
+    let linked = Command::new(cc)
+        .arg(dir.join("kmain.s"))
+        .arg("-nostdlib")
+        .arg("-o")
+        .arg(dir.join("kmain"))
+        .output()
+        .unwrap();
+    let run = Command::new(dir.join("kmain")).output();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(
+        linked.status.success(),
+        "{}",
+        String::from_utf8_lossy(&linked.stderr)
+    );
+    assert_eq!(run.unwrap().status.code(), Some(42));
+}
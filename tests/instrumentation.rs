@@ -0,0 +1,240 @@
+//! Counters compiled in by `--instrument-functions` and `--coverage`.
+//!
+//! Besides checking the text, the programs are linked and run with the system
+//! C compiler when there is one, to read the counts they write at exit.
+
+mod common;
+
+use std::process::Command;
+
+use ulang::{
+    assembly::{
+        generate_assembly_with_source_map, FunctionSections, Instrumentation, RuntimeChecks,
+        TargetPlatform,
+    },
+    coverage::{annotate, parse_counts, CoverageError},
+    optimize::PassManager,
+};
+
+const SOURCE: &str = "int main(void) {
+    return 3;
+}
+";
+
+const BRANCHES: &str = "int main(void) {
+    return 0 &&
+        4 / 2;
+}
+";
+
+/// Line 3 never runs.
+const DEAD_CODE: &str = "int main(void) {
+    return 0;
+    return 4 / 2;
+}
+";
+
+fn compile(instrumentation: &Instrumentation, platform: TargetPlatform) -> String {
+    compile_source(SOURCE, instrumentation, &RuntimeChecks::default(), platform)
+}
+
+fn compile_source(
+    source: &str,
+    instrumentation: &Instrumentation,
+    checks: &RuntimeChecks,
+    platform: TargetPlatform,
+) -> String {
+    let tacky = common::lower_with_locations(source);
+    let (assembly, _) = generate_assembly_with_source_map(
+        &tacky,
+        platform,
+        &PassManager::default(),
+        checks,
+        instrumentation,
+        FunctionSections::Shared,
+    );
+    assembly.0
+}
+
+fn find_cc() -> Option<&'static str> {
+    ["cc", "gcc", "clang"].into_iter().find(|cc| {
+        Command::new(cc)
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    })
+}
+
+/// Links `assembly` in a fresh directory and runs it there.
+fn link_and_run(cc: &str, assembly: &str, dir: &std::path::Path) -> std::process::Output {
+    std::fs::create_dir_all(dir).unwrap();
+    let source = dir.join("instrumented.s");
+    let binary = dir.join("instrumented");
+    std::fs::write(&source, assembly).unwrap();
+    let linked = Command::new(cc)
+        .arg(&source)
+        .arg("-o")
+        .arg(&binary)
+        .output()
+        .unwrap();
+    assert!(
+        linked.status.success(),
+        "{}",
+        String::from_utf8_lossy(&linked.stderr)
+    );
+    Command::new(&binary).output().unwrap()
+}
+
+#[cfg(target_os = "linux")]
+const HOST: TargetPlatform = TargetPlatform::X64Linux;
+#[cfg(not(target_os = "linux"))]
+const HOST: TargetPlatform = TargetPlatform::MacOsX64;
+
+/// Lines of the assembly with whitespace normalized.
+fn lines(assembly: &str) -> Vec<String> {
+    assembly
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect()
+}
+
+fn count_functions() -> Instrumentation {
+    Instrumentation {
+        functions: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn counts_calls_after_the_prologue() {
+    let lines = lines(&compile(&count_functions(), TargetPlatform::X64Linux));
+    let entry = lines.iter().position(|line| line == "main:").unwrap();
+    assert_eq!(
+        lines[entry + 1..entry + 5],
+        [
+            "pushq %rbp",
+            "movq %rsp, %rbp",
+            "subq $0, %rsp",
+            "incq main.calls.0(%rip)"
+        ]
+    );
+    let bss = lines.iter().position(|line| line == ".bss").unwrap();
+    assert_eq!(
+        lines[bss..bss + 6],
+        [
+            ".bss",
+            ".balign 8",
+            ".type main.calls.0, @object",
+            ".size main.calls.0, 8",
+            "main.calls.0:",
+            ".zero 8"
+        ]
+    );
+    assert!(lines.contains(&".section .init_array,\"aw\"".to_string()));
+    assert!(lines.contains(&"call atexit@PLT".to_string()));
+    assert!(lines.contains(&".asciz \"%lu\\011main\\n\"".to_string()));
+}
+
+#[test]
+fn macos_registers_the_dump_as_an_initializer() {
+    let lines = lines(&compile(&count_functions(), TargetPlatform::MacOsX64));
+    assert!(lines.contains(&"incq _main.calls.0(%rip)".to_string()));
+    assert!(lines.contains(&".section __DATA,__mod_init_func,mod_init_funcs".to_string()));
+    assert!(lines.contains(&"call _atexit".to_string()));
+    assert!(lines.contains(&"call _dprintf".to_string()));
+}
+
+#[test]
+fn nothing_is_added_by_default() {
+    let assembly = compile(&Instrumentation::default(), TargetPlatform::X64Linux);
+    assert!(!assembly.contains("calls"));
+    assert!(!assembly.contains(".bss"));
+    assert!(!assembly.contains("atexit"));
+}
+
+#[test]
+fn prints_the_counts_at_exit() {
+    let Some(cc) = find_cc() else {
+        return;
+    };
+    let dir = std::env::temp_dir().join(format!("ulang-profile-{}", std::process::id()));
+    let run = link_and_run(cc, &compile(&count_functions(), HOST), &dir);
+    let _ = std::fs::remove_dir_all(&dir);
+    assert_eq!(run.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&run.stderr), "1\tmain\n");
+}
+
+fn coverage(file: &str) -> Instrumentation {
+    Instrumentation {
+        coverage: Some(file.to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn counts_blocks_at_labels_and_after_jumps() {
+    let checks = RuntimeChecks {
+        division_by_zero: true,
+        ..Default::default()
+    };
+    let assembly = compile_source(BRANCHES, &coverage("branches.ulangcov"), &checks, HOST);
+    let lines = lines(&assembly);
+    let increments: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.starts_with("incq"))
+        .map(|(i, _)| i)
+        .collect();
+    // Entry, the right side, the false and true results and the join.
+    assert_eq!(increments.len(), 5);
+    for i in &increments[1..] {
+        let before = &lines[i - 1];
+        assert!(
+            before.starts_with(".Lmain.label") || before.starts_with('j'),
+            "{} follows {}",
+            lines[*i],
+            before
+        );
+    }
+    // The division check jumps to a trap, which does not end the block.
+    let check = lines
+        .iter()
+        .position(|line| line.starts_with("je .Lmain.trap"));
+    assert!(!lines[check.unwrap() + 1].starts_with("incq"));
+}
+
+#[test]
+fn writes_the_coverage_file_at_exit() {
+    let Some(cc) = find_cc() else {
+        return;
+    };
+    let dir = std::env::temp_dir().join(format!("ulang-coverage-{}", std::process::id()));
+    let file = dir.join("dead_code.ulangcov");
+    let assembly = compile_source(
+        DEAD_CODE,
+        &coverage(&file.display().to_string()),
+        &RuntimeChecks::default(),
+        HOST,
+    );
+    let run = link_and_run(cc, &assembly, &dir);
+    let written = std::fs::read_to_string(&file);
+    let _ = std::fs::remove_dir_all(&dir);
+    assert_eq!(run.status.code(), Some(0));
+    let counts = parse_counts(&written.unwrap()).unwrap();
+    assert_eq!(counts.get(&2), Some(&1));
+    assert_eq!(counts.get(&3), Some(&0));
+}
+
+#[test]
+fn annotates_the_source_like_gcov() {
+    let counts = parse_counts("2\t1\n3\t0\n2\t0\n").unwrap();
+    assert_eq!(
+        annotate(DEAD_CODE, &counts),
+        "        -:    1:int main(void) {
+        1:    2:    return 0;
+    #####:    3:    return 4 / 2;
+        -:    4:}
+"
+    );
+    assert_eq!(parse_counts("2 1\n"), Err(CoverageError::Malformed(1)));
+}
@@ -0,0 +1,157 @@
+//! In process execution with the `jit` feature: the encoding of single
+//! instructions, and whole programs checked against the interpreter.
+#![cfg(all(feature = "jit", target_arch = "x86_64", unix))]
+
+mod common;
+
+use ulang::{
+    assembly::{
+        AsmFunctionDef, AsmInstruction, AsmRegistry, AsmType, ConditionCode, Operand, Trap,
+    },
+    ast::Identifier,
+    jit::{encode, JitError},
+    optimize::{OptimizationLevel, PassManager},
+    synth::Generator,
+    tacky::interp::Interpreter,
+};
+
+use AsmRegistry::{AX, BP, DX, R10, R11, SP};
+
+fn function(instructions: Vec<AsmInstruction>) -> AsmFunctionDef {
+    AsmFunctionDef {
+        name: Identifier::from("main"),
+        instructions,
+        traps: vec![],
+        counters: vec![],
+    }
+}
+
+#[test]
+fn encodes_the_prologue_and_epilogue() {
+    let code = encode(&function(vec![
+        AsmInstruction::Push(BP),
+        AsmInstruction::MovQ { src: SP, dst: BP },
+        AsmInstruction::AllocateStack(16),
+        AsmInstruction::Mov {
+            ty: AsmType::Longword,
+            src: Operand::Imm(7),
+            dst: AX.into(),
+        },
+        AsmInstruction::MovQ { src: BP, dst: SP },
+        AsmInstruction::Pop(BP),
+        AsmInstruction::Ret,
+    ]))
+    .unwrap();
+    assert_eq!(
+        code,
+        [
+            0x55, 0x48, 0x89, 0xe5, 0x48, 0x81, 0xec, 0x10, 0x00, 0x00, 0x00, 0xb8, 0x07, 0x00,
+            0x00, 0x00, 0x48, 0x89, 0xec, 0x5d, 0xc3
+        ]
+    );
+}
+
+#[test]
+fn encodes_extended_registers_and_stack_slots() {
+    let encoded = |instruction| encode(&function(vec![instruction])).unwrap();
+    // movl %r10d, -8(%rbp)
+    assert_eq!(
+        encoded(AsmInstruction::Mov {
+            ty: AsmType::Longword,
+            src: R10.into(),
+            dst: Operand::Stack(-8),
+        }),
+        [0x44, 0x89, 0x95, 0xf8, 0xff, 0xff, 0xff]
+    );
+    // imull -4(%rbp), %r11d
+    assert_eq!(
+        encoded(AsmInstruction::Binary(
+            ulang::assembly::AsmBinaryOperator::Mult,
+            AsmType::Longword,
+            Operand::Stack(-4),
+            R11.into(),
+        )),
+        [0x44, 0x0f, 0xaf, 0x9d, 0xfc, 0xff, 0xff, 0xff]
+    );
+    // sall $3, -4(%rbp)
+    assert_eq!(
+        encoded(AsmInstruction::Binary(
+            ulang::assembly::AsmBinaryOperator::Shl,
+            AsmType::Longword,
+            Operand::Imm(3),
+            Operand::Stack(-4),
+        )),
+        [0xc1, 0xa5, 0xfc, 0xff, 0xff, 0xff, 0x03]
+    );
+    // leal 8(%rax), %edx
+    assert_eq!(
+        encoded(AsmInstruction::Lea {
+            base: AX,
+            offset: 8,
+            dst: DX,
+        }),
+        [0x8d, 0x90, 0x08, 0x00, 0x00, 0x00]
+    );
+    // sete -4(%rbp)
+    assert_eq!(
+        encoded(AsmInstruction::SetCC(ConditionCode::E, Operand::Stack(-4))),
+        [0x0f, 0x94, 0x85, 0xfc, 0xff, 0xff, 0xff]
+    );
+}
+
+#[test]
+fn resolves_jumps_in_both_directions() {
+    let code = encode(&function(vec![
+        AsmInstruction::Label(Identifier::from("top")),
+        AsmInstruction::JmpCC(ConditionCode::NE, Identifier::from("end")),
+        AsmInstruction::Jmp(Identifier::from("top")),
+        AsmInstruction::Label(Identifier::from("end")),
+    ]))
+    .unwrap();
+    // jne +5 over the jmp, then jmp -11 back to the start.
+    assert_eq!(
+        code,
+        [0x0f, 0x85, 0x05, 0x00, 0x00, 0x00, 0xe9, 0xf5, 0xff, 0xff, 0xff]
+    );
+
+    let undefined = encode(&function(vec![AsmInstruction::Jmp(Identifier::from(
+        "nowhere",
+    ))]));
+    assert_eq!(
+        undefined,
+        Err(JitError::UndefinedLabel(Identifier::from("nowhere")))
+    );
+}
+
+#[test]
+fn refuses_runtime_checks() {
+    let mut checked = function(vec![AsmInstruction::Ret]);
+    checked.traps.push(Trap {
+        label: Identifier::from("main.trap.0"),
+        message: "division by zero\n".to_string(),
+    });
+    assert!(matches!(encode(&checked), Err(JitError::Unsupported(_))));
+}
+
+#[test]
+fn refuses_operands_other_than_longwords() {
+    let quad = function(vec![AsmInstruction::Mov {
+        ty: AsmType::Quadword,
+        src: Operand::Imm(7),
+        dst: AX.into(),
+    }]);
+    assert!(matches!(encode(&quad), Err(JitError::Unsupported(_))));
+}
+
+#[test]
+fn matches_the_interpreter_on_random_programs() {
+    for seed in 0..100 {
+        let source = Generator::new(seed).program(4);
+        let mut tacky = common::lower(&source);
+        let passes = PassManager::new(OptimizationLevel::O2);
+        passes.run_tacky(&mut tacky).unwrap();
+        let expected = Interpreter::new(&tacky).run().unwrap();
+        let actual = ulang::jit::run(&tacky, &passes).unwrap();
+        assert_eq!(actual, expected, "seed {}:\n{}", seed, source);
+    }
+}
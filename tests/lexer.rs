@@ -0,0 +1,302 @@
+//! Keywords and punctuators: what they lex to, how they print and their
+//! kinds, and the positions of tokens and errors in files with tabs, `\r\n`
+//! and multibyte chars.
+
+mod common;
+
+use ulang::{
+    lexer::{line_column, Lexer, Token, TokenKind, Trivia, INT_MIN_MAGNITUDE},
+    parser::{Parser, ParserErrorType},
+    symbol::Symbol,
+};
+
+fn lex(source: &str) -> Vec<Token> {
+    let (_, tokens) = common::lex(source);
+    tokens.into_iter().map(|token| token.token).collect()
+}
+
+const KEYWORDS: &str = "int void return if else while do for break continue goto switch case \
+                        default static extern long unsigned signed char double struct sizeof";
+
+#[test]
+fn lexes_every_keyword() {
+    let tokens = lex(KEYWORDS);
+    assert_eq!(tokens.len(), 23);
+    assert_eq!(tokens[3], Token::IfKeyword);
+    assert_eq!(tokens[22], Token::SizeofKeyword);
+    for (token, text) in tokens.iter().zip(KEYWORDS.split_whitespace()) {
+        assert_eq!(token.to_string(), text);
+        assert_eq!(token.text(), Some(text));
+    }
+    // Only whole words are keywords.
+    assert_eq!(
+        lex("iffy int2 Do"),
+        [
+            Token::Identifier(Symbol::intern("iffy")),
+            Token::Identifier(Symbol::intern("int2")),
+            Token::Identifier(Symbol::intern("Do")),
+        ]
+    );
+}
+
+#[test]
+fn prints_tokens_as_written() {
+    let source = "int main(void) { return ~-(1 + 2) * 3 / 4 % 5 >= !6 && 7 <= 8 || 9 != 10 == 11 < 12 > 13 & 14 | 15; }";
+    let mut lexer = Lexer::from_content(source.to_string());
+    let tokens = lexer.tokenize().unwrap();
+    for token in &tokens {
+        let text = &source[token.offset..token.offset + token.len];
+        assert_eq!(token.token.to_string(), text);
+        let length = match token.token {
+            // Could have leading zeros.
+            Token::Constant(_) => None,
+            _ => Some(token.len),
+        };
+        assert_eq!(token.token.text_length(), length);
+    }
+    assert_eq!(Token::EndOfFile.to_string(), "end of file");
+}
+
+#[test]
+fn keeps_positions_on_real_world_files() {
+    let source = "#include <x.h>\r\nint main(void) {\r\n\t/* h\u{e9}llo \u{2713} */ int a = 1; // \u{e9}\r\n\treturn a;\r\n}\r\n";
+    let mut lexer = Lexer::from_content(source.to_string()).with_trivia(true);
+    let tokens = lexer.tokenize().unwrap();
+    for token in &tokens {
+        let text = &source[token.offset..token.offset + token.len];
+        assert_eq!(token.token.to_string(), text);
+    }
+    let positions: Vec<(usize, usize)> = tokens
+        .iter()
+        .map(|token| token.line_column(source))
+        .collect();
+    // `int` after the tab and the comment, then `return` after the tab.
+    assert_eq!(positions[6], (3, 16));
+    assert_eq!(positions[11], (4, 2));
+    assert_eq!(
+        tokens[0].leading_trivia,
+        [Trivia::Directive("#include <x.h>".to_string())]
+    );
+    assert_eq!(
+        tokens[10].trailing_trivia,
+        [Trivia::LineComment("// \u{e9}".to_string())]
+    );
+    // Inside the `\u{e9}`, which starts at byte 39.
+    assert_eq!(line_column(source, 40), (3, 6));
+    assert_eq!(line_column(source, source.len() + 10), (6, 1));
+}
+
+#[test]
+fn errors_cover_the_whole_bad_text() {
+    let source = "int 12ab = $ + \u{e9} + 99999999999;";
+    let results: Vec<_> = Lexer::from_content(source.to_string()).collect();
+    let errors: Vec<(usize, usize)> = results
+        .iter()
+        .filter_map(|result| result.as_ref().err())
+        .map(|e| (e.span.offset(), e.span.len()))
+        .collect();
+    assert_eq!(errors, [(4, 4), (11, 1), (15, 2), (20, 11)]);
+    // The malformed constant doesn't leave a token behind.
+    assert!(!results
+        .iter()
+        .flatten()
+        .any(|token| matches!(token.token, Token::Constant(_))));
+}
+
+#[test]
+fn turns_errors_into_tokens_when_tolerant() {
+    let source = "int 12ab = $ + \u{e9};\nreturn 1 */ 2; /* never closed\n}";
+    let mut lexer = Lexer::from_content(source.to_string())
+        .with_error_tokens(true)
+        .with_trivia(true);
+    let tokens = lexer.tokenize().unwrap();
+    let errors: Vec<(&str, usize, usize)> = tokens
+        .iter()
+        .filter_map(|token| match &token.token {
+            Token::Error(text) => Some((text.as_str(), token.offset, token.len)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            ("12ab", 4, 4),
+            ("$", 11, 1),
+            ("\u{e9}", 15, 2),
+            ("*/", 28, 2),
+            ("/* never closed\n}", 34, 17),
+        ]
+    );
+    let kinds: Vec<_> = lexer.errors.iter().map(|e| e.error.to_string()).collect();
+    assert_eq!(kinds.len(), 5);
+    assert_eq!(kinds[4], "Block comment is never closed");
+    // The text of an error prints as it is written.
+    let text: Vec<String> = tokens.iter().map(|token| token.token.to_string()).collect();
+    assert_eq!(text[..4], ["int", "12ab", "=", "$"]);
+    assert_eq!(Token::Error("@".to_string()).kind(), TokenKind::Error);
+
+    // Lexing again starts over.
+    lexer.content = "int".to_string();
+    assert_eq!(lexer.tokenize().unwrap().len(), 1);
+    assert!(lexer.errors.is_empty());
+}
+
+#[test]
+fn takes_constants_up_to_the_magnitude_of_int_min() {
+    assert_eq!(lex("2147483648"), [Token::Constant(INT_MIN_MAGNITUDE)]);
+    let mut lexer = Lexer::from_content("2147483649".to_string());
+    assert!(lexer.tokenize().is_err());
+
+    // Only the negated one fits in an `int`.
+    let source = "int main(void) { return 2147483648; }";
+    let mut lexer = Lexer::from_content(source.to_string());
+    let tokens = lexer.tokenize().unwrap();
+    let error = Parser::new(&tokens, &lexer.content).parse().unwrap_err();
+    assert!(matches!(error.error, ParserErrorType::ConstantTooLarge));
+    assert_eq!((error.span.offset(), error.span.len()), (24, 10));
+}
+
+#[test]
+fn splices_continued_lines() {
+    let source = "in\\\nt ma\\\r\nin = 1\\\n2; // one \\\ncomment\nreturn \\ 0;";
+    let mut lexer = Lexer::from_content(source.to_string()).with_trivia(true);
+    let results: Vec<_> = lexer.by_ref().collect();
+    let tokens: Vec<_> = results.iter().flatten().collect();
+    let kinds: Vec<&Token> = tokens.iter().map(|token| &token.token).collect();
+    assert_eq!(
+        kinds,
+        [
+            &Token::IntKeyword,
+            &Token::Identifier(Symbol::intern("main")),
+            &Token::Assign,
+            &Token::Constant(12),
+            &Token::Semicolon,
+            &Token::ReturnKeyWord,
+            &Token::Constant(0),
+            &Token::Semicolon,
+        ]
+    );
+    // Spans cover the physical text, so lines are the ones in the file.
+    assert_eq!((tokens[0].offset, tokens[0].len), (0, 5));
+    assert_eq!(tokens[1].line_column(source), (2, 3));
+    assert_eq!(tokens[3].line_column(source), (3, 6));
+    assert_eq!(
+        tokens[4].trailing_trivia,
+        [Trivia::LineComment("// one comment".to_string())]
+    );
+    assert_eq!(tokens[5].line_column(source), (6, 1));
+    // A backslash not followed by a line break is still an error.
+    let errors: Vec<_> = results
+        .iter()
+        .filter_map(|result| result.as_ref().err())
+        .collect();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].span.offset(), source.len() - 4);
+}
+
+#[test]
+fn translates_trigraphs_and_digraphs() {
+    let source = "%:include <a.h>\nint main(void) ??<\n    ret??/\nurn 1 ??!??! 2;\n<% %>%>";
+    let mut lexer = Lexer::from_content(source.to_string())
+        .with_trivia(true)
+        .with_trigraphs(true);
+    let tokens = lexer.tokenize().unwrap();
+    let kinds: Vec<&Token> = tokens.iter().map(|token| &token.token).collect();
+    assert_eq!(
+        kinds,
+        [
+            &Token::IntKeyword,
+            &Token::Identifier(Symbol::intern("main")),
+            &Token::OpenParenthesis,
+            &Token::VoidKeyWord,
+            &Token::CloseParenthesis,
+            &Token::OpenBrace,
+            &Token::ReturnKeyWord,
+            &Token::Constant(1),
+            &Token::Or,
+            &Token::Constant(2),
+            &Token::Semicolon,
+            &Token::OpenBrace,
+            &Token::CloseBrace,
+            &Token::CloseBrace,
+        ]
+    );
+    assert_eq!(
+        tokens[0].leading_trivia,
+        [Trivia::Directive("#include <a.h>".to_string())]
+    );
+    // Spans cover the chars as written.
+    let spans: Vec<_> = tokens
+        .iter()
+        .map(|token| (token.offset, token.len))
+        .collect();
+    assert_eq!(spans[5], (source.find("??<").unwrap(), 3));
+    assert_eq!(spans[6], (source.find("ret").unwrap(), 10));
+    assert_eq!(spans[8], (source.find("??!").unwrap(), 6));
+    assert_eq!(spans[11], (source.find("<%").unwrap(), 2));
+    assert_eq!(tokens[7].line_column(source), (4, 5));
+
+    // Without the flag they are what they look like.
+    assert_eq!(
+        lex("<% %>"),
+        [
+            Token::LessThan,
+            Token::PercentSign,
+            Token::PercentSign,
+            Token::GreaterThan
+        ]
+    );
+    let mut lexer = Lexer::from_content("??<".to_string());
+    assert_eq!(lexer.tokenize().unwrap_err().span.offset(), 0);
+}
+
+#[test]
+fn reports_unbalanced_comments() {
+    let error = |source: &str| {
+        let e = Lexer::from_content(source.to_string())
+            .tokenize()
+            .unwrap_err();
+        (e.error.to_string(), e.span.offset(), e.span.len())
+    };
+    assert_eq!(
+        error("int a; /* never closed *"),
+        ("Block comment is never closed".to_string(), 7, 2)
+    );
+    assert_eq!(
+        error("return 2 */ 1;"),
+        ("`*/` outside of a block comment".to_string(), 9, 2)
+    );
+    // A comment right after a multiplication.
+    assert_eq!(
+        lex("2 */* times */3"),
+        [Token::Constant(2), Token::Asteriks, Token::Constant(3)]
+    );
+}
+
+#[test]
+fn classifies_token_kinds() {
+    let kinds: Vec<TokenKind> = lex("int x = 3; return ~x;")
+        .iter()
+        .map(Token::kind)
+        .collect();
+    let keywords: Vec<bool> = kinds.iter().map(TokenKind::is_keyword).collect();
+    let operators: Vec<bool> = kinds.iter().map(TokenKind::is_operator).collect();
+    let literals: Vec<bool> = kinds.iter().map(TokenKind::is_literal).collect();
+    let (t, f) = (true, false);
+    assert_eq!(keywords, [t, f, f, f, f, t, f, f, f]);
+    assert_eq!(operators, [f, f, t, f, f, f, t, f, f]);
+    assert_eq!(literals, [f, f, f, t, f, f, f, f, f]);
+
+    assert_eq!(Token::Identifier("x".into()).kind(), TokenKind::Identifier);
+    assert_eq!(TokenKind::Semicolon.text(), Some(";"));
+    assert!(!TokenKind::Semicolon.is_operator());
+    for kind in TokenKind::ALL {
+        assert_eq!(TokenKind::from_code(kind.code()), Some(kind));
+    }
+    // Codes are part of the interface, they must not move.
+    assert_eq!(TokenKind::Identifier.code(), 0x01);
+    assert_eq!(TokenKind::Error.code(), 0x04);
+    assert_eq!(TokenKind::IntKeyword.code(), 0x100);
+    assert_eq!(TokenKind::Assign.code(), 0x30c);
+    assert_eq!(TokenKind::from_code(0), None);
+}
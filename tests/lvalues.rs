@@ -0,0 +1,63 @@
+//! Operators that need an object as their operand, and the errors pointing
+//! at the operand when it is a value.
+
+mod common;
+
+use ulang::typecheck::{LvalueUse, TypeError};
+
+fn check(body: &str) -> Result<(), TypeError> {
+    let source = format!(
+        "int main(void) {{\n    int a = 1;\n    {}\n    return a;\n}}\n",
+        body
+    );
+    common::try_check(&source).map(|_| ())
+}
+
+/// Offset of `text` in the body given to [`check`].
+fn offset(body: &str, text: &str) -> usize {
+    "int main(void) {\n    int a = 1;\n    ".len() + body.find(text).unwrap()
+}
+
+#[test]
+fn accepts_variables_maybe_in_parentheses() {
+    for body in ["a = 2;", "(a) = 2;", "a++;", "--(a);", "a = ++a + a--;"] {
+        assert!(check(body).is_ok(), "{}", body);
+    }
+}
+
+#[test]
+fn points_at_the_operand_that_is_a_value() {
+    let cases = [
+        ("a + 1 = 3;", "a + 1", "=", LvalueUse::Assign),
+        ("(a + 1)++;", "a + 1", "++", LvalueUse::Increment),
+        ("--3;", "3", "--", LvalueUse::Decrement),
+        ("a++--;", "a++", "--", LvalueUse::Decrement),
+        ("a = &-a;", "a;", "&", LvalueUse::AddressOf),
+    ];
+    for (body, operand, operator, use_) in cases {
+        let Err(TypeError::NotAnLvalue {
+            operation,
+            target,
+            span,
+        }) = check(body)
+        else {
+            panic!("{} is not an lvalue error", body);
+        };
+        assert_eq!(operation, use_);
+        assert_eq!(target.offset(), offset(body, operand), "{}", body);
+        assert_eq!(span.offset(), offset(body, operator), "{}", body);
+    }
+    let error = check("(a + 1)++;").unwrap_err();
+    assert_eq!(error.to_string(), "Cannot increment this expression");
+    let error = check("a + 1 = 3;").unwrap_err();
+    assert_eq!(error.to_string(), "Cannot assign to this expression");
+}
+
+#[test]
+fn address_of_a_variable_is_not_supported_yet() {
+    let error = check("a = &a;").unwrap_err();
+    let TypeError::UnsupportedAddressOf { span } = error else {
+        panic!("unexpected error {:?}", error);
+    };
+    assert_eq!(span.offset(), offset("a = &a;", "&"));
+}
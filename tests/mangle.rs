@@ -0,0 +1,57 @@
+//! Symbol and label names of the generated assembly on each platform.
+
+use ulang::{
+    assembly::{Mangler, TargetPlatform},
+    ast::Identifier,
+    names::NameGenerator,
+};
+
+#[test]
+fn mangles_for_each_platform() {
+    let main = Identifier::from("main");
+    let linux = Mangler::new(TargetPlatform::X64Linux);
+    let macos = Mangler::new(TargetPlatform::MacOsX64);
+    assert_eq!(linux.global(&main), "main");
+    assert_eq!(macos.global(&main), "_main");
+    assert_eq!(linux.libc("_exit"), "_exit@PLT");
+    assert_eq!(macos.libc("_exit"), "__exit");
+    let label = Identifier::from("main.label.0");
+    assert_eq!(linux.local(label), ".Lmain.label.0");
+    assert_eq!(macos.local(label), "Lmain.label.0");
+}
+
+#[test]
+fn escapes_what_assemblers_do_not_take_in_a_label() {
+    let linux = Mangler::new(TargetPlatform::X64Linux);
+    let macos = Mangler::new(TargetPlatform::MacOsX64);
+    assert_eq!(linux.local("main.case.-1.0"), ".Lmain.case.$2d$1.0");
+    assert_eq!(macos.local("f.label.caf\u{e9}.2"), "Lf.label.caf$e9$.2");
+    // `$` is escaped too, so no escape can be spelled out in the name.
+    assert_eq!(linux.local("a$2d$"), ".La$24$2d$24$");
+    assert_ne!(linux.local("a-"), linux.local("a$2d$"));
+}
+
+#[test]
+fn keeps_derived_labels_apart() {
+    let mut main = NameGenerator::new(Identifier::from("main"));
+    let mut other = NameGenerator::new(Identifier::from("other"));
+    let labels = [
+        main.derived("case", "1"),
+        main.derived("case", "1"),
+        main.fresh("case"),
+        main.derived("case", "-1"),
+        other.derived("case", "1"),
+    ];
+    let linux = Mangler::new(TargetPlatform::X64Linux);
+    let mangled: Vec<String> = labels.iter().map(|label| linux.local(label)).collect();
+    assert_eq!(
+        mangled,
+        [
+            ".Lmain.case.1.0",
+            ".Lmain.case.1.1",
+            ".Lmain.case.2",
+            ".Lmain.case.$2d$1.3",
+            ".Lother.case.1.0",
+        ]
+    );
+}
@@ -0,0 +1,237 @@
+//! Emission of every instruction with an operand size, for each size: the
+//! mnemonic suffix and the register names follow the `AsmType`. Then the
+//! fix-up of quadword immediates that don't fit in 32 bits.
+
+use ulang::{
+    assembly::{
+        AsmBinaryOperator, AsmFunctionDef, AsmInstruction, AsmPipeline, AsmProgram, AsmRegistry,
+        AsmType, AsmUnaryOperator, ConditionCode, Operand, TargetPlatform,
+    },
+    ast::Identifier,
+};
+
+use AsmRegistry::{AX, BP, DX, R10, R11, SP};
+
+fn program(instructions: Vec<AsmInstruction>) -> AsmProgram {
+    AsmProgram(AsmFunctionDef {
+        name: Identifier::from("main"),
+        instructions,
+        traps: vec![],
+        counters: vec![],
+    })
+}
+
+/// Assembly lines of the function body, whitespace normalized.
+fn emit(instructions: Vec<AsmInstruction>) -> Vec<String> {
+    lines(&program(instructions))
+}
+
+/// Assembly lines of the function body after the instructions are fixed up,
+/// without the prologue and epilogue.
+fn fixed(instructions: Vec<AsmInstruction>) -> Vec<String> {
+    let mut pipeline = AsmPipeline::new(program(instructions));
+    pipeline.replace_pseudos().unwrap();
+    let lines = lines(pipeline.fix_instructions().unwrap());
+    lines[3..lines.len() - 3].to_vec()
+}
+
+fn lines(program: &AsmProgram) -> Vec<String> {
+    program
+        .generate(TargetPlatform::X64Linux)
+        .0
+        .lines()
+        .skip_while(|line| *line != "main:")
+        .skip(1)
+        .take_while(|line| !line.contains(".size"))
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect()
+}
+
+fn sized(ty: AsmType) -> Vec<AsmInstruction> {
+    vec![
+        AsmInstruction::Mov {
+            ty,
+            src: Operand::Imm(1),
+            dst: AX.into(),
+        },
+        AsmInstruction::Mov {
+            ty,
+            src: R10.into(),
+            dst: Operand::Stack(-8),
+        },
+        AsmInstruction::Unary(AsmUnaryOperator::Neg, ty, DX.into()),
+        AsmInstruction::Binary(AsmBinaryOperator::Add, ty, R11.into(), AX.into()),
+        AsmInstruction::Binary(AsmBinaryOperator::Mult, ty, Operand::Stack(-4), R11.into()),
+        AsmInstruction::Cmp(ty, Operand::Imm(0), R10.into()),
+        AsmInstruction::Cdq(ty),
+        AsmInstruction::Idiv(ty, R10.into()),
+    ]
+}
+
+#[test]
+fn emits_byte_operands() {
+    assert_eq!(
+        emit(sized(AsmType::Byte)),
+        [
+            "movb $1, %al",
+            "movb %r10b, -8(%rbp)",
+            "negb %dl",
+            "addb %r11b, %al",
+            "imulb -4(%rbp), %r11b",
+            "cmpb $0, %r10b",
+            "cdq",
+            "idivb %r10b",
+        ]
+    );
+}
+
+#[test]
+fn emits_longword_operands() {
+    assert_eq!(
+        emit(sized(AsmType::Longword)),
+        [
+            "movl $1, %eax",
+            "movl %r10d, -8(%rbp)",
+            "negl %edx",
+            "addl %r11d, %eax",
+            "imull -4(%rbp), %r11d",
+            "cmpl $0, %r10d",
+            "cdq",
+            "idivl %r10d",
+        ]
+    );
+}
+
+#[test]
+fn emits_quadword_operands() {
+    assert_eq!(
+        emit(sized(AsmType::Quadword)),
+        [
+            "movq $1, %rax",
+            "movq %r10, -8(%rbp)",
+            "negq %rdx",
+            "addq %r11, %rax",
+            "imulq -4(%rbp), %r11",
+            "cmpq $0, %r10",
+            "cqo",
+            "idivq %r10",
+        ]
+    );
+}
+
+#[test]
+fn sets_the_low_byte_of_the_register() {
+    let lines = emit(vec![
+        AsmInstruction::SetCC(ConditionCode::E, AX.into()),
+        AsmInstruction::SetCC(ConditionCode::NE, R11.into()),
+        AsmInstruction::SetCC(ConditionCode::L, Operand::Stack(-4)),
+    ]);
+    assert_eq!(lines, ["sete %al", "setne %r11b", "setl -4(%rbp)"]);
+}
+
+#[test]
+fn names_every_register_in_every_size() {
+    let names = |ty| [AX, DX, R10, R11, BP, SP].map(|register| register.name(ty));
+    assert_eq!(
+        names(AsmType::Byte),
+        ["%al", "%dl", "%r10b", "%r11b", "%bpl", "%spl"]
+    );
+    assert_eq!(
+        names(AsmType::Longword),
+        ["%eax", "%edx", "%r10d", "%r11d", "%ebp", "%esp"]
+    );
+    assert_eq!(
+        names(AsmType::Quadword),
+        ["%rax", "%rdx", "%r10", "%r11", "%rbp", "%rsp"]
+    );
+}
+
+fn quad(src: i64, dst: Operand) -> AsmInstruction {
+    AsmInstruction::Mov {
+        ty: AsmType::Quadword,
+        src: Operand::Imm(src),
+        dst,
+    }
+}
+
+#[test]
+fn keeps_immediates_that_fit_in_32_bits() {
+    let lines = fixed(vec![
+        quad(i32::MAX.into(), Operand::Stack(-8)),
+        quad(i32::MIN.into(), Operand::Stack(-8)),
+        AsmInstruction::Binary(
+            AsmBinaryOperator::Add,
+            AsmType::Quadword,
+            Operand::Imm(i32::MAX.into()),
+            Operand::Stack(-8),
+        ),
+        AsmInstruction::Cmp(
+            AsmType::Quadword,
+            Operand::Imm(i32::MIN.into()),
+            Operand::Stack(-8),
+        ),
+    ]);
+    assert_eq!(
+        lines,
+        [
+            "movq $2147483647, -8(%rbp)",
+            "movq $-2147483648, -8(%rbp)",
+            "addq $2147483647, -8(%rbp)",
+            "cmpq $-2147483648, -8(%rbp)",
+        ]
+    );
+}
+
+#[test]
+fn loads_larger_immediates_with_movabsq() {
+    let lines = fixed(vec![
+        quad(i64::from(i32::MAX) + 1, AX.into()),
+        quad(i64::from(i32::MIN) - 1, Operand::Stack(-8)),
+        AsmInstruction::Binary(
+            AsmBinaryOperator::Add,
+            AsmType::Quadword,
+            Operand::Imm(i64::MAX),
+            Operand::Stack(-8),
+        ),
+        AsmInstruction::Binary(
+            AsmBinaryOperator::Mult,
+            AsmType::Quadword,
+            Operand::Imm(i64::MIN),
+            Operand::Stack(-8),
+        ),
+        AsmInstruction::Cmp(AsmType::Quadword, Operand::Imm(i64::MAX), AX.into()),
+    ]);
+    assert_eq!(
+        lines,
+        [
+            "movabsq $2147483648, %rax",
+            "movabsq $-2147483649, %r10",
+            "movq %r10, -8(%rbp)",
+            "movabsq $9223372036854775807, %r10",
+            "addq %r10, -8(%rbp)",
+            "movabsq $-9223372036854775808, %r10",
+            "movq -8(%rbp), %r11",
+            "imulq %r10, %r11",
+            "movq %r11, -8(%rbp)",
+            "movabsq $9223372036854775807, %r10",
+            "cmpq %r10, %rax",
+        ]
+    );
+}
+
+#[test]
+fn loads_both_immediates_of_a_comparison() {
+    let lines = fixed(vec![AsmInstruction::Cmp(
+        AsmType::Quadword,
+        Operand::Imm(i64::MIN),
+        Operand::Imm(i64::MAX),
+    )]);
+    assert_eq!(
+        lines,
+        [
+            "movabsq $-9223372036854775808, %r10",
+            "movabsq $9223372036854775807, %r11",
+            "cmpq %r10, %r11",
+        ]
+    );
+}
@@ -0,0 +1,363 @@
+//! The optimization passes, each run on TACKY written by hand and checked
+//! against the TACKY it should leave, since C has no loops here yet.
+
+use ulang::{
+    assembly::{generate_optimized_assembly, RuntimeChecks, TargetPlatform},
+    optimize::{OptimizationLevel, Pass, PassManager},
+    tacky::{TackyProgram, VerifyError},
+};
+
+fn program(text: &str) -> TackyProgram {
+    let program: TackyProgram = text.parse().unwrap();
+    program.verify().unwrap();
+    program
+}
+
+/// Runs `pass` on `before`, checking the result and that it computes the
+/// same value.
+fn optimize(pass: Pass, before: &str, after: &str) {
+    let mut tacky = program(before);
+    let expected = tacky.interpret().unwrap();
+    PassManager::with_passes(vec![pass])
+        .run_tacky(&mut tacky)
+        .unwrap();
+    assert_eq!(tacky.to_string(), after);
+    assert_eq!(tacky.interpret().unwrap(), expected);
+}
+
+const SUM: &str = "function main {
+    n = 3
+    i = 10
+    sum = 0
+  main.label.0:
+    ifz i goto main.label.1
+    main.tmp.0 = n * 4
+    main.tmp.1 = main.tmp.0 + 1
+    sum = sum + main.tmp.1
+    i = i - 1
+    goto main.label.0
+  main.label.1:
+    return sum
+}
+";
+
+#[test]
+fn hoists_invariant_computations_to_a_preheader() {
+    optimize(
+        Pass::LoopInvariantCodeMotion,
+        SUM,
+        "function main {
+    n = 3
+    i = 10
+    sum = 0
+  main.preheader.0:
+    main.tmp.0 = n * 4
+    main.tmp.1 = main.tmp.0 + 1
+  main.label.0:
+    ifz i goto main.label.1
+    sum = sum + main.tmp.1
+    i = i - 1
+    goto main.label.0
+  main.label.1:
+    return sum
+}
+",
+    );
+}
+
+#[test]
+fn keeps_what_the_loop_changes_or_may_not_compute() {
+    let unchanged = "function main {
+    n = 3
+    x = 0
+    i = 10
+  main.label.0:
+    ifz i goto main.label.1
+    x = n * 4
+    d = n / i
+    i = i - 1
+    goto main.label.0
+  main.label.1:
+    return x
+}
+";
+    // `x` is read after the loop, which may not run, `d` divides and `i`
+    // changes every iteration.
+    optimize(Pass::LoopInvariantCodeMotion, unchanged, unchanged);
+}
+
+#[test]
+fn moves_computations_out_of_nested_loops() {
+    optimize(
+        Pass::LoopInvariantCodeMotion,
+        "function main {
+    n = 2
+    i = 3
+    sum = 0
+  main.label.0:
+    ifz i goto main.label.3
+    j = 4
+  main.label.1:
+    ifz j goto main.label.2
+    k = n + 5
+    sum = sum + k
+    j = j - 1
+    goto main.label.1
+  main.label.2:
+    i = i - 1
+    goto main.label.0
+  main.label.3:
+    return sum
+}
+",
+        "function main {
+    n = 2
+    i = 3
+    sum = 0
+  main.preheader.1:
+    k = n + 5
+  main.label.0:
+    ifz i goto main.label.3
+    j = 4
+  main.preheader.0:
+  main.label.1:
+    ifz j goto main.label.2
+    sum = sum + k
+    j = j - 1
+    goto main.label.1
+  main.label.2:
+    i = i - 1
+    goto main.label.0
+  main.label.3:
+    return sum
+}
+",
+    );
+}
+
+#[test]
+fn removes_unread_stores_and_unreachable_blocks() {
+    optimize(
+        Pass::DeadCodeElimination,
+        "function main {
+    a = 1
+    b = 2
+    c = b * 4
+    ifz a goto main.label.0
+    main.tmp.0 = a + 3
+    return main.tmp.0
+    b = 7
+  main.label.1:
+    return b
+  main.label.0:
+    c = 2
+    return c
+}
+",
+        "function main {
+    a = 1
+    ifz a goto main.label.0
+    main.tmp.0 = a + 3
+    return main.tmp.0
+  main.label.0:
+    c = 2
+    return c
+}
+",
+    );
+}
+
+#[test]
+fn propagates_copies_until_their_source_changes() {
+    // `x = 1` ends the copies of `x` into `y` and `z`, the label all of them.
+    optimize(
+        Pass::CopyPropagation,
+        "function main {
+    x = 2 * 3
+    y = x
+    z = y
+    main.tmp.0 = z + y
+    x = 1
+    main.tmp.1 = y + x
+    ifz main.tmp.1 goto main.label.0
+    return main.tmp.0
+  main.label.0:
+    return z
+}
+",
+        "function main {
+    x = 2 * 3
+    y = x
+    z = x
+    main.tmp.0 = x + x
+    x = 1
+    main.tmp.1 = y + 1
+    ifz main.tmp.1 goto main.label.0
+    return main.tmp.0
+  main.label.0:
+    return z
+}
+",
+    );
+}
+
+#[test]
+fn drops_jumps_to_the_next_instruction_and_unused_labels() {
+    // Both jumps land right after them once the adjacent labels are merged.
+    optimize(
+        Pass::JumpCleanup,
+        "function main {
+    a = 1
+    ifz a goto main.label.1
+    goto main.label.0
+  main.label.0:
+  main.label.1:
+    a = 2
+    goto main.label.2
+  main.label.3:
+  main.label.2:
+    return a
+}
+",
+        "function main {
+    a = 1
+    a = 2
+    return a
+}
+",
+    );
+    let kept = "function main {
+    a = 1
+    ifz a goto main.label.0
+    a = 2
+  main.label.0:
+    return a
+}
+";
+    optimize(Pass::JumpCleanup, kept, kept);
+}
+
+#[test]
+fn folds_the_arithmetic_of_constants() {
+    optimize(
+        Pass::ConstantFolding,
+        "function main {
+    a = 7
+    main.tmp.0 = a / 2
+    main.tmp.1 = -7 % 2
+    main.tmp.2 = main.tmp.0 * main.tmp.1
+    main.tmp.3 = - main.tmp.2
+    return main.tmp.3
+}
+",
+        "function main {
+    a = 7
+    main.tmp.0 = 3
+    main.tmp.1 = -1
+    main.tmp.2 = -3
+    main.tmp.3 = 3
+    return 3
+}
+",
+    );
+}
+
+#[test]
+fn leaves_divisions_that_would_trap_to_runtime() {
+    // Folding into an error would reject a valid program: the divisions
+    // only run when `a` isn't 0.
+    optimize(
+        Pass::ConstantFolding,
+        "function main {
+    a = 0
+    b = 5
+    ifz a goto main.label.0
+    main.tmp.0 = b / a
+    main.tmp.1 = b % 0
+    main.tmp.2 = -2147483648 / -1
+    main.tmp.3 = -2147483648 % -1
+    return main.tmp.0
+  main.label.0:
+    return b
+}
+",
+        "function main {
+    a = 0
+    b = 5
+    ifz 0 goto main.label.0
+    main.tmp.0 = 5 / 0
+    main.tmp.1 = 5 % 0
+    main.tmp.2 = -2147483648 / -1
+    main.tmp.3 = -2147483648 % -1
+    return main.tmp.0
+  main.label.0:
+    return b
+}
+",
+    );
+}
+
+#[test]
+fn multiplies_by_powers_of_two_with_shifts() {
+    optimize(
+        Pass::StrengthReduction,
+        "function main {
+    x = 5
+    a = x * 8
+    b = 4 * a
+    c = b * 6
+    d = c * 1
+    e = d * -2
+    return e
+}
+",
+        "function main {
+    x = 5
+    a = x << 3
+    b = a << 2
+    c = b * 6
+    d = c * 1
+    e = d * -2
+    return e
+}
+",
+    );
+}
+
+#[test]
+fn shifts_by_constants_only() {
+    let shift = "function main {
+    x = 5
+    a = x << x
+    return a
+}
+";
+    let program: TackyProgram = shift.parse().unwrap();
+    assert!(matches!(program.verify(), Err(VerifyError::ShiftCount(_))));
+}
+
+#[test]
+fn emits_shifts_and_runs_both_passes_at_o2() {
+    let passes = OptimizationLevel::O2.passes();
+    let licm = passes
+        .iter()
+        .position(|p| *p == Pass::LoopInvariantCodeMotion);
+    let reduce = passes.iter().position(|p| *p == Pass::StrengthReduction);
+    assert!(licm.is_some() && reduce.is_some());
+    assert!(!OptimizationLevel::O1
+        .passes()
+        .contains(&Pass::StrengthReduction));
+
+    let mut tacky = program(SUM);
+    let passes = PassManager::with_passes(passes);
+    passes.run_tacky(&mut tacky).unwrap();
+    assert_eq!(tacky.interpret().unwrap(), 130);
+    let assembly = generate_optimized_assembly(
+        &tacky,
+        TargetPlatform::X64Linux,
+        &passes,
+        &RuntimeChecks::default(),
+    )
+    .0;
+    assert!(assembly.contains("sall\t$2"), "{}", assembly);
+}
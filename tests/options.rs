@@ -0,0 +1,52 @@
+//! The settings the stages take from [`CompileOptions`].
+
+use std::path::Path;
+
+use ulang::{
+    assembly::TargetPlatform,
+    optimize::{OptimizationLevel, Pass},
+    options::{CompileOptions, Stage},
+};
+
+#[test]
+fn defaults_to_the_host_and_the_whole_pipeline() {
+    let options = CompileOptions::default();
+    assert_eq!(options.target, TargetPlatform::host());
+    assert_eq!(options.emit, Stage::Codegen);
+    assert_eq!(options.cc, "gcc");
+    assert!(options.passes().passes.is_empty());
+    assert!(!options.source_locations());
+    let instrumentation = options.instrumentation(Path::new("missing.c")).unwrap();
+    assert!(!instrumentation.functions);
+    assert_eq!(instrumentation.coverage, None);
+}
+
+#[test]
+fn derives_the_settings_of_each_stage() {
+    let options = CompileOptions {
+        opt_level: OptimizationLevel::O1,
+        checked_arithmetic: true,
+        print_after: vec![Pass::ConstantFolding],
+        ..CompileOptions::default()
+    };
+    assert!(options.source_locations());
+    let passes = options.passes();
+    assert_eq!(passes.passes, OptimizationLevel::O1.passes());
+    assert_eq!(passes.print_after, [Pass::ConstantFolding]);
+    let checks = options.runtime_checks("main.c");
+    assert!(checks.division_by_zero && checks.overflow);
+    assert_eq!(checks.file_name, "main.c");
+}
+
+#[test]
+fn keeps_the_checked_arithmetic_where_the_source_has_it() {
+    let options = CompileOptions {
+        opt_level: OptimizationLevel::O2,
+        checked_arithmetic: true,
+        ..CompileOptions::default()
+    };
+    let passes = options.passes().passes;
+    assert!(!passes.contains(&Pass::LoopInvariantCodeMotion));
+    assert!(!passes.contains(&Pass::StrengthReduction));
+    assert!(passes.contains(&Pass::ConstantFolding));
+}
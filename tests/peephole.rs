@@ -0,0 +1,155 @@
+//! One test per peephole rule, each checking where the rule applies and where
+//! it has to leave the instructions alone.
+
+use ulang::{
+    assembly::{
+        AsmBinaryOperator, AsmFunctionDef, AsmInstruction, AsmProgram, AsmRegistry, AsmType,
+        ConditionCode, Operand, TargetPlatform,
+    },
+    ast::Identifier,
+};
+
+use AsmRegistry::{AX, DX};
+
+/// Assembly lines of the function body after running the peephole pass.
+fn peephole(instructions: Vec<AsmInstruction>) -> Vec<String> {
+    let mut program = AsmProgram(AsmFunctionDef {
+        name: Identifier::from("main"),
+        instructions,
+        traps: vec![],
+        counters: vec![],
+    });
+    program.peephole();
+    let generated = program.generate(TargetPlatform::X64Linux);
+    generated
+        .0
+        .lines()
+        .skip_while(|line| *line != "main:")
+        .skip(1)
+        .take_while(|line| !line.contains(".size"))
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect()
+}
+
+fn mov(src: Operand, dst: Operand) -> AsmInstruction {
+    AsmInstruction::Mov {
+        ty: AsmType::Longword,
+        src,
+        dst,
+    }
+}
+
+fn binary(operator: AsmBinaryOperator, src: Operand, dst: Operand) -> AsmInstruction {
+    AsmInstruction::Binary(operator, AsmType::Longword, src, dst)
+}
+
+fn label(name: &str) -> AsmInstruction {
+    AsmInstruction::Label(Identifier::from(name))
+}
+
+#[test]
+fn removes_self_moves() {
+    let lines = peephole(vec![
+        mov(AX.into(), AX.into()),
+        mov(Operand::Stack(-4), Operand::Stack(-4)),
+        AsmInstruction::Ret,
+    ]);
+    assert_eq!(lines, ["ret"]);
+}
+
+#[test]
+fn removes_jumps_to_the_next_label() {
+    let lines = peephole(vec![
+        AsmInstruction::Jmp(Identifier::from("next")),
+        label("next"),
+        AsmInstruction::Jmp(Identifier::from("next")),
+        label("other"),
+    ]);
+    assert_eq!(lines, [".Lnext:", "jmp .Lnext", ".Lother:"]);
+}
+
+#[test]
+fn zeroes_registers_with_xor() {
+    let lines = peephole(vec![mov(Operand::Imm(0), AX.into()), AsmInstruction::Ret]);
+    assert_eq!(lines[0], "xorl %eax, %eax");
+}
+
+#[test]
+fn keeps_zeroing_moves_when_flags_are_read() {
+    let lines = peephole(vec![
+        AsmInstruction::Cmp(AsmType::Longword, Operand::Imm(1), Operand::Stack(-4)),
+        mov(Operand::Imm(0), AX.into()),
+        AsmInstruction::SetCC(ConditionCode::E, AX.into()),
+        // Moves to memory have no xor form.
+        mov(Operand::Imm(0), Operand::Stack(-8)),
+        AsmInstruction::Ret,
+    ]);
+    assert_eq!(lines[1], "movl $0, %eax");
+    assert_eq!(lines[3], "movl $0, -8(%rbp)");
+}
+
+#[test]
+fn follows_fallthrough_but_not_jumps() {
+    let fallthrough = peephole(vec![
+        mov(Operand::Imm(0), AX.into()),
+        label("epilogue"),
+        AsmInstruction::Ret,
+    ]);
+    assert_eq!(fallthrough[0], "xorl %eax, %eax");
+
+    let jump = peephole(vec![
+        mov(Operand::Imm(0), AX.into()),
+        AsmInstruction::Jmp(Identifier::from("join")),
+    ]);
+    assert_eq!(jump[0], "movl $0, %eax");
+}
+
+#[test]
+fn adds_and_subtracts_one_with_inc_and_dec() {
+    let lines = peephole(vec![
+        binary(AsmBinaryOperator::Add, Operand::Imm(1), Operand::Stack(-4)),
+        binary(AsmBinaryOperator::Sub, Operand::Imm(1), Operand::Stack(-4)),
+        binary(AsmBinaryOperator::Add, Operand::Imm(-1), AX.into()),
+        binary(AsmBinaryOperator::Sub, Operand::Imm(-1), AX.into()),
+        binary(AsmBinaryOperator::Add, Operand::Imm(2), AX.into()),
+    ]);
+    assert_eq!(
+        lines,
+        [
+            "incl -4(%rbp)",
+            "decl -4(%rbp)",
+            "decl %eax",
+            "incl %eax",
+            "addl $2, %eax"
+        ]
+    );
+}
+
+#[test]
+fn adds_constants_into_another_register_with_lea() {
+    let lines = peephole(vec![
+        mov(AX.into(), DX.into()),
+        binary(AsmBinaryOperator::Add, Operand::Imm(8), DX.into()),
+        AsmInstruction::Ret,
+    ]);
+    assert_eq!(lines[0], "leal 8(%rax), %edx");
+}
+
+#[test]
+fn keeps_additions_when_lea_does_not_fit() {
+    // The overflow check reads the flags of the addition.
+    let checked = peephole(vec![
+        mov(AX.into(), DX.into()),
+        binary(AsmBinaryOperator::Add, Operand::Imm(8), DX.into()),
+        AsmInstruction::JmpCC(ConditionCode::O, Identifier::from("trap")),
+    ]);
+    assert_eq!(checked[..2], ["movl %eax, %edx", "addl $8, %edx"]);
+
+    // Memory operands can't be a base or the destination of lea.
+    let memory = peephole(vec![
+        mov(Operand::Stack(-4), DX.into()),
+        binary(AsmBinaryOperator::Add, Operand::Imm(8), DX.into()),
+        AsmInstruction::Ret,
+    ]);
+    assert_eq!(memory[..2], ["movl -4(%rbp), %edx", "addl $8, %edx"]);
+}
@@ -0,0 +1,108 @@
+//! Return statements checked against the return type, and the warning for a
+//! function that can run off its end.
+
+mod common;
+
+use miette::Severity;
+use ulang::{
+    assembly::{generate_assembly, TargetPlatform},
+    ast::Identifier,
+    diagnostics::{Collect, DiagnosticSink},
+    lexer::Lexer,
+    parser::Parser,
+    reachability,
+    tacky::{Instruction, Tacky, TackyProgram, Value},
+    typecheck::{typecheck_into, TypeError, TypeWarning},
+};
+
+/// Functions warned about running off their end.
+fn missing_returns(source: &str) -> Vec<String> {
+    reachability::missing_returns(&common::parse(source), Identifier::from("main"))
+        .iter()
+        .map(|w| w.0.to_string())
+        .collect()
+}
+
+#[test]
+fn returns_without_a_value() {
+    let source = "void f(void) {\n    return;\n}\n";
+    assert!(common::try_check(source).unwrap().warnings.is_empty());
+
+    let source = "int f(void) {\n    return;\n}\n";
+    let program = common::try_check(source).unwrap();
+    assert_eq!(program.warnings.len(), 1);
+    let TypeWarning::ReturnWithoutValue(warning) = &program.warnings[0] else {
+        panic!("expected a return without a value: {:?}", program.warnings);
+    };
+    assert_eq!(
+        warning.to_string(),
+        "`f` returns a value but a return statement has none"
+    );
+    assert_eq!(warning.span.offset(), source.find("return").unwrap());
+    assert_eq!(warning.span.len(), "return;".len());
+    // The caller gets a 0, like from a function that runs off its end.
+    let tacky = Tacky::new(&program).parse().unwrap();
+    assert!(matches!(
+        tacky.0.instruction[..],
+        [Instruction::Return(Some(Value::Constant(0)))]
+    ));
+
+    let mut lexer = Lexer::from_content(source.to_string());
+    let mut sink = Collect::default();
+    let tokens = lexer.tokenize_into(&mut sink).unwrap();
+    let ast = Parser::new(&tokens, &lexer.content)
+        .parse_into(&mut sink)
+        .unwrap();
+    assert!(typecheck_into(&ast, &mut sink).is_some());
+    assert_eq!(sink.count(Severity::Warning), 1);
+}
+
+#[test]
+fn rejects_a_value_returned_from_void() {
+    let source = "void f(void) {\n    return 1 + 2;\n}\n";
+    let Err(TypeError::ReturnValueInVoidFunction { function, span }) = common::try_check(source)
+    else {
+        panic!("expected an error");
+    };
+    assert_eq!(function.to_string(), "f");
+    assert_eq!(span.offset(), source.find("1 + 2").unwrap());
+    assert_eq!(span.len(), "1 + 2".len());
+}
+
+#[test]
+fn warns_when_control_reaches_the_end() {
+    assert_eq!(missing_returns("int f(void) {\n    int a = 1;\n}\n"), ["f"]);
+    // Dead code after the return doesn't make the function run off its end.
+    assert!(
+        missing_returns("int f(void) {\n    int a = 1;\n    return a;\n    a = 2;\n}\n").is_empty()
+    );
+    assert!(missing_returns("void f(void) {\n    int a = 1;\n}\n").is_empty());
+    // `main` returns 0 when it runs off its end.
+    assert!(missing_returns("int main(void) {\n}\n").is_empty());
+}
+
+#[test]
+fn void_functions_return_nothing() {
+    let source = "void f(void) {\n    int a = 1;\n    return;\n    a = 2;\n}\n";
+    let program = common::try_check(source).unwrap();
+    let tacky = Tacky::new(&program).parse().unwrap();
+    assert!(matches!(
+        tacky.0.instruction[..],
+        [
+            Instruction::Copy { .. },
+            Instruction::Return(None),
+            Instruction::Copy { .. },
+            Instruction::Return(None)
+        ]
+    ));
+    let text = tacky.to_string();
+    assert!(text.contains("    return\n"), "{}", text);
+    let parsed: TackyProgram = text.parse().unwrap();
+    assert_eq!(parsed.to_string(), text);
+    assert_eq!(tacky.interpret().unwrap(), 0);
+
+    // Only the epilogue, %eax keeps whatever it held.
+    let assembly = generate_assembly(&tacky, TargetPlatform::X64Linux).0;
+    assert!(!assembly.contains("%eax"), "{}", assembly);
+    assert!(assembly.contains("ret"), "{}", assembly);
+}
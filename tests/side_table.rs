@@ -0,0 +1,91 @@
+//! Node ids of the parser output and the analysis results keyed by them.
+
+mod common;
+
+use ulang::{
+    ast::{AstNode, BlockItem, FunctionDecl, NodeId, Statement, VarType},
+    incremental::{Session, TextEdit},
+    typecheck::typecheck,
+};
+
+const SOURCE: &str = "int main(void) {\n    int a = 2 * 3;\n    a = a + 1;\n    return a;\n}\nint f(void) {\n    return 4;\n}\n";
+
+fn functions(ast: &AstNode) -> Vec<&FunctionDecl> {
+    let AstNode::Program(nodes) = ast else {
+        panic!("not a program");
+    };
+    nodes
+        .iter()
+        .filter_map(|node| match node {
+            AstNode::FunctionDeclaration(function) => Some(function),
+            _ => None,
+        })
+        .collect()
+}
+
+fn items(function: &FunctionDecl) -> &[BlockItem] {
+    let Statement::Compound(items) = &function.body else {
+        panic!("no body");
+    };
+    items
+}
+
+#[test]
+fn records_types_symbols_and_constants() {
+    let ast = common::parse(SOURCE);
+    let program = typecheck(&ast).unwrap();
+    let analysis = &program.analysis;
+    let functions = functions(&ast);
+
+    // Two literals and `*`, `a`, `a`, `1`, `+` and `=`, and the two returned values.
+    assert_eq!(analysis.types.len(), 10);
+    let body = items(functions[0]);
+    let BlockItem::Declaration(declaration) = &body[0] else {
+        panic!("expected a declaration");
+    };
+    let BlockItem::Statement(Statement::Expression(assignment)) = &body[1] else {
+        panic!("expected an assignment");
+    };
+    let BlockItem::Statement(Statement::ReturnStatement(Some(returned), _)) = &body[2] else {
+        panic!("expected a return");
+    };
+    let symbol = *analysis.resolutions.get(declaration.id).unwrap();
+    assert_eq!(analysis.resolutions.get(returned.id), Some(&symbol));
+    assert_eq!(program.symbols.get(symbol).references.len(), 3);
+    assert_eq!(analysis.types.get(returned.id), Some(&VarType::Int));
+
+    let initializer = declaration.initializer.as_ref().unwrap();
+    assert_eq!(analysis.constants.get(initializer.id), Some(&6));
+    assert_eq!(analysis.constants.get(assignment.id), None);
+    let [BlockItem::Statement(Statement::ReturnStatement(Some(four), _))] = items(functions[1])
+    else {
+        panic!("unexpected body");
+    };
+    assert_eq!(analysis.constants.get(four.id), Some(&4));
+    assert!(analysis.resolutions.get(functions[1].id).is_some());
+}
+
+#[test]
+fn sessions_never_reuse_ids() {
+    let mut session = Session::new("main.c".into(), SOURCE.to_string()).unwrap();
+    let offset = SOURCE.find("4;").unwrap();
+    session
+        .apply_edit(&TextEdit {
+            range: offset..offset + 1,
+            replacement: "5".to_string(),
+        })
+        .unwrap();
+    assert_eq!(session.reparsed_items, 1);
+
+    let ast = session.ast().unwrap();
+    let functions = functions(&ast);
+    let ids: Vec<NodeId> = functions.iter().map(|function| function.id).collect();
+    assert_ne!(ids[0], ids[1]);
+    let [BlockItem::Statement(Statement::ReturnStatement(Some(five), _))] = items(functions[1])
+    else {
+        panic!("unexpected body");
+    };
+    let program = typecheck(&ast).unwrap();
+    assert_eq!(program.analysis.constants.get(five.id), Some(&5));
+    assert_eq!(program.analysis.types.len(), 10);
+}
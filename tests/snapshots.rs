@@ -0,0 +1,195 @@
+//! Golden file tests: every `tests/snapshots/*.c` file is run through the
+//! compiler and the output of each stage is compared with the checked in
+//! `<name>.tokens`, `<name>.ast`, `<name>.symbols`, `<name>.tacky` and `<name>.s`
+//! files.
+//!
+//! A stage that fails writes its error instead and the later stages are skipped.
+//! Warnings are listed at the top of the `.tacky` file.
+//! Run `cargo test --test snapshots -- --bless` (or set `ULANG_BLESS=1`) to
+//! update the expected files after an intended change.
+
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use miette::{Diagnostic, SourceSpan};
+use ulang::{
+    assembly::{generate_assembly, TargetPlatform},
+    ast::Identifier,
+    lexer::Lexer,
+    parser::Parser,
+    reachability::{missing_returns, unreachable_code},
+    tacky::Tacky,
+    typecheck::typecheck,
+};
+
+const STAGES: [&str; 5] = ["tokens", "ast", "symbols", "tacky", "s"];
+
+fn span(span: SourceSpan) -> String {
+    format!("{}..{}", span.offset(), span.offset() + span.len())
+}
+
+/// Output of every stage that ran, in the order of [`STAGES`].
+fn run_stages(path: &Path) -> Vec<String> {
+    let mut outputs = vec![];
+    let mut lexer = Lexer::from_path(path.to_path_buf()).unwrap();
+    let tokens = match lexer.tokenize() {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            outputs.push(format!("error: {} at {}\n", e.error, span(e.span)));
+            return outputs;
+        }
+    };
+    let mut listing = String::new();
+    for token in &tokens {
+        let (line, column) = token.line_column(&lexer.content);
+        let _ = writeln!(listing, "{}:{} {:?}", line, column, token.token);
+    }
+    outputs.push(listing);
+
+    let ast = match Parser::new(&tokens, &lexer.content).parse() {
+        Ok(ast) => ast,
+        Err(e) => {
+            outputs.push(format!(
+                "error: {}, found {} at {}\n",
+                e.error,
+                e.token,
+                span(e.span)
+            ));
+            return outputs;
+        }
+    };
+    outputs.push(format!("{:#?}\n", ast));
+
+    let typed = match typecheck(&ast) {
+        Ok(typed) => typed,
+        Err(e) => {
+            let label = e.labels().and_then(|mut labels| labels.next());
+            match label {
+                Some(label) => outputs.push(format!(
+                    "error: {}, {} at {}\n",
+                    e,
+                    label.label().unwrap_or_default(),
+                    span(*label.inner())
+                )),
+                None => outputs.push(format!("error: {}\n", e)),
+            }
+            return outputs;
+        }
+    };
+    let mut symbols = String::new();
+    for symbol in typed.symbols.symbols() {
+        let _ = writeln!(
+            symbols,
+            "{:?} {}: {} at {}, {} references",
+            symbol.kind,
+            symbol.name,
+            symbol.ty,
+            span(symbol.declaration),
+            symbol.references.len()
+        );
+    }
+    outputs.push(symbols);
+
+    // Warnings go in front of the TACKY they were reported with.
+    let mut warnings = String::new();
+    for warning in unreachable_code(&ast) {
+        let _ = writeln!(warnings, "warning: {} at {}", warning, span(warning.span));
+    }
+    for warning in &typed.warnings {
+        let label = warning.labels().and_then(|mut labels| labels.next());
+        let _ = match label {
+            Some(label) => writeln!(warnings, "warning: {} at {}", warning, span(*label.inner())),
+            None => writeln!(warnings, "warning: {}", warning),
+        };
+    }
+    for warning in missing_returns(&ast, Identifier::from("main")) {
+        let _ = writeln!(warnings, "warning: {}", warning);
+    }
+    let tacky = match Tacky::new(&typed).parse() {
+        Ok(tacky) => tacky,
+        Err(e) => {
+            outputs.push(format!("{}error: {}\n", warnings, e));
+            return outputs;
+        }
+    };
+    outputs.push(warnings + &tacky.to_string());
+
+    outputs.push(generate_assembly(&tacky, TargetPlatform::X64Linux).0);
+    outputs
+}
+
+/// First line where the outputs differ, for a readable failure message.
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for nr in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => continue,
+            (None, None) => break,
+            (e, a) => {
+                return format!(
+                    "line {}:\n    expected: {}\n    actual:   {}",
+                    nr,
+                    e.unwrap_or("<end of file>"),
+                    a.unwrap_or("<end of file>")
+                )
+            }
+        }
+    }
+    "only whitespace differs".to_string()
+}
+
+fn main() -> ExitCode {
+    let bless =
+        std::env::args().any(|arg| arg == "--bless") || std::env::var_os("ULANG_BLESS").is_some();
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let mut inputs: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "c"))
+        .collect();
+    inputs.sort();
+
+    let mut failures = 0;
+    for input in &inputs {
+        let outputs = run_stages(input);
+        for (i, stage) in STAGES.iter().enumerate() {
+            let snapshot = input.with_extension(stage);
+            let expected = fs::read_to_string(&snapshot).ok();
+            let actual = outputs.get(i);
+            if expected.as_ref() == actual {
+                continue;
+            }
+            if bless {
+                match actual {
+                    Some(actual) => fs::write(&snapshot, actual).unwrap(),
+                    None => fs::remove_file(&snapshot).unwrap(),
+                }
+                println!("blessed {}", snapshot.display());
+                continue;
+            }
+            failures += 1;
+            let reason = match (&expected, actual) {
+                (None, _) => "snapshot is missing".to_string(),
+                (_, None) => "stage did not run".to_string(),
+                (Some(expected), Some(actual)) => first_difference(expected, actual),
+            };
+            println!("FAILED {}: {}", snapshot.display(), reason);
+        }
+    }
+
+    println!(
+        "snapshots: {} inputs, {} mismatches",
+        inputs.len(),
+        failures
+    );
+    if failures > 0 {
+        println!("run `cargo test --test snapshots -- --bless` to accept the new output");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,3 @@
+int main(void) {
+    return 1 + 2 * 3 - 8 / 4 % 3;
+}
@@ -0,0 +1,36 @@
+	.text
+	.balign 16
+	.globl main
+	.type	main, @function
+main:
+	pushq	%rbp
+	movq	%rsp, %rbp
+	subq $20, %rsp
+	movl	$2, -4(%rbp)
+	movl	-4(%rbp), %r11d
+	imull	$3, %r11d
+	movl	%r11d, -4(%rbp)
+	movl	$1, -8(%rbp)
+	movl	-4(%rbp), %r10d
+	addl	%r10d, -8(%rbp)
+	movl	$8, %eax
+	cdq
+	movl	$4, %r10d
+	idivl	%r10d
+	movl	%eax, -12(%rbp)
+	movl	-12(%rbp), %eax
+	cdq
+	movl	$3, %r10d
+	idivl	%r10d
+	movl	%edx, -16(%rbp)
+	movl	-8(%rbp), %r10d
+	movl	%r10d, -20(%rbp)
+	movl	-16(%rbp), %r10d
+	subl	%r10d, -20(%rbp)
+	movl	-20(%rbp), %eax
+	movq	%rbp, %rsp
+	popq	%rbp
+	ret
+	.size	main, .-main
+	.ident	"ulang 0.1.0"
+	.section	.note.GNU-stack,"",@progbits
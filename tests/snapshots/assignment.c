@@ -0,0 +1,6 @@
+int main(void) {
+    int a;
+    int b = 2;
+    a = b = b * 3;
+    return a + b;
+}
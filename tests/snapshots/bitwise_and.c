@@ -0,0 +1,4 @@
+int main(void) {
+    int a = 6;
+    return a == 6 & 3;
+}
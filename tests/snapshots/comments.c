@@ -0,0 +1,6 @@
+#include <stdio.h>
+
+// leading comment
+int main(void) { /* block */
+    return 10 % 4; // trailing
+}
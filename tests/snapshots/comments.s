@@ -0,0 +1,20 @@
+	.text
+	.balign 16
+	.globl main
+	.type	main, @function
+main:
+	pushq	%rbp
+	movq	%rsp, %rbp
+	subq $4, %rsp
+	movl	$10, %eax
+	cdq
+	movl	$4, %r10d
+	idivl	%r10d
+	movl	%edx, -4(%rbp)
+	movl	-4(%rbp), %eax
+	movq	%rbp, %rsp
+	popq	%rbp
+	ret
+	.size	main, .-main
+	.ident	"ulang 0.1.0"
+	.section	.note.GNU-stack,"",@progbits
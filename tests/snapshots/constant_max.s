@@ -0,0 +1,15 @@
+	.text
+	.balign 16
+	.globl main
+	.type	main, @function
+main:
+	pushq	%rbp
+	movq	%rsp, %rbp
+	subq $0, %rsp
+	movl	$2147483647, %eax
+	movq	%rbp, %rsp
+	popq	%rbp
+	ret
+	.size	main, .-main
+	.ident	"ulang 0.1.0"
+	.section	.note.GNU-stack,"",@progbits
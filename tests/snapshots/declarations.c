@@ -0,0 +1,5 @@
+int main(void) {
+    int a;
+    int b;
+    return 3;
+}
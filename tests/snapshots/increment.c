@@ -0,0 +1,8 @@
+int main(void) {
+    int a = 5;
+    int b = a++;
+    int c = ++a;
+    int d = a--;
+    --a;
+    return a + b * 2 + c * 4 + d * 8;
+}
@@ -0,0 +1,65 @@
+	.text
+	.balign 16
+	.globl main
+	.type	main, @function
+main:
+	pushq	%rbp
+	movq	%rsp, %rbp
+	subq $48, %rsp
+	movl	$5, -4(%rbp)
+	movl	-4(%rbp), %r10d
+	movl	%r10d, -8(%rbp)
+	movl	-4(%rbp), %r10d
+	movl	%r10d, -4(%rbp)
+	addl	$1, -4(%rbp)
+	movl	-8(%rbp), %r10d
+	movl	%r10d, -12(%rbp)
+	movl	-4(%rbp), %r10d
+	movl	%r10d, -4(%rbp)
+	addl	$1, -4(%rbp)
+	movl	-4(%rbp), %r10d
+	movl	%r10d, -16(%rbp)
+	movl	-4(%rbp), %r10d
+	movl	%r10d, -20(%rbp)
+	movl	-4(%rbp), %r10d
+	movl	%r10d, -4(%rbp)
+	subl	$1, -4(%rbp)
+	movl	-20(%rbp), %r10d
+	movl	%r10d, -24(%rbp)
+	movl	-4(%rbp), %r10d
+	movl	%r10d, -4(%rbp)
+	subl	$1, -4(%rbp)
+	movl	-12(%rbp), %r10d
+	movl	%r10d, -28(%rbp)
+	movl	-28(%rbp), %r11d
+	imull	$2, %r11d
+	movl	%r11d, -28(%rbp)
+	movl	-4(%rbp), %r10d
+	movl	%r10d, -32(%rbp)
+	movl	-28(%rbp), %r10d
+	addl	%r10d, -32(%rbp)
+	movl	-16(%rbp), %r10d
+	movl	%r10d, -36(%rbp)
+	movl	-36(%rbp), %r11d
+	imull	$4, %r11d
+	movl	%r11d, -36(%rbp)
+	movl	-32(%rbp), %r10d
+	movl	%r10d, -40(%rbp)
+	movl	-36(%rbp), %r10d
+	addl	%r10d, -40(%rbp)
+	movl	-24(%rbp), %r10d
+	movl	%r10d, -44(%rbp)
+	movl	-44(%rbp), %r11d
+	imull	$8, %r11d
+	movl	%r11d, -44(%rbp)
+	movl	-40(%rbp), %r10d
+	movl	%r10d, -48(%rbp)
+	movl	-44(%rbp), %r10d
+	addl	%r10d, -48(%rbp)
+	movl	-48(%rbp), %eax
+	movq	%rbp, %rsp
+	popq	%rbp
+	ret
+	.size	main, .-main
+	.ident	"ulang 0.1.0"
+	.section	.note.GNU-stack,"",@progbits
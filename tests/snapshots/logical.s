@@ -0,0 +1,39 @@
+	.text
+	.balign 16
+	.globl main
+	.type	main, @function
+main:
+	pushq	%rbp
+	movq	%rsp, %rbp
+	subq $12, %rsp
+	movl	$0, %r11d
+	cmpl	$0, %r11d
+	movl	$0, -4(%rbp)
+	sete	-4(%rbp)
+	cmpl	$0, -4(%rbp)
+	je	.Lmain.label.0
+	movl	$2, %r11d
+	cmpl	$0, %r11d
+	jne	.Lmain.label.2
+	movl	$0, %r11d
+	cmpl	$0, %r11d
+	jne	.Lmain.label.2
+	movl	$0, -8(%rbp)
+	jmp	.Lmain.label.3
+.Lmain.label.2:
+	movl	$1, -8(%rbp)
+.Lmain.label.3:
+	cmpl	$0, -8(%rbp)
+	je	.Lmain.label.0
+	movl	$1, -12(%rbp)
+	jmp	.Lmain.label.1
+.Lmain.label.0:
+	movl	$0, -12(%rbp)
+.Lmain.label.1:
+	movl	-12(%rbp), %eax
+	movq	%rbp, %rsp
+	popq	%rbp
+	ret
+	.size	main, .-main
+	.ident	"ulang 0.1.0"
+	.section	.note.GNU-stack,"",@progbits
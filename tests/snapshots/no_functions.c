@@ -0,0 +1 @@
+// nothing but a comment
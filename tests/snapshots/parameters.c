@@ -0,0 +1,3 @@
+int main(int argc, char **argv, unsigned long size, char c) {
+    return argc * 2;
+}
@@ -0,0 +1,3 @@
+int main(void) {
+    return (1 < 2) == (3 >= 3) != (4 <= 1) > 0;
+}
@@ -0,0 +1,38 @@
+	.text
+	.balign 16
+	.globl main
+	.type	main, @function
+main:
+	pushq	%rbp
+	movq	%rsp, %rbp
+	subq $24, %rsp
+	movl	$1, %r11d
+	cmpl	$2, %r11d
+	movl	$0, -4(%rbp)
+	setl	-4(%rbp)
+	movl	$3, %r11d
+	cmpl	$3, %r11d
+	movl	$0, -8(%rbp)
+	setge	-8(%rbp)
+	movl	-8(%rbp), %r10d
+	cmpl	%r10d, -4(%rbp)
+	movl	$0, -12(%rbp)
+	sete	-12(%rbp)
+	movl	$4, %r11d
+	cmpl	$1, %r11d
+	movl	$0, -16(%rbp)
+	setle	-16(%rbp)
+	cmpl	$0, -16(%rbp)
+	movl	$0, -20(%rbp)
+	setg	-20(%rbp)
+	movl	-20(%rbp), %r10d
+	cmpl	%r10d, -12(%rbp)
+	movl	$0, -24(%rbp)
+	setne	-24(%rbp)
+	movl	-24(%rbp), %eax
+	movq	%rbp, %rsp
+	popq	%rbp
+	ret
+	.size	main, .-main
+	.ident	"ulang 0.1.0"
+	.section	.note.GNU-stack,"",@progbits
@@ -0,0 +1,88 @@
+//! Files loaded into one manager, positions resolved in them and diagnostics
+//! of several files reported into one sink.
+
+use std::path::{Path, PathBuf};
+
+use ulang::{
+    diagnostics::{Collect, DiagnosticSink},
+    source_manager::{InFile, Location, SourceManager},
+};
+
+#[test]
+fn resolves_offsets_to_lines_and_columns() {
+    let mut sources = SourceManager::default();
+    let file = sources.add(PathBuf::from("a.c"), "ab\n\u{e9}x\n\nz".to_string());
+    let location = |offset| {
+        let Location { line, column, .. } = sources.resolve(file, offset);
+        (line, column)
+    };
+    assert_eq!(location(0), (1, 1));
+    assert_eq!(location(2), (1, 3));
+    assert_eq!(location(3), (2, 1));
+    // `x` follows a two byte char.
+    assert_eq!(location(5), (2, 2));
+    // Inside the two byte char, and past the end.
+    assert_eq!(location(4), (2, 1));
+    assert_eq!(location(7), (3, 1));
+    assert_eq!(location(100), (4, 2));
+}
+
+#[test]
+fn assigns_ids_per_file() {
+    let mut sources = SourceManager::default();
+    let a = sources.add(PathBuf::from("a.c"), "int a;".to_string());
+    let b = sources.add(PathBuf::from("b.c"), "int b;".to_string());
+    assert_ne!(a, b);
+    assert_eq!(sources.find(Path::new("b.c")), Some(b));
+    assert_eq!(sources.content(a), "int a;");
+    assert_eq!(sources.lexer(b).path, Path::new("b.c"));
+    assert_eq!(sources.files().count(), 2);
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("samples/return_2.c");
+    let loaded = sources.load(path.clone()).unwrap();
+    assert_eq!(sources.load(path).unwrap(), loaded);
+    assert_eq!(sources.files().count(), 3);
+}
+
+#[test]
+fn tags_diagnostics_with_their_file() {
+    let mut sources = SourceManager::default();
+    let good = sources.add(PathBuf::from("good.c"), "int main(void) {}".to_string());
+    let bad = sources.add(PathBuf::from("bad.c"), "int main(void) { $ }".to_string());
+
+    let mut collected = Collect::default();
+    for file in [good, bad] {
+        let mut sink = sources.sink_for(file, &mut collected);
+        sources.lexer(file).tokenize_into(&mut sink);
+    }
+    assert_eq!(collected.diagnostics.len(), 1);
+    let diagnostic = collected.diagnostics[0].as_ref();
+    assert_eq!(InFile::file_of(diagnostic), Some(bad));
+    assert_eq!(diagnostic.to_string(), "Failed to parse the code");
+
+    let label = diagnostic.labels().unwrap().next().unwrap();
+    let span = diagnostic
+        .source_code()
+        .unwrap()
+        .read_span(label.inner(), 0, 0)
+        .unwrap();
+    assert_eq!(span.name(), Some("bad.c"));
+    assert_eq!(span.data(), b"$");
+    assert_eq!(collected.count(miette::Severity::Error), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn names_the_files_with_terminal_hyperlinks() {
+    let path = PathBuf::from("/src/my file.c");
+    let mut sources = SourceManager::default();
+    let plain = sources.add(path.clone(), "int".to_string());
+    assert_eq!(sources.named_source(plain).name(), "/src/my file.c");
+
+    let mut sources = SourceManager::default().with_hyperlinks(true);
+    let linked = sources.add(path, "int".to_string());
+    assert_eq!(
+        sources.named_source(linked).name(),
+        "\x1b]8;;file:///src/my%20file.c\x1b\\/src/my file.c\x1b]8;;\x1b\\"
+    );
+}
@@ -0,0 +1,106 @@
+//! Source maps relating the generated assembly to the C source.
+
+mod common;
+
+use ulang::{
+    assembly::{
+        generate_assembly_with_source_map, FunctionSections, Instrumentation, Mapping,
+        RuntimeChecks, SourceMap, TargetPlatform,
+    },
+    optimize::PassManager,
+    tacky::TackyProgram,
+};
+
+const SOURCE: &str = "int main(void) {
+    return 1 +
+        4 * 5;
+}
+";
+
+fn compile(source: &str) -> (Vec<String>, SourceMap) {
+    let tacky = common::lower_with_locations(source);
+    let (assembly, map) = generate_assembly_with_source_map(
+        &tacky,
+        TargetPlatform::X64Linux,
+        &PassManager::default(),
+        &RuntimeChecks::default(),
+        &Instrumentation::default(),
+        FunctionSections::Shared,
+    );
+    let lines = assembly.0.lines().map(str::to_string).collect();
+    (lines, map)
+}
+
+/// Position the assembly line containing `text` maps to.
+fn position_of(lines: &[String], map: &SourceMap, text: &str) -> Option<(usize, usize)> {
+    let index = lines.iter().position(|line| line.contains(text))?;
+    map.get(index + 1)
+        .map(|mapping| (mapping.line, mapping.column))
+}
+
+#[test]
+fn operators_map_to_their_position() {
+    let (lines, map) = compile(SOURCE);
+    assert_eq!(position_of(&lines, &map, "imull"), Some((3, 11)));
+    assert_eq!(position_of(&lines, &map, "addl"), Some((2, 14)));
+}
+
+#[test]
+fn return_maps_to_the_statement() {
+    let (lines, map) = compile(SOURCE);
+    assert_eq!(position_of(&lines, &map, "%eax"), Some((2, 5)));
+    assert_eq!(position_of(&lines, &map, "ret"), Some((2, 5)));
+}
+
+#[test]
+fn prologue_is_not_mapped() {
+    let (lines, map) = compile(SOURCE);
+    assert_eq!(position_of(&lines, &map, "pushq"), None);
+    assert_eq!(position_of(&lines, &map, ".size"), None);
+}
+
+#[test]
+fn json_lists_every_mapping() {
+    let map = SourceMap {
+        mappings: vec![
+            Mapping {
+                asm_line: 7,
+                line: 2,
+                column: 5,
+            },
+            Mapping {
+                asm_line: 8,
+                line: 3,
+                column: 11,
+            },
+        ],
+    };
+    assert_eq!(
+        map.to_json("out.s", "dir\\\"quoted\".c"),
+        r#"{
+  "version": 1,
+  "file": "out.s",
+  "source": "dir\\\"quoted\".c",
+  "mappings": [
+    {"asm_line": 7, "line": 2, "column": 5},
+    {"asm_line": 8, "line": 3, "column": 11}
+  ]
+}
+"#
+    );
+}
+
+#[test]
+fn annotates_the_tacky_text_with_positions() {
+    let tacky = common::lower_with_locations(SOURCE);
+    let text = tacky.to_annotated_string();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[1], "    main.tmp.0 = 4 * 5               // 3:11");
+    assert_eq!(lines[2], "    main.tmp.1 = 1 + main.tmp.0      // 2:14");
+    assert_eq!(lines[3], "    return main.tmp.1                // 2:5");
+
+    // The comments are skipped when the text is read back.
+    let read: TackyProgram = text.parse().unwrap();
+    let plain = common::lower(SOURCE);
+    assert_eq!(read.to_string(), plain.to_string());
+}
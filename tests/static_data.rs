@@ -0,0 +1,299 @@
+//! Emission of variables with static storage and of pooled constants into the
+//! data sections, and of the functions into the text sections.
+//!
+//! Besides checking the text, the output is assembled with the system C
+//! compiler when there is one.
+
+use std::process::Command;
+
+use ulang::{
+    assembly::{
+        generate_top_levels, AsmFunctionDef, AsmInstruction, AsmRegistry, AsmTopLevel, AsmType,
+        ConstantPool, FunctionSections, Operand, StaticInit, StaticVariable, TargetPlatform,
+    },
+    ast::Identifier,
+};
+
+fn variable(name: &str, global: bool, alignment: u32, init: Vec<StaticInit>) -> AsmTopLevel {
+    AsmTopLevel::StaticVariable(StaticVariable {
+        name: Identifier::from(name),
+        global,
+        alignment,
+        init,
+    })
+}
+
+fn main_function() -> AsmTopLevel {
+    AsmTopLevel::Function(AsmFunctionDef {
+        name: Identifier::from("main"),
+        instructions: vec![
+            AsmInstruction::Mov {
+                ty: AsmType::Longword,
+                src: Operand::Imm(0),
+                dst: Operand::Register(AsmRegistry::AX),
+            },
+            AsmInstruction::Ret,
+        ],
+        traps: vec![],
+        counters: vec![],
+    })
+}
+
+/// Lines of the generated assembly with whitespace normalized.
+fn lines(items: &[AsmTopLevel], platform: TargetPlatform) -> Vec<String> {
+    lines_with(items, &ConstantPool::default(), platform)
+}
+
+fn lines_with(
+    items: &[AsmTopLevel],
+    constants: &ConstantPool,
+    platform: TargetPlatform,
+) -> Vec<String> {
+    sectioned_lines(items, constants, platform, FunctionSections::Shared)
+}
+
+fn sectioned_lines(
+    items: &[AsmTopLevel],
+    constants: &ConstantPool,
+    platform: TargetPlatform,
+    sections: FunctionSections,
+) -> Vec<String> {
+    generate_top_levels(items, constants, platform, sections)
+        .0
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect()
+}
+
+fn contains_sequence(lines: &[String], expected: &[&str]) -> bool {
+    lines
+        .windows(expected.len())
+        .any(|window| window == expected)
+}
+
+#[test]
+fn initialized_variables_go_to_data() {
+    let items = [
+        main_function(),
+        variable("answer", true, 4, vec![StaticInit::Long(42)]),
+    ];
+    let lines = lines(&items, TargetPlatform::X64Linux);
+    assert!(contains_sequence(
+        &lines,
+        &[
+            ".globl answer",
+            ".data",
+            ".balign 4",
+            ".type answer, @object",
+            ".size answer, 4",
+            "answer:",
+            ".long 42",
+        ]
+    ));
+}
+
+#[test]
+fn zeroed_variables_go_to_bss() {
+    let items = [variable(
+        "table",
+        true,
+        8,
+        vec![StaticInit::Quad(0), StaticInit::Zero(12)],
+    )];
+    let lines = lines(&items, TargetPlatform::X64Linux);
+    assert!(contains_sequence(
+        &lines,
+        &[
+            ".bss",
+            ".balign 8",
+            ".type table, @object",
+            ".size table, 20"
+        ]
+    ));
+    assert!(contains_sequence(
+        &lines,
+        &["table:", ".zero 8", ".zero 12"]
+    ));
+}
+
+#[test]
+fn static_variables_are_not_exported() {
+    let items = [variable("counter", false, 4, vec![StaticInit::Long(1)])];
+    let lines = lines(&items, TargetPlatform::X64Linux);
+    assert!(!lines.iter().any(|line| line.starts_with(".globl")));
+}
+
+#[test]
+fn macos_symbols_get_an_underscore() {
+    let items = [
+        main_function(),
+        variable("answer", true, 4, vec![StaticInit::Long(42)]),
+    ];
+    let lines = lines(&items, TargetPlatform::MacOsX64);
+    assert!(contains_sequence(&lines, &[".globl _main", "_main:"]));
+    assert!(contains_sequence(
+        &lines,
+        &[
+            ".globl _answer",
+            ".data",
+            ".p2align 2",
+            "_answer:",
+            ".long 42"
+        ]
+    ));
+    assert!(!lines.iter().any(|line| line.starts_with(".type")));
+    // The `as` of clang knows neither `.ident` nor the GNU-stack note.
+    assert!(lines.last().unwrap().starts_with("## ulang"));
+    assert!(!lines.iter().any(|line| line.contains("GNU-stack")));
+}
+
+#[test]
+fn output_assembles() {
+    let Some(cc) = ["cc", "gcc", "clang"].into_iter().find(|cc| {
+        Command::new(cc)
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }) else {
+        return;
+    };
+    #[cfg(target_os = "linux")]
+    let platform = TargetPlatform::X64Linux;
+    #[cfg(not(target_os = "linux"))]
+    let platform = TargetPlatform::MacOsX64;
+    let items = [
+        main_function(),
+        variable("answer", true, 4, vec![StaticInit::Long(42)]),
+        variable("counter", false, 4, vec![StaticInit::Long(0)]),
+        variable(
+            "wide",
+            true,
+            8,
+            vec![StaticInit::Quad(-1), StaticInit::Zero(8)],
+        ),
+    ];
+    let mut constants = ConstantPool::default();
+    constants.string("say \"hi\"\n");
+    constants.double(0.5);
+    let dir = std::env::temp_dir().join(format!("ulang-static-data-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("data.s");
+    let assembly = generate_top_levels(&items, &constants, platform, FunctionSections::Separate);
+    std::fs::write(&source, assembly.0).unwrap();
+    let output = Command::new(cc)
+        .arg("-c")
+        .arg(&source)
+        .arg("-o")
+        .arg(dir.join("data.o"))
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn functions_are_aligned_in_their_sections() {
+    let items = [main_function()];
+    let constants = ConstantPool::default();
+    let shared = lines(&items, TargetPlatform::X64Linux);
+    assert!(contains_sequence(
+        &shared,
+        &[".text", ".balign 16", ".globl main"]
+    ));
+    let separate = sectioned_lines(
+        &items,
+        &constants,
+        TargetPlatform::X64Linux,
+        FunctionSections::Separate,
+    );
+    assert!(contains_sequence(
+        &separate,
+        &[
+            ".section .text.main,\"ax\",@progbits",
+            ".balign 16",
+            ".globl main"
+        ]
+    ));
+    // Mach-O has no sections per function.
+    let macos = sectioned_lines(
+        &items,
+        &constants,
+        TargetPlatform::MacOsX64,
+        FunctionSections::Separate,
+    );
+    assert!(contains_sequence(
+        &macos,
+        &[".text", ".p2align 4", ".globl _main"]
+    ));
+}
+
+#[test]
+fn constants_are_pooled_by_content() {
+    let mut constants = ConstantPool::default();
+    let hello = constants.string("hello");
+    let half = constants.double(0.5);
+    assert_eq!(constants.string("hello"), hello);
+    assert_eq!(constants.double(0.5), half);
+    assert_ne!(constants.string("world"), hello);
+    // Equal as numbers but stored differently.
+    assert_ne!(constants.double(-0.0), constants.double(0.0));
+    assert_eq!(constants.constants().len(), 5);
+}
+
+#[test]
+fn constant_labels_are_numbered_per_kind() {
+    let mut constants = ConstantPool::default();
+    let labels = [
+        constants.string("a"),
+        constants.double(1.0),
+        constants.string("b"),
+        constants.double(2.0),
+    ];
+    let names: Vec<String> = labels.iter().map(|label| label.to_string()).collect();
+    assert_eq!(
+        names,
+        [
+            "ulang.string.0",
+            "ulang.double.0",
+            "ulang.string.1",
+            "ulang.double.1"
+        ]
+    );
+}
+
+#[test]
+fn constants_go_to_read_only_sections() {
+    let mut constants = ConstantPool::default();
+    constants.string("line\n");
+    constants.double(1.5);
+
+    let linux = lines_with(&[], &constants, TargetPlatform::X64Linux);
+    assert!(contains_sequence(
+        &linux,
+        &[
+            ".section .rodata",
+            ".Lulang.string.0:",
+            ".asciz \"line\\n\""
+        ]
+    ));
+    assert!(contains_sequence(
+        &linux,
+        &[
+            ".section .rodata",
+            ".balign 8",
+            ".Lulang.double.0:",
+            &format!(".quad {}", 1.5f64.to_bits())
+        ]
+    ));
+
+    let macos = lines_with(&[], &constants, TargetPlatform::MacOsX64);
+    assert!(contains_sequence(&macos, &[".cstring", "Lulang.string.0:"]));
+    assert!(contains_sequence(
+        &macos,
+        &[".literal8", ".p2align 3", "Lulang.double.0:"]
+    ));
+}
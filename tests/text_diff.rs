@@ -0,0 +1,60 @@
+//! Line differences as a unified diff: the lines are lined up, the hunks
+//! keep their context and merge when it overlaps, and the headers count the
+//! lines the way `diff -u` does.
+
+use ulang::text_diff::{diff_lines, unified, DiffLine};
+
+#[test]
+fn lines_up_the_common_lines() {
+    assert_eq!(
+        diff_lines("a\nb\nc\n", "a\nx\nc\nd\n"),
+        [
+            DiffLine::Same("a"),
+            DiffLine::Removed("b"),
+            DiffLine::Added("x"),
+            DiffLine::Same("c"),
+            DiffLine::Added("d"),
+        ]
+    );
+}
+
+#[test]
+fn prints_nothing_for_the_same_lines() {
+    assert_eq!(unified("a\nb\n", "a\nb", "old", "new", 3), "");
+}
+
+#[test]
+fn keeps_the_context_around_a_change() {
+    let old = "1\n2\n3\n4\n5\n6\n7\n8\n";
+    let new = "1\n2\n3\n4\nfive\n6\n7\n8\n";
+    assert_eq!(
+        unified(old, new, "old", "new", 2),
+        "--- old\n+++ new\n@@ -3,5 +3,5 @@\n 3\n 4\n-5\n+five\n 6\n 7\n"
+    );
+}
+
+#[test]
+fn splits_the_changes_far_apart_into_hunks() {
+    let old = "a\n1\n2\n3\n4\n5\nb\n";
+    let new = "A\n1\n2\n3\n4\n5\nB\n";
+    assert_eq!(
+        unified(old, new, "old", "new", 1),
+        "--- old\n+++ new\n@@ -1,2 +1,2 @@\n-a\n+A\n 1\n@@ -6,2 +6,2 @@\n 5\n-b\n+B\n"
+    );
+    // With more context the hunks would overlap, so they are one.
+    let merged = unified(old, new, "old", "new", 3);
+    assert!(merged.contains("@@ -1,7 +1,7 @@\n"), "{}", merged);
+    assert_eq!(merged.matches("@@ -").count(), 1);
+}
+
+#[test]
+fn counts_empty_ranges_from_the_line_before() {
+    assert_eq!(
+        unified("a\n", "", "old", "new", 3),
+        "--- old\n+++ new\n@@ -1 +0,0 @@\n-a\n"
+    );
+    assert_eq!(
+        unified("", "a\n", "old", "new", 3),
+        "--- old\n+++ new\n@@ -0,0 +1 @@\n+a\n"
+    );
+}
@@ -0,0 +1,46 @@
+//! Lookahead, expectations and rollback of the parser's token cursor.
+
+use ulang::{
+    lexer::{Lexer, Token},
+    parser::ParserErrorType,
+    token_stream::TokenStream,
+};
+
+#[test]
+fn looks_ahead_and_rolls_back() {
+    let source = "return (x);";
+    let tokens = Lexer::from_content(source.to_string()).tokenize().unwrap();
+    let mut stream = TokenStream::new(&tokens, source.len());
+    assert_eq!(stream.peek_n(2).token, Token::Identifier("x".into()));
+    assert_eq!(stream.previous(), None);
+
+    let start = stream.checkpoint();
+    assert!(stream.eat(&Token::ReturnKeyWord));
+    assert!(stream
+        .expect(&Token::OpenParenthesis, ParserErrorType::ExpectedChar('('))
+        .is_ok());
+    let error = stream
+        .expect(&Token::Constant(1), ParserErrorType::ExpectedExpression)
+        .unwrap_err();
+    assert_eq!((error.span.offset(), error.span.len()), (8, 1));
+    assert_eq!(stream.previous().unwrap().token, Token::OpenParenthesis);
+
+    stream.rollback(start);
+    assert_eq!(stream.peek().token, Token::ReturnKeyWord);
+}
+
+#[test]
+fn ends_with_an_end_of_file_token() {
+    let source = "x ";
+    let tokens = Lexer::from_content(source.to_string()).tokenize().unwrap();
+    let mut stream = TokenStream::new(&tokens, source.len());
+    stream.advance();
+    assert!(stream.is_at_end());
+    for _ in 0..2 {
+        let end = stream.advance();
+        assert_eq!((&end.token, end.offset, end.len), (&Token::EndOfFile, 2, 0));
+    }
+    assert_eq!(stream.peek_n(5).token, Token::EndOfFile);
+    let error = stream.error(ParserErrorType::ExpectedStatement);
+    assert_eq!(error.token, Token::EndOfFile);
+}
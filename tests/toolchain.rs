@@ -0,0 +1,127 @@
+//! The commands of the toolchain steps, and how running a tool reports its
+//! output and its failures, with `tests/fake_cc.sh` standing in for the C
+//! compiler. Then the defaults read from the environment.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use ulang::{
+    assembly::TargetPlatform,
+    toolchain::{self, ToolOutput, ToolchainEnv, ToolchainError},
+};
+
+fn fake_cc() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fake_cc.sh")
+}
+
+fn args(command: &Command) -> Vec<String> {
+    command
+        .get_args()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn splits_the_compiler_from_its_own_arguments() {
+    let command = toolchain::link("gcc -m64", Path::new("a.s"), Path::new("a"), &["-nostdlib"]);
+    assert_eq!(command.get_program(), "gcc");
+    assert_eq!(args(&command), ["-m64", "a.s", "-o", "a", "-nostdlib"]);
+    let command = toolchain::assemble("cc", Path::new("a.s"), Path::new("a.o"));
+    assert_eq!(args(&command), ["-c", "a.s", "-o", "a.o"]);
+    let command = toolchain::preprocess("cc", Path::new("a.c"), Path::new("a.i"));
+    assert_eq!(args(&command), ["-E", "-P", "a.c", "-o", "a.i"]);
+    let command = toolchain::archive(Path::new("lib.a"), &["a.o", "b.o"]);
+    assert_eq!(command.get_program(), "ar");
+    assert_eq!(args(&command), ["rcs", "lib.a", "a.o", "b.o"]);
+}
+
+#[test]
+fn quotes_the_words_a_shell_would_split() {
+    let mut command = Command::new("cc");
+    command.args(["-o", "my program", "it's", ""]);
+    assert_eq!(
+        toolchain::command_line(&command),
+        r"cc -o 'my program' 'it'\''s' ''"
+    );
+}
+
+#[test]
+fn reports_a_tool_that_can_not_be_started() {
+    let mut command = toolchain::command("ulang-no-such-cc -v");
+    let error = toolchain::run(&mut command).unwrap_err();
+    assert!(matches!(error, ToolchainError::Spawn { .. }));
+    assert_eq!(error.to_string(), "Failed to run `ulang-no-such-cc -v`");
+    assert!(error.output().is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn captures_what_the_tool_prints() {
+    let mut command = toolchain::command(fake_cc().to_str().unwrap());
+    command.env_remove("FAKE_CC_STATUS");
+    assert_eq!(
+        toolchain::run(&mut command).unwrap(),
+        ToolOutput {
+            status: Some(0),
+            stdout: "fake cc ran\n".to_string(),
+            stderr: "fake cc: warning on stderr\n".to_string(),
+        }
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn fails_with_the_status_and_the_stderr_of_the_tool() {
+    let mut command = toolchain::command(fake_cc().to_str().unwrap());
+    command.arg("-c").env("FAKE_CC_STATUS", "3");
+    let error = toolchain::run(&mut command).unwrap_err();
+    let ToolchainError::Failed { stderr, .. } = &error else {
+        panic!("expected a failure, got {:?}", error);
+    };
+    assert_eq!(stderr.as_deref(), Some("fake cc: warning on stderr"));
+    assert_eq!(error.output().unwrap().status, Some(3));
+    assert_eq!(error.output().unwrap().stdout, "fake cc ran\n");
+    assert_eq!(
+        error.to_string(),
+        format!("`{} -c` failed with exit status 3", fake_cc().display())
+    );
+}
+
+fn env(vars: &[(&str, &str)]) -> Result<ToolchainEnv, ToolchainError> {
+    let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+    ToolchainEnv::from_vars(|name| vars.get(name).map(|value| value.to_string()))
+}
+
+#[test]
+fn reads_the_defaults_from_the_environment() {
+    assert_eq!(env(&[]).unwrap(), ToolchainEnv::default());
+    assert_eq!(
+        env(&[
+            ("CC", "clang -m64"),
+            ("ULANG_TARGET", "macos-x64"),
+            ("LDFLAGS", "-L/opt/lib  -lm"),
+            ("CFLAGS", "-O2"),
+        ])
+        .unwrap(),
+        ToolchainEnv {
+            cc: Some("clang -m64".to_string()),
+            target: Some(TargetPlatform::MacOsX64),
+            ldflags: vec!["-L/opt/lib".to_string(), "-lm".to_string()],
+        }
+    );
+    // Set but empty, as `CC= make` does.
+    let empty = env(&[("CC", ""), ("ULANG_TARGET", " "), ("LDFLAGS", "")]);
+    assert_eq!(empty.unwrap(), ToolchainEnv::default());
+}
+
+#[test]
+fn rejects_an_unknown_target() {
+    let error = env(&[("ULANG_TARGET", "vax")]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Invalid $ULANG_TARGET: Unknown target \"vax\", expected x64-linux or macos-x64"
+    );
+}
@@ -0,0 +1,178 @@
+//! The invariants the TACKY verifier checks between the passes, one program
+//! breaking each of them, then programs it has to accept.
+
+use ulang::tacky::{cfg::ControlFlowGraph, ssa::SsaProgram, TackyProgram, VerifyError};
+
+fn verify(text: &str) -> Result<(), VerifyError> {
+    text.parse::<TackyProgram>().unwrap().verify()
+}
+
+fn error(text: &str) -> String {
+    verify(text).unwrap_err().to_string()
+}
+
+#[test]
+fn rejects_a_jump_to_an_undefined_label() {
+    let text = "function main {
+    goto main.label.0
+  main.label.1:
+    return 0
+}
+";
+    assert!(matches!(verify(text), Err(VerifyError::UndefinedLabel(_))));
+    assert_eq!(error(text), "Jump to undefined label main.label.0");
+}
+
+#[test]
+fn rejects_a_label_defined_twice() {
+    let text = "function main {
+  main.label.0:
+    a = 1
+  main.label.0:
+    return a
+}
+";
+    assert!(matches!(verify(text), Err(VerifyError::DuplicateLabel(_))));
+    assert_eq!(error(text), "Label main.label.0 is defined more than once");
+}
+
+#[test]
+fn rejects_a_temporary_read_before_it_is_written_on_every_path() {
+    let text = "function main {
+    a = 1
+    ifz a goto main.label.0
+    main.tmp.0 = a + 1
+  main.label.0:
+    return main.tmp.0
+}
+";
+    assert!(matches!(
+        verify(text),
+        Err(VerifyError::UseBeforeDefinition(_))
+    ));
+    assert_eq!(error(text), "main.tmp.0 is used before it is defined");
+}
+
+#[test]
+fn rejects_a_write_to_a_constant() {
+    let text = "function main {
+    3 = 1
+    return 0
+}
+";
+    assert!(matches!(
+        verify(text),
+        Err(VerifyError::ConstantDestination(_))
+    ));
+    assert_eq!(error(text), "Instruction `3 = 1` writes to a constant");
+}
+
+#[test]
+fn rejects_running_off_the_end() {
+    let text = "function main {
+    a = 1
+}
+";
+    assert!(matches!(
+        verify(text),
+        Err(VerifyError::UnterminatedBlock(_))
+    ));
+    assert_eq!(
+        error(text),
+        "Control falls off the end of the function after `a = 1`"
+    );
+    let text = "function main {
+  main.label.0:
+    goto main.label.0
+}
+";
+    assert!(matches!(verify(text), Err(VerifyError::MissingReturn(_))));
+    assert_eq!(error(text), "Function main does not end with a return");
+}
+
+#[test]
+fn rejects_a_shift_by_a_variable() {
+    let text = "function main {
+    x = 5
+    a = x << x
+    return a
+}
+";
+    assert!(matches!(verify(text), Err(VerifyError::ShiftCount(_))));
+    let text = text.replace("x << x", "x << 32");
+    assert!(matches!(verify(&text), Err(VerifyError::ShiftCount(_))));
+}
+
+#[test]
+fn rejects_a_variable_written_twice_in_ssa_form() {
+    let tacky: TackyProgram = "function main {
+    a.0 = 1
+    a.0 = 2
+    return a.0
+}
+"
+    .parse()
+    .unwrap();
+    let cfg = ControlFlowGraph::from_instructions(&tacky.0.instruction);
+    let ssa = SsaProgram {
+        identifier: tacky.0.identifier,
+        parameters: vec![],
+        phis: vec![vec![]; cfg.blocks.len()],
+        cfg,
+    };
+    assert!(matches!(
+        ssa.verify(),
+        Err(VerifyError::MultipleDefinitions(_))
+    ));
+    assert_eq!(
+        ssa.verify().unwrap_err().to_string(),
+        "a.0 is written more than once in SSA form"
+    );
+}
+
+#[test]
+fn accepts_reading_the_parameters() {
+    verify(
+        "function main(argc, argv) {
+    main.tmp.0 = argc + 40
+    return main.tmp.0
+}
+",
+    )
+    .unwrap();
+}
+
+#[test]
+fn accepts_the_parameters_through_ssa() {
+    let program: TackyProgram = "function main(argc, argv) {
+    ifz argc goto main.label.0
+    argc = argc + 1
+  main.label.0:
+    return argc
+}
+"
+    .parse()
+    .unwrap();
+    let ssa = program.to_ssa();
+    // The parameter itself is the version coming from the entry.
+    assert!(
+        ssa.to_string().contains("argc.1 = phi argc, argc.0"),
+        "{}",
+        ssa
+    );
+    ssa.verify().unwrap();
+    ssa.into_tacky().verify().unwrap();
+}
+
+#[test]
+fn accepts_reading_a_variable_never_written() {
+    // Valid C, the value is only unspecified.
+    verify(
+        "function main {
+    a = a + 1
+    return 3
+}
+",
+    )
+    .unwrap();
+}
@@ -0,0 +1,49 @@
+//! The playground entry points, called as plain functions, and the JSON they
+//! report failures with.
+
+use ulang::{
+    json::JsonDiagnostics,
+    wasm::{ast, compile_to_asm, compile_to_asm_with, tacky, tokens},
+};
+
+const SOURCE: &str = "int main(void) {\n    return 1 + 2;\n}\n";
+
+#[test]
+fn shows_every_stage() {
+    let tokens = tokens(SOURCE).unwrap();
+    assert!(tokens.starts_with("0+3\tIntKeyword\n"), "{}", tokens);
+    assert!(ast(SOURCE).unwrap().contains("Binary"));
+    assert!(tacky(SOURCE, 0).unwrap().contains("main.tmp.0 = 1 + 2"));
+
+    let asm = compile_to_asm(SOURCE).unwrap();
+    assert!(asm.contains("main:"), "{}", asm);
+    assert!(asm.contains("addl"), "{}", asm);
+    let folded = compile_to_asm_with(SOURCE, 2).unwrap();
+    assert!(folded.contains("movl\t$3, %eax"), "{}", folded);
+}
+
+#[test]
+fn reports_diagnostics_as_json() {
+    let JsonDiagnostics(json) = compile_to_asm("int main(void) {\n    return +;\n}\n").unwrap_err();
+    assert!(json.starts_with("[{\"severity\":\"error\""), "{}", json);
+    assert!(
+        json.contains("\"offset\":28,\"length\":1,\"line\":2,\"column\":12"),
+        "{}",
+        json
+    );
+
+    let JsonDiagnostics(json) = tacky(SOURCE, 9).unwrap_err();
+    assert_eq!(
+        json,
+        "[{\"severity\":\"error\",\"message\":\"Unknown optimization level 9\",\"labels\":[]}]"
+    );
+}
+
+#[test]
+fn escapes_json_strings() {
+    let JsonDiagnostics(json) = JsonDiagnostics::message("\"a\"\\\n\t");
+    assert_eq!(
+        json,
+        "[{\"severity\":\"error\",\"message\":\"\\\"a\\\"\\\\\\n\\u0009\",\"labels\":[]}]"
+    );
+}